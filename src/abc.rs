@@ -0,0 +1,914 @@
+use crate::errors::HarmonyError;
+use crate::notenames::NoteName;
+use crate::accidentals::Accidental;
+use crate::notes::Note;
+use crate::notesequences::NoteSequence;
+use crate::diatonic_scales::Mode;
+use crate::chords::Chord;
+use crate::keys::Key;
+use crate::rhythm::gcd;
+
+pub const TICKS_PER_QUARTER: u32 = 480;
+
+const MODES: [(&str, Mode); 9] = [
+    ("maj", Mode::Ionian), ("ion", Mode::Ionian), ("min", Mode::Aeolian), ("aeo", Mode::Aeolian),
+    ("dor", Mode::Dorian), ("phr", Mode::Phrygian), ("lyd", Mode::Lydian), ("mix", Mode::Mixolydian),
+    ("loc", Mode::Locrian),
+];
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AbcNote {
+    // None is a rest
+    pub note: Option<Note>,
+    // start and duration in ticks
+    pub start: u32,
+    pub duration: u32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AbcTune {
+    pub reference: u32,
+    pub title: String,
+    // None is free meter
+    pub meter: Option<(u8, u8)>,
+    // unit note length L: as a fraction of a whole note
+    pub unit: (u32, u32),
+    // beat as a fraction of a whole note and beats per minute
+    pub tempo: Option<((u32, u32), u32)>,
+    pub key: Key,
+    pub notes: Vec<AbcNote>,
+    // chord symbols in quotes with their position in ticks
+    pub chords: Vec<(u32, Chord)>,
+    // positions of the bar lines after the start of the tune
+    pub bars: Vec<u32>,
+    pub key_changes: Vec<(u32, Key)>,
+    pub meter_changes: Vec<(u32, Option<(u8, u8)>)>,
+}
+
+impl AbcTune {
+    pub fn new(title: &str, key: Key) -> Self {
+        Self {
+            reference: 1,
+            title: String::from(title),
+            meter: Some((4, 4)),
+            unit: (1, 8),
+            tempo: None,
+            key,
+            notes: Vec::new(),
+            chords: Vec::new(),
+            bars: Vec::new(),
+            key_changes: Vec::new(),
+            meter_changes: Vec::new(),
+        }
+    }
+
+    // the notes one after the other, each lasting duration ticks
+    pub fn from_sequence(title: &str, key: Key, meter: (u8, u8), sequence: &NoteSequence, duration: u32)
+        -> Result<Self, HarmonyError> {
+        if duration == 0 || meter.0 == 0 || meter.1 == 0 {
+            return Err(HarmonyError::InvalidArgument("Durations and meters must be positive"));
+        }
+        let mut tune = Self::new(title, key);
+        tune.meter = Some(meter);
+        tune.notes = sequence.notes.iter().enumerate()
+            .map(|(i, x)| AbcNote {note: Some(*x), start: i as u32 * duration, duration})
+            .collect();
+        let measure = 4 * TICKS_PER_QUARTER * meter.0 as u32 / meter.1 as u32;
+        let end = sequence.notes.len() as u32 * duration;
+        tune.bars = (1..=end / measure).map(|x| x * measure).collect();
+        Ok(tune)
+    }
+
+    // A single tune. It ends at the first empty line after the K: field.
+    pub fn from_abc(s: &str) -> Result<Self, HarmonyError> {
+        let lines: Vec<&str> = s.lines().collect();
+        parse_tune(&lines, 1)
+    }
+
+    // all tunes of a file, each starting with an X: field
+    pub fn collection(s: &str) -> Result<Vec<Self>, HarmonyError> {
+        let lines: Vec<&str> = s.lines().collect();
+        let mut tunes = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            if line.starts_with("X:") {
+                tunes.push(parse_tune(&lines[i..], i + 1)?);
+            }
+        }
+        Ok(tunes)
+    }
+
+    pub fn key_at(&self, tick: u32) -> Key {
+        self.key_changes.iter().rev()
+            .find(|(change, _)| *change <= tick)
+            .map(|(_, key)| *key)
+            .unwrap_or(self.key)
+    }
+
+    // all pitched notes in order, simultaneous notes lowest first
+    pub fn sequence(&self) -> NoteSequence {
+        let mut notes: Vec<(u32, Note)> = self.notes.iter()
+            .filter_map(|x| x.note.map(|y| (x.start, y)))
+            .collect();
+        notes.sort_by_key(|(start, note)| (*start, Note::default().dist_hsteps(note)));
+        NoteSequence {notes: notes.iter().map(|(_, x)| *x).collect()}
+    }
+
+    pub fn to_abc(&self) -> String {
+        let mut s = format!("X:{}\n", self.reference);
+        if !self.title.is_empty() {
+            s += &format!("T:{}\n", self.title);
+        }
+        s += &format!("M:{}\n", meter_to_str(self.meter));
+        s += &format!("L:{}/{}\n", self.unit.0, self.unit.1);
+        if let Some(((numerator, denominator), bpm)) = self.tempo {
+            s += &format!("Q:{}/{}={}\n", numerator, denominator, bpm);
+        }
+        s += &format!("K:{}\n", key_to_str(&self.key));
+
+        let end = self.notes.iter().map(|x| x.start + x.duration)
+            .chain(self.bars.last().copied())
+            .chain(self.chords.iter().map(|(x, _)| *x))
+            .max().unwrap_or(0);
+        let mut writer = Writer {
+            tune: self, end, position: 0, bar: 0, chord: 0, key_change: 0, meter_change: 0, key: self.key,
+            accidentals: Vec::new(), line: Vec::new(), lines: Vec::new(),
+        };
+        let mut notes: Vec<&AbcNote> = self.notes.iter().collect();
+        notes.sort_by_key(|x| x.start);
+        let mut i = 0;
+        while i < notes.len() {
+            // notes starting together form a chord with the length of the first one
+            let group: Vec<&AbcNote> = notes[i..].iter().take_while(|x| x.start == notes[i].start).copied().collect();
+            i += group.len();
+            writer.advance_to(group[0].start);
+            let pitches: Vec<(Note, u32)> = group.iter().filter_map(|x| x.note.map(|y| (y, x.duration))).collect();
+            writer.span(&pitches, group[0].duration);
+        }
+        writer.advance_to(end);
+        writer.line.push(String::from("|]"));
+        writer.lines.push(writer.line.join(" "));
+        s += &writer.lines.join("\n");
+        s += "\n";
+        s
+    }
+}
+
+struct Writer<'a> {
+    tune: &'a AbcTune,
+    // the last bar line is written as the end of the tune
+    end: u32,
+    position: u32,
+    // index of the next bar, chord symbol and changes to write
+    bar: usize,
+    chord: usize,
+    key_change: usize,
+    meter_change: usize,
+    key: Key,
+    accidentals: Vec<(NoteName, i8, Accidental)>,
+    line: Vec<String>,
+    lines: Vec<String>,
+}
+
+impl Writer<'_> {
+    // rests up to the target with chord symbols where they start
+    fn advance_to(&mut self, target: u32) {
+        let mut chords: Vec<&(u32, Chord)> = self.tune.chords.iter().collect();
+        chords.sort_by_key(|(start, _)| *start);
+        loop {
+            self.flush();
+            while chords.get(self.chord).is_some_and(|(start, _)| *start <= self.position) {
+                self.line.push(format!("\"{}\"", chords[self.chord].1));
+                self.chord += 1;
+            }
+            if self.position >= target {
+                break;
+            }
+            let next = chords.get(self.chord).map(|(start, _)| *start).unwrap_or(target).min(target);
+            self.span(&[], next - self.position);
+        }
+    }
+
+    // notes with their own lengths or a rest, tied over the bar lines
+    fn span(&mut self, notes: &[(Note, u32)], mut ticks: u32) {
+        let mut notes = notes.to_vec();
+        while ticks > 0 {
+            self.flush();
+            let bar = self.tune.bars.get(self.bar).copied().filter(|x| *x < self.position + ticks);
+            let length = bar.map(|x| x - self.position).unwrap_or(ticks);
+            // notes shorter than the chord end inside it, longer ones are written in full after the last bar line
+            notes.retain(|(_, x)| *x > 0);
+            let mut token = String::new();
+            for (note, remaining) in notes.iter_mut() {
+                let written = if bar.is_some() {length.min(*remaining)} else {*remaining};
+                token += &self.pitch_to_str(note);
+                token += &length_to_str(written, self.tune.unit);
+                *remaining -= written;
+            }
+            token = match notes.len() {
+                0 => format!("z{}", length_to_str(length, self.tune.unit)),
+                1 => token,
+                _ => format!("[{}]", token),
+            };
+            if bar.is_some() && !notes.is_empty() {
+                token.push('-');
+            }
+            self.line.push(token);
+            self.position += length;
+            ticks -= length;
+        }
+    }
+
+    // bar lines and changes of key and meter up to the current position
+    fn flush(&mut self) {
+        while self.tune.bars.get(self.bar).is_some_and(|x| *x <= self.position && *x < self.end) {
+            self.bar += 1;
+            self.accidentals.clear();
+            self.line.push(String::from("|"));
+            if self.bar.is_multiple_of(4) {
+                self.lines.push(self.line.join(" "));
+                self.line.clear();
+            }
+        }
+        while let Some((_, key)) = self.tune.key_changes.get(self.key_change).filter(|(x, _)| *x <= self.position) {
+            self.line.push(format!("[K:{}]", key_to_str(key)));
+            self.key = *key;
+            self.key_change += 1;
+        }
+        while let Some((_, meter)) = self.tune.meter_changes.get(self.meter_change).filter(|(x, _)| *x <= self.position) {
+            self.line.push(format!("[M:{}]", meter_to_str(*meter)));
+            self.meter_change += 1;
+        }
+    }
+
+    fn pitch_to_str(&mut self, note: &Note) -> String {
+        let current = self.accidentals.iter()
+            .find(|(name, octave, _)| *name == note.name() && *octave == note.octave())
+            .map(|(_, _, x)| *x)
+            .unwrap_or(self.key.signature().accidental(note.name()));
+        let mut s = String::new();
+        if note.accidental() != current {
+            s += match note.accidental() {
+                Accidental::Doubleflat => "__",
+                Accidental::Flat => "_",
+                Accidental::Natural => "=",
+                Accidental::Sharp => "^",
+                Accidental::Doublesharp => "^^",
+            };
+            self.accidentals.retain(|(name, octave, _)| *name != note.name() || *octave != note.octave());
+            self.accidentals.push((note.name(), note.octave(), note.accidental()));
+        }
+        if note.octave() >= 5 {
+            s += &note.name().to_str().to_lowercase();
+            s += &"'".repeat(note.octave() as usize - 5);
+        } else {
+            s += note.name().to_str();
+            s += &",".repeat((4 - note.octave()) as usize);
+        }
+        s
+    }
+}
+
+fn length_to_str(ticks: u32, unit: (u32, u32)) -> String {
+    let numerator = ticks as u64 * unit.1 as u64;
+    let denominator = 4 * TICKS_PER_QUARTER as u64 * unit.0 as u64;
+    let divisor = gcd(numerator, denominator);
+    match (numerator / divisor, denominator / divisor) {
+        (1, 1) => String::new(),
+        (numerator, 1) => numerator.to_string(),
+        (1, denominator) => format!("/{}", denominator),
+        (numerator, denominator) => format!("{}/{}", numerator, denominator),
+    }
+}
+
+fn meter_to_str(meter: Option<(u8, u8)>) -> String {
+    match meter {
+        Some((beats, beat_type)) => format!("{}/{}", beats, beat_type),
+        None => String::from("none"),
+    }
+}
+
+fn key_to_str(key: &Key) -> String {
+    let suffix = match key.mode() {
+        Mode::Ionian => "",
+        Mode::Aeolian => "m",
+        Mode::Dorian => "Dor",
+        Mode::Phrygian => "Phr",
+        Mode::Lydian => "Lyd",
+        Mode::Mixolydian => "Mix",
+        Mode::Locrian => "Loc",
+        // other modes are written as the major key with the same signature
+        _ => return Key::from_signature(key.signature(), Mode::Ionian).or(key.parallel(Mode::Ionian))
+            .map(|x| key_to_str(&x)).unwrap_or_default(),
+    };
+    format!("{}{}{}", key.tonic().name().to_str(), key.tonic().accidental().to_str(), suffix)
+}
+
+fn field(line: &str) -> Option<(char, &str)> {
+    let mut chars = line.chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), Some(':')) if letter.is_ascii_alphabetic() => Some((letter, &line[2..])),
+        _ => None,
+    }
+}
+
+fn parse_number(s: &str, expected: &str) -> Result<u32, HarmonyError> {
+    s.trim().parse().map_err(|_| HarmonyError::parse(s, 0, s, &[expected]))
+}
+
+fn parse_fraction(s: &str) -> Result<(u32, u32), HarmonyError> {
+    let (numerator, denominator) = s.trim().split_once('/').ok_or(HarmonyError::parse(s, 0, s, &["fraction"]))?;
+    let fraction = (parse_number(numerator, "fraction")?, parse_number(denominator, "fraction")?);
+    if fraction.0 == 0 || fraction.1 == 0 {
+        return Err(HarmonyError::parse(s, 0, s, &["fraction"]));
+    }
+    Ok(fraction)
+}
+
+fn parse_meter(s: &str) -> Result<Option<(u8, u8)>, HarmonyError> {
+    let error = || HarmonyError::parse(s, 0, s.trim(), &["C", "C|", "none", "meter"]);
+    match s.trim() {
+        "" | "none" => Ok(None),
+        "C" => Ok(Some((4, 4))),
+        "C|" => Ok(Some((2, 2))),
+        meter => {
+            // additive meters like 2+3/8 count all beats
+            let (beats, beat_type) = meter.split_once('/').ok_or_else(error)?;
+            let beats: u32 = beats.split('+').map(|x| parse_number(x, "meter")).sum::<Result<u32, HarmonyError>>()
+                .map_err(|_| error())?;
+            let beat_type = parse_number(beat_type, "meter").map_err(|_| error())?;
+            match (u8::try_from(beats), u8::try_from(beat_type)) {
+                (Ok(beats), Ok(beat_type)) if beats > 0 && beat_type > 0 => Ok(Some((beats, beat_type))),
+                _ => Err(error()),
+            }
+        },
+    }
+}
+
+fn parse_tempo(s: &str, unit: (u32, u32)) -> Result<((u32, u32), u32), HarmonyError> {
+    // quoted texts like "Allegro" are left out
+    let mut text = String::new();
+    for (i, part) in s.split('"').enumerate() {
+        if i % 2 == 0 {
+            text += part;
+        }
+    }
+    match text.split_once('=') {
+        Some((beat, bpm)) => {
+            let beat = beat.split_whitespace().next().unwrap_or("");
+            Ok((parse_fraction(beat).map_err(|_| HarmonyError::parse(s, 0, s, &["tempo"]))?, parse_number(bpm, "tempo")?))
+        },
+        None => Ok((unit, parse_number(&text, "tempo")?)),
+    }
+}
+
+fn parse_key(s: &str) -> Result<Key, HarmonyError> {
+    let offset = s.chars().count() - s.trim_start().chars().count();
+    let chars: Vec<char> = s.trim().chars().collect();
+    if chars.is_empty() || s.trim() == "none" {
+        return Ok(Key::default());
+    }
+    let name = NoteName::from_str(&chars[0].to_string()).map_err(|x| x.within(s, offset))?;
+    let (accidental, length) = match chars.get(1) {
+        Some('#') => (Accidental::Sharp, 2),
+        Some('b') => (Accidental::Flat, 2),
+        _ => (Accidental::Natural, 1),
+    };
+    let rest: String = chars[length..].iter().collect();
+    let mode_str = rest.split_whitespace().next().unwrap_or("");
+    let mode_lower = mode_str.to_lowercase();
+    let mode = if mode_str.is_empty() || mode_str.contains('=') {
+        Mode::Ionian
+    } else if mode_lower == "m" {
+        Mode::Aeolian
+    } else {
+        let prefix: String = mode_lower.chars().take(3).collect();
+        match MODES.iter().find(|(x, _)| *x == prefix && mode_lower.len() >= 3) {
+            Some((_, mode)) => *mode,
+            None => {
+                let position = offset + length + rest.chars().count() - rest.trim_start().chars().count();
+                let expected: Vec<&str> = MODES.iter().map(|(x, _)| *x).collect();
+                return Err(HarmonyError::parse(s, position, mode_str, &expected));
+            },
+        }
+    };
+    Key::new(Note::new(name, accidental, Note::default().octave()), mode).map_err(|x| x.within(s, offset))
+}
+
+// lengths like 3, 3/2, / and // as a fraction of the unit note length
+fn parse_length(chars: &[char], i: &mut usize) -> Result<(u32, u32), HarmonyError> {
+    let digits = |i: &mut usize| {
+        let start = *i;
+        while chars.get(*i).is_some_and(|x| x.is_ascii_digit()) {
+            *i += 1;
+        }
+        let s: String = chars[start..*i].iter().collect();
+        if s.is_empty() {Ok(None)} else {s.parse::<u32>().map(Some).map_err(|_| HarmonyError::parse("", start, "", &["length"]))}
+    };
+    let numerator = digits(i)?.unwrap_or(1);
+    let mut denominator: u32 = 1;
+    while chars.get(*i) == Some(&'/') {
+        *i += 1;
+        denominator = denominator.saturating_mul(digits(i)?.unwrap_or(2));
+    }
+    Ok((numerator, denominator))
+}
+
+fn scale_ticks(ticks: u32, (numerator, denominator): (u32, u32)) -> Result<u32, HarmonyError> {
+    let scaled = ticks as u64 * numerator as u64;
+    if numerator == 0 || !scaled.is_multiple_of(denominator as u64) || scaled / denominator as u64 > u32::MAX as u64 {
+        return Err(HarmonyError::OutOfRange("Note length can not be expressed in ticks"));
+    }
+    Ok((scaled / denominator as u64) as u32)
+}
+
+fn unit_ticks(unit: (u32, u32)) -> Result<u32, HarmonyError> {
+    let ticks = (4 * TICKS_PER_QUARTER).checked_mul(unit.0).ok_or(HarmonyError::OutOfRange("Unit note length is too long"))?;
+    if !ticks.is_multiple_of(unit.1) {
+        return Err(HarmonyError::OutOfRange("Unit note length is too short"));
+    }
+    Ok(ticks / unit.1)
+}
+
+// the default unit note length is an eighth, or a sixteenth in short meters
+fn default_unit(meter: Option<(u8, u8)>) -> (u32, u32) {
+    match meter {
+        Some((beats, beat_type)) if 4 * (beats as u32) < 3 * beat_type as u32 => (1, 16),
+        _ => (1, 8),
+    }
+}
+
+fn parse_tune(lines: &[&str], first_line: usize) -> Result<AbcTune, HarmonyError> {
+    let mut parser = Parser {
+        tune: AbcTune::new("", Key::default()),
+        unit: 0,
+        key: Key::default(),
+        position: 0,
+        accidentals: Vec::new(),
+        chord: None,
+        group: Vec::new(),
+        ties: Vec::new(),
+        tied: Vec::new(),
+    };
+    parser.tune.meter = None;
+    let mut unit = None;
+    let mut tempo = None;
+    let mut header = true;
+    for (i, line) in lines.iter().enumerate() {
+        let number = first_line + i;
+        let content = line.split('%').next().unwrap_or("");
+        if header {
+            if content.trim().is_empty() {
+                continue;
+            }
+            let Some((letter, value)) = field(content) else {
+                return Err(HarmonyError::parse(line, 0, line, &["K:"]).at_line(number));
+            };
+            let result = match letter {
+                'X' => parse_number(value, "reference number").map(|x| parser.tune.reference = x),
+                'T' if parser.tune.title.is_empty() => {
+                    parser.tune.title = String::from(value.trim());
+                    Ok(())
+                },
+                'M' => parse_meter(value).map(|x| parser.tune.meter = x),
+                'L' => parse_fraction(value).and_then(|x| unit_ticks(x).map(|_| unit = Some(x))),
+                'Q' => {
+                    tempo = Some((number, value));
+                    Ok(())
+                },
+                'K' => {
+                    header = false;
+                    let unit = unit.unwrap_or(default_unit(parser.tune.meter));
+                    parser.tune.unit = unit;
+                    if let Some((number, value)) = tempo {
+                        parser.tune.tempo = Some(parse_tempo(value, unit).map_err(|x| x.within(lines[number - first_line], 2).at_line(number))?);
+                    }
+                    unit_ticks(unit).map(|x| parser.unit = x)
+                        .and_then(|_| parse_key(value))
+                        .map(|x| {
+                            parser.tune.key = x;
+                            parser.key = x;
+                        })
+                },
+                _ => Ok(()),
+            };
+            result.map_err(|x| x.within(line, 2).at_line(number))?;
+        } else if content.trim().is_empty() {
+            break;
+        } else if let Some((letter, value)) = field(content) {
+            parser.field(letter, value).map_err(|x| x.within(line, 2).at_line(number))?;
+        } else {
+            parser.line(line, number)?;
+        }
+    }
+    if header {
+        return Err(HarmonyError::InvalidArgument("Missing K: field").at_line(first_line + lines.len().saturating_sub(1)));
+    }
+    if parser.chord.is_some() {
+        return Err(HarmonyError::InvalidArgument("Unclosed chord"));
+    }
+    Ok(parser.tune)
+}
+
+struct Parser {
+    tune: AbcTune,
+    // ticks of the unit note length
+    unit: u32,
+    key: Key,
+    position: u32,
+    // accidentals of the current bar
+    accidentals: Vec<(NoteName, i8, Accidental)>,
+    // start of an open chord and the length of its first note
+    chord: Option<(u32, Option<u32>)>,
+    // the notes of the last note or chord with the ticks they added, ties to
+    // the next group and ties from the previous group
+    group: Vec<(usize, u32)>,
+    ties: Vec<usize>,
+    tied: Vec<usize>,
+}
+
+impl Parser {
+    fn field(&mut self, letter: char, value: &str) -> Result<(), HarmonyError> {
+        match letter {
+            'K' => {
+                let key = parse_key(value)?;
+                self.key = key;
+                if self.position == 0 {
+                    self.tune.key = key;
+                } else {
+                    self.tune.key_changes.retain(|(x, _)| *x != self.position);
+                    self.tune.key_changes.push((self.position, key));
+                }
+            },
+            'M' => {
+                let meter = parse_meter(value)?;
+                if self.position == 0 {
+                    self.tune.meter = meter;
+                } else {
+                    self.tune.meter_changes.retain(|(x, _)| *x != self.position);
+                    self.tune.meter_changes.push((self.position, meter));
+                }
+            },
+            'L' => self.unit = unit_ticks(parse_fraction(value)?)?,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn line(&mut self, line: &str, number: usize) -> Result<(), HarmonyError> {
+        let chars: Vec<char> = line.chars().collect();
+        let error = |position: usize, expected: &[&str]| {
+            let found = chars.get(position).map(|x| x.to_string()).unwrap_or_default();
+            HarmonyError::parse(line, position, &found, expected).at_line(number)
+        };
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '%' => break,
+                // spaces, slurs and decorations do not change the notes
+                ' ' | '\t' | '\\' | '`' | '(' | ')' | '.' | '~' | 'H' | 'L' | 'M' | 'O' | 'P' | 'S' | 'T' | 'u' | 'v' => i += 1,
+                open @ ('!' | '+' | '"' | '{') => {
+                    let close = if open == '{' {'}'} else {open};
+                    let end = chars[i + 1..].iter().position(|x| *x == close)
+                        .ok_or_else(|| error(chars.len(), &[&close.to_string()]))? + i + 1;
+                    let text: String = chars[i + 1..end].iter().collect();
+                    // quoted texts starting with a position are annotations
+                    if open == '"' && !text.starts_with(['^', '_', '<', '>', '@']) {
+                        if let Ok(chord) = Chord::from_symbol(&text) {
+                            self.tune.chords.push((self.position, chord));
+                        }
+                    }
+                    i = end + 1;
+                },
+                '|' | ':' => i = self.bar(&chars, i),
+                '[' if chars.get(i + 1) == Some(&'|') => i = self.bar(&chars, i),
+                '[' if chars.get(i + 1).is_some_and(|x| x.is_ascii_digit()) => i += 2,
+                '[' if chars.get(i + 2) == Some(&':') => {
+                    let end = chars[i..].iter().position(|x| *x == ']').ok_or_else(|| error(chars.len(), &["]"]))? + i;
+                    let value: String = chars[i + 3..end].iter().collect();
+                    self.field(chars[i + 1], &value).map_err(|x| x.within(line, i + 3).at_line(number))?;
+                    i = end + 1;
+                },
+                '[' => {
+                    if self.chord.is_some() {
+                        return Err(error(i, &["note", "]"]));
+                    }
+                    self.chord = Some((self.position, None));
+                    self.tied = std::mem::take(&mut self.ties);
+                    self.group.clear();
+                    i += 1;
+                },
+                ']' => {
+                    let (start, duration) = self.chord.take().ok_or_else(|| error(i, &["note", "rest", "bar line"]))?;
+                    i += 1;
+                    // a length after the chord applies to all of its notes
+                    let length = parse_length(&chars, &mut i).map_err(|x| self.located(x, &error, number))?;
+                    for (index, ticks) in self.group.iter_mut() {
+                        let scaled = scale_ticks(*ticks, length).map_err(|x| x.at_line(number))?;
+                        let note = &mut self.tune.notes[*index];
+                        // a tied note keeps the ticks of the notes before the chord
+                        note.duration = (note.duration - *ticks).checked_add(scaled)
+                            .ok_or(HarmonyError::OutOfRange("Tune is too long").at_line(number))?;
+                        *ticks = scaled;
+                    }
+                    self.position = scale_ticks(duration.unwrap_or(0), length)
+                        .and_then(|x| start.checked_add(x).ok_or(HarmonyError::OutOfRange("Tune is too long")))
+                        .map_err(|x| x.at_line(number))?;
+                },
+                '-' => {
+                    match self.chord {
+                        Some(_) => self.ties.extend(self.group.last().map(|(x, _)| *x)),
+                        None => self.ties.extend(self.group.iter().map(|(x, _)| *x)),
+                    }
+                    i += 1;
+                },
+                '^' | '_' | '=' | 'A'..='G' | 'a'..='g' | 'z' | 'x' => {
+                    i = self.note(&chars, i).map_err(|x| self.located(x, &error, number))?;
+                },
+                _ => return Err(error(i, &["note", "rest", "bar line"])),
+            }
+        }
+        Ok(())
+    }
+
+    // parse errors of notes only carry the position in the line
+    fn located(&self, error: HarmonyError, at: &dyn Fn(usize, &[&str]) -> HarmonyError, number: usize) -> HarmonyError {
+        match error {
+            HarmonyError::Parse {position, expected, ..} => at(position, &expected.iter().map(|x| x.as_str()).collect::<Vec<_>>()),
+            error => error.at_line(number),
+        }
+    }
+
+    fn bar(&mut self, chars: &[char], mut i: usize) -> usize {
+        while i < chars.len() && (matches!(chars[i], '|' | ':' | ']') || (chars[i] == '[' && chars.get(i + 1) == Some(&'|'))) {
+            i += 1;
+        }
+        // numbers of repeat endings
+        while i < chars.len() && chars[i].is_ascii_digit() {
+            i += 1;
+        }
+        if self.position > 0 && self.tune.bars.last() != Some(&self.position) {
+            self.tune.bars.push(self.position);
+        }
+        self.accidentals.clear();
+        i
+    }
+
+    // parses a note or rest starting at i and returns the index after it
+    fn note(&mut self, chars: &[char], mut i: usize) -> Result<usize, HarmonyError> {
+        let accidental = match (chars[i], chars.get(i + 1)) {
+            ('^', Some('^')) => Some(Accidental::Doublesharp),
+            ('_', Some('_')) => Some(Accidental::Doubleflat),
+            ('^', _) => Some(Accidental::Sharp),
+            ('_', _) => Some(Accidental::Flat),
+            ('=', _) => Some(Accidental::Natural),
+            _ => None,
+        };
+        i += match accidental {
+            Some(Accidental::Doublesharp | Accidental::Doubleflat) => 2,
+            Some(_) => 1,
+            None => 0,
+        };
+        let letter = match chars.get(i) {
+            Some(letter @ ('A'..='G' | 'a'..='g')) => Some(*letter),
+            Some('z' | 'x') if accidental.is_none() => None,
+            _ => return Err(HarmonyError::parse("", i, "", &["note name"])),
+        };
+        i += 1;
+        let mut octave: i8 = if letter.is_some_and(|x| x.is_ascii_lowercase()) {5} else {4};
+        while let Some(mark @ ('\'' | ',')) = chars.get(i) {
+            octave = octave.checked_add(if *mark == '\'' {1} else {-1}).ok_or(HarmonyError::OutOfRange("Octave is out of range"))?;
+            i += 1;
+        }
+
+        let ticks = scale_ticks(self.unit, parse_length(chars, &mut i)?)?;
+
+        // accidentals last until the end of the bar
+        let note = match letter {
+            Some(letter) => {
+                let name = NoteName::from_str(&letter.to_string())?;
+                let accidental = match accidental {
+                    Some(accidental) => {
+                        self.accidentals.retain(|(x, y, _)| *x != name || *y != octave);
+                        self.accidentals.push((name, octave, accidental));
+                        accidental
+                    },
+                    None => self.accidentals.iter()
+                        .find(|(x, y, _)| *x == name && *y == octave)
+                        .map(|(_, _, x)| *x)
+                        .unwrap_or(self.key.signature().accidental(name)),
+                };
+                Some(Note::new(name, accidental, octave))
+            },
+            None => None,
+        };
+        self.add(note, ticks)?;
+        Ok(i)
+    }
+
+    fn add(&mut self, note: Option<Note>, ticks: u32) -> Result<(), HarmonyError> {
+        let too_long = || HarmonyError::OutOfRange("Tune is too long");
+        let start = match self.chord {
+            Some((start, _)) => start,
+            None => {
+                self.tied = std::mem::take(&mut self.ties);
+                self.position
+            },
+        };
+        let notes = &mut self.tune.notes;
+        let tied = self.tied.iter()
+            .position(|x| note.is_some() && notes[*x].note == note && notes[*x].start.checked_add(notes[*x].duration) == Some(start));
+        let index = match tied {
+            Some(tied) => {
+                let index = self.tied.remove(tied);
+                notes[index].duration = notes[index].duration.checked_add(ticks).ok_or_else(too_long)?;
+                index
+            },
+            None => {
+                notes.push(AbcNote {note, start, duration: ticks});
+                notes.len() - 1
+            },
+        };
+        match &mut self.chord {
+            Some((_, duration)) => {
+                duration.get_or_insert(ticks);
+                self.group.push((index, ticks));
+            },
+            None => {
+                self.position = start.checked_add(ticks).ok_or_else(too_long)?;
+                self.group = vec![(index, ticks)];
+            },
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TUNES: &str = "%abc-2.1
+% a small collection
+
+X:1
+T:Speed the Plough
+T:alternative title
+M:C|
+Q:\"Allegro\" 1/2=96
+K:G % G major
+|:\"G\"GABG \"D7\"^FGAF|\"G\"G2 g2 d4 :|
+[K:Dm]\"Dm\"d2^c2 c2 =c2|[DFA]4- [DFA]2 z2|]
+
+X:2
+T:Short
+M:2/4
+K:Bb
+B/c/d- d//e//z/ f3/2|d'2 C,2|
+";
+
+    fn notes(tune: &AbcTune) -> Vec<(String, u32, u32)> {
+        tune.notes.iter()
+            .map(|x| (x.note.map(|y| y.to_string()).unwrap_or(String::from("z")), x.start, x.duration))
+            .collect()
+    }
+
+    fn expected(notes: &[(&str, u32, u32)]) -> Vec<(String, u32, u32)> {
+        notes.iter().map(|(x, y, z)| (String::from(*x), *y, *z)).collect()
+    }
+
+    #[test]
+    fn parse() {
+        let tunes = AbcTune::collection(TUNES).unwrap();
+        assert_eq!(tunes.len(), 2);
+
+        let tune = &tunes[0];
+        assert_eq!((tune.reference, tune.title.as_str()), (1, "Speed the Plough"));
+        assert_eq!((tune.meter, tune.unit, tune.tempo), (Some((2, 2)), (1, 8), Some(((1, 2), 96))));
+        assert_eq!(tune.key, Key::from_str("G major").unwrap());
+        assert_eq!(notes(tune), expected(&[
+            ("G4", 0, 240), ("A4", 240, 240), ("B4", 480, 240), ("G4", 720, 240),
+            ("F#4", 960, 240), ("G4", 1200, 240), ("A4", 1440, 240), ("F#4", 1680, 240),
+            ("G4", 1920, 480), ("G5", 2400, 480), ("D5", 2880, 960),
+            // the accidental lasts until the bar line
+            ("D5", 3840, 480), ("C#5", 4320, 480), ("C#5", 4800, 480), ("C5", 5280, 480),
+            ("D4", 5760, 1440), ("F4", 5760, 1440), ("A4", 5760, 1440), ("z", 7200, 480),
+        ]));
+        assert_eq!(tune.bars, vec![1920, 3840, 5760, 7680]);
+        assert_eq!(tune.key_changes, vec![(3840, Key::from_str("D minor").unwrap())]);
+        let symbols: Vec<(u32, String)> = tune.chords.iter().map(|(x, y)| (*x, y.to_string())).collect();
+        assert_eq!(symbols, vec![(0, String::from("G")), (960, String::from("D7")), (1920, String::from("G")), (3840, String::from("Dm"))]);
+
+        let tune = &tunes[1];
+        assert_eq!((tune.meter, tune.unit, tune.tempo), (Some((2, 4)), (1, 16), None));
+        assert_eq!(notes(tune), expected(&[
+            ("Bb4", 0, 60), ("C5", 60, 60), ("D5", 120, 150), ("Eb5", 270, 30), ("z", 300, 60), ("F5", 360, 180),
+            ("D6", 540, 240), ("C3", 780, 240),
+        ]));
+        assert_eq!(format!("{}", tune.sequence()), "Bb4 C5 D5 Eb5 F5 D6 C3");
+        assert_eq!(AbcTune::from_abc(&TUNES[TUNES.find("X:2").unwrap()..]), Ok(tune.clone()));
+    }
+
+    #[test]
+    fn chord_lengths() {
+        // a length after the chord scales the length of each note
+        let tune = AbcTune::from_abc("X:1\nK:C\n[CE]/2 D [C2E]3/2 F").unwrap();
+        assert_eq!(notes(&tune), expected(&[
+            ("C4", 0, 120), ("E4", 0, 120), ("D4", 120, 240), ("C4", 360, 720), ("E4", 360, 360), ("F4", 1080, 240),
+        ]));
+        assert_eq!(tune.to_abc(), "X:1\nM:none\nL:1/8\nK:C\n[C/2E/2] D [C3E3/2] F |]\n");
+
+        // each note keeps its own length, also across bar lines
+        let mut tune = AbcTune::new("", Key::from_str("C major").unwrap());
+        tune.bars = vec![1920];
+        tune.notes = vec![
+            AbcNote {note: None, start: 0, duration: 1440},
+            AbcNote {note: Some(Note::from_str("C4").unwrap()), start: 1440, duration: 960},
+            AbcNote {note: Some(Note::from_str("E4").unwrap()), start: 1440, duration: 240},
+            AbcNote {note: Some(Note::from_str("G4").unwrap()), start: 1440, duration: 720},
+        ];
+        let written = tune.to_abc();
+        assert_eq!(written, "X:1\nM:4/4\nL:1/8\nK:C\nz6 [C2EG2]- | [C2G] |]\n");
+        assert_eq!(notes(&AbcTune::from_abc(&written).unwrap()), notes(&tune));
+    }
+
+    #[test]
+    fn keys() {
+        let key = |s: &str| parse_key(s).map(|x| x.to_string());
+        assert_eq!(key("G"), Ok(String::from("G major")));
+        assert_eq!(key("Em"), Ok(String::from("E minor")));
+        assert_eq!(key("F#min"), Ok(String::from("F# minor")));
+        assert_eq!(key("Bb Mixolydian"), Ok(String::from("Bb Mixolydian")));
+        assert_eq!(key("ADor"), Ok(String::from("A Dorian")));
+        assert_eq!(key("D clef=bass"), Ok(String::from("D major")));
+        assert_eq!(key("none"), Ok(String::from("C major")));
+        assert_eq!(key("Ebx"), Err(HarmonyError::parse("Ebx", 2, "x", &["maj", "ion", "min", "aeo", "dor", "phr", "lyd", "mix", "loc"])));
+        assert_eq!(key_to_str(&Key::from_str("Eb dorian").unwrap()), "EbDor");
+        assert_eq!(key_to_str(&Key::from_str("c# minor").unwrap()), "C#m");
+        assert_eq!(key_to_str(&Key::new(Note::from_str("A3").unwrap(), Mode::HarmonicMinor).unwrap()), "C");
+
+        assert_eq!(parse_meter("6/8"), Ok(Some((6, 8))));
+        assert_eq!(parse_meter("2+2+3/8"), Ok(Some((7, 8))));
+        assert_eq!(parse_meter(" C"), Ok(Some((4, 4))));
+        assert!(parse_meter("3/").is_err());
+        assert_eq!(default_unit(Some((3, 4))), (1, 8));
+        assert_eq!(default_unit(Some((5, 8))), (1, 16));
+        assert_eq!(parse_tempo("120", (1, 8)), Ok(((1, 8), 120)));
+    }
+
+    #[test]
+    fn errors() {
+        let parse = |body: &str| AbcTune::from_abc(&format!("X:1\nK:C\n{}", body));
+        assert_eq!(parse("CDE F>G"), Err(HarmonyError::parse("CDE F>G", 5, ">", &["note", "rest", "bar line"]).at_line(3)));
+        assert_eq!(parse("C\n^z2"), Err(HarmonyError::parse("^z2", 1, "z", &["note name"]).at_line(4)));
+        assert_eq!(parse("C \"G"), Err(HarmonyError::parse("C \"G", 4, "", &["\""]).at_line(3)));
+        assert_eq!(parse("[K:Cq]"), Err(HarmonyError::parse("[K:Cq]", 4, "q", &["maj", "ion", "min", "aeo", "dor", "phr", "lyd", "mix", "loc"]).at_line(3)));
+        assert_eq!(parse("C/7"), Err(HarmonyError::OutOfRange("Note length can not be expressed in ticks").at_line(3)));
+        assert_eq!(AbcTune::from_abc("X:1\nM:7\nK:C"), Err(HarmonyError::parse("M:7", 2, "7", &["C", "C|", "none", "meter"]).at_line(2)));
+        assert_eq!(AbcTune::from_abc("X:1\nT:No key"), Err(HarmonyError::InvalidArgument("Missing K: field").at_line(2)));
+        assert_eq!(AbcTune::from_abc("CDE"), Err(HarmonyError::parse("CDE", 0, "CDE", &["K:"]).at_line(1)));
+        assert_eq!(AbcTune::from_abc("X:1\nL:9999999/1\nK:C"), Err(HarmonyError::OutOfRange("Unit note length is too long").at_line(2)));
+        assert_eq!(parse("C17000000 C17000000"), Err(HarmonyError::OutOfRange("Tune is too long").at_line(3)));
+        assert_eq!(parse("[C17000000] [C17000000]"), Err(HarmonyError::OutOfRange("Tune is too long").at_line(3)));
+        assert_eq!(parse("C9000000- [C]9000000"), Err(HarmonyError::OutOfRange("Tune is too long").at_line(3)));
+        assert_eq!(parse(&format!("c{}", "'".repeat(130))), Err(HarmonyError::OutOfRange("Octave is out of range").at_line(3)));
+    }
+
+    #[test]
+    fn write() {
+        let sequence: NoteSequence = "D4 F#4 A4 C5 B4 Bb4 Bb4 A4 G4 F4 E4 D4 C#4 B3 A3".parse().unwrap();
+        let mut tune = AbcTune::from_sequence("Exercise", Key::from_str("D minor").unwrap(), (3, 4), &sequence, 480).unwrap();
+        tune.chords = vec![(0, Chord::from_symbol("D").unwrap()), (2880, Chord::from_symbol("Gm").unwrap())];
+        tune.tempo = Some(((1, 4), 100));
+        assert_eq!(tune.to_abc(), concat!(
+            "X:1\nT:Exercise\nM:3/4\nL:1/8\nQ:1/4=100\nK:Dm\n",
+            "\"D\" D2 ^F2 A2 | c2 =B2 _B2 | \"Gm\" B2 A2 G2 | F2 E2 D2 |\n",
+            "^C2 =B,2 A,2 |]\n",
+        ));
+
+        // notes across bar lines are tied, gaps are rests
+        let mut tune = AbcTune::new("", Key::from_str("A major").unwrap());
+        tune.meter = Some((2, 4));
+        tune.unit = (1, 16);
+        tune.bars = vec![960, 1920];
+        tune.notes = vec![
+            AbcNote {note: Some(Note::from_str("A4").unwrap()), start: 0, duration: 360},
+            AbcNote {note: Some(Note::from_str("C5").unwrap()), start: 720, duration: 480},
+            AbcNote {note: Some(Note::from_str("E5").unwrap()), start: 720, duration: 480},
+            AbcNote {note: Some(Note::from_str("G5").unwrap()), start: 1200, duration: 720},
+        ];
+        assert_eq!(tune.to_abc(), "X:1\nM:2/4\nL:1/16\nK:A\nA3 z3 [=c2e2]- | [=c2e2] =g6 |]\n");
+    }
+
+    #[test]
+    fn round_trip() {
+        for tune in AbcTune::collection(TUNES).unwrap() {
+            let written = tune.to_abc();
+            let read = AbcTune::from_abc(&written).unwrap();
+            assert_eq!(notes(&read), notes(&tune), "{}", written);
+            assert_eq!(read.chords, tune.chords);
+            assert_eq!(read.bars, tune.bars);
+            assert_eq!(read.key_changes, tune.key_changes);
+            assert_eq!((read.meter, read.unit, read.tempo, &read.title), (tune.meter, tune.unit, tune.tempo, &tune.title));
+            assert_eq!(read.to_abc(), written);
+        }
+    }
+}
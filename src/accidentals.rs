@@ -1,3 +1,7 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::errors::HarmonyError;
+
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum Accidental {
     Doubleflat,
@@ -8,14 +12,14 @@ pub enum Accidental {
 }
 
 impl Accidental {
-    pub fn from_str(s: &str) -> Result<Self, &'static str> {
+    pub fn from_str(s: &str) -> Result<Self, HarmonyError> {
         match s {
             "bb" => Ok(Accidental::Doubleflat),
             "b" => Ok(Accidental::Flat),
             "" => Ok(Accidental::Natural),
             "#" => Ok(Accidental::Sharp),
             "##" => Ok(Accidental::Doublesharp),
-            _ => Err("Invalid Accidental symbol"),
+            _ => Err(HarmonyError::parse(s, 0, s, &["bb", "b", "", "#", "##"])),
         }
     }
 
@@ -38,6 +42,31 @@ impl Accidental {
             Accidental::Doublesharp => 2,
         }
     }
+
+    pub fn from_offset(offset: i32) -> Result<Self, HarmonyError> {
+        match offset {
+            -2 => Ok(Accidental::Doubleflat),
+            -1 => Ok(Accidental::Flat),
+            0 => Ok(Accidental::Natural),
+            1 => Ok(Accidental::Sharp),
+            2 => Ok(Accidental::Doublesharp),
+            _ => Err(HarmonyError::OutOfRange("Offset can not be expressed by a single accidental")),
+        }
+    }
+}
+
+impl FromStr for Accidental {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str(s)
+    }
+}
+
+impl fmt::Display for Accidental {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
 }
 
 #[cfg(test)]
@@ -75,4 +104,24 @@ mod test {
         assert_eq!(Accidental::Sharp.offset(), 1);
         assert_eq!(Accidental::Doublesharp.offset(), 2);
     }
+
+    #[test]
+    fn from_offset() {
+        assert_eq!(Accidental::from_offset(-2), Ok(Accidental::Doubleflat));
+        assert_eq!(Accidental::from_offset(-1), Ok(Accidental::Flat));
+        assert_eq!(Accidental::from_offset(0), Ok(Accidental::Natural));
+        assert_eq!(Accidental::from_offset(1), Ok(Accidental::Sharp));
+        assert_eq!(Accidental::from_offset(2), Ok(Accidental::Doublesharp));
+
+        assert!(Accidental::from_offset(-3).is_err());
+        assert!(Accidental::from_offset(3).is_err());
+    }
+
+    #[test]
+    fn errors_and_traits() {
+        assert_eq!(Accidental::from_str("x"), Err(HarmonyError::parse("x", 0, "x", &["bb", "b", "", "#", "##"])));
+        assert_eq!(Accidental::from_offset(3), Err(HarmonyError::OutOfRange("Offset can not be expressed by a single accidental")));
+        assert_eq!("##".parse::<Accidental>(), Ok(Accidental::Doublesharp));
+        assert_eq!(format!("{}", Accidental::Flat), "b");
+    }
 }
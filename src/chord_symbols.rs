@@ -0,0 +1,442 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::notenames::NoteName;
+use crate::accidentals::Accidental;
+use crate::notes::Note;
+use crate::intervals::{Interval, Quality};
+use crate::chords::{Chord, ChordQuality};
+use crate::errors::HarmonyError;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ChordSymbolStyle {
+    Ascii,
+    Jazz,
+    Classical,
+}
+
+const QUALITY_TOKENS: [(&str, ChordQuality); 65] = [
+    ("m", ChordQuality::Minor), ("-", ChordQuality::Minor), ("mi", ChordQuality::Minor), ("min", ChordQuality::Minor),
+    ("M", ChordQuality::Major), ("maj", ChordQuality::Major),
+    ("dim", ChordQuality::Diminished), ("°", ChordQuality::Diminished), ("o", ChordQuality::Diminished),
+    ("aug", ChordQuality::Augmented), ("+", ChordQuality::Augmented),
+    ("sus2", ChordQuality::Sus2),
+    ("sus4", ChordQuality::Sus4), ("sus", ChordQuality::Sus4),
+    ("6", ChordQuality::Major6),
+    ("m6", ChordQuality::Minor6), ("-6", ChordQuality::Minor6), ("min6", ChordQuality::Minor6),
+    ("7", ChordQuality::Dominant7),
+    ("maj7", ChordQuality::Major7), ("Δ7", ChordQuality::Major7), ("Δ", ChordQuality::Major7),
+    ("M7", ChordQuality::Major7), ("ma7", ChordQuality::Major7),
+    ("m7", ChordQuality::Minor7), ("-7", ChordQuality::Minor7), ("min7", ChordQuality::Minor7), ("mi7", ChordQuality::Minor7),
+    ("m(maj7)", ChordQuality::MinorMajor7), ("mmaj7", ChordQuality::MinorMajor7), ("mM7", ChordQuality::MinorMajor7),
+    ("-Δ7", ChordQuality::MinorMajor7), ("-maj7", ChordQuality::MinorMajor7),
+    ("m7b5", ChordQuality::HalfDiminished7), ("-7b5", ChordQuality::HalfDiminished7),
+    ("ø7", ChordQuality::HalfDiminished7), ("ø", ChordQuality::HalfDiminished7),
+    ("dim7", ChordQuality::Diminished7), ("°7", ChordQuality::Diminished7), ("o7", ChordQuality::Diminished7),
+    ("aug7", ChordQuality::Augmented7), ("+7", ChordQuality::Augmented7),
+    ("augmaj7", ChordQuality::AugmentedMajor7), ("+maj7", ChordQuality::AugmentedMajor7), ("+Δ7", ChordQuality::AugmentedMajor7),
+    ("7sus4", ChordQuality::Dominant7Sus4), ("7sus", ChordQuality::Dominant7Sus4),
+    ("add9", ChordQuality::Add9),
+    ("madd9", ChordQuality::MinorAdd9), ("-add9", ChordQuality::MinorAdd9),
+    ("9", ChordQuality::Dominant9),
+    ("maj9", ChordQuality::Major9), ("Δ9", ChordQuality::Major9),
+    ("m9", ChordQuality::Minor9), ("-9", ChordQuality::Minor9),
+    ("11", ChordQuality::Dominant11),
+    ("m11", ChordQuality::Minor11), ("-11", ChordQuality::Minor11),
+    ("7alt", ChordQuality::Altered),
+    ("13", ChordQuality::Dominant13),
+    ("maj13", ChordQuality::Major13), ("Δ13", ChordQuality::Major13),
+    ("m13", ChordQuality::Minor13), ("-13", ChordQuality::Minor13),
+    ("alt", ChordQuality::Altered),
+];
+
+// A single alteration written after a basic quality is folded into the
+// dedicated quality, so "G7b9" and an identified G7b9 compare equal.
+const FOLDED_ALTERATIONS: [(ChordQuality, &str, ChordQuality); 10] = [
+    (ChordQuality::Major, "A5", ChordQuality::Augmented),
+    (ChordQuality::Minor, "d5", ChordQuality::Diminished),
+    (ChordQuality::Minor7, "d5", ChordQuality::HalfDiminished7),
+    (ChordQuality::Major7, "A5", ChordQuality::AugmentedMajor7),
+    (ChordQuality::Dominant7, "A5", ChordQuality::Augmented7),
+    (ChordQuality::Dominant7, "d5", ChordQuality::Dominant7Flat5),
+    (ChordQuality::Dominant7, "m9", ChordQuality::Dominant7Flat9),
+    (ChordQuality::Dominant7, "A9", ChordQuality::Dominant7Sharp9),
+    (ChordQuality::Dominant7, "A11", ChordQuality::Dominant7Sharp11),
+    (ChordQuality::Dominant7, "m13", ChordQuality::Dominant7Flat13),
+];
+
+fn quality_suffix(quality: ChordQuality, style: ChordSymbolStyle) -> &'static str {
+    use ChordSymbolStyle::*;
+    match (quality, style) {
+        (ChordQuality::Major, _) => "",
+        (ChordQuality::Minor, Jazz) => "-",
+        (ChordQuality::Minor, _) => "m",
+        (ChordQuality::Diminished, Ascii) => "dim",
+        (ChordQuality::Diminished, _) => "°",
+        (ChordQuality::Augmented, Ascii) => "aug",
+        (ChordQuality::Augmented, _) => "+",
+        (ChordQuality::Sus2, _) => "sus2",
+        (ChordQuality::Sus4, _) => "sus4",
+        (ChordQuality::Major6, _) => "6",
+        (ChordQuality::Minor6, Jazz) => "-6",
+        (ChordQuality::Minor6, _) => "m6",
+        (ChordQuality::Dominant7, _) => "7",
+        (ChordQuality::Major7, Jazz) => "Δ7",
+        (ChordQuality::Major7, _) => "maj7",
+        (ChordQuality::Minor7, Jazz) => "-7",
+        (ChordQuality::Minor7, _) => "m7",
+        (ChordQuality::MinorMajor7, Jazz) => "-Δ7",
+        (ChordQuality::MinorMajor7, _) => "m(maj7)",
+        (ChordQuality::HalfDiminished7, Ascii) => "m7b5",
+        (ChordQuality::HalfDiminished7, _) => "ø7",
+        (ChordQuality::Diminished7, Ascii) => "dim7",
+        (ChordQuality::Diminished7, _) => "°7",
+        (ChordQuality::Augmented7, Ascii) => "aug7",
+        (ChordQuality::Augmented7, _) => "+7",
+        (ChordQuality::AugmentedMajor7, Ascii) => "augmaj7",
+        (ChordQuality::AugmentedMajor7, Jazz) => "+Δ7",
+        (ChordQuality::AugmentedMajor7, Classical) => "+maj7",
+        (ChordQuality::Dominant7Sus4, _) => "7sus4",
+        (ChordQuality::Add9, _) => "add9",
+        (ChordQuality::MinorAdd9, Jazz) => "-add9",
+        (ChordQuality::MinorAdd9, _) => "madd9",
+        (ChordQuality::Dominant9, _) => "9",
+        (ChordQuality::Major9, Jazz) => "Δ9",
+        (ChordQuality::Major9, _) => "maj9",
+        (ChordQuality::Minor9, Jazz) => "-9",
+        (ChordQuality::Minor9, _) => "m9",
+        (ChordQuality::Dominant11, _) => "11",
+        (ChordQuality::Minor11, Jazz) => "-11",
+        (ChordQuality::Minor11, _) => "m11",
+        (ChordQuality::Dominant13, _) => "13",
+        (ChordQuality::Major13, Jazz) => "Δ13",
+        (ChordQuality::Major13, _) => "maj13",
+        (ChordQuality::Minor13, Jazz) => "-13",
+        (ChordQuality::Minor13, _) => "m13",
+        (ChordQuality::Dominant7Flat5, _) => "7b5",
+        (ChordQuality::Dominant7Flat9, _) => "7b9",
+        (ChordQuality::Dominant7Sharp9, _) => "7#9",
+        (ChordQuality::Dominant7Sharp11, _) => "7#11",
+        (ChordQuality::Dominant7Flat13, _) => "7b13",
+        (ChordQuality::Altered, _) => "7alt",
+    }
+}
+
+fn alteration_from_symbol(accidental: char, degree: u32) -> Option<Interval> {
+    let interval_str = match (accidental, degree) {
+        ('b', 5) => "d5",
+        ('#', 5) => "A5",
+        ('b', 9) => "m9",
+        ('#', 9) => "A9",
+        ('#', 11) => "A11",
+        ('b', 13) => "m13",
+        _ => return None,
+    };
+    Interval::from_str(interval_str).ok()
+}
+
+fn alteration_to_symbol(alteration: Interval) -> String {
+    let perfect = matches!(alteration.steps() % 7, 0 | 3 | 4);
+    let accidental = match alteration.quality() {
+        Quality::Diminished(n) => "b".repeat(n as usize + usize::from(!perfect)),
+        Quality::Minor => String::from("b"),
+        Quality::Augmented(n) => "#".repeat(n as usize),
+        Quality::Perfect | Quality::Major => String::new(),
+    };
+    format!("{}{}", accidental, alteration.number())
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, token: &str) -> bool {
+        let len = token.chars().count();
+        self.pos + len <= self.chars.len()
+            && self.chars[self.pos..self.pos+len].iter().copied().eq(token.chars())
+    }
+
+    fn error_at(&self, position: usize, found: &str, expected: &[&str]) -> HarmonyError {
+        let input: String = self.chars.iter().collect();
+        HarmonyError::parse(&input, position, found, expected)
+    }
+
+    fn error(&self, expected: &[&str]) -> HarmonyError {
+        let found = self.peek().map_or(String::new(), |c| c.to_string());
+        self.error_at(self.pos, &found, expected)
+    }
+
+    fn note(&mut self) -> Result<Note, HarmonyError> {
+        let name = match self.peek() {
+            Some(c) => NoteName::from_str(&c.to_string())
+                .map_err(|x| x.within(&self.chars.iter().collect::<String>(), self.pos))?,
+            None => return Err(self.error(&["C", "D", "E", "F", "G", "A", "B"])),
+        };
+        self.pos += 1;
+
+        let accidental_start_idx = self.pos;
+        if let Some(c @ ('b' | '#')) = self.peek() {
+            self.pos += 1;
+            if self.peek() == Some(c) {
+                self.pos += 1;
+            }
+        }
+        let accidental_str: String = self.chars[accidental_start_idx..self.pos].iter().collect();
+        let accidental = Accidental::from_str(&accidental_str)
+            .map_err(|x| x.within(&self.chars.iter().collect::<String>(), accidental_start_idx))?;
+        Ok(Note::new(name, accidental, Note::default().octave()))
+    }
+
+    fn quality(&mut self) -> ChordQuality {
+        let mut best: Option<(&str, ChordQuality)> = None;
+        for (token, quality) in QUALITY_TOKENS {
+            if self.starts_with(token) && best.is_none_or(|(x, _)| token.chars().count() > x.chars().count()) {
+                best = Some((token, quality));
+            }
+        }
+        match best {
+            Some((token, quality)) => {
+                self.pos += token.chars().count();
+                quality
+            },
+            None => ChordQuality::Major,
+        }
+    }
+
+    fn alteration(&mut self) -> Result<(usize, Interval), HarmonyError> {
+        let start = self.pos;
+        let accidental = match self.peek() {
+            Some(c @ ('b' | '#')) => c,
+            _ => return Err(self.error(&["b", "#"])),
+        };
+        self.pos += 1;
+        let degree_start_idx = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let degree_str: String = self.chars[degree_start_idx..self.pos].iter().collect();
+        let degree = degree_str.parse::<u32>()
+            .map_err(|_| self.error(&["5", "9", "11", "13"]))?;
+        alteration_from_symbol(accidental, degree)
+            .map(|x| (start, x))
+            .ok_or(self.error_at(degree_start_idx, &degree_str, if accidental == 'b' {&["5", "9", "13"]} else {&["5", "9", "11"]}))
+    }
+
+    // the alterations with their positions in the input
+    fn alterations(&mut self) -> Result<Vec<(usize, Interval)>, HarmonyError> {
+        let mut alterations = Vec::new();
+        loop {
+            match self.peek() {
+                Some('b' | '#') => alterations.push(self.alteration()?),
+                Some('(') => {
+                    self.pos += 1;
+                    loop {
+                        alterations.push(self.alteration()?);
+                        match self.peek() {
+                            Some(',') => self.pos += 1,
+                            Some(')') => break,
+                            Some('b' | '#') => (),
+                            _ => return Err(self.error(&[")", ",", "b", "#"])),
+                        }
+                    }
+                    self.pos += 1;
+                },
+                _ => return Ok(alterations),
+            }
+        }
+    }
+}
+
+impl Chord {
+    pub fn from_symbol(s: &str) -> Result<Self, HarmonyError> {
+        let mut parser = Parser {chars: s.chars().collect(), pos: 0};
+        let root = parser.note()?;
+        let mut quality = parser.quality();
+        let quality_end = parser.pos;
+        let mut alterations = parser.alterations()?;
+        if alterations.len() == 1 {
+            for (base, alteration, folded) in FOLDED_ALTERATIONS {
+                if quality == base && alterations[0].1 == Interval::from_str(alteration).unwrap() {
+                    quality = folded;
+                    alterations.clear();
+                    break;
+                }
+            }
+        }
+
+        // chords that would need more than double accidentals
+        let text = |start: usize, end: usize| parser.chars[start..end].iter().collect::<String>();
+        let mut chord = Chord::from_root(root, quality)
+            .map_err(|_| parser.error_at(0, &text(0, quality_end), &[]))?;
+        for (start, alteration) in alterations {
+            chord = chord.alter(alteration)
+                .map_err(|_| parser.error_at(start, &alteration_to_symbol(alteration), &[]))?;
+        }
+
+        let mut expected: &[&str] = &["/", "(", "b", "#"];
+        if parser.peek() == Some('/') {
+            parser.pos += 1;
+            let bass = parser.note()?;
+            chord = chord.with_bass_spelling(bass)?;
+            expected = &[];
+        }
+
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error(expected));
+        }
+        Ok(chord)
+    }
+
+    pub fn to_symbol(&self, style: ChordSymbolStyle) -> String {
+        let class_str = |note: Note| format!("{}{}", note.name().to_str(), note.accidental().to_str());
+        let mut symbol = class_str(self.root());
+        let suffix = quality_suffix(self.quality(), style);
+        symbol.push_str(suffix);
+
+        if !self.alterations().is_empty() {
+            let mut alterations = self.alterations().to_vec();
+            alterations.sort_by_key(|x| (x.steps(), x.hsteps()));
+            let alterations: Vec<String> = alterations.into_iter().map(alteration_to_symbol).collect();
+            if suffix.is_empty() {
+                symbol.push_str(&format!("({})", alterations.join(",")));
+            } else {
+                symbol.push_str(&alterations.concat());
+            }
+        }
+
+        if !self.bass().same_spelling(&self.root()) {
+            symbol.push('/');
+            symbol.push_str(&class_str(self.bass()));
+        }
+        symbol
+    }
+}
+
+// chords parse from and print as ASCII chord symbols
+impl FromStr for Chord {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_symbol(s)
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_symbol(ChordSymbolStyle::Ascii))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_symbol() {
+        let chord = Chord::from_symbol("F#m7b5/C").unwrap();
+        assert_eq!((chord.root(), chord.quality(), chord.bass()), (Note::from_str("F#3").unwrap(), ChordQuality::HalfDiminished7, Note::from_str("C4").unwrap()));
+        assert_eq!(chord.inversion(), Some(2));
+
+        let chord = Chord::from_symbol("Bbmaj9#11").unwrap();
+        assert_eq!((chord.root(), chord.quality()), (Note::from_str("Bb3").unwrap(), ChordQuality::Major9));
+        assert_eq!(chord.alterations(), &[Interval::from_str("A11").unwrap()]);
+        assert_eq!(chord.tones(), ["Bb3", "D4", "F4", "A4", "C5", "E5"].map(|x| Note::from_str(x).unwrap()).to_vec());
+
+        let chord = Chord::from_symbol("G7alt").unwrap();
+        assert_eq!((chord.root(), chord.quality()), (Note::from_str("G3").unwrap(), ChordQuality::Altered));
+        let chord = Chord::from_symbol("Ebsus4").unwrap();
+        assert_eq!((chord.root(), chord.quality()), (Note::from_str("Eb3").unwrap(), ChordQuality::Sus4));
+        let chord = Chord::from_symbol("C").unwrap();
+        assert_eq!((chord.root(), chord.quality()), (Note::from_str("C3").unwrap(), ChordQuality::Major));
+        let chord = Chord::from_symbol("Dbb-7").unwrap();
+        assert_eq!((chord.root(), chord.quality()), (Note::from_str("Dbb3").unwrap(), ChordQuality::Minor7));
+        let chord = Chord::from_symbol("AΔ7").unwrap();
+        assert_eq!((chord.root(), chord.quality()), (Note::from_str("A3").unwrap(), ChordQuality::Major7));
+        let chord = Chord::from_symbol("Bø7").unwrap();
+        assert_eq!((chord.root(), chord.quality()), (Note::from_str("B3").unwrap(), ChordQuality::HalfDiminished7));
+        let chord = Chord::from_symbol("C#°7").unwrap();
+        assert_eq!((chord.root(), chord.quality()), (Note::from_str("C#3").unwrap(), ChordQuality::Diminished7));
+        let chord = Chord::from_symbol("Fm(maj7)").unwrap();
+        assert_eq!((chord.root(), chord.quality()), (Note::from_str("F3").unwrap(), ChordQuality::MinorMajor7));
+        let chord = Chord::from_symbol("E7/G#").unwrap();
+        assert_eq!((chord.root(), chord.quality(), chord.inversion()), (Note::from_str("E3").unwrap(), ChordQuality::Dominant7, Some(1)));
+
+        // single alterations fold into their dedicated quality
+        let chord = Chord::from_symbol("G7b9").unwrap();
+        assert_eq!((chord.quality(), chord.alterations()), (ChordQuality::Dominant7Flat9, &[][..]));
+        let chord = Chord::from_symbol("C7(#5)").unwrap();
+        assert_eq!((chord.quality(), chord.alterations()), (ChordQuality::Augmented7, &[][..]));
+
+        let chord = Chord::from_symbol("C7(b9,#9)").unwrap();
+        assert_eq!(chord.quality(), ChordQuality::Dominant7);
+        assert_eq!(chord.alterations(), &[Interval::from_str("m9").unwrap(), Interval::from_str("A9").unwrap()]);
+        let chord = Chord::from_symbol("C7b9#11").unwrap();
+        assert_eq!(chord.alterations(), &[Interval::from_str("m9").unwrap(), Interval::from_str("A11").unwrap()]);
+    }
+
+    #[test]
+    fn from_symbol_errors() {
+        let error = |s: &str| Chord::from_symbol(s).unwrap_err();
+        assert_eq!(error(""), HarmonyError::parse("", 0, "", &["C", "D", "E", "F", "G", "A", "B"]));
+        assert_eq!(error("H7"), HarmonyError::parse("H7", 0, "H", &["C", "D", "E", "F", "G", "A", "B"]));
+        assert_eq!(error("Cmaj7x"), HarmonyError::parse("Cmaj7x", 5, "x", &["/", "(", "b", "#"]));
+        assert_eq!(error("C7#3"), HarmonyError::parse("C7#3", 3, "3", &["5", "9", "11"]));
+        assert_eq!(error("C7b"), HarmonyError::parse("C7b", 3, "", &["5", "9", "11", "13"]));
+        assert_eq!(error("C7(b9"), HarmonyError::parse("C7(b9", 5, "", &[")", ",", "b", "#"]));
+        assert_eq!(error("C7(b9x)"), HarmonyError::parse("C7(b9x)", 5, "x", &[")", ",", "b", "#"]));
+        assert_eq!(error("F#m7/"), HarmonyError::parse("F#m7/", 5, "", &["C", "D", "E", "F", "G", "A", "B"]));
+        assert_eq!(error("C/E7"), HarmonyError::parse("C/E7", 3, "7", &[]));
+        assert_eq!(error("B##aug"), HarmonyError::parse("B##aug", 0, "B##aug", &[]));
+        assert_eq!(error("Fbbdim7"), HarmonyError::parse("Fbbdim7", 0, "Fbbdim7", &[]));
+        assert_eq!(error("B#7b5#9"), HarmonyError::parse("B#7b5#9", 5, "#9", &[]));
+        assert_eq!(error("B#7(b5,#9)"), HarmonyError::parse("B#7(b5,#9)", 7, "#9", &[]));
+        assert_eq!(error("F#m7/X").position(), Some(5));
+        assert_eq!(error("AΔ7/Q").position(), Some(4));
+        assert_eq!(format!("{}", error("Cmaj7x")),
+            "Unexpected \"x\" in \"Cmaj7x\" at position 5, expected one of \"/\", \"(\", \"b\", \"#\"");
+    }
+
+    #[test]
+    fn to_symbol() {
+        let symbol = |s: &str, style: ChordSymbolStyle| Chord::from_symbol(s).unwrap().to_symbol(style);
+        assert_eq!(symbol("F#m7b5/C", ChordSymbolStyle::Ascii), "F#m7b5/C");
+        assert_eq!(symbol("F#m7b5/C", ChordSymbolStyle::Jazz), "F#ø7/C");
+        assert_eq!(symbol("F#m7b5/C", ChordSymbolStyle::Classical), "F#ø7/C");
+        assert_eq!(symbol("Bbmaj9#11", ChordSymbolStyle::Ascii), "Bbmaj9#11");
+        assert_eq!(symbol("Bbmaj9#11", ChordSymbolStyle::Jazz), "BbΔ9#11");
+        assert_eq!(symbol("Cm7", ChordSymbolStyle::Jazz), "C-7");
+        assert_eq!(symbol("Cm7", ChordSymbolStyle::Classical), "Cm7");
+        assert_eq!(symbol("Bdim", ChordSymbolStyle::Ascii), "Bdim");
+        assert_eq!(symbol("Bdim", ChordSymbolStyle::Classical), "B°");
+        assert_eq!(symbol("Bdim7", ChordSymbolStyle::Jazz), "B°7");
+        assert_eq!(symbol("Eaug", ChordSymbolStyle::Jazz), "E+");
+        assert_eq!(symbol("G7alt", ChordSymbolStyle::Classical), "G7alt");
+        assert_eq!(symbol("Ebsus", ChordSymbolStyle::Ascii), "Ebsus4");
+        assert_eq!(symbol("C(#11)", ChordSymbolStyle::Ascii), "C(#11)");
+        assert_eq!(symbol("C7#9b9", ChordSymbolStyle::Ascii), "C7b9#9");
+    }
+
+    #[test]
+    fn round_trip() {
+        for style in [ChordSymbolStyle::Ascii, ChordSymbolStyle::Jazz, ChordSymbolStyle::Classical] {
+            for quality in ChordQuality::ALL {
+                for root in ["C3", "F#3", "Bb3"] {
+                    let chord = Chord::from_root(Note::from_str(root).unwrap(), quality).unwrap();
+                    let symbol = chord.to_symbol(style);
+                    assert_eq!(Chord::from_symbol(&symbol), Ok(chord.clone()), "{}", symbol);
+
+                    let slash = chord.with_bass(Note::from_str("Ab2").unwrap()).unwrap();
+                    let symbol = slash.to_symbol(style);
+                    let parsed = Chord::from_symbol(&symbol).unwrap();
+                    assert_eq!((parsed.root(), parsed.quality(), parsed.inversion()),
+                               (slash.root(), slash.quality(), slash.inversion()), "{}", symbol);
+                }
+            }
+        }
+    }
+}
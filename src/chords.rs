@@ -0,0 +1,593 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::notes::Note;
+use crate::intervals::Interval;
+use crate::notesequences::NoteSequence;
+use crate::errors::HarmonyError;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ChordQuality {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Sus2,
+    Sus4,
+    Major6,
+    Minor6,
+    Dominant7,
+    Major7,
+    Minor7,
+    MinorMajor7,
+    HalfDiminished7,
+    Diminished7,
+    Augmented7,
+    AugmentedMajor7,
+    Dominant7Sus4,
+    Add9,
+    MinorAdd9,
+    Dominant9,
+    Major9,
+    Minor9,
+    Dominant11,
+    Minor11,
+    Dominant13,
+    Major13,
+    Minor13,
+    Dominant7Flat5,
+    Dominant7Flat9,
+    Dominant7Sharp9,
+    Dominant7Sharp11,
+    Dominant7Flat13,
+    Altered,
+}
+
+impl ChordQuality {
+    pub const ALL: [ChordQuality; 33] = [
+        ChordQuality::Major, ChordQuality::Minor, ChordQuality::Diminished, ChordQuality::Augmented,
+        ChordQuality::Sus2, ChordQuality::Sus4, ChordQuality::Major6, ChordQuality::Minor6,
+        ChordQuality::Dominant7, ChordQuality::Major7, ChordQuality::Minor7, ChordQuality::MinorMajor7,
+        ChordQuality::HalfDiminished7, ChordQuality::Diminished7, ChordQuality::Augmented7,
+        ChordQuality::AugmentedMajor7, ChordQuality::Dominant7Sus4, ChordQuality::Add9, ChordQuality::MinorAdd9,
+        ChordQuality::Dominant9, ChordQuality::Major9, ChordQuality::Minor9, ChordQuality::Dominant11,
+        ChordQuality::Minor11, ChordQuality::Dominant13, ChordQuality::Major13, ChordQuality::Minor13,
+        ChordQuality::Dominant7Flat5, ChordQuality::Dominant7Flat9, ChordQuality::Dominant7Sharp9,
+        ChordQuality::Dominant7Sharp11, ChordQuality::Dominant7Flat13, ChordQuality::Altered,
+    ];
+
+    fn interval_strs(self) -> &'static [&'static str] {
+        match self {
+            ChordQuality::Major =>            &["P1", "M3", "P5"],
+            ChordQuality::Minor =>            &["P1", "m3", "P5"],
+            ChordQuality::Diminished =>       &["P1", "m3", "d5"],
+            ChordQuality::Augmented =>        &["P1", "M3", "A5"],
+            ChordQuality::Sus2 =>             &["P1", "M2", "P5"],
+            ChordQuality::Sus4 =>             &["P1", "P4", "P5"],
+            ChordQuality::Major6 =>           &["P1", "M3", "P5", "M6"],
+            ChordQuality::Minor6 =>           &["P1", "m3", "P5", "M6"],
+            ChordQuality::Dominant7 =>        &["P1", "M3", "P5", "m7"],
+            ChordQuality::Major7 =>           &["P1", "M3", "P5", "M7"],
+            ChordQuality::Minor7 =>           &["P1", "m3", "P5", "m7"],
+            ChordQuality::MinorMajor7 =>      &["P1", "m3", "P5", "M7"],
+            ChordQuality::HalfDiminished7 =>  &["P1", "m3", "d5", "m7"],
+            ChordQuality::Diminished7 =>      &["P1", "m3", "d5", "d7"],
+            ChordQuality::Augmented7 =>       &["P1", "M3", "A5", "m7"],
+            ChordQuality::AugmentedMajor7 =>  &["P1", "M3", "A5", "M7"],
+            ChordQuality::Dominant7Sus4 =>    &["P1", "P4", "P5", "m7"],
+            ChordQuality::Add9 =>             &["P1", "M3", "P5", "M9"],
+            ChordQuality::MinorAdd9 =>        &["P1", "m3", "P5", "M9"],
+            ChordQuality::Dominant9 =>        &["P1", "M3", "P5", "m7", "M9"],
+            ChordQuality::Major9 =>           &["P1", "M3", "P5", "M7", "M9"],
+            ChordQuality::Minor9 =>           &["P1", "m3", "P5", "m7", "M9"],
+            ChordQuality::Dominant11 =>       &["P1", "M3", "P5", "m7", "M9", "P11"],
+            ChordQuality::Minor11 =>          &["P1", "m3", "P5", "m7", "M9", "P11"],
+            ChordQuality::Dominant13 =>       &["P1", "M3", "P5", "m7", "M9", "M13"],
+            ChordQuality::Major13 =>          &["P1", "M3", "P5", "M7", "M9", "M13"],
+            ChordQuality::Minor13 =>          &["P1", "m3", "P5", "m7", "M9", "P11", "M13"],
+            ChordQuality::Dominant7Flat5 =>   &["P1", "M3", "d5", "m7"],
+            ChordQuality::Dominant7Flat9 =>   &["P1", "M3", "P5", "m7", "m9"],
+            ChordQuality::Dominant7Sharp9 =>  &["P1", "M3", "P5", "m7", "A9"],
+            ChordQuality::Dominant7Sharp11 => &["P1", "M3", "P5", "m7", "A11"],
+            ChordQuality::Dominant7Flat13 =>  &["P1", "M3", "P5", "m7", "m13"],
+            ChordQuality::Altered =>          &["P1", "M3", "m7", "m9", "A9", "A11", "m13"],
+        }
+    }
+
+    pub fn intervals(self) -> Vec<Interval> {
+        self.interval_strs().iter().map(|x| Interval::from_str(x).unwrap()).collect()
+    }
+
+    pub fn from_long_str(s: &str) -> Result<Self, HarmonyError> {
+        ChordQuality::ALL.iter()
+            .find(|x| x.to_long_str() == s.trim().to_lowercase())
+            .copied()
+            .ok_or_else(|| HarmonyError::parse(s, 0, s, &ChordQuality::ALL.map(|x| x.to_long_str())))
+    }
+
+    pub fn to_long_str(self) -> &'static str {
+        match self {
+            ChordQuality::Major => "major",
+            ChordQuality::Minor => "minor",
+            ChordQuality::Diminished => "diminished",
+            ChordQuality::Augmented => "augmented",
+            ChordQuality::Sus2 => "suspended second",
+            ChordQuality::Sus4 => "suspended fourth",
+            ChordQuality::Major6 => "major sixth",
+            ChordQuality::Minor6 => "minor sixth",
+            ChordQuality::Dominant7 => "dominant seventh",
+            ChordQuality::Major7 => "major seventh",
+            ChordQuality::Minor7 => "minor seventh",
+            ChordQuality::MinorMajor7 => "minor major seventh",
+            ChordQuality::HalfDiminished7 => "half-diminished seventh",
+            ChordQuality::Diminished7 => "diminished seventh",
+            ChordQuality::Augmented7 => "augmented seventh",
+            ChordQuality::AugmentedMajor7 => "augmented major seventh",
+            ChordQuality::Dominant7Sus4 => "dominant seventh suspended fourth",
+            ChordQuality::Add9 => "added ninth",
+            ChordQuality::MinorAdd9 => "minor added ninth",
+            ChordQuality::Dominant9 => "dominant ninth",
+            ChordQuality::Major9 => "major ninth",
+            ChordQuality::Minor9 => "minor ninth",
+            ChordQuality::Dominant11 => "dominant eleventh",
+            ChordQuality::Minor11 => "minor eleventh",
+            ChordQuality::Dominant13 => "dominant thirteenth",
+            ChordQuality::Major13 => "major thirteenth",
+            ChordQuality::Minor13 => "minor thirteenth",
+            ChordQuality::Dominant7Flat5 => "dominant seventh flat five",
+            ChordQuality::Dominant7Flat9 => "dominant seventh flat nine",
+            ChordQuality::Dominant7Sharp9 => "dominant seventh sharp nine",
+            ChordQuality::Dominant7Sharp11 => "dominant seventh sharp eleven",
+            ChordQuality::Dominant7Flat13 => "dominant seventh flat thirteen",
+            ChordQuality::Altered => "altered dominant",
+        }
+    }
+}
+
+impl FromStr for ChordQuality {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_long_str(s)
+    }
+}
+
+impl fmt::Display for ChordQuality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_long_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Chord {
+    root: Note,
+    quality: ChordQuality,
+    alterations: Vec<Interval>,
+    bass: Note,
+    notesequence: NoteSequence,
+}
+
+impl Chord {
+    pub fn from_root(root: Note, quality: ChordQuality) -> Result<Self, HarmonyError> {
+        let mut notesequence = NoteSequence::empty();
+        for interval in quality.intervals() {
+            notesequence.notes.push(root.transpose(interval)?);
+        }
+        Ok(Self {
+            root, quality, alterations: Vec::new(), bass: root, notesequence,
+        })
+    }
+
+    pub fn alter(&self, alteration: Interval) -> Result<Self, HarmonyError> {
+        let mut chord = Self {
+            alterations: self.alterations.clone(),
+            ..self.clone()
+        };
+        chord.alterations.push(alteration);
+        // a bass on the altered tone takes its new spelling in the same octave
+        let mut bass = self.bass;
+        let altered = self.root.transpose(alteration)?;
+        let tones = chord.intervals().iter().map(|x| self.root.transpose(*x)).collect::<Result<Vec<_>, _>>()?;
+        if self.inversion().is_some() && altered.name() == bass.name() && !tones.iter().any(|x| x.same_spelling(&bass)) {
+            bass = Note::new(altered.name(), altered.accidental(), bass.octave());
+        }
+        chord.voiced(bass)
+    }
+
+    pub fn root(&self) -> Note {
+        self.root
+    }
+
+    pub fn quality(&self) -> ChordQuality {
+        self.quality
+    }
+
+    pub fn alterations(&self) -> &[Interval] {
+        &self.alterations
+    }
+
+    pub fn bass(&self) -> Note {
+        self.bass
+    }
+
+    pub fn notesequence(&self) -> &NoteSequence {
+        &self.notesequence
+    }
+
+    pub fn intervals(&self) -> Vec<Interval> {
+        let mut intervals = self.quality.intervals();
+        for alteration in &self.alterations {
+            // an altered tone replaces the unaltered tone of the same degree
+            intervals.retain(|x| self.alterations.contains(x) || x.steps() % 7 != alteration.steps() % 7);
+            if !intervals.contains(alteration) {
+                intervals.push(*alteration);
+            }
+        }
+        intervals.sort_by_key(|x| (x.steps(), x.hsteps()));
+        intervals
+    }
+
+    pub fn tones(&self) -> Vec<Note> {
+        self.intervals().iter()
+            .map(|x| self.root.transpose(*x).unwrap())
+            .collect()
+    }
+
+    pub fn inversion(&self) -> Option<usize> {
+        self.tones().iter().position(|x| x.same_spelling(&self.bass))
+    }
+
+    pub fn is_slash(&self) -> bool {
+        self.inversion().is_none()
+    }
+
+    pub fn invert(&self, inversion: usize) -> Result<Self, HarmonyError> {
+        let tones = self.tones();
+        if inversion >= tones.len() {
+            return Err(HarmonyError::InvalidArgument("Chord does not have enough tones for this inversion"));
+        }
+        let octave = Interval::from_str("P8").unwrap();
+        let mut notes = Vec::new();
+        for (itone, tone) in tones.iter().enumerate() {
+            if itone < inversion {
+                notes.push(tone.transpose(octave)?);
+            } else {
+                notes.push(*tone);
+            }
+        }
+        sort_by_pitch(&mut notes);
+        Ok(Self {
+            bass: tones[inversion],
+            notesequence: NoteSequence {notes},
+            ..self.clone()
+        })
+    }
+
+    pub fn with_bass(&self, bass: Note) -> Result<Self, HarmonyError> {
+        self.voiced(bass)
+    }
+
+    // For a bass without a meaningful octave, as in chord symbols. A chord tone
+    // makes an inversion, other notes go below the root.
+    pub fn with_bass_spelling(&self, bass: Note) -> Result<Self, HarmonyError> {
+        if let Some(tone) = self.tones().into_iter().find(|x| x.same_spelling(&bass)) {
+            return self.voiced(tone);
+        }
+        let mut bass = Note::new(bass.name(), bass.accidental(), self.root.octave());
+        while self.root.dist_hsteps(&bass) >= 0 {
+            bass = bass.transpose_down(Interval::from_str("P8").unwrap())?;
+        }
+        self.voiced(bass)
+    }
+
+    // the bass in its own octave and the other tones above it
+    fn voiced(&self, bass: Note) -> Result<Self, HarmonyError> {
+        let octave = Interval::from_str("P8").unwrap();
+        let mut notes = vec![bass];
+        for interval in self.intervals() {
+            let mut tone = self.root.transpose(interval)?;
+            if !tone.same_spelling(&bass) {
+                while bass.dist_hsteps(&tone) <= 0 {
+                    tone = tone.transpose(octave)?;
+                }
+                notes.push(tone);
+            }
+        }
+        sort_by_pitch(&mut notes);
+        Ok(Self {
+            bass,
+            notesequence: NoteSequence {notes},
+            ..self.clone()
+        })
+    }
+
+    pub fn identify(noteseq: &NoteSequence) -> Result<Self, HarmonyError> {
+        if noteseq.notes.len() < 3 {
+            return Err(HarmonyError::InvalidArgument("Note sequence too short! Must be at least three notes long!"));
+        }
+        let mut notes = noteseq.notes.clone();
+        sort_by_pitch(&mut notes);
+        let bass = notes[0];
+
+        // A candidate is ranked by whether it matches the spelling exactly,
+        // whether it is in root position and whether it needs a foreign bass.
+        let mut best: Option<((bool, bool, bool), Self)> = None;
+        for spelled in [true, false] {
+            for root in distinct(&notes, spelled) {
+                for quality in ChordQuality::ALL {
+                    let chord = match Self::from_root(root, quality) {
+                        Ok(chord) => chord,
+                        Err(_) => continue,
+                    };
+                    let tones = chord.tones();
+                    let slash = if same_classes(&notes, &tones, spelled) {
+                        false
+                    } else if !contains(&tones, &bass, spelled)
+                        && !contains(&notes[1..], &bass, spelled)
+                        && same_classes(&notes[1..], &tones, spelled) {
+                        true
+                    } else {
+                        continue;
+                    };
+                    let rank = (!spelled, !matches(&root, &bass, spelled), slash);
+                    if best.as_ref().is_none_or(|(best_rank, _)| rank < *best_rank) {
+                        let bass = if slash || spelled {
+                            bass
+                        } else {
+                            *tones.iter().find(|x| x.pitch_class() == bass.pitch_class()).unwrap()
+                        };
+                        best = Some((rank, Self {
+                            root: chord.root,
+                            quality,
+                            alterations: Vec::new(),
+                            bass,
+                            notesequence: noteseq.clone(),
+                        }));
+                    }
+                }
+            }
+            if best.is_some() {
+                break;
+            }
+        }
+        match best {
+            Some((_, chord)) => Ok(chord),
+            None => Err(HarmonyError::Unidentified("Unknown chord")),
+        }
+    }
+
+    // A seventh chord with exactly the spelled tones, inverted to the lowest note.
+    // Sixth chords like F6 are also seventh chords like Dm7/F.
+    pub fn identify_seventh(noteseq: &NoteSequence) -> Result<Option<Self>, HarmonyError> {
+        let mut notes = noteseq.notes.clone();
+        sort_by_pitch(&mut notes);
+        let Some(bass) = notes.first() else {
+            return Ok(None);
+        };
+        let sevenths = ChordQuality::ALL.into_iter()
+            .filter(|x| x.intervals().len() == 4 && x.intervals().iter().any(|y| y.number() == 7));
+        for quality in sevenths {
+            for root in distinct(&notes, true) {
+                let chord = Self::from_root(root, quality)?;
+                if same_classes(&notes, &chord.tones(), true) {
+                    return Ok(Some(chord.with_bass(*bass)?));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn sort_by_pitch(notes: &mut [Note]) {
+    notes.sort_by(|a, b| {
+        b.dist_hsteps(a).cmp(&0).then(b.dist_steps(a).cmp(&0))
+    });
+}
+
+fn matches(note1: &Note, note2: &Note, spelled: bool) -> bool {
+    if spelled {
+        note1.same_spelling(note2)
+    } else {
+        note1.pitch_class() == note2.pitch_class()
+    }
+}
+
+fn contains(notes: &[Note], note: &Note, spelled: bool) -> bool {
+    notes.iter().any(|x| matches(x, note, spelled))
+}
+
+fn distinct(notes: &[Note], spelled: bool) -> Vec<Note> {
+    let mut distinct_notes: Vec<Note> = Vec::new();
+    for note in notes {
+        if !contains(&distinct_notes, note, true) && (spelled || !contains(&distinct_notes, note, false)) {
+            distinct_notes.push(*note);
+        }
+    }
+    distinct_notes
+}
+
+fn same_classes(notes: &[Note], tones: &[Note], spelled: bool) -> bool {
+    notes.iter().all(|x| contains(tones, x, spelled))
+        && tones.iter().all(|x| contains(notes, x, spelled))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord_notes(root: &str, quality: ChordQuality) -> NoteSequence {
+        Chord::from_root(Note::from_str(root).unwrap(), quality).unwrap().notesequence
+    }
+
+    #[test]
+    fn from_root_triads() {
+        assert_eq!(chord_notes("C4", ChordQuality::Major), NoteSequence::from_strs(["C4", "E4", "G4"].to_vec()).unwrap());
+        assert_eq!(chord_notes("C4", ChordQuality::Minor), NoteSequence::from_strs(["C4", "Eb4", "G4"].to_vec()).unwrap());
+        assert_eq!(chord_notes("B3", ChordQuality::Diminished), NoteSequence::from_strs(["B3", "D4", "F4"].to_vec()).unwrap());
+        assert_eq!(chord_notes("Ab3", ChordQuality::Augmented), NoteSequence::from_strs(["Ab3", "C4", "E4"].to_vec()).unwrap());
+        assert_eq!(chord_notes("D4", ChordQuality::Sus2), NoteSequence::from_strs(["D4", "E4", "A4"].to_vec()).unwrap());
+        assert_eq!(chord_notes("Eb4", ChordQuality::Sus4), NoteSequence::from_strs(["Eb4", "Ab4", "Bb4"].to_vec()).unwrap());
+        assert_eq!(chord_notes("F#3", ChordQuality::Major), NoteSequence::from_strs(["F#3", "A#3", "C#4"].to_vec()).unwrap());
+        assert_eq!(chord_notes("G#3", ChordQuality::Augmented), NoteSequence::from_strs(["G#3", "B#3", "D##4"].to_vec()).unwrap());
+    }
+
+    #[test]
+    fn from_root_sevenths() {
+        assert_eq!(chord_notes("G3", ChordQuality::Dominant7), NoteSequence::from_strs(["G3", "B3", "D4", "F4"].to_vec()).unwrap());
+        assert_eq!(chord_notes("Db4", ChordQuality::Major7), NoteSequence::from_strs(["Db4", "F4", "Ab4", "C5"].to_vec()).unwrap());
+        assert_eq!(chord_notes("F#3", ChordQuality::HalfDiminished7), NoteSequence::from_strs(["F#3", "A3", "C4", "E4"].to_vec()).unwrap());
+        assert_eq!(chord_notes("C#4", ChordQuality::Diminished7), NoteSequence::from_strs(["C#4", "E4", "G4", "Bb4"].to_vec()).unwrap());
+        assert_eq!(chord_notes("A3", ChordQuality::MinorMajor7), NoteSequence::from_strs(["A3", "C4", "E4", "G#4"].to_vec()).unwrap());
+        assert_eq!(chord_notes("C4", ChordQuality::Major6), NoteSequence::from_strs(["C4", "E4", "G4", "A4"].to_vec()).unwrap());
+    }
+
+    #[test]
+    fn from_root_extended() {
+        assert_eq!(chord_notes("C4", ChordQuality::Dominant9), NoteSequence::from_strs(["C4", "E4", "G4", "Bb4", "D5"].to_vec()).unwrap());
+        assert_eq!(chord_notes("Bb3", ChordQuality::Major9), NoteSequence::from_strs(["Bb3", "D4", "F4", "A4", "C5"].to_vec()).unwrap());
+        assert_eq!(chord_notes("D3", ChordQuality::Minor11), NoteSequence::from_strs(["D3", "F3", "A3", "C4", "E4", "G4"].to_vec()).unwrap());
+        assert_eq!(chord_notes("G3", ChordQuality::Dominant13), NoteSequence::from_strs(["G3", "B3", "D4", "F4", "A4", "E5"].to_vec()).unwrap());
+        assert_eq!(chord_notes("E3", ChordQuality::Add9), NoteSequence::from_strs(["E3", "G#3", "B3", "F#4"].to_vec()).unwrap());
+        assert_eq!(chord_notes("G3", ChordQuality::Dominant7Flat9), NoteSequence::from_strs(["G3", "B3", "D4", "F4", "Ab4"].to_vec()).unwrap());
+        assert_eq!(chord_notes("G3", ChordQuality::Altered), NoteSequence::from_strs(["G3", "B3", "F4", "Ab4", "A#4", "C#5", "Eb5"].to_vec()).unwrap());
+
+        assert!(Chord::from_root(Note::from_str("B##3").unwrap(), ChordQuality::Augmented).is_err());
+    }
+
+    #[test]
+    fn alter() {
+        let chord = Chord::from_root(Note::from_str("Bb3").unwrap(), ChordQuality::Major9).unwrap();
+        let altered = chord.alter(Interval::from_str("A11").unwrap()).unwrap();
+        assert_eq!(altered.notesequence, NoteSequence::from_strs(["Bb3", "D4", "F4", "A4", "C5", "E5"].to_vec()).unwrap());
+        assert_eq!(altered.alterations(), &[Interval::from_str("A11").unwrap()]);
+
+        let chord = Chord::from_root(Note::from_str("C4").unwrap(), ChordQuality::Dominant7).unwrap();
+        let altered = chord.alter(Interval::from_str("A5").unwrap()).unwrap();
+        assert_eq!(altered.notesequence, NoteSequence::from_strs(["C4", "E4", "G#4", "Bb4"].to_vec()).unwrap());
+        let altered = altered.alter(Interval::from_str("m9").unwrap()).unwrap()
+                             .alter(Interval::from_str("A9").unwrap()).unwrap();
+        assert_eq!(altered.notesequence, NoteSequence::from_strs(["C4", "E4", "G#4", "Bb4", "Db5", "D#5"].to_vec()).unwrap());
+
+        let chord = Chord::from_root(Note::from_str("C4").unwrap(), ChordQuality::Dominant9).unwrap()
+            .with_bass(Note::from_str("E3").unwrap()).unwrap();
+        let altered = chord.alter(Interval::from_str("A9").unwrap()).unwrap();
+        assert_eq!(altered.bass(), Note::from_str("E3").unwrap());
+        assert_eq!(altered.inversion(), Some(1));
+
+        // with_bass before alter keeps the bass octave, an altered bass tone is respelled
+        let chord = Chord::from_root(Note::from_str("C4").unwrap(), ChordQuality::Major).unwrap()
+            .with_bass(Note::from_str("G2").unwrap()).unwrap();
+        let altered = chord.alter(Interval::from_str("A5").unwrap()).unwrap();
+        assert_eq!(altered.bass(), Note::from_str("G#2").unwrap());
+        assert_eq!(altered.notesequence, NoteSequence::from_strs(["G#2", "C4", "E4"].to_vec()).unwrap());
+        assert_eq!(altered.inversion(), Some(2));
+        let chord = Chord::from_root(Note::from_str("C4").unwrap(), ChordQuality::Dominant7).unwrap()
+            .with_bass(Note::from_str("D3").unwrap()).unwrap();
+        let altered = chord.alter(Interval::from_str("m9").unwrap()).unwrap();
+        assert_eq!(altered.bass(), Note::from_str("D3").unwrap());
+        assert_eq!(altered.notesequence, NoteSequence::from_strs(["D3", "C4", "E4", "G4", "Bb4", "Db5"].to_vec()).unwrap());
+        let chord = Chord::identify(&NoteSequence::from_strs(["F#3", "D4", "A4"].to_vec()).unwrap()).unwrap();
+        let altered = chord.alter(Interval::from_str("A5").unwrap()).unwrap();
+        assert_eq!(altered.bass(), Note::from_str("F#3").unwrap());
+        assert_eq!(altered.notesequence.notes[0], Note::from_str("F#3").unwrap());
+    }
+
+    #[test]
+    fn invert() {
+        let chord = Chord::from_root(Note::from_str("C4").unwrap(), ChordQuality::Major).unwrap();
+        assert_eq!(chord.invert(0).unwrap().notesequence, NoteSequence::from_strs(["C4", "E4", "G4"].to_vec()).unwrap());
+        assert_eq!(chord.invert(1).unwrap().notesequence, NoteSequence::from_strs(["E4", "G4", "C5"].to_vec()).unwrap());
+        assert_eq!(chord.invert(2).unwrap().notesequence, NoteSequence::from_strs(["G4", "C5", "E5"].to_vec()).unwrap());
+        assert_eq!(chord.invert(1).unwrap().inversion(), Some(1));
+        assert!(chord.invert(3).is_err());
+
+        let chord = Chord::from_root(Note::from_str("G3").unwrap(), ChordQuality::Dominant7).unwrap();
+        assert_eq!(chord.invert(3).unwrap().notesequence, NoteSequence::from_strs(["F4", "G4", "B4", "D5"].to_vec()).unwrap());
+        assert_eq!(chord.invert(3).unwrap().bass(), Note::from_str("F4").unwrap());
+    }
+
+    #[test]
+    fn with_bass() {
+        let chord = Chord::from_root(Note::from_str("D4").unwrap(), ChordQuality::Major).unwrap();
+        let slash = chord.with_bass(Note::from_str("C4").unwrap()).unwrap();
+        assert_eq!(slash.notesequence, NoteSequence::from_strs(["C4", "D4", "F#4", "A4"].to_vec()).unwrap());
+        assert!(slash.is_slash());
+        assert_eq!(slash.inversion(), None);
+
+        let inverted = chord.with_bass(Note::from_str("F#2").unwrap()).unwrap();
+        assert_eq!(inverted.inversion(), Some(1));
+        assert!(!inverted.is_slash());
+        assert_eq!(inverted.bass(), Note::from_str("F#2").unwrap());
+        assert_eq!(inverted.notesequence, NoteSequence::from_strs(["F#2", "D4", "A4"].to_vec()).unwrap());
+        let inverted = chord.with_bass(Note::from_str("A4").unwrap()).unwrap();
+        assert_eq!(inverted.notesequence, NoteSequence::from_strs(["A4", "D5", "F#5"].to_vec()).unwrap());
+
+        // without an octave a chord tone inverts, other notes go below the root
+        let inverted = chord.with_bass_spelling(Note::from_str("F#2").unwrap()).unwrap();
+        assert_eq!(inverted.notesequence, NoteSequence::from_strs(["F#4", "A4", "D5"].to_vec()).unwrap());
+        let slash = chord.with_bass_spelling(Note::from_str("E6").unwrap()).unwrap();
+        assert_eq!(slash.notesequence, NoteSequence::from_strs(["E3", "D4", "F#4", "A4"].to_vec()).unwrap());
+    }
+
+    #[test]
+    fn identify() {
+        let identify = |strs: Vec<&str>| Chord::identify(&NoteSequence::from_strs(strs).unwrap()).unwrap();
+
+        let chord = identify(["C4", "E4", "G4"].to_vec());
+        assert_eq!((chord.root(), chord.quality(), chord.inversion()), (Note::from_str("C4").unwrap(), ChordQuality::Major, Some(0)));
+        let chord = identify(["E3", "C4", "G4"].to_vec());
+        assert_eq!((chord.root(), chord.quality(), chord.inversion()), (Note::from_str("C4").unwrap(), ChordQuality::Major, Some(1)));
+        let chord = identify(["D4", "F4", "B4", "G3"].to_vec());
+        assert_eq!((chord.root(), chord.quality(), chord.inversion()), (Note::from_str("G3").unwrap(), ChordQuality::Dominant7, Some(0)));
+        let chord = identify(["F4", "G3", "B3", "D4"].to_vec());
+        assert_eq!((chord.root(), chord.quality(), chord.inversion()), (Note::from_str("G3").unwrap(), ChordQuality::Dominant7, Some(0)));
+        let chord = identify(["F3", "G3", "B3", "D4"].to_vec());
+        assert_eq!((chord.root(), chord.quality(), chord.inversion()), (Note::from_str("G3").unwrap(), ChordQuality::Dominant7, Some(3)));
+        let chord = identify(["F#3", "A3", "C4", "E4"].to_vec());
+        assert_eq!((chord.root(), chord.quality()), (Note::from_str("F#3").unwrap(), ChordQuality::HalfDiminished7));
+        let chord = identify(["G#3", "B3", "D4", "F4"].to_vec());
+        assert_eq!((chord.root(), chord.quality()), (Note::from_str("G#3").unwrap(), ChordQuality::Diminished7));
+        let chord = identify(["B3", "D4", "F4", "Ab4"].to_vec());
+        assert_eq!((chord.root(), chord.quality()), (Note::from_str("B3").unwrap(), ChordQuality::Diminished7));
+        let chord = identify(["C4", "E4", "G4", "Bb4", "D5"].to_vec());
+        assert_eq!((chord.root(), chord.quality()), (Note::from_str("C4").unwrap(), ChordQuality::Dominant9));
+
+        // same pitch classes, the bass decides
+        let chord = identify(["C4", "E4", "G4", "A4"].to_vec());
+        assert_eq!((chord.root(), chord.quality(), chord.inversion()), (Note::from_str("C4").unwrap(), ChordQuality::Major6, Some(0)));
+        let chord = identify(["A3", "C4", "E4", "G4"].to_vec());
+        assert_eq!((chord.root(), chord.quality(), chord.inversion()), (Note::from_str("A3").unwrap(), ChordQuality::Minor7, Some(0)));
+
+        let seventh = |strs: Vec<&str>| Chord::identify_seventh(&NoteSequence::from_strs(strs).unwrap()).unwrap()
+            .map(|x| (x.root(), x.quality(), x.inversion()));
+        assert_eq!(seventh(["C4", "E4", "G4", "A4"].to_vec()), Some((Note::from_str("A4").unwrap(), ChordQuality::Minor7, Some(1))));
+        assert_eq!(seventh(["F3", "Ab3", "C4", "D4"].to_vec()), Some((Note::from_str("D4").unwrap(), ChordQuality::HalfDiminished7, Some(1))));
+        assert_eq!(seventh(["G3", "B3", "D4", "F4"].to_vec()), Some((Note::from_str("G3").unwrap(), ChordQuality::Dominant7, Some(0))));
+        assert_eq!(seventh(["C4", "E4", "G4"].to_vec()), None);
+        assert_eq!(seventh(["C4", "E4", "G4", "D5"].to_vec()), None);
+
+        // slash chords
+        let chord = identify(["F#3", "C4", "E4", "G4"].to_vec());
+        assert_eq!((chord.root(), chord.quality(), chord.inversion()), (Note::from_str("C4").unwrap(), ChordQuality::Major, None));
+        assert_eq!(chord.bass(), Note::from_str("F#3").unwrap());
+        let chord = identify(["C3", "D4", "F#4", "A4"].to_vec());
+        assert_eq!((chord.root(), chord.quality(), chord.inversion()), (Note::from_str("D4").unwrap(), ChordQuality::Dominant7, Some(3)));
+
+        // enharmonic misspellings are still recognized
+        let chord = identify(["C4", "E4", "Ab4"].to_vec());
+        assert_eq!((chord.root(), chord.quality(), chord.inversion()), (Note::from_str("Ab4").unwrap(), ChordQuality::Augmented, Some(1)));
+        let chord = identify(["C4", "Fb4", "G4"].to_vec());
+        assert_eq!((chord.root(), chord.quality(), chord.inversion()), (Note::from_str("C4").unwrap(), ChordQuality::Major, Some(0)));
+
+        assert!(Chord::identify(&NoteSequence::from_strs(["C4", "E4"].to_vec()).unwrap()).is_err());
+        assert!(Chord::identify(&NoteSequence::from_strs(["C4", "C#4", "D4"].to_vec()).unwrap()).is_err());
+    }
+
+    #[test]
+    fn quality_traits() {
+        assert_eq!("half-diminished seventh".parse::<ChordQuality>(), Ok(ChordQuality::HalfDiminished7));
+        assert_eq!(format!("{}", ChordQuality::Dominant7Sharp9), "dominant seventh sharp nine");
+        assert_eq!(ChordQuality::from_long_str("mega").unwrap_err().position(), Some(0));
+        let notesequence = NoteSequence::from_strs(["C3", "C#3", "D3"].to_vec()).unwrap();
+        assert_eq!(Chord::identify(&notesequence), Err(HarmonyError::Unidentified("Unknown chord")));
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+
+use crate::notenames::NoteName;
+use crate::accidentals::Accidental;
+use crate::notes::Note;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Clef {
+    Treble,
+    Bass,
+    Alto,
+    Tenor,
+}
+
+impl Clef {
+    pub const ALL: [Clef; 4] = [Clef::Treble, Clef::Bass, Clef::Alto, Clef::Tenor];
+
+    // Treble or bass clef, whichever needs fewer ledger lines for the notes.
+    pub fn from_range(notes: &[Note]) -> Self {
+        let middle_c = Note::new(NoteName::C, Accidental::Natural, 4);
+        let hsteps: Vec<i32> = notes.iter().map(|x| middle_c.dist_hsteps(x)).collect();
+        let (Some(lowest), Some(highest)) = (hsteps.iter().min(), hsteps.iter().max()) else {
+            return Clef::Treble;
+        };
+        if *lowest >= -5 {
+            Clef::Treble
+        } else if *highest <= 4 {
+            Clef::Bass
+        } else if hsteps.iter().sum::<i32>() >= 0 {
+            Clef::Treble
+        } else {
+            Clef::Bass
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Clef::Treble => "treble",
+            Clef::Bass => "bass",
+            Clef::Alto => "alto",
+            Clef::Tenor => "tenor",
+        }
+    }
+}
+
+impl fmt::Display for Clef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notesequences::NoteSequence;
+
+    #[test]
+    fn from_range() {
+        assert_eq!(Clef::from_range(&NoteSequence::from_strs(["C4", "E4", "G4"].to_vec()).unwrap().notes), Clef::Treble);
+        assert_eq!(Clef::from_range(&NoteSequence::from_strs(["G3", "A5"].to_vec()).unwrap().notes), Clef::Treble);
+        assert_eq!(Clef::from_range(&NoteSequence::from_strs(["C2", "E4"].to_vec()).unwrap().notes), Clef::Bass);
+        assert_eq!(Clef::from_range(&NoteSequence::from_strs(["C3", "G4"].to_vec()).unwrap().notes), Clef::Bass);
+        assert_eq!(Clef::from_range(&NoteSequence::from_strs(["E3", "C5"].to_vec()).unwrap().notes), Clef::Treble);
+        assert_eq!(Clef::from_range(&[]), Clef::Treble);
+        assert_eq!(Clef::Tenor.to_str(), "tenor");
+    }
+}
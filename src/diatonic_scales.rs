@@ -1,7 +1,10 @@
 use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
 use crate::notes::Note;
 use crate::accidentals::Accidental;
 use crate::notesequences::NoteSequence;
+use crate::errors::HarmonyError;
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum Mode {
@@ -12,38 +15,131 @@ pub enum Mode {
     Mixolydian,
     Aeolian,
     Locrian,
+    HarmonicMinor,
+    LocrianNatural6,
+    IonianAugmented,
+    DorianSharp4,
+    PhrygianDominant,
+    LydianSharp2,
+    Ultralocrian,
+    MelodicMinor,
+    DorianFlat2,
+    LydianAugmented,
+    LydianDominant,
+    MixolydianFlat6,
+    LocrianSharp2,
+    Altered,
 }
 
 impl Mode {
-    fn identify(noteseq: &NoteSequence) -> Result<Self, &'static str> {
+    pub const ALL: [Mode; 21] = [
+        Mode::Ionian, Mode::Dorian, Mode::Phrygian, Mode::Lydian,
+        Mode::Mixolydian, Mode::Aeolian, Mode::Locrian,
+        Mode::HarmonicMinor, Mode::LocrianNatural6, Mode::IonianAugmented, Mode::DorianSharp4,
+        Mode::PhrygianDominant, Mode::LydianSharp2, Mode::Ultralocrian,
+        Mode::MelodicMinor, Mode::DorianFlat2, Mode::LydianAugmented, Mode::LydianDominant,
+        Mode::MixolydianFlat6, Mode::LocrianSharp2, Mode::Altered,
+    ];
+
+    pub fn identify(noteseq: &NoteSequence) -> Result<Self, HarmonyError> {
         match noteseq.notes.len().cmp(&7) {
-            Ordering::Less => return Err("Note sequence to short! Must be seven notes long!"),
+            Ordering::Less => return Err(HarmonyError::InvalidArgument("Note sequence to short! Must be seven notes long!")),
             Ordering::Equal => (),
-            Ordering::Greater => return Err("Note sequence to long! Must be seven notes long!"),
+            Ordering::Greater => return Err(HarmonyError::InvalidArgument("Note sequence to long! Must be seven notes long!")),
         }
         let steps: Vec<i32> = noteseq.notes.windows(2).map(|x| x[0].dist_hsteps(&x[1])).collect();
-        for mode in [Mode::Ionian, Mode::Dorian, Mode::Phrygian, Mode::Lydian,
-                     Mode::Mixolydian, Mode::Aeolian, Mode::Locrian] {
+        for mode in Mode::ALL {
             if steps == mode.get_dists() {return Ok(mode);}
         }
-        Err("Unknown diatonic note sequence mode")
+        Err(HarmonyError::Unidentified("Unknown diatonic note sequence mode"))
+    }
+
+    pub fn from_str(s: &str) -> Result<Self, HarmonyError> {
+        Mode::ALL.iter()
+            .find(|x| x.to_str().to_lowercase() == s.trim().to_lowercase())
+            .copied()
+            .ok_or_else(|| HarmonyError::parse(s, 0, s, &Mode::ALL.map(|x| x.to_str())))
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Mode::Ionian => "Ionian",
+            Mode::Dorian => "Dorian",
+            Mode::Phrygian => "Phrygian",
+            Mode::Lydian => "Lydian",
+            Mode::Mixolydian => "Mixolydian",
+            Mode::Aeolian => "Aeolian",
+            Mode::Locrian => "Locrian",
+            Mode::HarmonicMinor => "Harmonic minor",
+            Mode::LocrianNatural6 => "Locrian natural 6",
+            Mode::IonianAugmented => "Ionian augmented",
+            Mode::DorianSharp4 => "Dorian #4",
+            Mode::PhrygianDominant => "Phrygian dominant",
+            Mode::LydianSharp2 => "Lydian #2",
+            Mode::Ultralocrian => "Ultralocrian",
+            Mode::MelodicMinor => "Melodic minor",
+            Mode::DorianFlat2 => "Dorian b2",
+            Mode::LydianAugmented => "Lydian augmented",
+            Mode::LydianDominant => "Lydian dominant",
+            Mode::MixolydianFlat6 => "Mixolydian b6",
+            Mode::LocrianSharp2 => "Locrian #2",
+            Mode::Altered => "Altered",
+        }
     }
 
-    fn get_dists(self) -> Vec<i32> {
-        let mut dists = vec![2,2,1,2,2,2,1];
+    // the first mode of the scale this mode is a rotation of, and the rotation
+    pub fn family(self) -> (Mode, usize) {
         match self {
-            Mode::Ionian =>     dists.rotate_left(0), 
-            Mode::Dorian =>     dists.rotate_left(1), 
-            Mode::Phrygian =>   dists.rotate_left(2), 
-            Mode::Lydian =>     dists.rotate_left(3), 
-            Mode::Mixolydian => dists.rotate_left(4),
-            Mode::Aeolian =>    dists.rotate_left(5),
-            Mode::Locrian =>    dists.rotate_left(6),
+            Mode::Ionian =>           (Mode::Ionian, 0),
+            Mode::Dorian =>           (Mode::Ionian, 1),
+            Mode::Phrygian =>         (Mode::Ionian, 2),
+            Mode::Lydian =>           (Mode::Ionian, 3),
+            Mode::Mixolydian =>       (Mode::Ionian, 4),
+            Mode::Aeolian =>          (Mode::Ionian, 5),
+            Mode::Locrian =>          (Mode::Ionian, 6),
+            Mode::HarmonicMinor =>    (Mode::HarmonicMinor, 0),
+            Mode::LocrianNatural6 =>  (Mode::HarmonicMinor, 1),
+            Mode::IonianAugmented =>  (Mode::HarmonicMinor, 2),
+            Mode::DorianSharp4 =>     (Mode::HarmonicMinor, 3),
+            Mode::PhrygianDominant => (Mode::HarmonicMinor, 4),
+            Mode::LydianSharp2 =>     (Mode::HarmonicMinor, 5),
+            Mode::Ultralocrian =>     (Mode::HarmonicMinor, 6),
+            Mode::MelodicMinor =>     (Mode::MelodicMinor, 0),
+            Mode::DorianFlat2 =>      (Mode::MelodicMinor, 1),
+            Mode::LydianAugmented =>  (Mode::MelodicMinor, 2),
+            Mode::LydianDominant =>   (Mode::MelodicMinor, 3),
+            Mode::MixolydianFlat6 =>  (Mode::MelodicMinor, 4),
+            Mode::LocrianSharp2 =>    (Mode::MelodicMinor, 5),
+            Mode::Altered =>          (Mode::MelodicMinor, 6),
         }
+    }
+
+    pub fn get_dists(self) -> Vec<i32> {
+        let (family, rotation) = self.family();
+        let mut dists = match family {
+            Mode::Ionian => [2,2,1,2,2,2,1],
+            Mode::HarmonicMinor => [2,1,2,2,1,3,1],
+            _ => [2,1,2,2,2,2,1],
+        };
+        dists.rotate_left(rotation);
         dists[..6].to_vec()
     }
 }
 
+impl FromStr for Mode {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str(s)
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct DiatonicScale {
     tonic: Note,
@@ -52,7 +148,7 @@ pub struct DiatonicScale {
 }
 
 impl DiatonicScale {
-    pub fn from_tonic(tonic: Note, mode: Mode) -> DiatonicScale {
+    pub fn from_tonic(tonic: Note, mode: Mode) -> Result<DiatonicScale, HarmonyError> {
         let mut scale = DiatonicScale {
             tonic, mode, notesequence: NoteSequence::empty(),
         };
@@ -62,18 +158,36 @@ impl DiatonicScale {
         }
 
         let dists = mode.get_dists();
-        for inote in 0..scale.notesequence.notes.len()-1 {
+        for (inote, dist) in dists.iter().enumerate() {
             scale.notesequence.notes[inote+1] = 
-                match scale.notesequence.notes[inote].dist_hsteps(&scale.notesequence.notes[inote+1])-dists[inote] {
+                match scale.notesequence.notes[inote].dist_hsteps(&scale.notesequence.notes[inote+1])-dist {
                     -2 => scale.notesequence.notes[inote+1].set_accidental(Accidental::Doublesharp),
                     -1 => scale.notesequence.notes[inote+1].set_accidental(Accidental::Sharp),
                      0 => scale.notesequence.notes[inote+1].set_accidental(Accidental::Natural),
                      1 => scale.notesequence.notes[inote+1].set_accidental(Accidental::Flat),
                      2 => scale.notesequence.notes[inote+1].set_accidental(Accidental::Doubleflat),
-                    _ => panic!("unknown accidental required"),
+                    _ => return Err(HarmonyError::OutOfRange("Scale would require more than double accidentals")),
             };
         }
-        scale
+        Ok(scale)
+    }
+
+    pub fn tonic(&self) -> Note {
+        self.tonic
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn notesequence(&self) -> &NoteSequence {
+        &self.notesequence
+    }
+}
+
+impl fmt::Display for DiatonicScale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.notesequence)
     }
 }
 
@@ -90,6 +204,48 @@ mod tests {
         assert_eq!(Mode::Mixolydian.get_dists(), vec![2,2,1,2,2,1]);
         assert_eq!(Mode::Aeolian.get_dists(),    vec![2,1,2,2,1,2]);
         assert_eq!(Mode::Locrian.get_dists(),    vec![1,2,2,1,2,2]);
+
+        assert_eq!(Mode::HarmonicMinor.get_dists(),    vec![2,1,2,2,1,3]);
+        assert_eq!(Mode::LocrianNatural6.get_dists(),  vec![1,2,2,1,3,1]);
+        assert_eq!(Mode::IonianAugmented.get_dists(),  vec![2,2,1,3,1,2]);
+        assert_eq!(Mode::DorianSharp4.get_dists(),     vec![2,1,3,1,2,1]);
+        assert_eq!(Mode::PhrygianDominant.get_dists(), vec![1,3,1,2,1,2]);
+        assert_eq!(Mode::LydianSharp2.get_dists(),     vec![3,1,2,1,2,2]);
+        assert_eq!(Mode::Ultralocrian.get_dists(),     vec![1,2,1,2,2,1]);
+
+        assert_eq!(Mode::MelodicMinor.get_dists(),    vec![2,1,2,2,2,2]);
+        assert_eq!(Mode::DorianFlat2.get_dists(),     vec![1,2,2,2,2,1]);
+        assert_eq!(Mode::LydianAugmented.get_dists(), vec![2,2,2,2,1,2]);
+        assert_eq!(Mode::LydianDominant.get_dists(),  vec![2,2,2,1,2,1]);
+        assert_eq!(Mode::MixolydianFlat6.get_dists(), vec![2,2,1,2,1,2]);
+        assert_eq!(Mode::LocrianSharp2.get_dists(),   vec![2,1,2,1,2,2]);
+        assert_eq!(Mode::Altered.get_dists(),         vec![1,2,1,2,2,2]);
+    }
+
+    #[test]
+    fn mode_family() {
+        assert_eq!(Mode::Ionian.family(), (Mode::Ionian, 0));
+        assert_eq!(Mode::Aeolian.family(), (Mode::Ionian, 5));
+        assert_eq!(Mode::PhrygianDominant.family(), (Mode::HarmonicMinor, 4));
+        assert_eq!(Mode::Altered.family(), (Mode::MelodicMinor, 6));
+        for mode in Mode::ALL {
+            let (family, rotation) = mode.family();
+            assert_eq!(family.family(), (family, 0));
+            let mut dists = family.get_dists();
+            dists.push(12 - dists.iter().sum::<i32>());
+            dists.rotate_left(rotation);
+            assert_eq!(dists[..6].to_vec(), mode.get_dists());
+        }
+    }
+
+    #[test]
+    fn mode_to_str() {
+        assert_eq!(Mode::Ionian.to_str(), "Ionian");
+        assert_eq!(Mode::Locrian.to_str(), "Locrian");
+        assert_eq!(Mode::PhrygianDominant.to_str(), "Phrygian dominant");
+        assert_eq!(Mode::LydianAugmented.to_str(), "Lydian augmented");
+        assert_eq!(Mode::LocrianSharp2.to_str(), "Locrian #2");
+        assert_eq!(Mode::Altered.to_str(), "Altered");
     }
 
     #[test]
@@ -127,164 +283,234 @@ mod tests {
         assert_eq!(Mode::identify(&locrian_notesequence), Ok(Mode::Locrian));
     }
 
+    #[test]
+    fn identify_minors() {
+        let modes = [
+            (Mode::HarmonicMinor,    ["C3","D3","Eb3","F3","G3","Ab3","B3"]),
+            (Mode::LocrianNatural6,  ["B3","C4","D4","E4","F4","G#4","A4"]),
+            (Mode::IonianAugmented,  ["C3","D3","E3","F3","G#3","A3","B3"]),
+            (Mode::DorianSharp4,     ["D3","E3","F3","G#3","A3","B3","C4"]),
+            (Mode::PhrygianDominant, ["E3","F3","G#3","A3","B3","C4","D4"]),
+            (Mode::LydianSharp2,     ["C3","D#3","E3","F#3","G3","A3","B3"]),
+            (Mode::Ultralocrian,     ["C3","Db3","Eb3","Fb3","Gb3","Ab3","Bbb3"]),
+            (Mode::MelodicMinor,     ["A3","B3","C4","D4","E4","F#4","G#4"]),
+            (Mode::DorianFlat2,      ["D3","Eb3","F3","G3","A3","B3","C4"]),
+            (Mode::LydianAugmented,  ["C3","D3","E3","F#3","G#3","A3","B3"]),
+            (Mode::LydianDominant,   ["G3","A3","B3","C#4","D4","E4","F4"]),
+            (Mode::MixolydianFlat6,  ["C3","D3","E3","F3","G3","Ab3","Bb3"]),
+            (Mode::LocrianSharp2,    ["B3","C#4","D4","E4","F4","G4","A4"]),
+            (Mode::Altered,          ["G3","Ab3","Bb3","Cb4","Db4","Eb4","F4"]),
+        ];
+        for (mode, strs) in modes {
+            assert_eq!(Mode::identify(&NoteSequence::from_strs(strs.to_vec()).unwrap()), Ok(mode));
+        }
+    }
+
+    #[test]
+    fn new_minors() {
+        let modes = [
+            ("C3", Mode::HarmonicMinor,    ["C3","D3","Eb3","F3","G3","Ab3","B3"]),
+            ("F#3", Mode::HarmonicMinor,   ["F#3","G#3","A3","B3","C#4","D4","E#4"]),
+            ("B3", Mode::LocrianNatural6,  ["B3","C4","D4","E4","F4","G#4","A4"]),
+            ("Eb3", Mode::IonianAugmented, ["Eb3","F3","G3","Ab3","B3","C4","D4"]),
+            ("D3", Mode::DorianSharp4,     ["D3","E3","F3","G#3","A3","B3","C4"]),
+            ("E3", Mode::PhrygianDominant, ["E3","F3","G#3","A3","B3","C4","D4"]),
+            ("Ab3", Mode::PhrygianDominant,["Ab3","Bbb3","C4","Db4","Eb4","Fb4","Gb4"]),
+            ("C3", Mode::LydianSharp2,     ["C3","D#3","E3","F#3","G3","A3","B3"]),
+            ("C#3", Mode::Ultralocrian,    ["C#3","D3","E3","F3","G3","A3","Bb3"]),
+            ("A3", Mode::MelodicMinor,     ["A3","B3","C4","D4","E4","F#4","G#4"]),
+            ("Bb3", Mode::MelodicMinor,    ["Bb3","C4","Db4","Eb4","F4","G4","A4"]),
+            ("D3", Mode::DorianFlat2,      ["D3","Eb3","F3","G3","A3","B3","C4"]),
+            ("Eb3", Mode::LydianAugmented, ["Eb3","F3","G3","A3","B3","C4","D4"]),
+            ("Db3", Mode::LydianDominant,  ["Db3","Eb3","F3","G3","Ab3","Bb3","Cb4"]),
+            ("G3", Mode::MixolydianFlat6,  ["G3","A3","B3","C4","D4","Eb4","F4"]),
+            ("F#3", Mode::LocrianSharp2,   ["F#3","G#3","A3","B3","C4","D4","E4"]),
+            ("B3", Mode::Altered,          ["B3","C4","D4","Eb4","F4","G4","A4"]),
+            ("G3", Mode::Altered,          ["G3","Ab3","Bb3","Cb4","Db4","Eb4","F4"]),
+        ];
+        for (tonic, mode, strs) in modes {
+            assert_eq!(DiatonicScale::from_tonic(Note::from_str(tonic).unwrap(), mode).unwrap(),
+                       DiatonicScale {tonic: Note::from_str(tonic).unwrap(), mode,
+                       notesequence: NoteSequence::from_strs(strs.to_vec()).unwrap()});
+        }
+        for (tonic, mode) in [("B##3", Mode::Ionian), ("Fb3", Mode::Ultralocrian), ("Cb3", Mode::Ultralocrian), ("Fbb3", Mode::Ionian), ("B#3", Mode::LydianAugmented)] {
+            assert_eq!(DiatonicScale::from_tonic(Note::from_str(tonic).unwrap(), mode),
+                       Err(HarmonyError::OutOfRange("Scale would require more than double accidentals")));
+        }
+    }
+
     #[test]
     fn new_ionian() {
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Cb3").unwrap(), Mode::Ionian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Cb3").unwrap(), Mode::Ionian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("Cb3").unwrap(), mode: Mode::Ionian,
                    notesequence: NoteSequence::from_strs(["Cb3", "Db3", "Eb3", "Fb3", "Gb3", "Ab3", "Bb3"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("C3").unwrap(), Mode::Ionian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("C3").unwrap(), Mode::Ionian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("C3").unwrap(), mode: Mode::Ionian,
                    notesequence: NoteSequence::from_strs(["C3", "D3", "E3", "F3", "G3", "A3", "B3"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("C#3").unwrap(), Mode::Ionian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("C#3").unwrap(), Mode::Ionian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("C#3").unwrap(), mode: Mode::Ionian,
                    notesequence: NoteSequence::from_strs(["C#3", "D#3", "E#3", "F#3", "G#3", "A#3", "B#3"].to_vec()).unwrap()});
 
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Eb3").unwrap(), Mode::Ionian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Eb3").unwrap(), Mode::Ionian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("Eb3").unwrap(), mode: Mode::Ionian,
                    notesequence: NoteSequence::from_strs(["Eb3", "F3", "G3", "Ab3", "Bb3", "C4", "D4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("E3").unwrap(), Mode::Ionian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("E3").unwrap(), Mode::Ionian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("E3").unwrap(), mode: Mode::Ionian,
                    notesequence: NoteSequence::from_strs(["E3", "F#3", "G#3", "A3", "B3", "C#4", "D#4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("E#3").unwrap(), Mode::Ionian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("E#3").unwrap(), Mode::Ionian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("E#3").unwrap(), mode: Mode::Ionian,
                    notesequence: NoteSequence::from_strs(["E#3", "F##3", "G##3", "A#3", "B#3", "C##4", "D##4"].to_vec()).unwrap()});
     }
 
     #[test]
     fn new_dorian() {
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Db3").unwrap(), Mode::Dorian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Db3").unwrap(), Mode::Dorian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("Db3").unwrap(), mode: Mode::Dorian,
                    notesequence: NoteSequence::from_strs(["Db3", "Eb3", "Fb3", "Gb3", "Ab3", "Bb3", "Cb4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("D3").unwrap(), Mode::Dorian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("D3").unwrap(), Mode::Dorian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("D3").unwrap(), mode: Mode::Dorian,
                    notesequence: NoteSequence::from_strs(["D3", "E3", "F3", "G3", "A3", "B3", "C4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("D#3").unwrap(), Mode::Dorian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("D#3").unwrap(), Mode::Dorian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("D#3").unwrap(), mode: Mode::Dorian,
                    notesequence: NoteSequence::from_strs(["D#3", "E#3", "F#3", "G#3", "A#3", "B#3", "C#4"].to_vec()).unwrap()});
 
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Fb3").unwrap(), Mode::Dorian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Fb3").unwrap(), Mode::Dorian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("Fb3").unwrap(), mode: Mode::Dorian,
                    notesequence: NoteSequence::from_strs(["Fb3", "Gb3", "Abb3", "Bbb3", "Cb4", "Db4", "Ebb4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("F3").unwrap(), Mode::Dorian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("F3").unwrap(), Mode::Dorian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("F3").unwrap(), mode: Mode::Dorian,
                    notesequence: NoteSequence::from_strs(["F3", "G3", "Ab3", "Bb3", "C4", "D4", "Eb4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("F#3").unwrap(), Mode::Dorian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("F#3").unwrap(), Mode::Dorian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("F#3").unwrap(), mode: Mode::Dorian,
                    notesequence: NoteSequence::from_strs(["F#3", "G#3", "A3", "B3", "C#4", "D#4", "E4"].to_vec()).unwrap()});
     }
 
     #[test]
     fn new_phrygian() {
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Eb3").unwrap(), Mode::Phrygian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Eb3").unwrap(), Mode::Phrygian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("Eb3").unwrap(), mode: Mode::Phrygian,
                    notesequence: NoteSequence::from_strs(["Eb3", "Fb3", "Gb3", "Ab3", "Bb3", "Cb4", "Db4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("E3").unwrap(), Mode::Phrygian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("E3").unwrap(), Mode::Phrygian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("E3").unwrap(), mode: Mode::Phrygian,
                    notesequence: NoteSequence::from_strs(["E3", "F3", "G3", "A3", "B3", "C4", "D4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("E#3").unwrap(), Mode::Phrygian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("E#3").unwrap(), Mode::Phrygian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("E#3").unwrap(), mode: Mode::Phrygian,
                    notesequence: NoteSequence::from_strs(["E#3", "F#3", "G#3", "A#3", "B#3", "C#4", "D#4"].to_vec()).unwrap()});
 
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Gb3").unwrap(), Mode::Phrygian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Gb3").unwrap(), Mode::Phrygian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("Gb3").unwrap(), mode: Mode::Phrygian,
                    notesequence: NoteSequence::from_strs(["Gb3", "Abb3", "Bbb3", "Cb4", "Db4", "Ebb4", "Fb4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("G3").unwrap(), Mode::Phrygian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("G3").unwrap(), Mode::Phrygian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("G3").unwrap(), mode: Mode::Phrygian,
                    notesequence: NoteSequence::from_strs(["G3", "Ab3", "Bb3", "C4", "D4", "Eb4", "F4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("G#3").unwrap(), Mode::Phrygian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("G#3").unwrap(), Mode::Phrygian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("G#3").unwrap(), mode: Mode::Phrygian,
                    notesequence: NoteSequence::from_strs(["G#3", "A3", "B3", "C#4", "D#4", "E4", "F#4"].to_vec()).unwrap()});
     }
 
     #[test]
     fn new_lydian() {
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Fb3").unwrap(), Mode::Lydian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Fb3").unwrap(), Mode::Lydian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("Fb3").unwrap(), mode: Mode::Lydian,
                    notesequence: NoteSequence::from_strs(["Fb3", "Gb3", "Ab3", "Bb3", "Cb4", "Db4", "Eb4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("F3").unwrap(), Mode::Lydian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("F3").unwrap(), Mode::Lydian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("F3").unwrap(), mode: Mode::Lydian,
                    notesequence: NoteSequence::from_strs(["F3", "G3", "A3", "B3", "C4", "D4", "E4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("F#3").unwrap(), Mode::Lydian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("F#3").unwrap(), Mode::Lydian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("F#3").unwrap(), mode: Mode::Lydian,
                    notesequence: NoteSequence::from_strs(["F#3", "G#3", "A#3", "B#3", "C#4", "D#4", "E#4"].to_vec()).unwrap()});
 
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Ab3").unwrap(), Mode::Lydian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Ab3").unwrap(), Mode::Lydian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("Ab3").unwrap(), mode: Mode::Lydian,
                    notesequence: NoteSequence::from_strs(["Ab3", "Bb3", "C4", "D4", "Eb4", "F4", "G4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("A3").unwrap(), Mode::Lydian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("A3").unwrap(), Mode::Lydian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("A3").unwrap(), mode: Mode::Lydian,
                    notesequence: NoteSequence::from_strs(["A3", "B3", "C#4", "D#4", "E4", "F#4", "G#4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("A#3").unwrap(), Mode::Lydian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("A#3").unwrap(), Mode::Lydian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("A#3").unwrap(), mode: Mode::Lydian,
                    notesequence: NoteSequence::from_strs(["A#3", "B#3", "C##4", "D##4", "E#4", "F##4", "G##4"].to_vec()).unwrap()});
     }
 
     #[test]
     fn new_mixolydian() {
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Gb3").unwrap(), Mode::Mixolydian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Gb3").unwrap(), Mode::Mixolydian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("Gb3").unwrap(), mode: Mode::Mixolydian,
                    notesequence: NoteSequence::from_strs(["Gb3", "Ab3", "Bb3", "Cb4", "Db4", "Eb4", "Fb4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("G3").unwrap(), Mode::Mixolydian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("G3").unwrap(), Mode::Mixolydian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("G3").unwrap(), mode: Mode::Mixolydian,
                    notesequence: NoteSequence::from_strs(["G3", "A3", "B3", "C4", "D4", "E4", "F4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("G#3").unwrap(), Mode::Mixolydian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("G#3").unwrap(), Mode::Mixolydian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("G#3").unwrap(), mode: Mode::Mixolydian,
                    notesequence: NoteSequence::from_strs(["G#3", "A#3", "B#3", "C#4", "D#4", "E#4", "F#4"].to_vec()).unwrap()});
 
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Bb3").unwrap(), Mode::Mixolydian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Bb3").unwrap(), Mode::Mixolydian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("Bb3").unwrap(), mode: Mode::Mixolydian,
                    notesequence: NoteSequence::from_strs(["Bb3", "C4", "D4", "Eb4", "F4", "G4", "Ab4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("B3").unwrap(), Mode::Mixolydian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("B3").unwrap(), Mode::Mixolydian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("B3").unwrap(), mode: Mode::Mixolydian,
                    notesequence: NoteSequence::from_strs(["B3", "C#4", "D#4", "E4", "F#4", "G#4", "A4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("B#3").unwrap(), Mode::Mixolydian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("B#3").unwrap(), Mode::Mixolydian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("B#3").unwrap(), mode: Mode::Mixolydian,
                    notesequence: NoteSequence::from_strs(["B#3", "C##4", "D##4", "E#4", "F##4", "G##4", "A#4"].to_vec()).unwrap()});
     }
 
     #[test]
     fn new_aeolian() {
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Ab3").unwrap(), Mode::Aeolian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Ab3").unwrap(), Mode::Aeolian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("Ab3").unwrap(), mode: Mode::Aeolian,
                    notesequence: NoteSequence::from_strs(["Ab3", "Bb3", "Cb4", "Db4", "Eb4", "Fb4", "Gb4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("A3").unwrap(), Mode::Aeolian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("A3").unwrap(), Mode::Aeolian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("A3").unwrap(), mode: Mode::Aeolian,
                    notesequence: NoteSequence::from_strs(["A3", "B3", "C4", "D4", "E4", "F4", "G4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("A#3").unwrap(), Mode::Aeolian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("A#3").unwrap(), Mode::Aeolian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("A#3").unwrap(), mode: Mode::Aeolian,
                    notesequence: NoteSequence::from_strs(["A#3", "B#3", "C#4", "D#4", "E#4", "F#4", "G#4"].to_vec()).unwrap()});
 
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Cb4").unwrap(), Mode::Aeolian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Cb4").unwrap(), Mode::Aeolian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("Cb4").unwrap(), mode: Mode::Aeolian,
                    notesequence: NoteSequence::from_strs(["Cb4", "Db4", "Ebb4", "Fb4", "Gb4", "Abb4", "Bbb4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("C4").unwrap(), Mode::Aeolian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("C4").unwrap(), Mode::Aeolian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("C4").unwrap(), mode: Mode::Aeolian,
                    notesequence: NoteSequence::from_strs(["C4", "D4", "Eb4", "F4", "G4", "Ab4", "Bb4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("C#4").unwrap(), Mode::Aeolian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("C#4").unwrap(), Mode::Aeolian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("C#4").unwrap(), mode: Mode::Aeolian,
                    notesequence: NoteSequence::from_strs(["C#4", "D#4", "E4", "F#4", "G#4", "A4", "B4"].to_vec()).unwrap()});
     }
 
     #[test]
     fn new_locrian() {
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Bb3").unwrap(), Mode::Locrian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Bb3").unwrap(), Mode::Locrian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("Bb3").unwrap(), mode: Mode::Locrian,
                    notesequence: NoteSequence::from_strs(["Bb3", "Cb4", "Db4", "Eb4", "Fb4", "Gb4", "Ab4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("B3").unwrap(), Mode::Locrian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("B3").unwrap(), Mode::Locrian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("B3").unwrap(), mode: Mode::Locrian,
                    notesequence: NoteSequence::from_strs(["B3", "C4", "D4", "E4", "F4", "G4", "A4"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("B#3").unwrap(), Mode::Locrian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("B#3").unwrap(), Mode::Locrian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("B#3").unwrap(), mode: Mode::Locrian,
                    notesequence: NoteSequence::from_strs(["B#3", "C#4", "D#4", "E#4", "F#4", "G#4", "A#4"].to_vec()).unwrap()});
 
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Db4").unwrap(), Mode::Locrian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("Db4").unwrap(), Mode::Locrian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("Db4").unwrap(), mode: Mode::Locrian,
                    notesequence: NoteSequence::from_strs(["Db4", "Ebb4", "Fb4", "Gb4", "Abb4", "Bbb4", "Cb5"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("D4").unwrap(), Mode::Locrian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("D4").unwrap(), Mode::Locrian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("D4").unwrap(), mode: Mode::Locrian,
                    notesequence: NoteSequence::from_strs(["D4", "Eb4", "F4", "G4", "Ab4", "Bb4", "C5"].to_vec()).unwrap()});
-        assert_eq!(DiatonicScale::from_tonic(Note::from_str("D#4").unwrap(), Mode::Locrian),
+        assert_eq!(DiatonicScale::from_tonic(Note::from_str("D#4").unwrap(), Mode::Locrian).unwrap(),
                    DiatonicScale {tonic: Note::from_str("D#4").unwrap(), mode: Mode::Locrian,
                    notesequence: NoteSequence::from_strs(["D#4", "E4", "F#4", "G#4", "A4", "B4", "C#5"].to_vec()).unwrap()});
     }
+
+    #[test]
+    fn mode_from_str() {
+        assert_eq!(Mode::from_str("phrygian dominant"), Ok(Mode::PhrygianDominant));
+        assert_eq!("Locrian #2".parse::<Mode>(), Ok(Mode::LocrianSharp2));
+        assert_eq!(Mode::from_str("Hypodorian").unwrap_err().position(), Some(0));
+        assert_eq!(format!("{}", Mode::DorianFlat2), "Dorian b2");
+        let notesequence = NoteSequence::from_strs(["C3","D3","Eb3"].to_vec()).unwrap();
+        assert!(matches!(Mode::identify(&notesequence), Err(HarmonyError::InvalidArgument(_))));
+        let notesequence = NoteSequence::from_strs(["C3","C#3","D3","D#3","E3","F3","F#3"].to_vec()).unwrap();
+        assert!(matches!(Mode::identify(&notesequence), Err(HarmonyError::Unidentified(_))));
+        let scale = DiatonicScale::from_tonic(Note::from_str("D4").unwrap(), Mode::Dorian).unwrap();
+        assert_eq!(format!("{}", scale), "D4 E4 F4 G4 A4 B4 C5");
+    }
 }
@@ -0,0 +1,1115 @@
+use std::fmt;
+use rand::Rng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use crate::errors::HarmonyError;
+use crate::notenames::NoteName;
+use crate::accidentals::Accidental;
+use crate::notes::{Note, SpellingPolicy};
+use crate::notesequences::NoteSequence;
+use crate::intervals::Interval;
+use crate::chords::{Chord, ChordQuality};
+use crate::diatonic_scales::{DiatonicScale, Mode};
+use crate::keys::Key;
+use crate::synth::Synth;
+use crate::tunings::NoteTuning;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Direction {
+    Ascending,
+    Descending,
+    Harmonic,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 3] = [Direction::Ascending, Direction::Descending, Direction::Harmonic];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Direction::Ascending => "ascending",
+            Direction::Descending => "descending",
+            Direction::Harmonic => "harmonic",
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct IntervalSettings {
+    // both notes of a question lie within low and high
+    pub low: Note,
+    pub high: Note,
+    pub intervals: Vec<Interval>,
+    pub directions: Vec<Direction>,
+}
+
+impl IntervalSettings {
+    pub fn new() -> Self {
+        let intervals = ["m2", "M2", "m3", "M3", "P4", "A4", "P5", "m6", "M6", "m7", "M7", "P8"];
+        Self {
+            low: Note::from_str("C3").unwrap(),
+            high: Note::from_str("C5").unwrap(),
+            intervals: intervals.iter().map(|x| Interval::from_str(x).unwrap()).collect(),
+            directions: Direction::ALL.to_vec(),
+        }
+    }
+}
+
+impl Default for IntervalSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct IntervalQuestion {
+    pub root: Note,
+    pub interval: Interval,
+    pub direction: Direction,
+}
+
+impl IntervalQuestion {
+    pub fn new(root: Note, interval: Interval, direction: Direction) -> Self {
+        Self {root, interval, direction}
+    }
+
+    // The root is spelled with sharps or flats, whichever keeps the target
+    // note at a single accidental at most.
+    pub fn random<R: Rng>(settings: &IntervalSettings, rng: &mut R) -> Result<Self, HarmonyError> {
+        let interval = *settings.intervals.choose(rng)
+            .ok_or(HarmonyError::InvalidArgument("No intervals to choose from"))?;
+        let direction = *settings.directions.choose(rng)
+            .ok_or(HarmonyError::InvalidArgument("No directions to choose from"))?;
+        let (low, high) = (settings.low.to_midi()?, settings.high.to_midi()?);
+        let mut candidates = Vec::new();
+        for midi in low..=high {
+            for policy in [SpellingPolicy::Sharps, SpellingPolicy::Flats] {
+                let question = Self::new(Note::from_midi(midi, policy)?, interval, direction);
+                if candidates.contains(&question) {
+                    continue;
+                }
+                let fits = question.target().ok()
+                    .filter(|x| x.accidental().offset().abs() <= 1)
+                    .and_then(|x| x.to_midi().ok())
+                    .is_some_and(|x| (low..=high).contains(&x));
+                if fits {
+                    candidates.push(question);
+                }
+            }
+        }
+        candidates.choose(rng).copied().ok_or(HarmonyError::OutOfRange("Range is too small for the interval"))
+    }
+
+    // the second note, below the root for descending intervals
+    pub fn target(&self) -> Result<Note, HarmonyError> {
+        match self.direction {
+            Direction::Descending => self.root.transpose_down(self.interval),
+            _ => self.root.transpose(self.interval),
+        }
+    }
+
+    // in the order they are played
+    pub fn notes(&self) -> Result<NoteSequence, HarmonyError> {
+        Ok(NoteSequence {notes: vec![self.root, self.target()?]})
+    }
+
+    // harmonic intervals sound as long as both notes of a melodic one
+    pub fn render<T: NoteTuning>(&self, synth: &Synth<T>, note_duration: f64) -> Result<Vec<f32>, HarmonyError> {
+        match self.direction {
+            Direction::Harmonic => synth.render_chord(&self.notes()?, 2.0 * note_duration),
+            _ => synth.render_melody(&self.notes()?, note_duration),
+        }
+    }
+
+    // enharmonic answers are accepted since they sound the same
+    pub fn is_correct(&self, answer: Interval) -> bool {
+        answer.hsteps() == self.interval.hsteps()
+    }
+
+    pub fn reveal(&self) -> Result<String, HarmonyError> {
+        Ok(format!("{} → {}, {}", self.root, self.target()?, self.interval.to_long_str()))
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct Score {
+    pub correct: u32,
+    pub total: u32,
+}
+
+impl Score {
+    pub fn record(&mut self, correct: bool) {
+        self.total += 1;
+        if correct {
+            self.correct += 1;
+        }
+    }
+
+    pub fn percentage(&self) -> Option<f64> {
+        (self.total > 0).then(|| 100.0 * self.correct as f64 / self.total as f64)
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.percentage() {
+            Some(percentage) => write!(f, "{}/{} ({:.0}%)", self.correct, self.total, percentage),
+            None => write!(f, "0/0"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IntervalTrainer {
+    pub settings: IntervalSettings,
+    pub score: Score,
+    question: Option<IntervalQuestion>,
+    answer: Option<Interval>,
+    rng: StdRng,
+}
+
+impl IntervalTrainer {
+    pub fn new(settings: IntervalSettings, rng: StdRng) -> Self {
+        Self {settings, score: Score::default(), question: None, answer: None, rng}
+    }
+
+    pub fn question(&self) -> Option<&IntervalQuestion> {
+        self.question.as_ref()
+    }
+
+    pub fn answer(&self) -> Option<Interval> {
+        self.answer
+    }
+
+    pub fn next_question(&mut self) -> Result<IntervalQuestion, HarmonyError> {
+        let question = IntervalQuestion::random(&self.settings, &mut self.rng)?;
+        self.question = Some(question);
+        self.answer = None;
+        Ok(question)
+    }
+
+    // only the first answer to a question is scored, None without a question
+    pub fn submit(&mut self, answer: Interval) -> Option<bool> {
+        let question = self.question?;
+        let correct = question.is_correct(answer);
+        if self.answer.is_none() {
+            self.answer = Some(answer);
+            self.score.record(correct);
+        }
+        Some(correct)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ChordFamily {
+    Triads,
+    Sevenths,
+    Extensions,
+}
+
+impl ChordFamily {
+    pub const ALL: [ChordFamily; 3] = [ChordFamily::Triads, ChordFamily::Sevenths, ChordFamily::Extensions];
+
+    // sixth and added tone chords count as extensions
+    pub fn of(quality: ChordQuality) -> Self {
+        let intervals = quality.intervals();
+        match intervals.len() {
+            3 => ChordFamily::Triads,
+            4 if intervals.iter().any(|x| x.number() == 7) => ChordFamily::Sevenths,
+            _ => ChordFamily::Extensions,
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            ChordFamily::Triads => "triads",
+            ChordFamily::Sevenths => "sevenths",
+            ChordFamily::Extensions => "extensions",
+        }
+    }
+}
+
+impl fmt::Display for ChordFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Voicing {
+    Close,
+    // the bass stays, every other tone above it moves up an octave
+    Open,
+}
+
+impl Voicing {
+    pub const ALL: [Voicing; 2] = [Voicing::Close, Voicing::Open];
+
+    pub fn apply(self, notes: &NoteSequence) -> Result<NoteSequence, HarmonyError> {
+        let octave = Interval::from_str("P8").unwrap();
+        let mut notes = notes.notes.clone();
+        if self == Voicing::Open {
+            for note in notes.iter_mut().skip(1).step_by(2) {
+                *note = note.transpose(octave)?;
+            }
+            let bass = notes[0];
+            notes.sort_by_key(|x| (bass.dist_hsteps(x), bass.dist_steps(x)));
+        }
+        Ok(NoteSequence {notes})
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Voicing::Close => "close",
+            Voicing::Open => "open",
+        }
+    }
+}
+
+impl fmt::Display for Voicing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Difficulty {
+    Beginner,
+    Intermediate,
+    Advanced,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Beginner, Difficulty::Intermediate, Difficulty::Advanced];
+
+    pub fn qualities(self) -> &'static [ChordQuality] {
+        match self {
+            Difficulty::Beginner => &[
+                ChordQuality::Major, ChordQuality::Minor, ChordQuality::Diminished, ChordQuality::Augmented,
+                ChordQuality::Dominant7, ChordQuality::Major7, ChordQuality::Minor7,
+                ChordQuality::Major6, ChordQuality::Add9,
+            ],
+            Difficulty::Intermediate => &[
+                ChordQuality::Major, ChordQuality::Minor, ChordQuality::Diminished, ChordQuality::Augmented,
+                ChordQuality::Sus2, ChordQuality::Sus4, ChordQuality::Dominant7, ChordQuality::Major7,
+                ChordQuality::Minor7, ChordQuality::HalfDiminished7, ChordQuality::Diminished7,
+                ChordQuality::MinorMajor7, ChordQuality::Major6, ChordQuality::Minor6, ChordQuality::Add9,
+                ChordQuality::Dominant9, ChordQuality::Major9, ChordQuality::Minor9,
+            ],
+            Difficulty::Advanced => &ChordQuality::ALL,
+        }
+    }
+
+    // range of the bass note
+    pub fn register(self) -> (Note, Note) {
+        let (low, high) = match self {
+            Difficulty::Beginner => ("C3", "C4"),
+            Difficulty::Intermediate => ("G2", "G4"),
+            Difficulty::Advanced => ("C2", "C5"),
+        };
+        (Note::from_str(low).unwrap(), Note::from_str(high).unwrap())
+    }
+
+    // how long the chord sounds in seconds
+    pub fn duration(self) -> f64 {
+        match self {
+            Difficulty::Beginner => 2.5,
+            Difficulty::Intermediate => 1.5,
+            Difficulty::Advanced => 0.8,
+        }
+    }
+
+    pub fn max_inversion(self) -> usize {
+        match self {
+            Difficulty::Beginner => 0,
+            Difficulty::Intermediate => 2,
+            Difficulty::Advanced => 3,
+        }
+    }
+
+    pub fn voicings(self) -> &'static [Voicing] {
+        match self {
+            Difficulty::Beginner => &[Voicing::Close],
+            _ => &Voicing::ALL,
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Difficulty::Beginner => "beginner",
+            Difficulty::Intermediate => "intermediate",
+            Difficulty::Advanced => "advanced",
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+pub fn inversion_to_str(inversion: usize) -> &'static str {
+    match inversion {
+        0 => "root position",
+        1 => "first inversion",
+        2 => "second inversion",
+        3 => "third inversion",
+        4 => "fourth inversion",
+        5 => "fifth inversion",
+        _ => "sixth inversion",
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ChordSettings {
+    pub difficulty: Difficulty,
+    pub families: Vec<ChordFamily>,
+}
+
+impl ChordSettings {
+    pub fn new() -> Self {
+        Self {difficulty: Difficulty::Beginner, families: ChordFamily::ALL.to_vec()}
+    }
+
+    pub fn qualities(&self) -> Vec<ChordQuality> {
+        self.difficulty.qualities().iter()
+            .filter(|x| self.families.contains(&ChordFamily::of(**x)))
+            .copied()
+            .collect()
+    }
+}
+
+impl Default for ChordSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct ChordQuestion {
+    pub root: Note,
+    pub quality: ChordQuality,
+    pub inversion: usize,
+    pub voicing: Voicing,
+}
+
+impl ChordQuestion {
+    pub fn new(root: Note, quality: ChordQuality, inversion: usize, voicing: Voicing) -> Self {
+        Self {root, quality, inversion, voicing}
+    }
+
+    // The bass lies in the register of the difficulty and no chord tone
+    // needs more than a single accidental.
+    pub fn random<R: Rng>(settings: &ChordSettings, rng: &mut R) -> Result<Self, HarmonyError> {
+        let quality = *settings.qualities().choose(rng)
+            .ok_or(HarmonyError::InvalidArgument("No chords to choose from"))?;
+        let difficulty = settings.difficulty;
+        let inversion = rng.gen_range(0..=difficulty.max_inversion().min(quality.intervals().len() - 1));
+        let voicing = *difficulty.voicings().choose(rng).unwrap();
+        let (low, high) = difficulty.register();
+        let (low, high) = (low.to_midi()?, high.to_midi()?);
+        let mut candidates = Vec::new();
+        for midi in 0..=127 {
+            for policy in [SpellingPolicy::Sharps, SpellingPolicy::Flats] {
+                let question = Self::new(Note::from_midi(midi, policy)?, quality, inversion, voicing);
+                if candidates.contains(&question) {
+                    continue;
+                }
+                let fits = question.chord().ok()
+                    .filter(|x| x.tones().iter().all(|x| x.accidental().offset().abs() <= 1))
+                    .and_then(|x| x.bass().to_midi().ok())
+                    .is_some_and(|x| (low..=high).contains(&x));
+                if fits {
+                    candidates.push(question);
+                }
+            }
+        }
+        candidates.choose(rng).copied().ok_or(HarmonyError::OutOfRange("No chord fits into the register"))
+    }
+
+    pub fn chord(&self) -> Result<Chord, HarmonyError> {
+        Chord::from_root(self.root, self.quality)?.invert(self.inversion)
+    }
+
+    // from the bass up
+    pub fn notes(&self) -> Result<NoteSequence, HarmonyError> {
+        self.voicing.apply(self.chord()?.notesequence())
+    }
+
+    pub fn render<T: NoteTuning>(&self, synth: &Synth<T>, duration: f64) -> Result<Vec<f32>, HarmonyError> {
+        synth.render_chord(&self.notes()?, duration)
+    }
+
+    // Answers are compared by sound, so an inversion of a diminished seventh
+    // chord or a minor seventh chord over its third for a sixth chord count.
+    pub fn is_correct(&self, quality: ChordQuality, inversion: usize) -> Result<bool, HarmonyError> {
+        let chord = self.chord()?;
+        let bass = chord.bass().pitch_class();
+        let mut expected: Vec<i32> = chord.tones().iter().map(|x| x.pitch_class()).collect();
+        let intervals = quality.intervals();
+        let Some(offset) = intervals.get(inversion).map(|x| x.hsteps()) else {
+            return Ok(false);
+        };
+        let mut answered: Vec<i32> = intervals.iter().map(|x| (bass + x.hsteps() - offset).rem_euclid(12)).collect();
+        for pitch_classes in [&mut expected, &mut answered] {
+            pitch_classes.sort();
+            pitch_classes.dedup();
+        }
+        Ok(expected == answered)
+    }
+
+    pub fn reveal(&self) -> Result<String, HarmonyError> {
+        let notes: Vec<String> = self.notes()?.notes.iter().map(|x| x.to_str()).collect();
+        Ok(format!("{}{} {}, {}, {} voicing: {}", self.root.name(), self.root.accidental(), self.quality, inversion_to_str(self.inversion),
+            self.voicing, notes.join(" ")))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChordTrainer {
+    pub settings: ChordSettings,
+    pub score: Score,
+    // per chord quality, in the order they were first asked
+    results: Vec<(ChordQuality, Score)>,
+    question: Option<ChordQuestion>,
+    answered: bool,
+    rng: StdRng,
+}
+
+impl ChordTrainer {
+    pub fn new(settings: ChordSettings, rng: StdRng) -> Self {
+        Self {settings, score: Score::default(), results: Vec::new(), question: None, answered: false, rng}
+    }
+
+    pub fn question(&self) -> Option<&ChordQuestion> {
+        self.question.as_ref()
+    }
+
+    pub fn is_answered(&self) -> bool {
+        self.answered
+    }
+
+    pub fn results(&self) -> &[(ChordQuality, Score)] {
+        &self.results
+    }
+
+    pub fn result(&self, quality: ChordQuality) -> Score {
+        self.results.iter().find(|(x, _)| *x == quality).map(|(_, x)| *x).unwrap_or_default()
+    }
+
+    pub fn next_question(&mut self) -> Result<ChordQuestion, HarmonyError> {
+        let question = ChordQuestion::random(&self.settings, &mut self.rng)?;
+        self.question = Some(question);
+        self.answered = false;
+        Ok(question)
+    }
+
+    // only the first answer to a question is scored, None without a question
+    pub fn submit(&mut self, quality: ChordQuality, inversion: usize) -> Result<Option<bool>, HarmonyError> {
+        let Some(question) = self.question else {
+            return Ok(None);
+        };
+        let correct = question.is_correct(quality, inversion)?;
+        if !self.answered {
+            self.answered = true;
+            self.score.record(correct);
+            match self.results.iter_mut().find(|(x, _)| *x == question.quality) {
+                Some((_, score)) => score.record(correct),
+                None => {
+                    let mut score = Score::default();
+                    score.record(correct);
+                    self.results.push((question.quality, score));
+                }
+            }
+        }
+        Ok(Some(correct))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ScaleFamily {
+    Major,
+    HarmonicMinor,
+    MelodicMinor,
+}
+
+impl ScaleFamily {
+    pub const ALL: [ScaleFamily; 3] = [ScaleFamily::Major, ScaleFamily::HarmonicMinor, ScaleFamily::MelodicMinor];
+
+    pub fn modes(self) -> Vec<Mode> {
+        let first = match self {
+            ScaleFamily::Major => Mode::Ionian,
+            ScaleFamily::HarmonicMinor => Mode::HarmonicMinor,
+            ScaleFamily::MelodicMinor => Mode::MelodicMinor,
+        };
+        Mode::ALL.into_iter().filter(|x| x.family().0 == first).collect()
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            ScaleFamily::Major => "major modes",
+            ScaleFamily::HarmonicMinor => "harmonic minor modes",
+            ScaleFamily::MelodicMinor => "melodic minor modes",
+        }
+    }
+}
+
+impl fmt::Display for ScaleFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScaleSettings {
+    pub families: Vec<ScaleFamily>,
+}
+
+impl ScaleSettings {
+    pub fn new() -> Self {
+        Self {families: vec![ScaleFamily::Major]}
+    }
+
+    pub fn modes(&self) -> Vec<Mode> {
+        ScaleFamily::ALL.iter()
+            .filter(|x| self.families.contains(x))
+            .flat_map(|x| x.modes())
+            .collect()
+    }
+}
+
+impl Default for ScaleSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Mark {
+    Correct,
+    // the right pitch under another letter, like D# for Eb
+    WrongSpelling,
+    // the right letter with an accidental that changes the pitch
+    WrongAccidental,
+    WrongPitch,
+    Missing,
+    Extra,
+}
+
+impl Mark {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Mark::Correct => "correct",
+            Mark::WrongSpelling => "right pitch, wrong spelling",
+            Mark::WrongAccidental => "wrong accidental",
+            Mark::WrongPitch => "wrong pitch",
+            Mark::Missing => "missing",
+            Mark::Extra => "extra",
+        }
+    }
+}
+
+impl fmt::Display for Mark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+// One position of a spelled scale, octaves don't matter.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct SpelledNote {
+    pub given: Option<(NoteName, Accidental)>,
+    pub expected: Option<(NoteName, Accidental)>,
+    pub mark: Mark,
+}
+
+impl SpelledNote {
+    fn check(given: Option<(NoteName, Accidental)>, expected: Option<(NoteName, Accidental)>) -> Self {
+        let pitch_class = |(name, accidental)| Note::new(name, accidental, 0).pitch_class();
+        let mark = match (given, expected) {
+            (Some(given), Some(expected)) if given == expected => Mark::Correct,
+            (Some(given), Some(expected)) if pitch_class(given) == pitch_class(expected) => Mark::WrongSpelling,
+            (Some(given), Some(expected)) if given.0 == expected.0 => Mark::WrongAccidental,
+            (Some(_), Some(_)) => Mark::WrongPitch,
+            (None, _) => Mark::Missing,
+            (_, None) => Mark::Extra,
+        };
+        Self {given, expected, mark}
+    }
+}
+
+// Note names with accidentals and without octaves, separated by spaces or commas.
+pub fn parse_spelling(s: &str) -> Result<Vec<(NoteName, Accidental)>, HarmonyError> {
+    let mut notes = Vec::new();
+    let mut start = 0;
+    for (i, token) in s.split(|c: char| c.is_whitespace() || c == ',').enumerate() {
+        if i > 0 {
+            start += 1;
+        }
+        if !token.is_empty() {
+            let (name, accidental) = token.split_at(token.chars().next().unwrap().len_utf8());
+            let name = NoteName::from_str(name).map_err(|x| x.within(s, start))?;
+            let accidental = Accidental::from_str(accidental).map_err(|x| x.within(s, start + 1))?;
+            notes.push((name, accidental));
+        }
+        start += token.chars().count();
+    }
+    Ok(notes)
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct ScaleQuestion {
+    pub tonic: Note,
+    pub mode: Mode,
+}
+
+impl ScaleQuestion {
+    pub fn new(tonic: Note, mode: Mode) -> Self {
+        Self {tonic, mode}
+    }
+
+    // A tonic in the fourth octave whose scale needs single accidentals at most.
+    pub fn random<R: Rng>(settings: &ScaleSettings, rng: &mut R) -> Result<Self, HarmonyError> {
+        let mode = *settings.modes().choose(rng)
+            .ok_or(HarmonyError::InvalidArgument("No modes to choose from"))?;
+        let names = [NoteName::C, NoteName::D, NoteName::E, NoteName::F, NoteName::G, NoteName::A, NoteName::B];
+        let tonics: Vec<Note> = names.iter()
+            .flat_map(|name| [Accidental::Flat, Accidental::Natural, Accidental::Sharp].map(|x| Note::new(*name, x, 4)))
+            .filter(|tonic| {
+                let mut hsteps = 0;
+                (1..7).zip(mode.get_dists()).all(|(step, dist)| {
+                    hsteps += dist;
+                    let natural = tonic.shift_natural(step).rm_accidental();
+                    (hsteps - tonic.dist_hsteps(&natural)).abs() <= 1
+                })
+            })
+            .collect();
+        let tonic = *tonics.choose(rng).ok_or(HarmonyError::OutOfRange("No tonic spells the mode with single accidentals"))?;
+        Ok(Self::new(tonic, mode))
+    }
+
+    pub fn scale(&self) -> Result<DiatonicScale, HarmonyError> {
+        DiatonicScale::from_tonic(self.tonic, self.mode)
+    }
+
+    pub fn notes(&self) -> Result<NoteSequence, HarmonyError> {
+        Ok(self.scale()?.notesequence().clone())
+    }
+
+    // the mode the played or displayed notes are in
+    pub fn identify(&self) -> Result<Mode, HarmonyError> {
+        Mode::identify(&self.notes()?)
+    }
+
+    pub fn render<T: NoteTuning>(&self, synth: &Synth<T>, note_duration: f64) -> Result<Vec<f32>, HarmonyError> {
+        synth.render_scale(&self.scale()?, note_duration)
+    }
+
+    pub fn is_correct(&self, answer: Mode) -> Result<bool, HarmonyError> {
+        Ok(self.identify()? == answer)
+    }
+
+    // like Eb Lydian
+    pub fn prompt(&self) -> String {
+        format!("{}{} {}", self.tonic.name(), self.tonic.accidental(), self.mode)
+    }
+
+    // played or clicked notes are spelled like the scale asked for
+    pub fn key(&self) -> Result<Key, HarmonyError> {
+        Key::new(self.tonic, self.mode)
+    }
+
+    pub fn spelling_policy(&self) -> Result<SpellingPolicy, HarmonyError> {
+        Ok(SpellingPolicy::Key(self.key()?))
+    }
+
+    pub fn check_spelling(&self, input: &str) -> Result<Vec<SpelledNote>, HarmonyError> {
+        let given = parse_spelling(input)?;
+        let expected: Vec<(NoteName, Accidental)> = self.notes()?.notes.iter().map(|x| (x.name(), x.accidental())).collect();
+        Ok((0..given.len().max(expected.len()))
+            .map(|i| SpelledNote::check(given.get(i).copied(), expected.get(i).copied()))
+            .collect())
+    }
+
+    pub fn reveal(&self) -> Result<String, HarmonyError> {
+        let notes: Vec<String> = self.notes()?.notes.iter().map(|x| format!("{}{}", x.name(), x.accidental())).collect();
+        Ok(format!("{}: {}", self.prompt(), notes.join(" ")))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScaleTrainer {
+    pub settings: ScaleSettings,
+    pub score: Score,
+    question: Option<ScaleQuestion>,
+    answered: bool,
+    rng: StdRng,
+}
+
+impl ScaleTrainer {
+    pub fn new(settings: ScaleSettings, rng: StdRng) -> Self {
+        Self {settings, score: Score::default(), question: None, answered: false, rng}
+    }
+
+    pub fn question(&self) -> Option<&ScaleQuestion> {
+        self.question.as_ref()
+    }
+
+    pub fn is_answered(&self) -> bool {
+        self.answered
+    }
+
+    pub fn next_question(&mut self) -> Result<ScaleQuestion, HarmonyError> {
+        let question = ScaleQuestion::random(&self.settings, &mut self.rng)?;
+        self.question = Some(question);
+        self.answered = false;
+        Ok(question)
+    }
+
+    fn record(&mut self, correct: bool) {
+        if !self.answered {
+            self.answered = true;
+            self.score.record(correct);
+        }
+    }
+
+    // only the first answer to a question is scored, None without a question
+    pub fn submit_mode(&mut self, answer: Mode) -> Result<Option<bool>, HarmonyError> {
+        let Some(question) = self.question else {
+            return Ok(None);
+        };
+        let correct = question.is_correct(answer)?;
+        self.record(correct);
+        Ok(Some(correct))
+    }
+
+    // input that doesn't parse is not scored
+    pub fn submit_spelling(&mut self, input: &str) -> Result<Option<Vec<SpelledNote>>, HarmonyError> {
+        let Some(question) = self.question else {
+            return Ok(None);
+        };
+        let marks = question.check_spelling(input)?;
+        self.record(marks.iter().all(|x| x.mark == Mark::Correct));
+        Ok(Some(marks))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use crate::synth::Timbre;
+    use crate::tunings::Tuning;
+    use crate::keyboard::Keyboard;
+
+    fn interval(s: &str) -> Interval {
+        Interval::from_str(s).unwrap()
+    }
+
+    fn settings(low: &str, high: &str, intervals: &[&str], directions: &[Direction]) -> IntervalSettings {
+        IntervalSettings {
+            low: Note::from_str(low).unwrap(),
+            high: Note::from_str(high).unwrap(),
+            intervals: intervals.iter().map(|x| interval(x)).collect(),
+            directions: directions.to_vec(),
+        }
+    }
+
+    #[test]
+    fn target() {
+        assert_eq!(IntervalQuestion::new(Note::from_str("C4").unwrap(), interval("A4"), Direction::Ascending).target(), Ok(Note::from_str("F#4").unwrap()));
+        assert_eq!(IntervalQuestion::new(Note::from_str("C4").unwrap(), interval("A4"), Direction::Harmonic).target(), Ok(Note::from_str("F#4").unwrap()));
+        assert_eq!(IntervalQuestion::new(Note::from_str("C4").unwrap(), interval("M3"), Direction::Descending).target(), Ok(Note::from_str("Ab3").unwrap()));
+        assert_eq!(IntervalQuestion::new(Note::from_str("Eb4").unwrap(), interval("m6"), Direction::Descending).notes(),
+            NoteSequence::from_strs(vec!["Eb4", "G3"]));
+    }
+
+    #[test]
+    fn reveal() {
+        assert_eq!(IntervalQuestion::new(Note::from_str("C4").unwrap(), interval("A4"), Direction::Ascending).reveal(),
+            Ok(String::from("C4 → F#4, augmented fourth")));
+        assert_eq!(IntervalQuestion::new(Note::from_str("D4").unwrap(), interval("m3"), Direction::Descending).reveal(),
+            Ok(String::from("D4 → B3, minor third")));
+    }
+
+    #[test]
+    fn is_correct() {
+        let question = IntervalQuestion::new(Note::from_str("C4").unwrap(), interval("A4"), Direction::Ascending);
+        assert!(question.is_correct(interval("A4")));
+        assert!(question.is_correct(interval("d5")));
+        assert!(!question.is_correct(interval("P4")));
+    }
+
+    #[test]
+    fn random() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let settings = settings("C3", "C5", &["m2", "A4", "M7", "P8"], &Direction::ALL);
+        for _ in 0..200 {
+            let question = IntervalQuestion::random(&settings, &mut rng).unwrap();
+            let target = question.target().unwrap();
+            assert!(settings.intervals.contains(&question.interval));
+            assert!(target.accidental().offset().abs() <= 1);
+            for x in [question.root, target] {
+                assert!((48..=72).contains(&x.to_midi().unwrap()));
+            }
+        }
+    }
+
+    #[test]
+    fn random_range() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let question = IntervalQuestion::random(&settings("C4", "C5", &["P8"], &[Direction::Descending]), &mut rng);
+        assert_eq!(question, Ok(IntervalQuestion::new(Note::from_str("C5").unwrap(), interval("P8"), Direction::Descending)));
+        assert_eq!(IntervalQuestion::random(&settings("C4", "B4", &["P8"], &Direction::ALL), &mut rng),
+            Err(HarmonyError::OutOfRange("Range is too small for the interval")));
+        assert_eq!(IntervalQuestion::random(&settings("C4", "C5", &[], &Direction::ALL), &mut rng),
+            Err(HarmonyError::InvalidArgument("No intervals to choose from")));
+        assert_eq!(IntervalQuestion::random(&settings("C4", "C5", &["P5"], &[]), &mut rng),
+            Err(HarmonyError::InvalidArgument("No directions to choose from")));
+    }
+
+    #[test]
+    fn render() {
+        let synth = Synth::new(Timbre::Sine, 8000, Tuning::equal(440.0).unwrap()).unwrap();
+        let melodic = IntervalQuestion::new(Note::from_str("C4").unwrap(), interval("P5"), Direction::Ascending);
+        let harmonic = IntervalQuestion::new(Note::from_str("C4").unwrap(), interval("P5"), Direction::Harmonic);
+        assert_eq!(melodic.render(&synth, 0.5).unwrap().len(), 8000);
+        assert_eq!(harmonic.render(&synth, 0.5).unwrap().len(), 8000);
+    }
+
+    #[test]
+    fn score() {
+        let mut score = Score::default();
+        assert_eq!(score.to_string(), "0/0");
+        score.record(true);
+        score.record(false);
+        score.record(true);
+        assert_eq!(score, Score {correct: 2, total: 3});
+        assert_eq!(score.to_string(), "2/3 (67%)");
+    }
+
+    #[test]
+    fn trainer() {
+        let mut trainer = IntervalTrainer::new(IntervalSettings::default(), StdRng::seed_from_u64(3));
+        assert_eq!(trainer.submit(interval("P5")), None);
+        let question = trainer.next_question().unwrap();
+        assert_eq!(trainer.question(), Some(&question));
+        assert_eq!(trainer.submit(question.interval), Some(true));
+        assert_eq!(trainer.submit(question.interval), Some(true));
+        assert_eq!(trainer.score, Score {correct: 1, total: 1});
+        let question = trainer.next_question().unwrap();
+        let wrong = if question.interval.hsteps() == 1 { interval("M2") } else { interval("m2") };
+        assert_eq!(trainer.submit(wrong), Some(false));
+        assert_eq!(trainer.submit(question.interval), Some(true));
+        assert_eq!(trainer.answer(), Some(wrong));
+        assert_eq!(trainer.score, Score {correct: 1, total: 2});
+    }
+
+    fn question(root: &str, quality: ChordQuality, inversion: usize, voicing: Voicing) -> ChordQuestion {
+        ChordQuestion::new(Note::from_str(root).unwrap(), quality, inversion, voicing)
+    }
+
+    #[test]
+    fn chord_family() {
+        assert_eq!(ChordFamily::of(ChordQuality::Minor), ChordFamily::Triads);
+        assert_eq!(ChordFamily::of(ChordQuality::Sus4), ChordFamily::Triads);
+        assert_eq!(ChordFamily::of(ChordQuality::HalfDiminished7), ChordFamily::Sevenths);
+        assert_eq!(ChordFamily::of(ChordQuality::Dominant7Flat5), ChordFamily::Sevenths);
+        assert_eq!(ChordFamily::of(ChordQuality::Major6), ChordFamily::Extensions);
+        assert_eq!(ChordFamily::of(ChordQuality::Dominant13), ChordFamily::Extensions);
+    }
+
+    #[test]
+    fn chord_settings() {
+        let settings = ChordSettings {difficulty: Difficulty::Beginner, families: vec![ChordFamily::Sevenths]};
+        assert_eq!(settings.qualities(), vec![ChordQuality::Dominant7, ChordQuality::Major7, ChordQuality::Minor7]);
+        assert_eq!(ChordSettings {difficulty: Difficulty::Advanced, ..ChordSettings::new()}.qualities().len(), 33);
+    }
+
+    #[test]
+    fn voicing() {
+        let notes = |question: ChordQuestion| question.notes().unwrap().notes.iter().map(|x| x.to_str()).collect::<Vec<_>>();
+        assert_eq!(notes(question("C4", ChordQuality::Major, 0, Voicing::Close)), ["C4", "E4", "G4"]);
+        assert_eq!(notes(question("C4", ChordQuality::Major, 0, Voicing::Open)), ["C4", "G4", "E5"]);
+        assert_eq!(notes(question("C4", ChordQuality::Major, 1, Voicing::Close)), ["E4", "G4", "C5"]);
+        assert_eq!(notes(question("C4", ChordQuality::Major, 1, Voicing::Open)), ["E4", "C5", "G5"]);
+        assert_eq!(notes(question("G3", ChordQuality::Dominant7, 3, Voicing::Close)), ["F4", "G4", "B4", "D5"]);
+        assert_eq!(notes(question("G3", ChordQuality::Dominant7, 3, Voicing::Open)), ["F4", "B4", "G5", "D6"]);
+    }
+
+    #[test]
+    fn chord_is_correct() {
+        let c = question("C4", ChordQuality::Major, 1, Voicing::Close);
+        assert_eq!(c.is_correct(ChordQuality::Major, 1), Ok(true));
+        assert_eq!(c.is_correct(ChordQuality::Major, 0), Ok(false));
+        assert_eq!(c.is_correct(ChordQuality::Minor, 1), Ok(false));
+        assert_eq!(c.is_correct(ChordQuality::Major, 3), Ok(false));
+        // C6 sounds like Am7 over C
+        let c6 = question("C4", ChordQuality::Major6, 0, Voicing::Open);
+        assert_eq!(c6.is_correct(ChordQuality::Minor7, 1), Ok(true));
+        let dim = question("B3", ChordQuality::Diminished7, 0, Voicing::Close);
+        assert_eq!(dim.is_correct(ChordQuality::Diminished7, 2), Ok(true));
+        assert_eq!(dim.is_correct(ChordQuality::HalfDiminished7, 0), Ok(false));
+    }
+
+    #[test]
+    fn chord_reveal() {
+        assert_eq!(question("F#3", ChordQuality::Minor7, 1, Voicing::Close).reveal(),
+            Ok(String::from("F# minor seventh, first inversion, close voicing: A3 C#4 E4 F#4")));
+        assert_eq!(question("Bb2", ChordQuality::Major, 0, Voicing::Open).reveal(),
+            Ok(String::from("Bb major, root position, open voicing: Bb2 F3 D4")));
+    }
+
+    #[test]
+    fn chord_random() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for difficulty in Difficulty::ALL {
+            let settings = ChordSettings {difficulty, ..ChordSettings::new()};
+            let (low, high) = difficulty.register();
+            for _ in 0..50 {
+                let question = ChordQuestion::random(&settings, &mut rng).unwrap();
+                let chord = question.chord().unwrap();
+                assert!(settings.qualities().contains(&question.quality));
+                assert!(question.inversion <= difficulty.max_inversion());
+                assert!(difficulty.voicings().contains(&question.voicing));
+                assert!(chord.tones().iter().all(|x| x.accidental().offset().abs() <= 1));
+                assert!((low.to_midi().unwrap()..=high.to_midi().unwrap()).contains(&chord.bass().to_midi().unwrap()));
+                assert_eq!(question.notes().unwrap().notes[0], chord.bass());
+            }
+        }
+        let settings = ChordSettings {difficulty: Difficulty::Beginner, families: vec![]};
+        assert_eq!(ChordQuestion::random(&settings, &mut rng), Err(HarmonyError::InvalidArgument("No chords to choose from")));
+    }
+
+    #[test]
+    fn chord_trainer() {
+        let mut trainer = ChordTrainer::new(ChordSettings::default(), StdRng::seed_from_u64(5));
+        assert_eq!(trainer.submit(ChordQuality::Major, 0), Ok(None));
+        let question = trainer.next_question().unwrap();
+        assert_eq!(trainer.question(), Some(&question));
+        assert_eq!(trainer.submit(question.quality, question.inversion), Ok(Some(true)));
+        assert!(trainer.is_answered());
+        assert_eq!(trainer.result(question.quality), Score {correct: 1, total: 1});
+
+        let mut trainer = ChordTrainer::new(ChordSettings::default(), StdRng::seed_from_u64(5));
+        trainer.question = Some(ChordQuestion::new(Note::from_str("D3").unwrap(), ChordQuality::Minor, 0, Voicing::Close));
+        assert_eq!(trainer.submit(ChordQuality::Minor, 0), Ok(Some(true)));
+        assert_eq!(trainer.submit(ChordQuality::Major, 0), Ok(Some(false)));
+        trainer.question = Some(ChordQuestion::new(Note::from_str("E3").unwrap(), ChordQuality::Minor, 0, Voicing::Close));
+        trainer.answered = false;
+        assert_eq!(trainer.submit(ChordQuality::Minor, 2), Ok(Some(false)));
+        trainer.question = Some(ChordQuestion::new(Note::from_str("G3").unwrap(), ChordQuality::Dominant7, 0, Voicing::Close));
+        trainer.answered = false;
+        assert_eq!(trainer.submit(ChordQuality::Dominant7, 0), Ok(Some(true)));
+        assert_eq!(trainer.results(), &[
+            (ChordQuality::Minor, Score {correct: 1, total: 2}),
+            (ChordQuality::Dominant7, Score {correct: 1, total: 1}),
+        ]);
+        assert_eq!(trainer.score, Score {correct: 2, total: 3});
+        assert_eq!(trainer.result(ChordQuality::Altered), Score::default());
+    }
+
+    fn spelled(s: &str) -> Option<(NoteName, Accidental)> {
+        let note = Note::from_str(&format!("{}0", s)).unwrap();
+        Some((note.name(), note.accidental()))
+    }
+
+    #[test]
+    fn scale_settings() {
+        assert_eq!(ScaleSettings::default().modes(), Mode::ALL[0..7].to_vec());
+        let settings = ScaleSettings {families: vec![ScaleFamily::MelodicMinor, ScaleFamily::HarmonicMinor]};
+        assert_eq!(settings.modes()[0], Mode::HarmonicMinor);
+        assert_eq!(settings.modes()[7], Mode::MelodicMinor);
+        assert_eq!(ScaleFamily::HarmonicMinor.modes().last(), Some(&Mode::Ultralocrian));
+    }
+
+    #[test]
+    fn scale_parse_spelling() {
+        assert_eq!(parse_spelling("Eb f, G  A# bbb"), Ok(vec![spelled("Eb").unwrap(), spelled("F").unwrap(),
+            spelled("G").unwrap(), spelled("A#").unwrap(), spelled("Bbb").unwrap()]));
+        assert_eq!(parse_spelling(" "), Ok(vec![]));
+        assert_eq!(parse_spelling("C D Hb").map_err(|x| x.position()), Err(Some(4)));
+        assert_eq!(parse_spelling("C, Dx").map_err(|x| x.position()), Err(Some(4)));
+    }
+
+    #[test]
+    fn check_spelling() {
+        let question = ScaleQuestion::new(Note::from_str("Eb4").unwrap(), Mode::Lydian);
+        assert_eq!(question.prompt(), "Eb Lydian");
+        assert_eq!(question.reveal(), Ok(String::from("Eb Lydian: Eb F G A Bb C D")));
+        assert_eq!(question.key().unwrap().signature().fifths(), -2);
+        let marks = |s: &str| question.check_spelling(s).unwrap().iter().map(|x| x.mark).collect::<Vec<_>>();
+        assert_eq!(marks("Eb F G A Bb C D"), [Mark::Correct; 7]);
+        assert_eq!(marks("D# F G Ab Bb C D"), [Mark::WrongSpelling, Mark::Correct, Mark::Correct, Mark::WrongAccidental,
+            Mark::Correct, Mark::Correct, Mark::Correct]);
+        assert_eq!(marks("Eb F G Bbb Bb C"), [Mark::Correct, Mark::Correct, Mark::Correct, Mark::WrongSpelling,
+            Mark::Correct, Mark::Correct, Mark::Missing]);
+        assert_eq!(marks("Eb F G A Bb C E Eb"), [Mark::Correct, Mark::Correct, Mark::Correct, Mark::Correct,
+            Mark::Correct, Mark::Correct, Mark::WrongPitch, Mark::Extra]);
+        assert_eq!(question.check_spelling("Eb F G A B C D").unwrap()[4],
+            SpelledNote {given: spelled("B"), expected: spelled("Bb"), mark: Mark::WrongAccidental});
+    }
+
+    #[test]
+    fn scale_keyboard_spelling() {
+        // clicking the keys of the scale spells it correctly, also on the flat side
+        let piano = Keyboard::new(Note::from_str("C4").unwrap(), Note::from_str("C6").unwrap()).unwrap();
+        for (tonic, mode) in [("Eb4", Mode::Phrygian), ("Bb4", Mode::Locrian), ("F4", Mode::HarmonicMinor), ("Db4", Mode::Ionian)] {
+            let question = ScaleQuestion::new(Note::from_str(tonic).unwrap(), mode);
+            let clicked: Vec<String> = question.notes().unwrap().notes.iter()
+                .map(|x| piano.keys().into_iter().find(|key| key.midi == x.to_midi().unwrap()).unwrap())
+                .map(|key| piano.note_at(key.x + key.width / 2.0, key.length / 2.0, question.spelling_policy().unwrap()).unwrap())
+                .map(|x| format!("{}{}", x.name(), x.accidental()))
+                .collect();
+            let marks: Vec<Mark> = question.check_spelling(&clicked.join(" ")).unwrap().iter().map(|x| x.mark).collect();
+            assert_eq!(marks, [Mark::Correct; 7], "{}", question.prompt());
+        }
+    }
+
+    #[test]
+    fn scale_is_correct() {
+        let question = ScaleQuestion::new(Note::from_str("E4").unwrap(), Mode::PhrygianDominant);
+        assert_eq!(question.identify(), Ok(Mode::PhrygianDominant));
+        assert_eq!(question.is_correct(Mode::PhrygianDominant), Ok(true));
+        assert_eq!(question.is_correct(Mode::Phrygian), Ok(false));
+        let synth = Synth::new(Timbre::Sine, 8000, Tuning::equal(440.0).unwrap()).unwrap();
+        assert_eq!(question.render(&synth, 0.25).unwrap().len(), 8 * 2000);
+    }
+
+    #[test]
+    fn scale_random() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let settings = ScaleSettings {families: ScaleFamily::ALL.to_vec()};
+        for _ in 0..200 {
+            let question = ScaleQuestion::random(&settings, &mut rng).unwrap();
+            assert_eq!(question.tonic.octave(), 4);
+            assert_eq!(question.identify(), Ok(question.mode));
+            assert!(question.notes().unwrap().notes.iter().all(|x| x.accidental().offset().abs() <= 1));
+        }
+        assert_eq!(ScaleQuestion::random(&ScaleSettings {families: vec![]}, &mut rng),
+            Err(HarmonyError::InvalidArgument("No modes to choose from")));
+    }
+
+    #[test]
+    fn scale_trainer() {
+        let mut trainer = ScaleTrainer::new(ScaleSettings::default(), StdRng::seed_from_u64(4));
+        assert_eq!(trainer.submit_mode(Mode::Dorian), Ok(None));
+        trainer.question = Some(ScaleQuestion::new(Note::from_str("D4").unwrap(), Mode::Dorian));
+        assert_eq!(trainer.submit_mode(Mode::Aeolian), Ok(Some(false)));
+        assert_eq!(trainer.submit_mode(Mode::Dorian), Ok(Some(true)));
+        assert_eq!(trainer.score, Score {correct: 0, total: 1});
+        let question = trainer.next_question().unwrap();
+        assert!(!trainer.is_answered());
+        assert!(trainer.submit_spelling("C D X").is_err());
+        assert!(!trainer.is_answered());
+        let marks = trainer.submit_spelling(&question.reveal().unwrap()[question.prompt().len() + 1..]).unwrap().unwrap();
+        assert!(marks.iter().all(|x| x.mark == Mark::Correct));
+        assert_eq!(trainer.score, Score {correct: 1, total: 2});
+    }
+}
@@ -0,0 +1,122 @@
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum HarmonyError {
+    // position counts characters, not bytes, from the start of input
+    Parse {
+        input: String,
+        position: usize,
+        found: String,
+        expected: Vec<String>,
+    },
+    OutOfRange(&'static str),
+    Unidentified(&'static str),
+    InvalidArgument(&'static str),
+    // line counts from 1
+    Line {
+        line: usize,
+        error: Box<HarmonyError>,
+    },
+}
+
+impl HarmonyError {
+    pub fn parse(input: &str, position: usize, found: &str, expected: &[&str]) -> Self {
+        HarmonyError::Parse {
+            input: String::from(input),
+            position,
+            found: String::from(found),
+            expected: expected.iter().map(|x| String::from(*x)).collect(),
+        }
+    }
+
+    // Moves a parse error of a substring into the coordinates of the enclosing input.
+    pub fn within(self, input: &str, offset: usize) -> Self {
+        match self {
+            HarmonyError::Parse {position, found, expected, ..} => HarmonyError::Parse {
+                input: String::from(input),
+                position: position + offset,
+                found,
+                expected,
+            },
+            error => error,
+        }
+    }
+
+    pub fn at_line(self, line: usize) -> Self {
+        HarmonyError::Line {line, error: Box::new(self)}
+    }
+
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            HarmonyError::Parse {position, ..} => Some(*position),
+            HarmonyError::Line {error, ..} => error.position(),
+            _ => None,
+        }
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            HarmonyError::Line {line, ..} => Some(*line),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HarmonyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HarmonyError::Parse {input, position, found, expected} => {
+                if found.is_empty() {
+                    write!(f, "Unexpected end of \"{}\" at position {}", input, position)?;
+                } else {
+                    write!(f, "Unexpected \"{}\" in \"{}\" at position {}", found, input, position)?;
+                }
+                if !expected.is_empty() {
+                    let expected: Vec<String> = expected.iter().map(|x| format!("\"{}\"", x)).collect();
+                    write!(f, ", expected one of {}", expected.join(", "))?;
+                }
+                Ok(())
+            },
+            HarmonyError::OutOfRange(message)
+                | HarmonyError::Unidentified(message)
+                | HarmonyError::InvalidArgument(message) => write!(f, "{}", message),
+            HarmonyError::Line {line, error} => write!(f, "Line {}: {}", line, error),
+        }
+    }
+}
+
+impl std::error::Error for HarmonyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        assert_eq!(format!("{}", HarmonyError::parse("H3", 0, "H", &["C", "D"])),
+            "Unexpected \"H\" in \"H3\" at position 0, expected one of \"C\", \"D\"");
+        assert_eq!(format!("{}", HarmonyError::parse("C#", 2, "", &["octave number"])),
+            "Unexpected end of \"C#\" at position 2, expected one of \"octave number\"");
+        assert_eq!(format!("{}", HarmonyError::parse("C3x", 2, "x", &[])),
+            "Unexpected \"x\" in \"C3x\" at position 2");
+        assert_eq!(format!("{}", HarmonyError::Unidentified("Unknown chord")), "Unknown chord");
+    }
+
+    #[test]
+    fn within() {
+        let error = HarmonyError::parse("x7", 0, "x", &["C"]).within("Cmaj7/x7", 6);
+        assert_eq!(error, HarmonyError::parse("Cmaj7/x7", 6, "x", &["C"]));
+        assert_eq!(error.position(), Some(6));
+        assert_eq!(HarmonyError::OutOfRange("Too many sharps").within("C", 3), HarmonyError::OutOfRange("Too many sharps"));
+        assert_eq!(HarmonyError::OutOfRange("Too many sharps").position(), None);
+    }
+
+    #[test]
+    fn at_line() {
+        let error = HarmonyError::parse("3/x", 2, "x", &["denominator"]).at_line(4);
+        assert_eq!(error.line(), Some(4));
+        assert_eq!(error.position(), Some(2));
+        assert_eq!(format!("{}", error), "Line 4: Unexpected \"x\" in \"3/x\" at position 2, expected one of \"denominator\"");
+        assert_eq!(HarmonyError::InvalidArgument("Empty file").line(), None);
+    }
+}
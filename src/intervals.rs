@@ -0,0 +1,454 @@
+use std::fmt;
+use std::ops::Add;
+use std::str::FromStr;
+use crate::notes::Note;
+use crate::errors::HarmonyError;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Quality {
+    Diminished(u8),
+    Minor,
+    Perfect,
+    Major,
+    Augmented(u8),
+}
+
+impl Quality {
+    pub fn from_str(s: &str) -> Result<Self, HarmonyError> {
+        match s {
+            "P" => Ok(Quality::Perfect),
+            "M" => Ok(Quality::Major),
+            "m" => Ok(Quality::Minor),
+            _ if !s.is_empty() && s.chars().all(|c| c == 'A') => Ok(Quality::Augmented(s.len() as u8)),
+            _ if !s.is_empty() && s.chars().all(|c| c == 'd') => Ok(Quality::Diminished(s.len() as u8)),
+            _ => Err(HarmonyError::parse(s, 0, s, &["P", "M", "m", "A", "d"])),
+        }
+    }
+
+    pub fn to_str(self) -> String {
+        match self {
+            Quality::Diminished(n) => "d".repeat(n as usize),
+            Quality::Minor => String::from("m"),
+            Quality::Perfect => String::from("P"),
+            Quality::Major => String::from("M"),
+            Quality::Augmented(n) => "A".repeat(n as usize),
+        }
+    }
+
+    pub fn to_long_str(self) -> String {
+        let multiplicity = |n: u8| match n {
+            1 => String::new(),
+            2 => String::from("doubly "),
+            3 => String::from("triply "),
+            _ => format!("{}-fold ", n),
+        };
+        match self {
+            Quality::Diminished(n) => format!("{}diminished", multiplicity(n)),
+            Quality::Minor => String::from("minor"),
+            Quality::Perfect => String::from("perfect"),
+            Quality::Major => String::from("major"),
+            Quality::Augmented(n) => format!("{}augmented", multiplicity(n)),
+        }
+    }
+
+    pub fn invert(self) -> Self {
+        match self {
+            Quality::Diminished(n) => Quality::Augmented(n),
+            Quality::Minor => Quality::Major,
+            Quality::Perfect => Quality::Perfect,
+            Quality::Major => Quality::Minor,
+            Quality::Augmented(n) => Quality::Diminished(n),
+        }
+    }
+}
+
+impl FromStr for Quality {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str(s)
+    }
+}
+
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Interval {
+    quality: Quality,
+    number: u32,
+}
+
+impl Interval {
+    pub fn new(quality: Quality, number: u32) -> Result<Self, HarmonyError> {
+        if number == 0 {
+            return Err(HarmonyError::InvalidArgument("Interval number must be at least one"));
+        }
+        let interval = Self {quality, number};
+        let steps = interval.steps();
+        match (quality, is_perfect_class(steps)) {
+            (Quality::Diminished(0), _) | (Quality::Augmented(0), _) => return Err(HarmonyError::InvalidArgument("Invalid Interval quality")),
+            (Quality::Perfect, false) => return Err(HarmonyError::InvalidArgument("Only unisons, fourths, fifths and octaves can be perfect")),
+            (Quality::Major, true) | (Quality::Minor, true) => return Err(HarmonyError::InvalidArgument("Unisons, fourths, fifths and octaves can not be major or minor")),
+            _ => (),
+        }
+        if interval.hsteps() < 0 {
+            return Err(HarmonyError::InvalidArgument("Interval spans a negative number of half steps"));
+        }
+        Ok(interval)
+    }
+
+    pub fn from_steps(steps: i32, hsteps: i32) -> Result<Self, HarmonyError> {
+        if steps < 0 {
+            return Err(HarmonyError::InvalidArgument("Interval spans a negative number of steps"));
+        }
+        let delta = hsteps - reference_hsteps(steps);
+        let quality = if is_perfect_class(steps) {
+            match delta {
+                0 => Quality::Perfect,
+                d if d > 0 => Quality::Augmented(d as u8),
+                d => Quality::Diminished((-d) as u8),
+            }
+        } else {
+            match delta {
+                0 => Quality::Major,
+                -1 => Quality::Minor,
+                d if d > 0 => Quality::Augmented(d as u8),
+                d => Quality::Diminished((-d-1) as u8),
+            }
+        };
+        Self::new(quality, steps as u32 + 1)
+    }
+
+    pub fn between(note1: &Note, note2: &Note) -> Result<Self, HarmonyError> {
+        let mut steps = note1.dist_steps(note2);
+        let mut hsteps = note1.dist_hsteps(note2);
+        if steps < 0 || (steps == 0 && hsteps < 0) {
+            steps = -steps;
+            hsteps = -hsteps;
+        }
+        Self::from_steps(steps, hsteps)
+    }
+
+    pub fn from_str(s: &str) -> Result<Self, HarmonyError> {
+        let number_start_idx = s.find(|c: char| c.is_ascii_digit()).unwrap_or(s.len());
+        let number_start_pos = s[..number_start_idx].chars().count();
+        let quality = Quality::from_str(&s[..number_start_idx]).map_err(|x| x.within(s, 0))?;
+        match s[number_start_idx..].parse::<u32>() {
+            Ok(number) => Self::new(quality, number),
+            Err(_) => Err(HarmonyError::parse(s, number_start_pos, &s[number_start_idx..], &["interval number"])),
+        }
+    }
+
+    pub fn to_str(self) -> String {
+        format!("{}{}", self.quality.to_str(), self.number)
+    }
+
+    pub fn to_long_str(self) -> String {
+        format!("{} {}", self.quality.to_long_str(), number_name(self.number))
+    }
+
+    pub fn quality(&self) -> Quality {
+        self.quality
+    }
+
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    pub fn steps(&self) -> i32 {
+        self.number as i32 - 1
+    }
+
+    pub fn hsteps(&self) -> i32 {
+        let steps = self.steps();
+        let reference = reference_hsteps(steps);
+        match self.quality {
+            Quality::Perfect | Quality::Major => reference,
+            Quality::Minor => reference - 1,
+            Quality::Augmented(n) => reference + n as i32,
+            Quality::Diminished(n) if is_perfect_class(steps) => reference - n as i32,
+            Quality::Diminished(n) => reference - 1 - n as i32,
+        }
+    }
+
+    pub fn is_compound(&self) -> bool {
+        self.number > 8
+    }
+
+    pub fn simple(&self) -> Self {
+        let mut octaves = self.steps() / 7;
+        if octaves > 0 && self.steps() % 7 == 0 {
+            // keep octaves as octaves instead of reducing them to unisons
+            octaves -= 1;
+        }
+        Self {
+            quality: self.quality,
+            number: self.number - 7*octaves as u32,
+        }
+    }
+
+    pub fn invert(&self) -> Self {
+        let simple = self.simple();
+        Self {
+            quality: simple.quality.invert(),
+            number: 9 - simple.number,
+        }
+    }
+
+    pub fn checked_sub(self, other: Self) -> Result<Self, HarmonyError> {
+        Self::from_steps(self.steps() - other.steps(), self.hsteps() - other.hsteps())
+    }
+}
+
+impl Add for Interval {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::from_steps(self.steps() + other.steps(), self.hsteps() + other.hsteps())
+            .expect("sum of two intervals is a valid interval")
+    }
+}
+
+fn is_perfect_class(steps: i32) -> bool {
+    matches!(steps.rem_euclid(7), 0 | 3 | 4)
+}
+
+fn reference_hsteps(steps: i32) -> i32 {
+    let simple_hsteps = [0, 2, 4, 5, 7, 9, 11];
+    12*steps.div_euclid(7) + simple_hsteps[steps.rem_euclid(7) as usize]
+}
+
+fn number_name(number: u32) -> String {
+    let names = ["unison", "second", "third", "fourth", "fifth", "sixth", "seventh", "octave",
+                 "ninth", "tenth", "eleventh", "twelfth", "thirteenth", "fourteenth", "double octave"];
+    match names.get(number as usize - 1) {
+        Some(name) => String::from(*name),
+        None => {
+            let suffix = match (number % 10, number % 100) {
+                (_, 11..=13) => "th",
+                (1, _) => "st",
+                (2, _) => "nd",
+                (3, _) => "rd",
+                _ => "th",
+            };
+            format!("{}{}", number, suffix)
+        },
+    }
+}
+
+impl FromStr for Interval {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str(s)
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quality_from_str() {
+        assert_eq!(Quality::from_str("P"), Ok(Quality::Perfect));
+        assert_eq!(Quality::from_str("M"), Ok(Quality::Major));
+        assert_eq!(Quality::from_str("m"), Ok(Quality::Minor));
+        assert_eq!(Quality::from_str("A"), Ok(Quality::Augmented(1)));
+        assert_eq!(Quality::from_str("AA"), Ok(Quality::Augmented(2)));
+        assert_eq!(Quality::from_str("d"), Ok(Quality::Diminished(1)));
+        assert_eq!(Quality::from_str("dd"), Ok(Quality::Diminished(2)));
+
+        assert!(Quality::from_str("").is_err());
+        assert!(Quality::from_str("p").is_err());
+        assert!(Quality::from_str("Ad").is_err());
+        assert!(Quality::from_str("D").is_err());
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!(Interval::from_str("P1"), Ok(Interval {quality: Quality::Perfect, number: 1}));
+        assert_eq!(Interval::from_str("m2"), Ok(Interval {quality: Quality::Minor, number: 2}));
+        assert_eq!(Interval::from_str("M3"), Ok(Interval {quality: Quality::Major, number: 3}));
+        assert_eq!(Interval::from_str("A4"), Ok(Interval {quality: Quality::Augmented(1), number: 4}));
+        assert_eq!(Interval::from_str("d7"), Ok(Interval {quality: Quality::Diminished(1), number: 7}));
+        assert_eq!(Interval::from_str("P12"), Ok(Interval {quality: Quality::Perfect, number: 12}));
+        assert_eq!(Interval::from_str("AA4"), Ok(Interval {quality: Quality::Augmented(2), number: 4}));
+        assert_eq!(Interval::from_str("dd5"), Ok(Interval {quality: Quality::Diminished(2), number: 5}));
+        assert_eq!(Interval::from_str("d2"), Ok(Interval {quality: Quality::Diminished(1), number: 2}));
+
+        assert!(Interval::from_str("P3").is_err());
+        assert!(Interval::from_str("M5").is_err());
+        assert!(Interval::from_str("m11").is_err());
+        assert!(Interval::from_str("d1").is_err());
+        assert!(Interval::from_str("P0").is_err());
+        assert!(Interval::from_str("M").is_err());
+        assert!(Interval::from_str("3").is_err());
+        assert!(Interval::from_str("X3").is_err());
+        assert!(Interval::from_str("M3b").is_err());
+        assert!(Interval::from_str("").is_err());
+    }
+
+    #[test]
+    fn to_str() {
+        for s in ["P1", "A1", "d2", "m2", "M2", "A2", "m3", "M3", "d4", "P4", "A4", "AA4",
+                  "dd5", "d5", "P5", "m6", "M6", "d7", "m7", "M7", "P8", "m9", "M10", "P11", "P12", "M13"] {
+            assert_eq!(Interval::from_str(s).unwrap().to_str(), s);
+        }
+    }
+
+    #[test]
+    fn to_long_str() {
+        assert_eq!(Interval::from_str("P1").unwrap().to_long_str(), "perfect unison");
+        assert_eq!(Interval::from_str("A4").unwrap().to_long_str(), "augmented fourth");
+        assert_eq!(Interval::from_str("d5").unwrap().to_long_str(), "diminished fifth");
+        assert_eq!(Interval::from_str("AA4").unwrap().to_long_str(), "doubly augmented fourth");
+        assert_eq!(Interval::from_str("dd7").unwrap().to_long_str(), "doubly diminished seventh");
+        assert_eq!(Interval::from_str("M10").unwrap().to_long_str(), "major tenth");
+        assert_eq!(Interval::from_str("P15").unwrap().to_long_str(), "perfect double octave");
+        assert_eq!(Interval::from_str("M16").unwrap().to_long_str(), "major 16th");
+        assert_eq!(Interval::from_str("M21").unwrap().to_long_str(), "major 21st");
+        assert_eq!(Interval::from_str("P22").unwrap().to_long_str(), "perfect 22nd");
+        assert_eq!(Interval::from_str("M23").unwrap().to_long_str(), "major 23rd");
+        assert_eq!(Interval::from_str("M111").unwrap().to_long_str(), "major 111th");
+        assert_eq!(Interval::from_str("m112").unwrap().to_long_str(), "minor 112th");
+        assert_eq!(Interval::from_str("P113").unwrap().to_long_str(), "perfect 113th");
+    }
+
+    #[test]
+    fn hsteps() {
+        let hsteps = |s: &str| Interval::from_str(s).unwrap().hsteps();
+        assert_eq!(hsteps("P1"), 0);
+        assert_eq!(hsteps("A1"), 1);
+        assert_eq!(hsteps("d2"), 0);
+        assert_eq!(hsteps("m2"), 1);
+        assert_eq!(hsteps("M2"), 2);
+        assert_eq!(hsteps("A2"), 3);
+        assert_eq!(hsteps("m3"), 3);
+        assert_eq!(hsteps("M3"), 4);
+        assert_eq!(hsteps("P4"), 5);
+        assert_eq!(hsteps("A4"), 6);
+        assert_eq!(hsteps("d5"), 6);
+        assert_eq!(hsteps("P5"), 7);
+        assert_eq!(hsteps("A5"), 8);
+        assert_eq!(hsteps("m6"), 8);
+        assert_eq!(hsteps("M6"), 9);
+        assert_eq!(hsteps("d7"), 9);
+        assert_eq!(hsteps("m7"), 10);
+        assert_eq!(hsteps("M7"), 11);
+        assert_eq!(hsteps("P8"), 12);
+        assert_eq!(hsteps("m9"), 13);
+        assert_eq!(hsteps("P12"), 19);
+        assert_eq!(hsteps("AA4"), 7);
+        assert_eq!(hsteps("dd7"), 8);
+    }
+
+    #[test]
+    fn between() {
+        let between = |n1: &str, n2: &str| {
+            Interval::between(&Note::from_str(n1).unwrap(), &Note::from_str(n2).unwrap()).unwrap().to_str()
+        };
+        assert_eq!(between("C4", "C4"), "P1");
+        assert_eq!(between("C4", "C#4"), "A1");
+        assert_eq!(between("C#4", "C4"), "A1");
+        assert_eq!(between("C4", "E4"), "M3");
+        assert_eq!(between("E4", "C4"), "M3");
+        assert_eq!(between("C4", "F#4"), "A4");
+        assert_eq!(between("C4", "Gb4"), "d5");
+        assert_eq!(between("C#4", "Bb4"), "d7");
+        assert_eq!(between("B#3", "C4"), "d2");
+        assert_eq!(between("E3", "E4"), "P8");
+        assert_eq!(between("D3", "A4"), "P12");
+        assert_eq!(between("Cb4", "F#4"), "AA4");
+        assert_eq!(between("C#4", "Gb4"), "dd5");
+        assert_eq!(between("F3", "B3"), "A4");
+        assert_eq!(between("B3", "F4"), "d5");
+    }
+
+    #[test]
+    fn is_compound() {
+        assert!(!Interval::from_str("P1").unwrap().is_compound());
+        assert!(!Interval::from_str("M7").unwrap().is_compound());
+        assert!(!Interval::from_str("P8").unwrap().is_compound());
+        assert!(Interval::from_str("m9").unwrap().is_compound());
+        assert!(Interval::from_str("P15").unwrap().is_compound());
+    }
+
+    #[test]
+    fn simple() {
+        let simple = |s: &str| Interval::from_str(s).unwrap().simple().to_str();
+        assert_eq!(simple("P1"), "P1");
+        assert_eq!(simple("M3"), "M3");
+        assert_eq!(simple("P8"), "P8");
+        assert_eq!(simple("m9"), "m2");
+        assert_eq!(simple("M10"), "M3");
+        assert_eq!(simple("P12"), "P5");
+        assert_eq!(simple("P15"), "P8");
+        assert_eq!(simple("A18"), "A4");
+    }
+
+    #[test]
+    fn invert() {
+        let invert = |s: &str| Interval::from_str(s).unwrap().invert().to_str();
+        assert_eq!(invert("P1"), "P8");
+        assert_eq!(invert("A1"), "d8");
+        assert_eq!(invert("m2"), "M7");
+        assert_eq!(invert("M3"), "m6");
+        assert_eq!(invert("P4"), "P5");
+        assert_eq!(invert("A4"), "d5");
+        assert_eq!(invert("d5"), "A4");
+        assert_eq!(invert("AA4"), "dd5");
+        assert_eq!(invert("m7"), "M2");
+        assert_eq!(invert("P8"), "P1");
+        assert_eq!(invert("M10"), "m6");
+    }
+
+    #[test]
+    fn add() {
+        let add = |s1: &str, s2: &str| (Interval::from_str(s1).unwrap() + Interval::from_str(s2).unwrap()).to_str();
+        assert_eq!(add("P1", "M3"), "M3");
+        assert_eq!(add("M3", "m3"), "P5");
+        assert_eq!(add("M3", "M3"), "A5");
+        assert_eq!(add("m3", "m3"), "d5");
+        assert_eq!(add("P5", "P4"), "P8");
+        assert_eq!(add("P5", "M3"), "M7");
+        assert_eq!(add("P8", "M2"), "M9");
+        assert_eq!(add("P5", "P8"), "P12");
+        assert_eq!(add("M2", "M2"), "M3");
+        assert_eq!(add("m2", "m2"), "d3");
+    }
+
+    #[test]
+    fn checked_sub() {
+        let sub = |s1: &str, s2: &str| Interval::from_str(s1).unwrap().checked_sub(Interval::from_str(s2).unwrap());
+        assert_eq!(sub("P5", "M3").unwrap().to_str(), "m3");
+        assert_eq!(sub("P8", "P5").unwrap().to_str(), "P4");
+        assert_eq!(sub("P12", "P8").unwrap().to_str(), "P5");
+        assert_eq!(sub("M7", "M7").unwrap().to_str(), "P1");
+        assert_eq!(sub("A4", "m2").unwrap().to_str(), "A3");
+
+        assert!(sub("M3", "P5").is_err());
+        assert!(sub("m3", "M3").is_err());
+    }
+
+    #[test]
+    fn errors_and_traits() {
+        assert_eq!(Interval::from_str("X3"), Err(HarmonyError::parse("X3", 0, "X", &["P", "M", "m", "A", "d"])));
+        assert_eq!(Interval::from_str("M"), Err(HarmonyError::parse("M", 1, "", &["interval number"])));
+        assert_eq!(Interval::from_str("M3x"), Err(HarmonyError::parse("M3x", 1, "3x", &["interval number"])));
+        assert!(matches!(Interval::from_str("P3"), Err(HarmonyError::InvalidArgument(_))));
+        assert_eq!("A4".parse::<Interval>(), Interval::from_str("A4"));
+        assert_eq!(format!("{}", Interval::from_str("dd5").unwrap()), "dd5");
+        assert_eq!("m".parse::<Quality>(), Ok(Quality::Minor));
+        assert_eq!(format!("{}", Quality::Augmented(2)), "AA");
+    }
+}
@@ -0,0 +1,163 @@
+use crate::errors::HarmonyError;
+use crate::notes::{Note, SpellingPolicy};
+
+// Layout is measured in white key widths across and key lengths down.
+pub const BLACK_KEY_WIDTH: f32 = 0.6;
+pub const BLACK_KEY_LENGTH: f32 = 0.62;
+
+// by pitch class
+const BLACK_KEYS: [bool; 12] = [false, true, false, true, false, false, true, false, true, false, true, false];
+
+pub fn is_black(midi: u8) -> bool {
+    BLACK_KEYS[midi as usize % 12]
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct PianoKey {
+    pub midi: u8,
+    pub black: bool,
+    // left edge
+    pub x: f32,
+    pub width: f32,
+    pub length: f32,
+}
+
+impl PianoKey {
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (0.0..self.length).contains(&y)
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Keyboard {
+    low: u8,
+    high: u8,
+}
+
+impl Keyboard {
+    // the range is widened to start and end on white keys
+    pub fn new(low: Note, high: Note) -> Result<Self, HarmonyError> {
+        let (mut low, mut high) = (low.to_midi()?, high.to_midi()?);
+        if low > high {
+            return Err(HarmonyError::InvalidArgument("Lowest key must not be above the highest key"));
+        }
+        // the ends of the MIDI range are white keys
+        while is_black(low) {
+            low -= 1;
+        }
+        while is_black(high) {
+            high += 1;
+        }
+        Ok(Self {low, high})
+    }
+
+    pub fn low(&self) -> u8 {
+        self.low
+    }
+
+    pub fn high(&self) -> u8 {
+        self.high
+    }
+
+    pub fn contains(&self, note: &Note) -> bool {
+        note.to_midi().is_ok_and(|x| (self.low..=self.high).contains(&x))
+    }
+
+    pub fn white_keys(&self) -> usize {
+        (self.low..=self.high).filter(|x| !is_black(*x)).count()
+    }
+
+    // white keys first so that black keys are drawn on top
+    pub fn keys(&self) -> Vec<PianoKey> {
+        let mut white = Vec::new();
+        let mut black = Vec::new();
+        for midi in self.low..=self.high {
+            if is_black(midi) {
+                // centered on the edge between its neighbours
+                black.push(PianoKey {
+                    midi, black: true, x: white.len() as f32 - BLACK_KEY_WIDTH / 2.0,
+                    width: BLACK_KEY_WIDTH, length: BLACK_KEY_LENGTH,
+                });
+            } else {
+                white.push(PianoKey {midi, black: false, x: white.len() as f32, width: 1.0, length: 1.0});
+            }
+        }
+        white.extend(black);
+        white
+    }
+
+    // black keys lie on top of the white ones
+    pub fn key_at(&self, x: f32, y: f32) -> Option<u8> {
+        self.keys().iter().rev().find(|key| key.contains(x, y)).map(|key| key.midi)
+    }
+
+    // the clicked key spelled for the context, like the key of an exercise
+    pub fn note_at(&self, x: f32, y: f32, policy: SpellingPolicy) -> Option<Note> {
+        self.key_at(x, y).and_then(|x| Note::from_midi(x, policy).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diatonic_scales::Mode;
+    use crate::keys::Key;
+
+    fn keyboard(low: &str, high: &str) -> Keyboard {
+        Keyboard::new(Note::from_str(low).unwrap(), Note::from_str(high).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn range() {
+        let piano = keyboard("C2", "C6");
+        assert_eq!((piano.low(), piano.high()), (36, 84));
+        assert_eq!(piano.white_keys(), 29);
+        assert_eq!(piano.keys().len(), 49);
+        assert_eq!(keyboard("C#4", "Bb4"), keyboard("C4", "B4"));
+        assert_eq!(keyboard("B#3", "Cb5"), keyboard("C4", "B4"));
+        assert!(piano.contains(&Note::from_str("B#5").unwrap()));
+        assert!(!piano.contains(&Note::from_str("C#6").unwrap()));
+        assert_eq!(Keyboard::new(Note::from_str("C4").unwrap(), Note::from_str("B3").unwrap()),
+            Err(HarmonyError::InvalidArgument("Lowest key must not be above the highest key")));
+        assert!(Keyboard::new(Note::from_str("C-2").unwrap(), Note::from_str("C4").unwrap()).is_err());
+    }
+
+    #[test]
+    fn keys() {
+        let keys = keyboard("C4", "E4").keys();
+        assert_eq!(keys.iter().map(|x| x.midi).collect::<Vec<_>>(), [60, 62, 64, 61, 63]);
+        assert_eq!(keys[1], PianoKey {midi: 62, black: false, x: 1.0, width: 1.0, length: 1.0});
+        assert_eq!(keys[3], PianoKey {midi: 61, black: true, x: 0.7, width: BLACK_KEY_WIDTH, length: BLACK_KEY_LENGTH});
+        assert_eq!(keys[4].x, 1.7);
+    }
+
+    #[test]
+    fn key_at() {
+        let piano = keyboard("C4", "C5");
+        assert_eq!(piano.key_at(0.5, 0.9), Some(60));
+        assert_eq!(piano.key_at(0.9, 0.9), Some(60));
+        assert_eq!(piano.key_at(0.9, 0.3), Some(61));
+        assert_eq!(piano.key_at(1.1, 0.3), Some(61));
+        assert_eq!(piano.key_at(1.5, 0.3), Some(62));
+        assert_eq!(piano.key_at(2.9, 0.3), Some(64));
+        assert_eq!(piano.key_at(3.1, 0.3), Some(65));
+        assert_eq!(piano.key_at(7.5, 0.5), Some(72));
+        assert_eq!(piano.key_at(8.5, 0.5), None);
+        assert_eq!(piano.key_at(0.5, 1.5), None);
+    }
+
+    #[test]
+    fn note_at() {
+        let piano = keyboard("C4", "C5");
+        let eb_major = SpellingPolicy::Key(Key::new(Note::from_str("Eb4").unwrap(), Mode::Ionian).unwrap());
+        let e_major = SpellingPolicy::Key(Key::new(Note::from_str("E4").unwrap(), Mode::Ionian).unwrap());
+        assert_eq!(piano.note_at(1.1, 0.3, eb_major), Some(Note::from_str("Db4").unwrap()));
+        assert_eq!(piano.note_at(1.1, 0.3, e_major), Some(Note::from_str("C#4").unwrap()));
+        assert_eq!(piano.note_at(2.5, 0.9, e_major), Some(Note::from_str("E4").unwrap()));
+        // E# is diatonic in F# major
+        let f_sharp_major = SpellingPolicy::Key(Key::new(Note::from_str("F#4").unwrap(), Mode::Ionian).unwrap());
+        assert_eq!(piano.note_at(3.5, 0.9, f_sharp_major), Some(Note::from_str("E#4").unwrap()));
+        assert_eq!(piano.note_at(3.5, 0.3, SpellingPolicy::Flats), Some(Note::from_str("F4").unwrap()));
+        assert_eq!(piano.note_at(9.0, 0.3, SpellingPolicy::Flats), None);
+    }
+}
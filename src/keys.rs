@@ -0,0 +1,394 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::notenames::NoteName;
+use crate::accidentals::Accidental;
+use crate::notes::Note;
+use crate::intervals::Interval;
+use crate::diatonic_scales::{DiatonicScale, Mode};
+use crate::errors::HarmonyError;
+
+const SHARP_ORDER: [NoteName; 7] = [NoteName::F, NoteName::C, NoteName::G, NoteName::D, NoteName::A, NoteName::E, NoteName::B];
+const FLAT_ORDER: [NoteName; 7] = [NoteName::B, NoteName::E, NoteName::A, NoteName::D, NoteName::G, NoteName::C, NoteName::F];
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct KeySignature {
+    fifths: i32,
+}
+
+impl KeySignature {
+    pub fn from_fifths(fifths: i32) -> Result<Self, HarmonyError> {
+        if fifths.abs() > 14 {
+            return Err(HarmonyError::OutOfRange("Key signature would require more than double accidentals"));
+        }
+        Ok(Self {fifths})
+    }
+
+    // compact notation counting the accidentals, e.g. "3#", "2b" or "0"
+    pub fn from_str(s: &str) -> Result<Self, HarmonyError> {
+        let count_end_idx = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let count = s[..count_end_idx].parse::<i32>()
+            .map_err(|_| HarmonyError::parse(s, 0, s, &["number of accidentals"]))?;
+        let sign = match &s[count_end_idx..] {
+            "#" => 1,
+            "b" => -1,
+            "" if count == 0 => 0,
+            rest => return Err(HarmonyError::parse(s, s[..count_end_idx].chars().count(), rest, &["#", "b"])),
+        };
+        Self::from_fifths(sign * count)
+    }
+
+    pub fn to_str(self) -> String {
+        match self.fifths {
+            0 => String::from("0"),
+            fifths if fifths > 0 => format!("{}#", fifths),
+            fifths => format!("{}b", -fifths),
+        }
+    }
+
+    pub fn fifths(&self) -> i32 {
+        self.fifths
+    }
+
+    pub fn sharps(&self) -> u32 {
+        self.fifths.max(0) as u32
+    }
+
+    pub fn flats(&self) -> u32 {
+        (-self.fifths).max(0) as u32
+    }
+
+    pub fn accidentals(&self) -> Vec<(NoteName, Accidental)> {
+        let (order, single, double) = if self.fifths >= 0 {
+            (SHARP_ORDER, Accidental::Sharp, Accidental::Doublesharp)
+        } else {
+            (FLAT_ORDER, Accidental::Flat, Accidental::Doubleflat)
+        };
+        (0..self.fifths.unsigned_abs() as usize)
+            .map(|i| (order[i % 7], if i < 7 {single} else {double}))
+            .collect()
+    }
+
+    pub fn accidental(&self, name: NoteName) -> Accidental {
+        self.accidentals().iter().rev()
+            .find(|(x, _)| *x == name)
+            .map_or(Accidental::Natural, |(_, accidental)| *accidental)
+    }
+}
+
+impl FromStr for KeySignature {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str(s)
+    }
+}
+
+impl fmt::Display for KeySignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Key {
+    tonic: Note,
+    mode: Mode,
+}
+
+impl Key {
+    // C major
+    pub fn default() -> Self {
+        Self {tonic: Note::default(), mode: Mode::Ionian}
+    }
+
+    // The scale and its signature must be spelled with double accidentals at most.
+    pub fn new(tonic: Note, mode: Mode) -> Result<Self, HarmonyError> {
+        let key = Self {
+            tonic: Note::new(tonic.name(), tonic.accidental(), Note::default().octave()),
+            mode,
+        };
+        key.spell_signature()?;
+        Ok(key)
+    }
+
+    pub fn from_str(input: &str) -> Result<Self, HarmonyError> {
+        let offset = input.chars().count() - input.trim_start().chars().count();
+        let s = input.trim();
+        let (tonic_str, mode_str) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let name_str: String = tonic_str.chars().take(1).collect();
+        let name = NoteName::from_str(&name_str).map_err(|x| x.within(input, offset))?;
+        let accidental_str: String = tonic_str.chars().skip(1).collect();
+        let accidental = Accidental::from_str(&accidental_str).map_err(|x| x.within(input, offset + 1))?;
+        let mode = match mode_str.trim().to_lowercase().as_str() {
+            "" if name_str.chars().all(char::is_lowercase) => Mode::Aeolian,
+            "" | "major" => Mode::Ionian,
+            "minor" => Mode::Aeolian,
+            mode_lower => match Mode::ALL.iter().find(|x| x.to_str().to_lowercase() == mode_lower) {
+                Some(mode) => *mode,
+                None => {
+                    let position = offset + tonic_str.chars().count() + 1
+                        + mode_str.chars().count() - mode_str.trim_start().chars().count();
+                    let mut expected = vec!["major", "minor"];
+                    expected.extend(Mode::ALL.iter().map(|x| x.to_str()));
+                    return Err(HarmonyError::parse(input, position, mode_str.trim(), &expected));
+                },
+            },
+        };
+        Self::new(Note::new(name, accidental, Note::default().octave()), mode)
+    }
+
+    pub fn to_str(self) -> String {
+        let mode_str = match self.mode {
+            Mode::Ionian => "major",
+            Mode::Aeolian => "minor",
+            mode => mode.to_str(),
+        };
+        format!("{}{} {}", self.tonic.name().to_str(), self.tonic.accidental().to_str(), mode_str)
+    }
+
+    pub fn tonic(&self) -> Note {
+        self.tonic
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    // new only accepts keys whose scale can be spelled
+    pub fn scale(&self) -> DiatonicScale {
+        DiatonicScale::from_tonic(self.tonic, self.mode).unwrap()
+    }
+
+    pub fn signature(&self) -> KeySignature {
+        self.spell_signature().unwrap()
+    }
+
+    fn spell_signature(&self) -> Result<KeySignature, HarmonyError> {
+        let (family, rotation) = self.mode.family();
+        let scale = DiatonicScale::from_tonic(self.tonic, self.mode)?;
+        let scale = if family == Mode::Ionian {
+            scale
+        } else {
+            // modes of harmonic and melodic minor use the signature of their natural minor
+            let parent = scale.notesequence().notes[(7 - rotation) % 7];
+            DiatonicScale::from_tonic(parent, Mode::Aeolian)?
+        };
+        let fifths = scale.notesequence().notes.iter().map(|x| x.accidental().offset()).sum();
+        Ok(KeySignature {fifths})
+    }
+
+    pub fn is_diatonic(&self, note: &Note) -> bool {
+        self.scale().notesequence().notes.iter().any(|x| x.same_spelling(note))
+    }
+
+    pub fn parallel(&self, mode: Mode) -> Result<Self, HarmonyError> {
+        Self::new(self.tonic, mode)
+    }
+
+    pub fn from_signature(signature: KeySignature, mode: Mode) -> Result<Self, HarmonyError> {
+        Self::new(ionian_tonic(signature.fifths())?, Mode::Ionian)?.relative(mode)
+    }
+
+    pub fn relative(&self, mode: Mode) -> Result<Self, HarmonyError> {
+        let ionian = DiatonicScale::from_tonic(ionian_tonic(self.signature().fifths())?, Mode::Ionian)?;
+        let (family, rotation) = mode.family();
+        let tonic = if family == Mode::Ionian {
+            ionian.notesequence().notes[rotation]
+        } else {
+            let parent = ionian.notesequence().notes[5];
+            DiatonicScale::from_tonic(parent, family)?.notesequence().notes[rotation]
+        };
+        Self::new(tonic, mode)
+    }
+
+    pub fn fifth_up(&self) -> Result<Self, HarmonyError> {
+        Self::new(self.tonic.transpose(Interval::from_str("P5").unwrap())?, self.mode)
+    }
+
+    pub fn fifth_down(&self) -> Result<Self, HarmonyError> {
+        Self::new(self.tonic.transpose_down(Interval::from_str("P5").unwrap())?, self.mode)
+    }
+}
+
+fn ionian_tonic(fifths: i32) -> Result<Note, HarmonyError> {
+    let fifth = Interval::from_str("P5").unwrap();
+    let mut tonic = Note::default();
+    for _ in 0..fifths.abs() {
+        tonic = if fifths > 0 {
+            tonic.transpose(fifth)?
+        } else {
+            tonic.transpose_down(fifth)?
+        };
+    }
+    Ok(Note::new(tonic.name(), tonic.accidental(), Note::default().octave()))
+}
+
+impl FromStr for Key {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str(s)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use NoteName::*;
+    use Accidental::*;
+
+    fn key(s: &str) -> Key {
+        Key::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!(key("C"), Key::new(Note::from_str("C3").unwrap(), Mode::Ionian).unwrap());
+        assert_eq!(key("c"), Key::new(Note::from_str("C3").unwrap(), Mode::Aeolian).unwrap());
+        assert_eq!(key("Bb major"), Key::new(Note::from_str("Bb5").unwrap(), Mode::Ionian).unwrap());
+        assert_eq!(key("f# minor"), Key::new(Note::from_str("F#3").unwrap(), Mode::Aeolian).unwrap());
+        assert_eq!(key("D Dorian"), Key::new(Note::from_str("D3").unwrap(), Mode::Dorian).unwrap());
+        assert_eq!(key("E phrygian dominant"), Key::new(Note::from_str("E3").unwrap(), Mode::PhrygianDominant).unwrap());
+
+        assert!(Key::from_str("").is_err());
+        assert!(Key::from_str("H major").is_err());
+        assert!(Key::from_str("C! major").is_err());
+        assert!(Key::from_str("C mixed").is_err());
+
+        // keys that would need triple accidentals
+        let error = Err(HarmonyError::OutOfRange("Scale would require more than double accidentals"));
+        assert_eq!(Key::from_str("B## major"), error);
+        assert_eq!(Key::from_str("Fb Ultralocrian"), error);
+        assert_eq!(Key::new(Note::from_str("Cb4").unwrap(), Mode::Ultralocrian), error);
+        assert!(Key::from_str("C## major").is_ok());
+    }
+
+    #[test]
+    fn to_str() {
+        assert_eq!(key("Bb").to_str(), "Bb major");
+        assert_eq!(key("c#").to_str(), "C# minor");
+        assert_eq!(key("G Mixolydian").to_str(), "G Mixolydian");
+    }
+
+    #[test]
+    fn signature() {
+        assert_eq!(key("C").signature().fifths(), 0);
+        assert_eq!(key("a").signature().fifths(), 0);
+        assert_eq!(key("G").signature().fifths(), 1);
+        assert_eq!(key("F").signature().fifths(), -1);
+        assert_eq!(key("C#").signature().fifths(), 7);
+        assert_eq!(key("Cb").signature().fifths(), -7);
+        assert_eq!(key("eb").signature().fifths(), -6);
+        assert_eq!(key("G#").signature().fifths(), 8);
+        assert_eq!(key("D Dorian").signature().fifths(), 0);
+        assert_eq!(key("E Lydian").signature().fifths(), 5);
+        assert_eq!(key("A harmonic minor").signature().fifths(), 0);
+        assert_eq!(key("G melodic minor").signature().fifths(), -2);
+        assert_eq!(key("E Phrygian dominant").signature().fifths(), 0);
+        assert_eq!(key("B Altered").signature().fifths(), -3);
+    }
+
+    #[test]
+    fn signature_accidentals() {
+        assert_eq!(key("C").signature().accidentals(), vec![]);
+        assert_eq!(key("D").signature().accidentals(), vec![(F, Sharp), (C, Sharp)]);
+        assert_eq!(key("Ab").signature().accidentals(), vec![(B, Flat), (E, Flat), (A, Flat), (D, Flat)]);
+        assert_eq!(key("G#").signature().accidentals().last(), Some(&(F, Doublesharp)));
+        assert_eq!(key("G#").signature().sharps(), 8);
+        assert_eq!(key("G#").signature().flats(), 0);
+        assert_eq!(key("bb").signature().flats(), 5);
+
+        assert_eq!(key("Eb").signature().accidental(E), Flat);
+        assert_eq!(key("Eb").signature().accidental(F), Natural);
+        assert_eq!(key("G#").signature().accidental(F), Doublesharp);
+        assert_eq!(key("G#").signature().accidental(C), Sharp);
+
+        assert!(KeySignature::from_fifths(15).is_err());
+        assert_eq!(KeySignature::from_fifths(-3).unwrap().accidentals(), vec![(B, Flat), (E, Flat), (A, Flat)]);
+    }
+
+    #[test]
+    fn is_diatonic() {
+        assert!(key("Eb").is_diatonic(&Note::from_str("Ab4").unwrap()));
+        assert!(!key("Eb").is_diatonic(&Note::from_str("G#4").unwrap()));
+        assert!(!key("Eb").is_diatonic(&Note::from_str("A4").unwrap()));
+        assert!(key("a harmonic minor").is_diatonic(&Note::from_str("G#2").unwrap()));
+        assert!(!key("a").is_diatonic(&Note::from_str("G#2").unwrap()));
+    }
+
+    #[test]
+    fn relative() {
+        assert_eq!(key("C").relative(Mode::Aeolian), Ok(key("a")));
+        assert_eq!(key("a").relative(Mode::Ionian), Ok(key("C")));
+        assert_eq!(key("Eb").relative(Mode::Aeolian), Ok(key("c")));
+        assert_eq!(key("f#").relative(Mode::Ionian), Ok(key("A")));
+        assert_eq!(key("C").relative(Mode::Dorian), Ok(key("D Dorian")));
+        assert_eq!(key("Bb").relative(Mode::Lydian), Ok(key("Eb Lydian")));
+        assert_eq!(key("C").relative(Mode::HarmonicMinor), Ok(key("A harmonic minor")));
+        assert_eq!(key("C").relative(Mode::PhrygianDominant), Ok(key("E Phrygian dominant")));
+        assert_eq!(key("g melodic minor").relative(Mode::Ionian), Ok(key("Bb")));
+    }
+
+    #[test]
+    fn from_signature() {
+        let signature = |s| KeySignature::from_str(s).unwrap();
+        assert_eq!(Key::from_signature(signature("0"), Mode::Ionian), Ok(key("C")));
+        assert_eq!(Key::from_signature(signature("3b"), Mode::Ionian), Ok(key("Eb")));
+        assert_eq!(Key::from_signature(signature("3#"), Mode::Aeolian), Ok(key("f#")));
+        assert_eq!(Key::from_signature(signature("7b"), Mode::Aeolian), Ok(key("ab")));
+        assert_eq!(Key::from_signature(signature("1#"), Mode::Dorian), Ok(key("A Dorian")));
+        assert_eq!(Key::from_signature(signature("1b"), Mode::HarmonicMinor), Ok(key("d harmonic minor")));
+    }
+
+    #[test]
+    fn parallel() {
+        assert_eq!(key("C").parallel(Mode::Aeolian), Ok(key("c")));
+        assert_eq!(key("f#").parallel(Mode::Ionian), Ok(key("F#")));
+        assert!(key("G#").parallel(Mode::Lydian).is_ok());
+        assert!(key("C##").parallel(Mode::Lydian).is_err());
+    }
+
+    #[test]
+    fn circle_of_fifths() {
+        assert_eq!(key("C").fifth_up(), Ok(key("G")));
+        assert_eq!(key("C").fifth_down(), Ok(key("F")));
+        assert_eq!(key("a").fifth_up(), Ok(key("e")));
+        assert_eq!(key("F#").fifth_up(), Ok(key("C#")));
+        assert_eq!(key("Gb").fifth_down(), Ok(key("Cb")));
+        assert_eq!(key("D").fifth_up().unwrap().signature().fifths(), key("D").signature().fifths() + 1);
+        assert!(key("C##").fifth_up().is_err());
+    }
+
+    #[test]
+    fn from_str_errors() {
+        assert_eq!(Key::from_str("H major").unwrap_err().position(), Some(0));
+        assert_eq!(Key::from_str("  Cx major").unwrap_err(), HarmonyError::parse("  Cx major", 3, "x", &["bb", "b", "", "#", "##"]));
+        match Key::from_str("C  mixed") {
+            Err(HarmonyError::Parse {position, found, expected, ..}) => {
+                assert_eq!((position, found.as_str()), (3, "mixed"));
+                assert!(expected.contains(&String::from("Dorian")));
+            },
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn traits() {
+        assert_eq!("Bb major".parse::<Key>(), Ok(key("Bb")));
+        assert_eq!(format!("{}", key("f#")), "F# minor");
+        assert_eq!(KeySignature::from_str("3#"), KeySignature::from_fifths(3));
+        assert_eq!(KeySignature::from_str("2b"), KeySignature::from_fifths(-2));
+        assert_eq!(KeySignature::from_str("0"), KeySignature::from_fifths(0));
+        assert_eq!(KeySignature::from_str("3x"), Err(HarmonyError::parse("3x", 1, "x", &["#", "b"])));
+        assert!(KeySignature::from_str("#").is_err());
+        assert!(matches!(KeySignature::from_str("15#"), Err(HarmonyError::OutOfRange(_))));
+        assert_eq!(format!("{}", key("Ab").signature()), "4b");
+        assert_eq!(key("E").signature().to_str().parse::<KeySignature>(), Ok(key("E").signature()));
+    }
+}
@@ -0,0 +1,28 @@
+#![allow(clippy::should_implement_trait)]
+
+pub mod errors;
+pub mod notenames;
+pub mod accidentals;
+pub mod notes;
+pub mod notation;
+pub mod notesequences;
+pub mod clefs;
+pub mod diatonic_scales;
+pub mod intervals;
+pub mod chords;
+pub mod chord_symbols;
+pub mod scales;
+pub mod keys;
+pub mod tunings;
+pub mod scala;
+pub mod synth;
+pub mod midi_files;
+pub mod musicxml;
+pub mod lilypond;
+pub mod abc;
+pub mod rhythm;
+pub mod staff;
+pub mod keyboard;
+pub mod roman_numerals;
+pub mod voice_leading;
+pub mod ear_training;
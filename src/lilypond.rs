@@ -0,0 +1,375 @@
+use crate::errors::HarmonyError;
+use crate::notenames::NoteName;
+use crate::accidentals::Accidental;
+use crate::notes::Note;
+use crate::notesequences::NoteSequence;
+use crate::diatonic_scales::{DiatonicScale, Mode};
+use crate::intervals::Interval;
+use crate::chords::{Chord, ChordQuality};
+use crate::keys::Key;
+use crate::clefs::Clef;
+use crate::roman_numerals::RomanNumeral;
+
+const VERSION: &str = "2.24.0";
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum PitchMode {
+    Absolute,
+    Relative,
+}
+
+// Settings for the LilyPond source of a single staff. Without a clef one is
+// chosen by the range of the notes.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LilyPond {
+    pub title: String,
+    pub pitch_mode: PitchMode,
+    pub clef: Option<Clef>,
+    pub key: Option<Key>,
+    // LilyPond duration like 4 for quarter notes
+    pub duration: u32,
+    // print a line of Roman numerals in the key below progressions
+    pub numerals: bool,
+}
+
+impl LilyPond {
+    pub fn new() -> Self {
+        Self {
+            title: String::new(),
+            pitch_mode: PitchMode::Absolute,
+            clef: None,
+            key: None,
+            duration: 4,
+            numerals: false,
+        }
+    }
+
+    // note name with the Dutch is/es accidentals and without octave
+    pub fn class_to_str(name: NoteName, accidental: Accidental) -> String {
+        let name = name.to_str().to_lowercase();
+        let suffix = match (name.as_str(), accidental) {
+            (_, Accidental::Natural) => "",
+            ("e" | "a", Accidental::Flat) => "s",
+            ("e" | "a", Accidental::Doubleflat) => "ses",
+            (_, Accidental::Flat) => "es",
+            (_, Accidental::Doubleflat) => "eses",
+            (_, Accidental::Sharp) => "is",
+            (_, Accidental::Doublesharp) => "isis",
+        };
+        name + suffix
+    }
+
+    // absolute pitch where c' is middle C
+    pub fn pitch_to_str(note: &Note) -> String {
+        Self::class_to_str(note.name(), note.accidental()) + &octave_marks(note.octave() as i32 - 3)
+    }
+
+    // Pitch in relative mode. The octave marks count from the note closest to
+    // the previous one on the staff, which is at most a fourth away.
+    pub fn relative_pitch_to_str(note: &Note, previous: &Note) -> String {
+        let steps = previous.dist_steps(note);
+        Self::class_to_str(note.name(), note.accidental()) + &octave_marks((steps + 3).div_euclid(7))
+    }
+
+    pub fn key_to_str(key: &Key) -> String {
+        let mode = match key.mode() {
+            Mode::Ionian => "major",
+            Mode::Aeolian | Mode::HarmonicMinor | Mode::MelodicMinor => "minor",
+            Mode::Dorian => "dorian",
+            Mode::Phrygian => "phrygian",
+            Mode::Lydian => "lydian",
+            Mode::Mixolydian => "mixolydian",
+            Mode::Locrian => "locrian",
+            // other modes show the major key with the same signature
+            _ => return match Key::from_signature(key.signature(), Mode::Ionian) {
+                Ok(major) => Self::key_to_str(&major),
+                Err(_) => key.parallel(Mode::Ionian).map(|x| Self::key_to_str(&x)).unwrap_or_default(),
+            },
+        };
+        let tonic = key.tonic();
+        format!("\\key {} \\{}", Self::class_to_str(tonic.name(), tonic.accidental()), mode)
+    }
+
+    // chord in LilyPond chord mode like bes:m7/f
+    pub fn chord_to_str(chord: &Chord) -> String {
+        let root = chord.root();
+        let mut s = Self::class_to_str(root.name(), root.accidental());
+        let mut modifiers = String::from(quality_modifiers(chord.quality()));
+        for alteration in chord.alterations() {
+            modifiers += if modifiers.is_empty() {"5."} else {"."};
+            modifiers += &degree_to_str(alteration);
+        }
+        if !modifiers.is_empty() {
+            s += ":";
+            s += &modifiers;
+        }
+        if !chord.bass().same_spelling(&root) {
+            s += "/";
+            s += &Self::class_to_str(chord.bass().name(), chord.bass().accidental());
+        }
+        s
+    }
+
+    pub fn note(&self, note: &Note) -> String {
+        self.sequence(&NoteSequence {notes: vec![*note]})
+    }
+
+    pub fn sequence(&self, sequence: &NoteSequence) -> String {
+        let chords: Vec<NoteSequence> = sequence.notes.iter().map(|x| NoteSequence {notes: vec![*x]}).collect();
+        let mut s = self.header();
+        s += "\\score {\n";
+        s += &self.staff(&chords, self.key.as_ref(), "  ");
+        s += "  \\layout {}\n}\n";
+        s
+    }
+
+    // The scale up to the tonic an octave higher in the key of the scale,
+    // unless another key is set.
+    pub fn scale(&self, scale: &DiatonicScale) -> String {
+        let mut notes = scale.notesequence().notes.clone();
+        notes.push(scale.tonic().transpose(Interval::from_str("P8").unwrap()).unwrap_or(scale.tonic()));
+        let key = self.key.or(Key::new(scale.tonic(), scale.mode()).ok());
+        Self {key, ..self.clone()}.sequence(&NoteSequence {notes})
+    }
+
+    // Block chords with their chord names above the staff. Chords that can not
+    // be named or analyzed leave a gap.
+    pub fn progression(&self, chords: &[NoteSequence]) -> Result<String, HarmonyError> {
+        let duration = self.duration;
+        let names: Vec<String> = chords.iter()
+            .map(|x| Chord::identify(x).map(|y| Self::chord_to_str(&y)).unwrap_or(String::from("s")))
+            .collect();
+        let mut s = self.header();
+        s += "\\score {\n  <<\n";
+        s += &format!("    \\new ChordNames \\chordmode {{\n      {}\n    }}\n", with_duration(&names, duration));
+        s += &self.staff(chords, self.key.as_ref(), "    ");
+        if self.numerals {
+            let key = self.key.ok_or(HarmonyError::InvalidArgument("Roman numerals need a key"))?;
+            let numerals: Vec<String> = RomanNumeral::analyze_progression(chords, &key).iter()
+                .map(|x| format!("\"{}\"", x.as_ref().ok().and_then(|y| y.to_str().ok()).unwrap_or_default()))
+                .collect();
+            s += &format!("    \\new Lyrics \\lyricmode {{\n      {}\n    }}\n", with_duration(&numerals, duration));
+        }
+        s += "  >>\n  \\layout {}\n}\n";
+        Ok(s)
+    }
+
+    fn header(&self) -> String {
+        let mut s = format!("\\version \"{}\"\n\n", VERSION);
+        if !self.title.is_empty() {
+            s += &format!("\\header {{\n  title = \"{}\"\n}}\n\n", escape(&self.title));
+        }
+        s
+    }
+
+    fn staff(&self, chords: &[NoteSequence], key: Option<&Key>, indent: &str) -> String {
+        let all: Vec<Note> = chords.iter().flat_map(|x| x.notes.clone()).collect();
+        let clef = self.clef.unwrap_or(Clef::from_range(&all));
+        let middle_c = Note::new(NoteName::C, Accidental::Natural, 4);
+        let mut s = String::from(indent);
+        s += "\\new Staff ";
+        if self.pitch_mode == PitchMode::Relative {
+            s += &format!("\\relative {} ", Self::pitch_to_str(&middle_c));
+        }
+        s += "{\n";
+        s += &format!("{}  \\clef {}\n", indent, clef);
+        if let Some(key) = key {
+            s += &format!("{}  {}\n", indent, Self::key_to_str(key));
+        }
+
+        // chords are relative to the first note of the previous chord
+        let mut previous = middle_c;
+        let mut items = Vec::new();
+        for chord in chords {
+            let mut last = previous;
+            let pitches: Vec<String> = chord.notes.iter()
+                .map(|x| {
+                    let pitch = match self.pitch_mode {
+                        PitchMode::Absolute => Self::pitch_to_str(x),
+                        PitchMode::Relative => Self::relative_pitch_to_str(x, &last),
+                    };
+                    last = *x;
+                    pitch
+                })
+                .collect();
+            if let Some(first) = chord.notes.first() {
+                previous = *first;
+            }
+            items.push(match pitches.len() {
+                0 => String::from("r"),
+                1 => pitches[0].clone(),
+                _ => format!("<{}>", pitches.join(" ")),
+            });
+        }
+        if !items.is_empty() {
+            s += &format!("{}  {}\n", indent, with_duration(&items, self.duration));
+        }
+        s += &format!("{}}}\n", indent);
+        s
+    }
+}
+
+impl Default for LilyPond {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn octave_marks(octaves: i32) -> String {
+    if octaves >= 0 {
+        "'".repeat(octaves as usize)
+    } else {
+        ",".repeat(-octaves as usize)
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// durations carry over in LilyPond, so only the first item needs one
+fn with_duration(items: &[String], duration: u32) -> String {
+    let mut items = items.to_vec();
+    if let Some(first) = items.first_mut() {
+        *first += &duration.to_string();
+    }
+    items.join(" ")
+}
+
+fn quality_modifiers(quality: ChordQuality) -> &'static str {
+    match quality {
+        ChordQuality::Major => "",
+        ChordQuality::Minor => "m",
+        ChordQuality::Diminished => "dim",
+        ChordQuality::Augmented => "aug",
+        ChordQuality::Sus2 => "sus2",
+        ChordQuality::Sus4 => "sus4",
+        ChordQuality::Major6 => "6",
+        ChordQuality::Minor6 => "m6",
+        ChordQuality::Dominant7 => "7",
+        ChordQuality::Major7 => "maj7",
+        ChordQuality::Minor7 => "m7",
+        ChordQuality::MinorMajor7 => "m7+",
+        ChordQuality::HalfDiminished7 => "m7.5-",
+        ChordQuality::Diminished7 => "dim7",
+        ChordQuality::Augmented7 => "aug7",
+        ChordQuality::AugmentedMajor7 => "maj7.5+",
+        ChordQuality::Dominant7Sus4 => "7sus4",
+        ChordQuality::Add9 => "5.9",
+        ChordQuality::MinorAdd9 => "m5.9",
+        ChordQuality::Dominant9 => "9",
+        ChordQuality::Major9 => "maj9",
+        ChordQuality::Minor9 => "m9",
+        ChordQuality::Dominant11 => "11",
+        ChordQuality::Minor11 => "m11",
+        ChordQuality::Dominant13 => "13",
+        ChordQuality::Major13 => "maj13",
+        ChordQuality::Minor13 => "m13",
+        ChordQuality::Dominant7Flat5 => "7.5-",
+        ChordQuality::Dominant7Flat9 => "7.9-",
+        ChordQuality::Dominant7Sharp9 => "7.9+",
+        ChordQuality::Dominant7Sharp11 => "7.11+",
+        ChordQuality::Dominant7Flat13 => "7.13-",
+        ChordQuality::Altered => "7.5-.9-.9+.13-",
+    }
+}
+
+// chord mode step like 9- relative to the major scale, sevenths are minor
+fn degree_to_str(interval: &Interval) -> String {
+    let number = interval.steps() + 1;
+    let hsteps = match interval.steps() % 7 {
+        0 => 0, 1 => 2, 2 => 4, 3 => 5, 4 => 7, 5 => 9, _ => 10,
+    } + 12 * (interval.steps() / 7);
+    let sign = match interval.hsteps() - hsteps {
+        0 => "",
+        x if x > 0 => "+",
+        _ => "-",
+    };
+    format!("{}{}", number, sign)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const SEQUENCE: &str = include_str!("../lilypond/sequence.ly");
+    const SCALE: &str = include_str!("../lilypond/scale.ly");
+    const PROGRESSION: &str = include_str!("../lilypond/progression.ly");
+
+    #[test]
+    fn pitches() {
+        assert_eq!(LilyPond::pitch_to_str(&Note::from_str("C4").unwrap()), "c'");
+        assert_eq!(LilyPond::pitch_to_str(&Note::from_str("C3").unwrap()), "c");
+        assert_eq!(LilyPond::pitch_to_str(&Note::from_str("B#3").unwrap()), "bis");
+        assert_eq!(LilyPond::pitch_to_str(&Note::from_str("Bb1").unwrap()), "bes,,");
+        assert_eq!(LilyPond::pitch_to_str(&Note::from_str("Eb6").unwrap()), "es'''");
+        assert_eq!(LilyPond::pitch_to_str(&Note::from_str("Abb0").unwrap()), "ases,,,");
+        assert_eq!(LilyPond::pitch_to_str(&Note::from_str("F##5").unwrap()), "fisis''");
+        assert_eq!(LilyPond::pitch_to_str(&Note::from_str("Dbb4").unwrap()), "deses'");
+
+        assert_eq!(LilyPond::relative_pitch_to_str(&Note::from_str("F4").unwrap(), &Note::from_str("C4").unwrap()), "f");
+        assert_eq!(LilyPond::relative_pitch_to_str(&Note::from_str("G4").unwrap(), &Note::from_str("C4").unwrap()), "g'");
+        assert_eq!(LilyPond::relative_pitch_to_str(&Note::from_str("G3").unwrap(), &Note::from_str("C4").unwrap()), "g");
+        assert_eq!(LilyPond::relative_pitch_to_str(&Note::from_str("F3").unwrap(), &Note::from_str("C4").unwrap()), "f,");
+        assert_eq!(LilyPond::relative_pitch_to_str(&Note::from_str("F#4").unwrap(), &Note::from_str("C4").unwrap()), "fis");
+        assert_eq!(LilyPond::relative_pitch_to_str(&Note::from_str("Gb4").unwrap(), &Note::from_str("C4").unwrap()), "ges'");
+        assert_eq!(LilyPond::relative_pitch_to_str(&Note::from_str("C6").unwrap(), &Note::from_str("C4").unwrap()), "c''");
+        assert_eq!(LilyPond::relative_pitch_to_str(&Note::from_str("B2").unwrap(), &Note::from_str("C4").unwrap()), "b,");
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(LilyPond::key_to_str(&Key::from_str("Eb major").unwrap()), "\\key es \\major");
+        assert_eq!(LilyPond::key_to_str(&Key::from_str("f# minor").unwrap()), "\\key fis \\minor");
+        assert_eq!(LilyPond::key_to_str(&Key::from_str("D dorian").unwrap()), "\\key d \\dorian");
+        assert_eq!(LilyPond::key_to_str(&Key::new(Note::from_str("A3").unwrap(), Mode::HarmonicMinor).unwrap()), "\\key a \\minor");
+        assert_eq!(LilyPond::key_to_str(&Key::new(Note::from_str("E3").unwrap(), Mode::PhrygianDominant).unwrap()), "\\key c \\major");
+    }
+
+    #[test]
+    fn chord_names() {
+        let chord = |s: &str| LilyPond::chord_to_str(&Chord::from_symbol(s).unwrap());
+        assert_eq!(chord("C"), "c");
+        assert_eq!(chord("Bbm7/F"), "bes:m7/f");
+        assert_eq!(chord("G7/B"), "g:7/b");
+        assert_eq!(chord("F#m7b5"), "fis:m7.5-");
+        assert_eq!(chord("Ebmaj7"), "es:maj7");
+        assert_eq!(chord("Bdim7"), "b:dim7");
+        assert_eq!(chord("C/D"), "c/d");
+        assert_eq!(chord("C7(b9,#11)"), "c:7.9-.11+");
+    }
+
+    #[test]
+    fn golden_files() {
+        let melody = LilyPond {title: String::from("Ode \"to\" Joy"), ..LilyPond::new()};
+        assert_eq!(melody.sequence(&NoteSequence::from_str("E4 E4 F4 G4 G4 F4 E4 D4 C4 C4 D4 E4 E4 D4 D4").unwrap()), SEQUENCE);
+
+        let scale = LilyPond {pitch_mode: PitchMode::Relative, duration: 8, ..LilyPond::new()};
+        let tonic = Note::from_str("Bb2").unwrap();
+        assert_eq!(scale.scale(&DiatonicScale::from_tonic(tonic, Mode::Mixolydian).unwrap()), SCALE);
+
+        let progression = LilyPond {
+            title: String::from("Cadence in D"),
+            pitch_mode: PitchMode::Relative,
+            key: Some(Key::from_str("D major").unwrap()),
+            duration: 1,
+            numerals: true,
+            ..LilyPond::new()
+        };
+        let chords = [
+            NoteSequence::from_str("D4 F#4 A4 D5").unwrap(), NoteSequence::from_str("D4 G4 B4 D5").unwrap(), NoteSequence::from_str("A3 E4 A4 C#5").unwrap(),
+            NoteSequence::from_str("B3 D4 F#4 B4").unwrap(), NoteSequence::from_str("C#4 E4 G4 A4").unwrap(), NoteSequence::from_str("D4 F#4 A4 D5").unwrap(),
+        ];
+        assert_eq!(progression.progression(&chords).unwrap(), PROGRESSION);
+        assert_eq!(LilyPond {key: None, ..progression}.progression(&chords), Err(HarmonyError::InvalidArgument("Roman numerals need a key")));
+    }
+
+    #[test]
+    fn single_note() {
+        assert_eq!(
+            LilyPond {clef: Some(Clef::Alto), ..LilyPond::new()}.note(&Note::from_str("C#4").unwrap()),
+            "\\version \"2.24.0\"\n\n\\score {\n  \\new Staff {\n    \\clef alto\n    cis'4\n  }\n  \\layout {}\n}\n",
+        );
+    }
+}
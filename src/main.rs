@@ -1,25 +1,250 @@
-mod notenames;
-mod accidentals;
-mod notes;
-mod notesequences;
-mod diatonic_scales;
+use std::f32::consts::PI;
+use iced::alignment;
+use iced::mouse;
+use iced::widget::canvas::{self, event, path, Frame, Geometry, Path, Stroke};
+use iced::widget::{button, canvas as canvas_widget, checkbox, column, pick_list, radio, row, scrollable, text, text_input};
+use iced::{theme, Color, Element, Point, Rectangle, Renderer, Sandbox, Settings, Size, Theme, Vector};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use harmony::errors::HarmonyError;
+use harmony::notes::{Note, SpellingPolicy};
+use harmony::accidentals::Accidental;
+use harmony::keys::Key;
+use harmony::keyboard::Keyboard;
+use harmony::clefs::Clef;
+use harmony::staff::{Glyph, Staff, StaffKind, StaffLayout};
+use harmony::intervals::Interval;
+use harmony::chords::ChordQuality;
+use harmony::diatonic_scales::Mode;
+use harmony::ear_training::{self, ChordFamily, ChordSettings, ChordTrainer, Difficulty, Direction, IntervalSettings,
+    IntervalTrainer, Mark, ScaleFamily, ScaleSettings, ScaleTrainer};
+use harmony::synth::{self, Synth, Timbre};
+use harmony::tunings::Tuning;
 
-use iced::{Element, Sandbox, Settings};
-use crate::notes::Note;
-use crate::diatonic_scales::{DiatonicScale, Mode};
+const SAMPLE_RATE: u32 = 44100;
+const NOTE_DURATION: f64 = 1.0;
+const RANGE: [&str; 5] = ["C2", "C3", "C4", "C5", "C6"];
+// pixels per staff space
+const SPACE: f32 = 10.0;
+const KEY_WIDTH: f32 = 22.0;
+const KEY_LENGTH: f32 = 100.0;
+
+// renders and plays, the error is meant for the feedback line
+fn play(samples: Result<Vec<f32>, HarmonyError>) -> Result<(), String> {
+    let samples = samples.map_err(|x| x.to_string())?;
+    synth::play(&samples, SAMPLE_RATE).map_err(|x| format!("Unable to play audio: {}", x))
+}
+
+// Draws a staff layout, clefs and accidentals are taken from the font.
+struct StaffView {
+    layout: StaffLayout,
+}
+
+impl StaffView {
+    // each column sounds at once
+    fn element<'a, Message: 'a>(staff: Staff, columns: &[Vec<Note>]) -> Element<'a, Message> {
+        let layout = staff.layout(columns);
+        let (width, height) = (layout.width * SPACE, layout.height * SPACE);
+        canvas_widget(Self {layout}).width(width).height(height).into()
+    }
+
+    fn symbol(glyph: &Glyph) -> Option<&'static str> {
+        match glyph {
+            Glyph::Clef {clef: Clef::Treble, ..} => Some("\u{1D11E}"),
+            Glyph::Clef {clef: Clef::Bass, ..} => Some("\u{1D122}"),
+            Glyph::Clef {clef: Clef::Alto | Clef::Tenor, ..} => Some("\u{1D121}"),
+            Glyph::Accidental {accidental, ..} => Some(match accidental {
+                Accidental::Doubleflat => "\u{1D12B}",
+                Accidental::Flat => "\u{266D}",
+                Accidental::Natural => "\u{266E}",
+                Accidental::Sharp => "\u{266F}",
+                Accidental::Doublesharp => "\u{1D12A}",
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl<Message> canvas::Program<Message> for StaffView {
+    type State = ();
+
+    fn draw(&self, _state: &(), renderer: &Renderer, theme: &Theme, bounds: Rectangle, _cursor: mouse::Cursor)
+        -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let color = theme.palette().text;
+        let point = |x: f32, y: f32| Point::new(x * SPACE, y * SPACE);
+        let line = |frame: &mut Frame, from: Point, to: Point, width: f32| {
+            frame.stroke(&Path::line(from, to), Stroke::default().with_color(color).with_width(width * SPACE));
+        };
+        for glyph in &self.layout.glyphs {
+            match *glyph {
+                Glyph::StaffLine {x0, x1, y} | Glyph::LedgerLine {x0, x1, y} => line(&mut frame, point(x0, y), point(x1, y), 0.1),
+                Glyph::SystemLine {x, y0, y1} => line(&mut frame, point(x, y0), point(x, y1), 0.15),
+                Glyph::Stem {x, y0, y1} => line(&mut frame, point(x, y0), point(x, y1), 0.12),
+                Glyph::Notehead {x, y} => {
+                    let notehead = Path::new(|builder| builder.ellipse(path::arc::Elliptical {
+                        center: point(x, y),
+                        radii: Vector::new(0.62 * SPACE, 0.42 * SPACE),
+                        rotation: -0.35,
+                        start_angle: 0.0,
+                        end_angle: 2.0 * PI,
+                    }));
+                    frame.fill(&notehead, color);
+                }
+                Glyph::Clef {x, y, ..} | Glyph::Accidental {x, y, ..} => {
+                    let size = if matches!(glyph, Glyph::Clef {..}) { 4.0 } else { 2.2 };
+                    frame.fill_text(canvas::Text {
+                        content: Self::symbol(glyph).unwrap().to_string(),
+                        position: point(x, y),
+                        color,
+                        size: size * SPACE,
+                        horizontal_alignment: alignment::Horizontal::Center,
+                        vertical_alignment: alignment::Vertical::Center,
+                        shaping: iced::widget::text::Shaping::Advanced,
+                        ..canvas::Text::default()
+                    });
+                }
+            }
+        }
+        vec![frame.into_geometry()]
+    }
+}
+
+// Draws a keyboard with highlighted notes, clicks are spelled by the policy.
+struct PianoView<'a, Message> {
+    keyboard: Keyboard,
+    highlights: Vec<Note>,
+    policy: SpellingPolicy,
+    on_press: Option<Box<dyn Fn(Note) -> Message + 'a>>,
+}
+
+impl<'a, Message: 'a> PianoView<'a, Message> {
+    fn new(keyboard: Keyboard, highlights: Vec<Note>) -> Self {
+        Self {keyboard, highlights, policy: SpellingPolicy::Minimal, on_press: None}
+    }
+
+    fn on_press(self, policy: SpellingPolicy, f: impl Fn(Note) -> Message + 'a) -> Self {
+        Self {policy, on_press: Some(Box::new(f)), ..self}
+    }
+
+    fn element(self) -> Element<'a, Message> {
+        let width = self.keyboard.white_keys() as f32 * KEY_WIDTH;
+        canvas_widget(self).width(width).height(KEY_LENGTH).into()
+    }
+
+    fn highlight(&self, midi: u8) -> Option<&Note> {
+        self.highlights.iter().find(|x| x.to_midi() == Ok(midi))
+    }
+}
+
+impl<'a, Message> canvas::Program<Message> for PianoView<'a, Message> {
+    type State = ();
+
+    fn update(&self, _state: &mut (), event: canvas::Event, bounds: Rectangle, cursor: mouse::Cursor)
+        -> (event::Status, Option<Message>) {
+        let (Some(on_press), Some(position)) = (&self.on_press, cursor.position_in(bounds)) else {
+            return (event::Status::Ignored, None);
+        };
+        match event {
+            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                match self.keyboard.note_at(position.x / KEY_WIDTH, position.y / KEY_LENGTH, self.policy) {
+                    Some(note) => (event::Status::Captured, Some(on_press(note))),
+                    None => (event::Status::Ignored, None),
+                }
+            }
+            _ => (event::Status::Ignored, None),
+        }
+    }
+
+    fn draw(&self, _state: &(), renderer: &Renderer, theme: &Theme, bounds: Rectangle, _cursor: mouse::Cursor)
+        -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let palette = theme.palette();
+        for key in self.keyboard.keys() {
+            let rectangle = Path::rectangle(
+                Point::new(key.x * KEY_WIDTH, 0.0),
+                Size::new(key.width * KEY_WIDTH, key.length * KEY_LENGTH),
+            );
+            let highlight = self.highlight(key.midi);
+            let fill = match (highlight, key.black) {
+                (Some(_), _) => palette.primary,
+                (None, true) => Color::BLACK,
+                (None, false) => Color::WHITE,
+            };
+            frame.fill(&rectangle, fill);
+            frame.stroke(&rectangle, Stroke::default().with_color(Color::BLACK).with_width(1.0));
+            if let Some(note) = highlight {
+                frame.fill_text(canvas::Text {
+                    content: format!("{}{}", note.name(), note.accidental()),
+                    position: Point::new((key.x + key.width / 2.0) * KEY_WIDTH, (key.length - 0.05) * KEY_LENGTH),
+                    color: Color::WHITE,
+                    size: 11.0,
+                    horizontal_alignment: alignment::Horizontal::Center,
+                    vertical_alignment: alignment::Vertical::Bottom,
+                    ..canvas::Text::default()
+                });
+            }
+        }
+        vec![frame.into_geometry()]
+    }
+
+    fn mouse_interaction(&self, _state: &(), bounds: Rectangle, cursor: mouse::Cursor) -> mouse::Interaction {
+        if self.on_press.is_some() && cursor.is_over(bounds) {
+            mouse::Interaction::Pointer
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+}
+
+// how notes are shown on every screen
+#[derive(Debug, PartialEq, Copy, Clone)]
+struct Notation {
+    staff: StaffKind,
+    keyboard: Keyboard,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Screen {
+    Intervals,
+    Chords,
+    Scales,
+}
 
 struct Harmony {
+    screen: Screen,
+    notation: Notation,
+    synth: Synth,
+    intervals: IntervalScreen,
+    chords: ChordScreen,
+    scales: ScaleScreen,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum Message {
+    ScreenSelected(Screen),
+    StaffSelected(StaffKind),
+    KeyboardLowSelected(String),
+    KeyboardHighSelected(String),
+    Interval(IntervalMessage),
+    Chord(ChordMessage),
+    Scale(ScaleMessage),
 }
 
 impl Sandbox for Harmony {
-    type Message = ();
+    type Message = Message;
 
     fn new() -> Self {
         Self {
+            screen: Screen::Intervals,
+            notation: Notation {
+                staff: StaffKind::Grand,
+                keyboard: Keyboard::new(Note::from_str("C2").unwrap(), Note::from_str("C6").unwrap()).unwrap(),
+            },
+            synth: Synth::new(Timbre::Piano, SAMPLE_RATE, Tuning::equal(440.0).unwrap()).unwrap(),
+            intervals: IntervalScreen::new(),
+            chords: ChordScreen::new(),
+            scales: ScaleScreen::new(),
         }
     }
 
@@ -27,16 +252,549 @@ impl Sandbox for Harmony {
         String::from("Harmony Trainer")
     }
 
-    fn update(&mut self, _message: Self::Message) {
-        todo!()
+    fn update(&mut self, message: Self::Message) {
+        match message {
+            Message::ScreenSelected(screen) => self.screen = screen,
+            Message::StaffSelected(staff) => self.notation.staff = staff,
+            // a range upside down keeps the old one
+            Message::KeyboardLowSelected(low) => {
+                let high = Note::from_midi(self.notation.keyboard.high(), SpellingPolicy::Sharps).unwrap();
+                if let Ok(keyboard) = Keyboard::new(Note::from_str(&low).unwrap(), high) {
+                    self.notation.keyboard = keyboard;
+                }
+            }
+            Message::KeyboardHighSelected(high) => {
+                let low = Note::from_midi(self.notation.keyboard.low(), SpellingPolicy::Sharps).unwrap();
+                if let Ok(keyboard) = Keyboard::new(low, Note::from_str(&high).unwrap()) {
+                    self.notation.keyboard = keyboard;
+                }
+            }
+            Message::Interval(message) => self.intervals.update(message, &self.synth),
+            Message::Chord(message) => self.chords.update(message, &self.synth),
+            Message::Scale(message) => self.scales.update(message, &self.synth),
+        }
+    }
+
+    fn view(&self) -> Element<'_, Self::Message> {
+        let tab = |label, screen| {
+            let style = if self.screen == screen { theme::Button::Primary } else { theme::Button::Secondary };
+            button(label).style(style).on_press(Message::ScreenSelected(screen))
+        };
+        let range: Vec<String> = RANGE.iter().map(|x| x.to_string()).collect();
+        let keyboard_low = Note::from_midi(self.notation.keyboard.low(), SpellingPolicy::Sharps).unwrap().to_str();
+        let keyboard_high = Note::from_midi(self.notation.keyboard.high(), SpellingPolicy::Sharps).unwrap().to_str();
+        let content = match self.screen {
+            Screen::Intervals => self.intervals.view(self.notation).map(Message::Interval),
+            Screen::Chords => self.chords.view(self.notation).map(Message::Chord),
+            Screen::Scales => self.scales.view(self.notation).map(Message::Scale),
+        };
+        column![
+            row![
+                tab("Intervals", Screen::Intervals),
+                tab("Chords", Screen::Chords),
+                tab("Scales", Screen::Scales),
+                text("Staff"),
+                pick_list(&StaffKind::ALL[..], Some(self.notation.staff), Message::StaffSelected),
+                text("Keyboard"),
+                pick_list(range.clone(), Some(keyboard_low), Message::KeyboardLowSelected),
+                text("to"),
+                pick_list(range, Some(keyboard_high), Message::KeyboardHighSelected),
+            ].spacing(10),
+            scrollable(content),
+        ].spacing(20).padding(20).into()
+    }
+}
+
+struct IntervalScreen {
+    trainer: IntervalTrainer,
+    feedback: String,
+}
+
+#[derive(Debug, Clone)]
+enum IntervalMessage {
+    NewQuestion,
+    Replay,
+    Answer(Interval),
+    KeyPressed(Note),
+    LowSelected(String),
+    HighSelected(String),
+    DirectionToggled(Direction, bool),
+}
+
+impl IntervalScreen {
+    fn new() -> Self {
+        Self {
+            trainer: IntervalTrainer::new(IntervalSettings::default(), StdRng::from_entropy()),
+            feedback: String::from("Press \"New interval\" to start"),
+        }
+    }
+
+    fn play(&mut self, synth: &Synth) {
+        if let Some(question) = self.trainer.question() {
+            if let Err(error) = play(question.render(synth, NOTE_DURATION)) {
+                self.feedback = error;
+            }
+        }
+    }
+
+    fn answer(&mut self, answer: Interval) {
+        let first = self.trainer.answer().is_none();
+        let reveal = self.trainer.question().map(|x| x.reveal());
+        match (self.trainer.submit(answer), reveal) {
+            (Some(true), Some(Ok(reveal))) => self.feedback = format!("Correct: {}", reveal),
+            (Some(false), Some(Ok(reveal))) if first => self.feedback = format!("Not quite, it was {}", reveal),
+            (Some(false), _) => self.feedback = format!("{} is not it, try again", answer.to_long_str()),
+            (_, Some(Err(error))) => self.feedback = error.to_string(),
+            _ => (),
+        }
+    }
+
+    fn update(&mut self, message: IntervalMessage, synth: &Synth) {
+        match message {
+            IntervalMessage::NewQuestion => match self.trainer.next_question() {
+                Ok(_) => {
+                    self.feedback = String::from("Which interval was that?");
+                    self.play(synth);
+                }
+                Err(error) => self.feedback = error.to_string(),
+            },
+            IntervalMessage::Replay => self.play(synth),
+            IntervalMessage::Answer(answer) => self.answer(answer),
+            // the interval between the root and the clicked key
+            IntervalMessage::KeyPressed(note) => {
+                let answer = self.trainer.question().map(|x| Interval::between(&x.root, &note));
+                match answer {
+                    Some(Ok(answer)) => self.answer(answer),
+                    Some(Err(error)) => self.feedback = error.to_string(),
+                    None => (),
+                }
+            }
+            IntervalMessage::LowSelected(low) => self.trainer.settings.low = Note::from_str(&low).unwrap(),
+            IntervalMessage::HighSelected(high) => self.trainer.settings.high = Note::from_str(&high).unwrap(),
+            IntervalMessage::DirectionToggled(direction, enabled) => {
+                let directions = &mut self.trainer.settings.directions;
+                directions.retain(|x| *x != direction);
+                if enabled {
+                    directions.push(direction);
+                }
+            }
+        }
+    }
+
+    // the answer once given
+    fn staff(&self, staff: StaffKind) -> Element<'_, IntervalMessage> {
+        let notes = self.trainer.question()
+            .filter(|_| self.trainer.answer().is_some())
+            .and_then(|x| x.notes().ok().map(|notes| (x.direction, notes)));
+        match notes {
+            Some((Direction::Harmonic, notes)) => StaffView::element(Staff::new(staff), &[notes.notes]),
+            Some((_, notes)) => StaffView::element(Staff::new(staff), &notes.notes.iter().map(|x| vec![*x]).collect::<Vec<_>>()),
+            None => column![].into(),
+        }
+    }
+
+    fn answer_buttons(&self) -> Element<'_, IntervalMessage> {
+        let rows = self.trainer.settings.intervals.chunks(6)
+            .map(|intervals| row(intervals.iter()
+                .map(|x| {
+                    let answer = button(text(x.to_str()));
+                    match self.trainer.question() {
+                        Some(_) => answer.on_press(IntervalMessage::Answer(*x)),
+                        None => answer,
+                    }.into()
+                })
+                .collect()).spacing(5).into())
+            .collect();
+        column(rows).spacing(5).into()
+    }
+
+    // the root, the second note once answered, clicking a key answers
+    fn keyboard(&self, keyboard: Keyboard) -> Element<'_, IntervalMessage> {
+        let Some(question) = self.trainer.question() else {
+            return PianoView::new(keyboard, Vec::new()).element();
+        };
+        let highlights = match (self.trainer.answer(), question.notes()) {
+            (Some(_), Ok(notes)) => notes.notes,
+            _ => vec![question.root],
+        };
+        let piano = PianoView::new(keyboard, highlights);
+        match Key::new(question.root, Mode::Ionian) {
+            Ok(key) => piano.on_press(SpellingPolicy::Key(key), IntervalMessage::KeyPressed).element(),
+            Err(_) => piano.element(),
+        }
+    }
+
+    fn view(&self, notation: Notation) -> Element<'_, IntervalMessage> {
+        let range: Vec<String> = RANGE.iter().map(|x| x.to_string()).collect();
+        let settings = &self.trainer.settings;
+        let directions = Direction::ALL.iter()
+            .map(|direction| {
+                let direction = *direction;
+                checkbox(direction.to_str(), settings.directions.contains(&direction),
+                    move |x| IntervalMessage::DirectionToggled(direction, x)).into()
+            })
+            .collect();
+        column![
+            text("Interval Training").size(28),
+            row![
+                button("New interval").on_press(IntervalMessage::NewQuestion),
+                button("Replay").on_press(IntervalMessage::Replay),
+            ].spacing(10),
+            self.answer_buttons(),
+            self.keyboard(notation.keyboard),
+            text(&self.feedback),
+            self.staff(notation.staff),
+            text(format!("Score: {}", self.trainer.score)),
+            row![
+                text("Range"),
+                pick_list(range.clone(), Some(settings.low.to_str()), IntervalMessage::LowSelected),
+                text("to"),
+                pick_list(range, Some(settings.high.to_str()), IntervalMessage::HighSelected),
+            ].spacing(10),
+            row(directions).spacing(10),
+        ].spacing(20).into()
+    }
+}
+
+struct ChordScreen {
+    trainer: ChordTrainer,
+    quality: Option<ChordQuality>,
+    inversion: usize,
+    feedback: String,
+}
+
+#[derive(Debug, Clone)]
+enum ChordMessage {
+    NewQuestion,
+    Replay,
+    QualitySelected(ChordQuality),
+    InversionSelected(usize),
+    Submit,
+    DifficultySelected(Difficulty),
+    FamilyToggled(ChordFamily, bool),
+}
+
+impl ChordScreen {
+    fn new() -> Self {
+        Self {
+            trainer: ChordTrainer::new(ChordSettings::default(), StdRng::from_entropy()),
+            quality: None,
+            inversion: 0,
+            feedback: String::from("Press \"New chord\" to start"),
+        }
+    }
+
+    fn play(&mut self, synth: &Synth) {
+        if let Some(question) = self.trainer.question() {
+            if let Err(error) = play(question.render(synth, self.trainer.settings.difficulty.duration())) {
+                self.feedback = error;
+            }
+        }
+    }
+
+    fn update(&mut self, message: ChordMessage, synth: &Synth) {
+        match message {
+            ChordMessage::NewQuestion => match self.trainer.next_question() {
+                Ok(_) => {
+                    self.quality = None;
+                    self.inversion = 0;
+                    self.feedback = String::from("Which chord and inversion was that?");
+                    self.play(synth);
+                }
+                Err(error) => self.feedback = error.to_string(),
+            },
+            ChordMessage::Replay => self.play(synth),
+            ChordMessage::QualitySelected(quality) => self.quality = Some(quality),
+            ChordMessage::InversionSelected(inversion) => self.inversion = inversion,
+            ChordMessage::Submit => {
+                let Some(quality) = self.quality else {
+                    return;
+                };
+                let first = !self.trainer.is_answered();
+                let reveal = self.trainer.question().map(|x| x.reveal());
+                match (self.trainer.submit(quality, self.inversion), reveal) {
+                    (Ok(Some(true)), Some(Ok(reveal))) => self.feedback = format!("Correct: {}", reveal),
+                    (Ok(Some(false)), Some(Ok(reveal))) if first => self.feedback = format!("Not quite, it was {}", reveal),
+                    (Ok(Some(false)), _) => self.feedback = format!("{}, {} is not it, try again", quality,
+                        ear_training::inversion_to_str(self.inversion)),
+                    (Err(error), _) | (_, Some(Err(error))) => self.feedback = error.to_string(),
+                    _ => (),
+                }
+            }
+            ChordMessage::DifficultySelected(difficulty) => {
+                self.trainer.settings.difficulty = difficulty;
+                self.inversion = self.inversion.min(difficulty.max_inversion());
+            }
+            ChordMessage::FamilyToggled(family, enabled) => {
+                let families = &mut self.trainer.settings.families;
+                families.retain(|x| *x != family);
+                if enabled {
+                    families.push(family);
+                }
+            }
+        }
+    }
+
+    fn quality_buttons(&self) -> Element<'_, ChordMessage> {
+        let rows = self.trainer.settings.qualities().chunks(4)
+            .map(|qualities| row(qualities.iter()
+                .map(|x| {
+                    let style = if self.quality == Some(*x) { theme::Button::Primary } else { theme::Button::Secondary };
+                    button(text(x.to_long_str())).style(style).on_press(ChordMessage::QualitySelected(*x)).into()
+                })
+                .collect()).spacing(5).into())
+            .collect();
+        column(rows).spacing(5).into()
+    }
+
+    fn view(&self, notation: Notation) -> Element<'_, ChordMessage> {
+        let answer = self.trainer.question().filter(|_| self.trainer.is_answered()).and_then(|x| x.notes().ok());
+        let settings = &self.trainer.settings;
+        let inversions = (0..=settings.difficulty.max_inversion())
+            .map(|x| radio(ear_training::inversion_to_str(x), x, Some(self.inversion), ChordMessage::InversionSelected).into())
+            .collect();
+        let difficulties = Difficulty::ALL.iter()
+            .map(|x| radio(x.to_str(), *x, Some(settings.difficulty), ChordMessage::DifficultySelected).into())
+            .collect();
+        let families = ChordFamily::ALL.iter()
+            .map(|family| {
+                let family = *family;
+                checkbox(family.to_str(), settings.families.contains(&family),
+                    move |x| ChordMessage::FamilyToggled(family, x)).into()
+            })
+            .collect();
+        let results = self.trainer.results().iter()
+            .map(|(quality, score)| text(format!("{}: {}", quality, score)).into())
+            .collect();
+        let submit = button("Check");
+        let submit = match (self.trainer.question(), self.quality) {
+            (Some(_), Some(_)) => submit.on_press(ChordMessage::Submit),
+            _ => submit,
+        };
+        column![
+            text("Chord Training").size(28),
+            row![
+                button("New chord").on_press(ChordMessage::NewQuestion),
+                button("Replay").on_press(ChordMessage::Replay),
+            ].spacing(10),
+            self.quality_buttons(),
+            row(inversions).spacing(10),
+            submit,
+            text(&self.feedback),
+            match &answer {
+                Some(notes) => StaffView::element(Staff::new(notation.staff), std::slice::from_ref(&notes.notes)),
+                None => column![].into(),
+            },
+            PianoView::new(notation.keyboard, answer.map(|x| x.notes).unwrap_or_default()).element(),
+            text(format!("Score: {}", self.trainer.score)),
+            row(difficulties).spacing(10),
+            row(families).spacing(10),
+            column(results).spacing(5),
+        ].spacing(20).into()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+enum Exercise {
+    NameMode,
+    SpellScale,
+}
+
+struct ScaleScreen {
+    trainer: ScaleTrainer,
+    exercise: Exercise,
+    show_notes: bool,
+    input: String,
+    marks: Vec<String>,
+    feedback: String,
+}
+
+#[derive(Debug, Clone)]
+enum ScaleMessage {
+    NewQuestion,
+    Replay,
+    ExerciseSelected(Exercise),
+    ShowNotesToggled(bool),
+    Answer(Mode),
+    InputChanged(String),
+    KeyPressed(Note),
+    SubmitSpelling,
+    FamilyToggled(ScaleFamily, bool),
+}
+
+impl ScaleScreen {
+    fn new() -> Self {
+        Self {
+            trainer: ScaleTrainer::new(ScaleSettings::default(), StdRng::from_entropy()),
+            exercise: Exercise::NameMode,
+            show_notes: false,
+            input: String::new(),
+            marks: Vec::new(),
+            feedback: String::from("Press \"New scale\" to start"),
+        }
+    }
+
+    fn play(&mut self, synth: &Synth) {
+        if let Some(question) = self.trainer.question() {
+            if let Err(error) = play(question.render(synth, NOTE_DURATION / 2.0)) {
+                self.feedback = error;
+            }
+        }
+    }
+
+    fn update(&mut self, message: ScaleMessage, synth: &Synth) {
+        match message {
+            ScaleMessage::NewQuestion => match self.trainer.next_question() {
+                Ok(question) => {
+                    self.input.clear();
+                    self.marks.clear();
+                    match self.exercise {
+                        Exercise::NameMode => {
+                            self.feedback = String::from("Which mode is this?");
+                            self.play(synth);
+                        }
+                        Exercise::SpellScale => self.feedback = format!("Spell {}", question.prompt()),
+                    }
+                }
+                Err(error) => self.feedback = error.to_string(),
+            },
+            ScaleMessage::Replay => self.play(synth),
+            ScaleMessage::ExerciseSelected(exercise) => self.exercise = exercise,
+            ScaleMessage::ShowNotesToggled(show_notes) => self.show_notes = show_notes,
+            ScaleMessage::Answer(answer) => {
+                let first = !self.trainer.is_answered();
+                let reveal = self.trainer.question().and_then(|x| x.reveal().ok());
+                match (self.trainer.submit_mode(answer), reveal) {
+                    (Ok(Some(true)), Some(reveal)) => self.feedback = format!("Correct: {}", reveal),
+                    (Ok(Some(false)), Some(reveal)) if first => self.feedback = format!("Not quite, it was {}", reveal),
+                    (Ok(Some(false)), _) => self.feedback = format!("{} is not it, try again", answer),
+                    (Err(error), _) => self.feedback = error.to_string(),
+                    _ => (),
+                }
+            }
+            ScaleMessage::InputChanged(input) => self.input = input,
+            ScaleMessage::KeyPressed(note) => {
+                if !self.input.trim().is_empty() {
+                    self.input.push(' ');
+                }
+                self.input += &format!("{}{}", note.name(), note.accidental());
+            }
+            ScaleMessage::SubmitSpelling => {
+                let reveal = self.trainer.question().and_then(|x| x.reveal().ok());
+                match (self.trainer.submit_spelling(&self.input), reveal) {
+                    (Ok(Some(marks)), Some(reveal)) => {
+                        self.marks = marks.iter().enumerate()
+                            .map(|(i, x)| match (x.given, x.mark) {
+                                (Some((name, accidental)), Mark::Correct) => format!("{}. {}{}", i + 1, name, accidental),
+                                (Some((name, accidental)), mark) => format!("{}. {}{}: {}", i + 1, name, accidental, mark),
+                                (None, mark) => format!("{}. {}", i + 1, mark),
+                            })
+                            .collect();
+                        self.feedback = if marks.iter().all(|x| x.mark == Mark::Correct) {
+                            format!("Correct: {}", reveal)
+                        } else {
+                            String::from("Some notes are wrong, try again")
+                        };
+                    }
+                    (Err(error), _) => self.feedback = error.to_string(),
+                    _ => (),
+                }
+            }
+            ScaleMessage::FamilyToggled(family, enabled) => {
+                let families = &mut self.trainer.settings.families;
+                families.retain(|x| *x != family);
+                if enabled {
+                    families.push(family);
+                }
+            }
+        }
+    }
+
+    fn mode_buttons(&self) -> Element<'_, ScaleMessage> {
+        let rows = self.trainer.settings.modes().chunks(4)
+            .map(|modes| row(modes.iter()
+                .map(|x| {
+                    let answer = button(text(x.to_str()));
+                    match self.trainer.question() {
+                        Some(_) => answer.on_press(ScaleMessage::Answer(*x)),
+                        None => answer,
+                    }.into()
+                })
+                .collect()).spacing(5).into())
+            .collect();
+        column(rows).spacing(5).into()
     }
 
-    fn view(&self) -> Element<Self::Message> {
-        "Harmony".into()
+    fn view(&self, notation: Notation) -> Element<'_, ScaleMessage> {
+        let exercises = [(Exercise::NameMode, "Name the mode"), (Exercise::SpellScale, "Spell the scale")].iter()
+            .map(|(exercise, label)| radio(*label, *exercise, Some(self.exercise), ScaleMessage::ExerciseSelected).into())
+            .collect();
+        let families = ScaleFamily::ALL.iter()
+            .map(|family| {
+                let family = *family;
+                checkbox(family.to_str(), self.trainer.settings.families.contains(&family),
+                    move |x| ScaleMessage::FamilyToggled(family, x)).into()
+            })
+            .collect();
+        let shown = self.trainer.question()
+            .filter(|_| self.trainer.is_answered() || (self.show_notes && self.exercise == Exercise::NameMode))
+            .and_then(|x| x.notes().ok())
+            .map(|x| x.notes);
+        // the scale is written in its own key
+        let staff = match self.trainer.question().and_then(|x| x.key().ok()) {
+            Some(key) => Staff {signature: key.signature(), ..Staff::new(notation.staff)},
+            None => Staff::new(notation.staff),
+        };
+        let notes: Element<'_, ScaleMessage> = match &shown {
+            Some(notes) => StaffView::element(staff, &notes.iter().map(|x| vec![*x]).collect::<Vec<_>>()),
+            None => column![].into(),
+        };
+        // clicked keys are spelled like the scale, the order is up to the student
+        let piano = PianoView::new(notation.keyboard, shown.unwrap_or_default());
+        let policy = self.trainer.question()
+            .filter(|_| self.exercise == Exercise::SpellScale)
+            .and_then(|x| x.spelling_policy().ok());
+        let piano = match policy {
+            Some(policy) => piano.on_press(policy, ScaleMessage::KeyPressed),
+            None => piano,
+        };
+        let exercise: Element<'_, ScaleMessage> = match self.exercise {
+            Exercise::NameMode => {
+                column![
+                    row![
+                        button("Replay").on_press(ScaleMessage::Replay),
+                        checkbox("Show notes", self.show_notes, ScaleMessage::ShowNotesToggled),
+                    ].spacing(10),
+                    self.mode_buttons(),
+                ].spacing(20).into()
+            }
+            Exercise::SpellScale => {
+                let marks = self.marks.iter().map(|x| text(x).into()).collect();
+                column![
+                    row![
+                        text_input("Eb F G A Bb C D", &self.input)
+                            .on_input(ScaleMessage::InputChanged)
+                            .on_submit(ScaleMessage::SubmitSpelling),
+                        button("Check").on_press(ScaleMessage::SubmitSpelling),
+                    ].spacing(10),
+                    column(marks).spacing(5),
+                ].spacing(20).into()
+            }
+        };
+        column![
+            text("Scale Training").size(28),
+            row(exercises).spacing(10),
+            button("New scale").on_press(ScaleMessage::NewQuestion),
+            exercise,
+            piano.element(),
+            text(&self.feedback),
+            notes,
+            text(format!("Score: {}", self.trainer.score)),
+            row(families).spacing(10),
+        ].spacing(20).into()
     }
 }
 
-fn main() {
-    let _ = Harmony::run(Settings::default());
-    println!("{:?}", DiatonicScale::from_tonic(Note::from_str("Gb3").unwrap(), Mode::Ionian));
+fn main() -> iced::Result {
+    Harmony::run(Settings::default())
 }
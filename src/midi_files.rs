@@ -0,0 +1,896 @@
+use std::io;
+use std::path::Path;
+
+use crate::errors::HarmonyError;
+use crate::notes::Note;
+use crate::notesequences::NoteSequence;
+use crate::keys::{Key, KeySignature};
+use crate::diatonic_scales::Mode;
+use crate::notes::SpellingPolicy;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum MidiFormat {
+    // format 0, everything in one track
+    SingleTrack,
+    // format 1, a conductor track followed by one track per voice
+    MultiTrack,
+}
+
+impl MidiFormat {
+    pub fn number(self) -> u16 {
+        match self {
+            MidiFormat::SingleTrack => 0,
+            MidiFormat::MultiTrack => 1,
+        }
+    }
+}
+
+// start and duration in ticks
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct MidiNote {
+    pub note: Note,
+    pub start: u32,
+    pub duration: u32,
+    pub velocity: u8,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MidiTrack {
+    pub name: String,
+    pub channel: u8,
+    pub notes: Vec<MidiNote>,
+}
+
+impl MidiTrack {
+    pub fn new(name: &str, channel: u8) -> Self {
+        Self {name: String::from(name), channel, notes: Vec::new()}
+    }
+
+    // the notes one after the other
+    pub fn from_sequence(name: &str, channel: u8, sequence: &NoteSequence, ticks: u32, velocity: u8) -> Self {
+        let mut track = Self::new(name, channel);
+        for (i, note) in sequence.notes.iter().enumerate() {
+            track.notes.push(MidiNote {note: *note, start: i as u32 * ticks, duration: ticks, velocity});
+        }
+        track
+    }
+
+    // each chord as a block
+    pub fn from_progression(name: &str, channel: u8, chords: &[NoteSequence], ticks: u32, velocity: u8) -> Self {
+        let mut track = Self::new(name, channel);
+        for (i, chord) in chords.iter().enumerate() {
+            for note in &chord.notes {
+                track.notes.push(MidiNote {note: *note, start: i as u32 * ticks, duration: ticks, velocity});
+            }
+        }
+        track
+    }
+
+    // the notes ordered by their start
+    pub fn sequence(&self) -> NoteSequence {
+        let mut notes = self.notes.clone();
+        notes.sort_by_key(|x| x.start);
+        NoteSequence {notes: notes.iter().map(|x| x.note).collect()}
+    }
+
+    // One track and channel per voice, the n-th note of every chord belongs to
+    // the n-th voice.
+    pub fn voices(chords: &[NoteSequence], ticks: u32, velocity: u8) -> Vec<Self> {
+        let count = chords.iter().map(|x| x.notes.len()).max().unwrap_or(0);
+        (0..count).map(|voice| {
+            let mut track = Self::new(&format!("Voice {}", voice + 1), voice as u8);
+            for (i, chord) in chords.iter().enumerate() {
+                if let Some(note) = chord.notes.get(voice) {
+                    track.notes.push(MidiNote {note: *note, start: i as u32 * ticks, duration: ticks, velocity});
+                }
+            }
+            track
+        }).collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MidiFile {
+    pub ticks_per_quarter: u16,
+    // microseconds per quarter note
+    pub tempo: u32,
+    // (tick, tempo) for later tempo changes
+    pub tempo_changes: Vec<(u32, u32)>,
+    // numerator and denominator, e.g. (6, 8)
+    pub time_signature: (u8, u8),
+    pub key: Option<Key>,
+    pub key_changes: Vec<(u32, Key)>,
+    pub tracks: Vec<MidiTrack>,
+}
+
+impl MidiFile {
+    pub fn new() -> Self {
+        Self {
+            ticks_per_quarter: 480,
+            tempo: 500_000,
+            tempo_changes: Vec::new(),
+            time_signature: (4, 4),
+            key: None,
+            key_changes: Vec::new(),
+            tracks: Vec::new(),
+        }
+    }
+
+    pub fn bpm(&self) -> f64 {
+        60_000_000.0 / self.tempo as f64
+    }
+
+    pub fn set_bpm(&mut self, bpm: f64) -> Result<(), HarmonyError> {
+        let tempo = (60_000_000.0 / bpm).round();
+        if !tempo.is_finite() || bpm <= 0.0 || tempo < 1.0 || tempo > 0xff_ffff as f64 {
+            return Err(HarmonyError::OutOfRange("Tempo is outside of the MIDI range"));
+        }
+        self.tempo = tempo as u32;
+        Ok(())
+    }
+
+    pub fn to_bytes(&self, format: MidiFormat) -> Result<Vec<u8>, HarmonyError> {
+        if self.ticks_per_quarter == 0 || self.ticks_per_quarter > 0x7fff {
+            return Err(HarmonyError::OutOfRange("Ticks per quarter note must be between 1 and 32767"));
+        }
+        let conductor = self.conductor_events()?;
+        let mut tracks = Vec::new();
+        match format {
+            MidiFormat::SingleTrack => {
+                let mut events = conductor;
+                for track in &self.tracks {
+                    events.extend(track_events(track)?);
+                }
+                tracks.push(events);
+            },
+            MidiFormat::MultiTrack => {
+                tracks.push(conductor);
+                for track in &self.tracks {
+                    let mut events = vec![(0, 0, meta_event(0x03, track.name.as_bytes()))];
+                    events.extend(track_events(track)?);
+                    tracks.push(events);
+                }
+            },
+        }
+
+        let mut bytes = Vec::new();
+        bytes.extend(b"MThd");
+        bytes.extend(6_u32.to_be_bytes());
+        bytes.extend(format.number().to_be_bytes());
+        bytes.extend((tracks.len() as u16).to_be_bytes());
+        bytes.extend(self.ticks_per_quarter.to_be_bytes());
+        for events in tracks {
+            let chunk = track_chunk(events);
+            bytes.extend(b"MTrk");
+            bytes.extend((chunk.len() as u32).to_be_bytes());
+            bytes.extend(chunk);
+        }
+        Ok(bytes)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: MidiFormat) -> io::Result<()> {
+        let bytes = self.to_bytes(format).map_err(|x| io::Error::new(io::ErrorKind::InvalidInput, x))?;
+        std::fs::write(path, bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, HarmonyError> {
+        let mut reader = Reader {bytes, position: 0};
+        if reader.take(4)? != b"MThd" {
+            return Err(HarmonyError::InvalidArgument("Missing MThd header"));
+        }
+        let length = reader.u32()? as usize;
+        if length < 6 {
+            return Err(HarmonyError::InvalidArgument("Header chunk is too short"));
+        }
+        let format = reader.u16()?;
+        let count = reader.u16()?;
+        let division = reader.u16()?;
+        reader.take(length - 6)?;
+        if format > 1 {
+            return Err(HarmonyError::InvalidArgument("Only SMF formats 0 and 1 are supported"));
+        }
+        if division & 0x8000 != 0 {
+            return Err(HarmonyError::InvalidArgument("SMPTE time division is not supported"));
+        }
+
+        let mut meta = Meta::default();
+        let mut tracks = Vec::new();
+        while tracks.len() < count as usize {
+            let kind = reader.take(4)?;
+            let length = reader.u32()? as usize;
+            let data = reader.take(length)?;
+            // unknown chunks are skipped
+            if kind == b"MTrk" {
+                tracks.push(read_track(data, &mut meta)?);
+            }
+        }
+
+        let mut file = Self {ticks_per_quarter: division, ..Self::new()};
+        meta.tempos.sort_by_key(|(tick, _)| *tick);
+        meta.keys.sort_by_key(|(tick, _)| *tick);
+        meta.time_signatures.sort_by_key(|(tick, _)| *tick);
+        // only the first time signature is kept
+        if let Some((_, time_signature)) = meta.time_signatures.first() {
+            file.time_signature = *time_signature;
+        }
+        for (tick, tempo) in meta.tempos {
+            match tick {
+                0 => file.tempo = tempo,
+                _ => file.tempo_changes.push((tick, tempo)),
+            }
+        }
+        for (tick, key) in meta.keys {
+            match tick {
+                0 => file.key = Some(key),
+                _ => file.key_changes.push((tick, key)),
+            }
+        }
+
+        let keys: Vec<(u32, u8)> = tracks.iter().flat_map(|(_, notes)| notes.iter().map(|x| (x.start, x.key))).collect();
+        let spellings = file.spell(&keys)?;
+        let mut index = 0;
+        for (name, notes) in tracks {
+            let spelled = &spellings[index..index + notes.len()];
+            index += notes.len();
+            let mut channels: Vec<u8> = notes.iter().map(|x| x.channel).collect();
+            channels.sort();
+            channels.dedup();
+            // a track playing on several channels is split
+            for channel in channels {
+                let mut track = MidiTrack::new(&name, channel);
+                for (note, spelling) in notes.iter().zip(spelled).filter(|(x, _)| x.channel == channel) {
+                    track.notes.push(MidiNote {
+                        note: *spelling, start: note.start, duration: note.end - note.start, velocity: note.velocity,
+                    });
+                }
+                file.tracks.push(track);
+            }
+        }
+        Ok(file)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?).map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x))
+    }
+
+    // key changes are expected in order
+    pub fn key_at(&self, tick: u32) -> Option<Key> {
+        self.key_changes.iter().rev()
+            .find(|(change, _)| *change <= tick)
+            .map(|(_, key)| *key)
+            .or(self.key)
+    }
+
+    // tempo changes may be listed in any order
+    pub fn seconds(&self, tick: u32) -> f64 {
+        let mut changes = self.tempo_changes.clone();
+        changes.sort_by_key(|(change, _)| *change);
+        let mut microseconds = 0.0;
+        let (mut last, mut tempo) = (0, self.tempo);
+        for (change, next) in &changes {
+            if *change >= tick {
+                break;
+            }
+            microseconds += (change - last) as f64 * tempo as f64;
+            last = *change;
+            tempo = *next;
+        }
+        microseconds += (tick - last) as f64 * tempo as f64;
+        microseconds / 1_000_000.0 / self.ticks_per_quarter as f64
+    }
+
+    // the notes sounding at every onset, lowest first
+    pub fn chords(&self) -> Vec<NoteSequence> {
+        let notes: Vec<&MidiNote> = self.tracks.iter().flat_map(|x| &x.notes).collect();
+        let mut onsets: Vec<u32> = notes.iter().map(|x| x.start).collect();
+        onsets.sort();
+        onsets.dedup();
+        onsets.iter().map(|onset| {
+            let mut chord: Vec<Note> = notes.iter()
+                .filter(|x| x.start <= *onset && *onset < x.start + x.duration)
+                .map(|x| x.note)
+                .collect();
+            chord.sort_by_key(|x| Note::default().dist_hsteps(x));
+            NoteSequence {notes: chord}
+        }).collect()
+    }
+
+    // Spells (tick, MIDI key) pairs. Under a key signature notes are spelled
+    // in that key, otherwise the spelling closest to the recent notes on the
+    // line of fifths is chosen.
+    pub fn spell(&self, keys: &[(u32, u8)]) -> Result<Vec<Note>, HarmonyError> {
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        // within an onset the white keys go first to steer the black keys
+        order.sort_by_key(|i| (keys[*i].0, matches!(keys[*i].1 % 12, 1 | 3 | 6 | 8 | 10)));
+        let mut center = initial_center(keys);
+        let mut spellings = vec![Note::default(); keys.len()];
+        for i in order {
+            let (tick, key) = keys[i];
+            let note = match self.key_at(tick) {
+                Some(signature) => Note::from_midi(key, SpellingPolicy::Key(signature))?,
+                None => {
+                    // every accidental counts like a step on the line of fifths
+                    let cost = |x: &Note| (x.line_of_fifths() as f64 - center).abs() + x.accidental().offset().abs() as f64;
+                    Note::from_midi(key, SpellingPolicy::Sharps)?.enharmonics().into_iter()
+                        .min_by(|a, b| cost(a).total_cmp(&cost(b)))
+                        .unwrap()
+                },
+            };
+            center = 0.75 * center + 0.25 * note.line_of_fifths() as f64;
+            spellings[i] = note;
+        }
+        Ok(spellings)
+    }
+
+    fn conductor_events(&self) -> Result<Vec<(u32, u8, Vec<u8>)>, HarmonyError> {
+        let (numerator, denominator) = self.time_signature;
+        if numerator == 0 || !denominator.is_power_of_two() {
+            return Err(HarmonyError::InvalidArgument("Invalid time signature"));
+        }
+        let mut events = Vec::new();
+        for (tick, tempo) in std::iter::once(&(0, self.tempo)).chain(&self.tempo_changes) {
+            if *tempo == 0 || *tempo > 0xff_ffff {
+                return Err(HarmonyError::OutOfRange("Tempo is outside of the MIDI range"));
+            }
+            events.push((*tick, 0, meta_event(0x51, &tempo.to_be_bytes()[1..])));
+        }
+        // 24 clocks per metronome click, 8 32nd notes per quarter
+        events.insert(1, (0, 0, meta_event(0x58, &[numerator, denominator.trailing_zeros() as u8, 24, 8])));
+        for (tick, key) in self.key.iter().map(|x| (0, *x)).chain(self.key_changes.iter().copied()) {
+            let fifths = key.signature().fifths();
+            if fifths.abs() > 7 {
+                return Err(HarmonyError::OutOfRange("Key signature has more than 7 accidentals"));
+            }
+            let minor = matches!(key.mode(), Mode::Aeolian | Mode::HarmonicMinor | Mode::MelodicMinor);
+            events.push((tick, 0, meta_event(0x59, &[fifths as i8 as u8, minor as u8])));
+        }
+        Ok(events)
+    }
+}
+
+impl Default for MidiFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn meta_event(kind: u8, data: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0xff, kind];
+    write_variable_length(data.len() as u32, &mut bytes);
+    bytes.extend(data);
+    bytes
+}
+
+// (tick, order, bytes), at the same tick meta events come first, then note
+// offs, then note ons. Notes without duration are left out, their note off
+// would come before the note on.
+fn track_events(track: &MidiTrack) -> Result<Vec<(u32, u8, Vec<u8>)>, HarmonyError> {
+    if track.channel > 15 {
+        return Err(HarmonyError::OutOfRange("MIDI channel must be between 0 and 15"));
+    }
+    let mut events = Vec::new();
+    for note in &track.notes {
+        if note.velocity == 0 || note.velocity > 127 {
+            return Err(HarmonyError::OutOfRange("Velocity must be between 1 and 127"));
+        }
+        let key = note.note.to_midi()?;
+        let end = note.start.checked_add(note.duration)
+            .ok_or(HarmonyError::OutOfRange("Note ends too late"))?;
+        if note.duration == 0 {
+            continue;
+        }
+        events.push((note.start, 2, vec![0x90 | track.channel, key, note.velocity]));
+        events.push((end, 1, vec![0x80 | track.channel, key, 0x40]));
+    }
+    Ok(events)
+}
+
+fn track_chunk(mut events: Vec<(u32, u8, Vec<u8>)>) -> Vec<u8> {
+    events.sort_by_key(|(tick, order, _)| (*tick, *order));
+    let mut bytes = Vec::new();
+    let mut tick = 0;
+    for (time, _, data) in events {
+        write_variable_length(time - tick, &mut bytes);
+        bytes.extend(data);
+        tick = time;
+    }
+    bytes.extend([0x00, 0xff, 0x2f, 0x00]);
+    bytes
+}
+
+// seven bits per byte, most significant first, the high bit marks continuation
+fn write_variable_length(value: u32, bytes: &mut Vec<u8>) {
+    let mut groups = vec![(value & 0x7f) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        groups.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    bytes.extend(groups.iter().rev());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], HarmonyError> {
+        let end = self.position.checked_add(count)
+            .filter(|x| *x <= self.bytes.len())
+            .ok_or(HarmonyError::InvalidArgument("Unexpected end of file"))?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, HarmonyError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn data_byte(&mut self) -> Result<u8, HarmonyError> {
+        match self.byte()? {
+            byte if byte < 0x80 => Ok(byte),
+            _ => Err(HarmonyError::InvalidArgument("Expected a data byte")),
+        }
+    }
+
+    fn u16(&mut self) -> Result<u16, HarmonyError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, HarmonyError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn variable_length(&mut self) -> Result<u32, HarmonyError> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let byte = self.byte()?;
+            value = (value << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(HarmonyError::InvalidArgument("Variable length quantity is longer than four bytes"))
+    }
+}
+
+// meta events of all tracks with their ticks
+#[derive(Default)]
+struct Meta {
+    tempos: Vec<(u32, u32)>,
+    keys: Vec<(u32, Key)>,
+    time_signatures: Vec<(u32, (u8, u8))>,
+}
+
+struct RawNote {
+    start: u32,
+    end: u32,
+    key: u8,
+    channel: u8,
+    velocity: u8,
+}
+
+fn read_track(data: &[u8], meta: &mut Meta) -> Result<(String, Vec<RawNote>), HarmonyError> {
+    let mut reader = Reader {bytes: data, position: 0};
+    let mut tick: u32 = 0;
+    let mut running_status = None;
+    let mut name = String::new();
+    let mut notes: Vec<RawNote> = Vec::new();
+    // (channel, key, index into notes) of the sounding notes, oldest first
+    let mut sounding: Vec<(u8, u8, usize)> = Vec::new();
+    while reader.position < data.len() {
+        tick = tick.checked_add(reader.variable_length()?)
+            .ok_or(HarmonyError::OutOfRange("Track is too long"))?;
+        let status = match reader.byte()? {
+            // running status, the byte already belongs to the data
+            byte if byte < 0x80 => {
+                reader.position -= 1;
+                running_status.ok_or(HarmonyError::InvalidArgument("Running status without a previous status byte"))?
+            },
+            byte => byte,
+        };
+        match status {
+            0xff => {
+                running_status = None;
+                let kind = reader.byte()?;
+                let length = reader.variable_length()? as usize;
+                let data = reader.take(length)?;
+                match (kind, length) {
+                    (0x03, _) if name.is_empty() => name = String::from_utf8_lossy(data).into_owned(),
+                    (0x51, 3) => meta.tempos.push((tick, u32::from_be_bytes([0, data[0], data[1], data[2]]))),
+                    (0x58, 4) if data[1] < 8 => meta.time_signatures.push((tick, (data[0], 1 << data[1]))),
+                    (0x59, 2) => {
+                        let signature = KeySignature::from_fifths(data[0] as i8 as i32)?;
+                        let mode = if data[1] == 1 {Mode::Aeolian} else {Mode::Ionian};
+                        meta.keys.push((tick, Key::from_signature(signature, mode)?));
+                    },
+                    (0x2f, _) => break,
+                    _ => (),
+                }
+            },
+            0xf0 | 0xf7 => {
+                running_status = None;
+                let length = reader.variable_length()? as usize;
+                reader.take(length)?;
+            },
+            0x80..=0xef => {
+                running_status = Some(status);
+                let channel = status & 0x0f;
+                match status & 0xf0 {
+                    0x80 | 0x90 => {
+                        let key = reader.data_byte()?;
+                        let velocity = reader.data_byte()?;
+                        // a note on without velocity ends a note
+                        if status & 0xf0 == 0x90 && velocity > 0 {
+                            sounding.push((channel, key, notes.len()));
+                            notes.push(RawNote {start: tick, end: tick, key, channel, velocity});
+                        } else if let Some(i) = sounding.iter().position(|(c, k, _)| *c == channel && *k == key) {
+                            let (_, _, index) = sounding.remove(i);
+                            notes[index].end = tick;
+                        }
+                    },
+                    0xc0 | 0xd0 => {
+                        reader.data_byte()?;
+                    },
+                    _ => {
+                        reader.data_byte()?;
+                        reader.data_byte()?;
+                    },
+                }
+            },
+            _ => return Err(HarmonyError::InvalidArgument("Unexpected status byte")),
+        }
+    }
+    // notes still sounding end with the track
+    for (_, _, index) in sounding {
+        notes[index].end = tick;
+    }
+    Ok((name, notes))
+}
+
+// The center of the major key signature whose scale covers the most keys,
+// simpler signatures win ties.
+fn initial_center(keys: &[(u32, u8)]) -> f64 {
+    let mut best = (0, 0);
+    for fifths in [0, 1, -1, 2, -2, 3, -3, 4, -4, 5, -5, 6, -6, 7, -7] {
+        let count = keys.iter()
+            .filter(|(_, key)| (fifths - 1..=fifths + 5).any(|x: i32| (x * 7).rem_euclid(12) == (*key % 12) as i32))
+            .count();
+        if count > best.1 {
+            best = (fifths, count);
+        }
+    }
+    best.0 as f64 + 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use crate::chords::Chord;
+
+    fn variable_length(value: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_variable_length(value, &mut bytes);
+        bytes
+    }
+
+    // the chunks of a file as (type, data)
+    fn split_chunks(bytes: &[u8]) -> Vec<(&[u8], &[u8])> {
+        let mut chunks = Vec::new();
+        let mut position = 0;
+        while position < bytes.len() {
+            let length = u32::from_be_bytes(bytes[position + 4..position + 8].try_into().unwrap()) as usize;
+            chunks.push((&bytes[position..position + 4], &bytes[position + 8..position + 8 + length]));
+            position += 8 + length;
+        }
+        chunks
+    }
+
+    #[test]
+    fn variable_lengths() {
+        assert_eq!(variable_length(0), [0x00]);
+        assert_eq!(variable_length(0x40), [0x40]);
+        assert_eq!(variable_length(0x7f), [0x7f]);
+        assert_eq!(variable_length(0x80), [0x81, 0x00]);
+        assert_eq!(variable_length(0x2000), [0xc0, 0x00]);
+        assert_eq!(variable_length(0x3fff), [0xff, 0x7f]);
+        assert_eq!(variable_length(0x4000), [0x81, 0x80, 0x00]);
+        assert_eq!(variable_length(0x0fff_ffff), [0xff, 0xff, 0xff, 0x7f]);
+    }
+
+    #[test]
+    fn single_track() {
+        let mut file = MidiFile::new();
+        file.ticks_per_quarter = 96;
+        file.tracks.push(MidiTrack::from_sequence("Melody", 0, &NoteSequence::from_str("C4 E4").unwrap(), 96, 100));
+        let bytes = file.to_bytes(MidiFormat::SingleTrack).unwrap();
+        assert_eq!(&bytes[..14], &[b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96]);
+        let chunks = split_chunks(&bytes);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].0, b"MTrk");
+        assert_eq!(chunks[1].1, [
+            0x00, 0xff, 0x51, 0x03, 0x07, 0xa1, 0x20,
+            0x00, 0xff, 0x58, 0x04, 0x04, 0x02, 0x18, 0x08,
+            0x00, 0x90, 60, 100,
+            0x60, 0x80, 60, 0x40,
+            0x00, 0x90, 64, 100,
+            0x60, 0x80, 64, 0x40,
+            0x00, 0xff, 0x2f, 0x00,
+        ]);
+
+        // a note without duration would hang
+        file.tracks[0].notes[1].duration = 0;
+        let bytes = file.to_bytes(MidiFormat::SingleTrack).unwrap();
+        assert_eq!(&split_chunks(&bytes)[1].1[15..], [
+            0x00, 0x90, 60, 100,
+            0x60, 0x80, 60, 0x40,
+            0x00, 0xff, 0x2f, 0x00,
+        ]);
+        assert_eq!(MidiFile::from_bytes(&bytes).unwrap().tracks[0].notes, file.tracks[0].notes[..1]);
+    }
+
+    #[test]
+    fn multi_track() {
+        let chords = [NoteSequence::from_str("C3 E4 G4").unwrap(), NoteSequence::from_str("B2 D4 G4").unwrap(), NoteSequence::from_str("C3 E4").unwrap()];
+        let mut file = MidiFile::new();
+        file.time_signature = (3, 4);
+        file.key = Some(Key::from_str("C major").unwrap());
+        file.tracks = MidiTrack::voices(&chords, 480, 80);
+        assert_eq!(file.tracks.len(), 3);
+        assert_eq!(file.tracks[2].notes.len(), 2);
+        assert_eq!(file.tracks[1].channel, 1);
+
+        let bytes = file.to_bytes(MidiFormat::MultiTrack).unwrap();
+        assert_eq!(&bytes[8..12], &[0, 1, 0, 4]);
+        let chunks = split_chunks(&bytes);
+        assert_eq!(chunks.len(), 5);
+        assert_eq!(chunks[1].1, [
+            0x00, 0xff, 0x51, 0x03, 0x07, 0xa1, 0x20,
+            0x00, 0xff, 0x58, 0x04, 0x03, 0x02, 0x18, 0x08,
+            0x00, 0xff, 0x59, 0x02, 0x00, 0x00,
+            0x00, 0xff, 0x2f, 0x00,
+        ]);
+        assert_eq!(chunks[2].1, [
+            0x00, 0xff, 0x03, 0x07, b'V', b'o', b'i', b'c', b'e', b' ', b'1',
+            0x00, 0x90, 48, 80,
+            0x83, 0x60, 0x80, 48, 0x40,
+            0x00, 0x90, 47, 80,
+            0x83, 0x60, 0x80, 47, 0x40,
+            0x00, 0x90, 48, 80,
+            0x83, 0x60, 0x80, 48, 0x40,
+            0x00, 0xff, 0x2f, 0x00,
+        ]);
+        assert_eq!(&chunks[4].1[11..15], &[0x00, 0x92, 67, 80]);
+
+        // block chords in a single channel
+        let track = MidiTrack::from_progression("Piano", 3, &chords, 240, 64);
+        assert_eq!(track.notes.len(), 8);
+        assert_eq!(track.notes[3], MidiNote {note: Note::from_str("B2").unwrap(), start: 240, duration: 240, velocity: 64});
+        file.tracks = vec![track];
+        let bytes = file.to_bytes(MidiFormat::SingleTrack).unwrap();
+        let single = split_chunks(&bytes);
+        assert_eq!(single.len(), 2);
+        // note offs of the first chord come before the note ons of the second
+        let events = &single[1].1[21..];
+        assert_eq!(&events[12..24], &[0x81, 0x70, 0x83, 48, 0x40, 0x00, 0x83, 64, 0x40, 0x00, 0x83, 67]);
+        assert_eq!(&events[24..32], &[0x40, 0x00, 0x93, 47, 64, 0x00, 0x93, 62]);
+    }
+
+    #[test]
+    fn key_signatures() {
+        let key_event = |key: &str| {
+            let mut file = MidiFile::new();
+            file.key = Some(Key::from_str(key).unwrap());
+            file.to_bytes(MidiFormat::MultiTrack).map(|x| x[38..43].to_vec())
+        };
+        assert_eq!(key_event("Eb major"), Ok(vec![0xff, 0x59, 0x02, 0xfd, 0x00]));
+        assert_eq!(key_event("f# minor"), Ok(vec![0xff, 0x59, 0x02, 0x03, 0x01]));
+        assert_eq!(key_event("Cb major"), Ok(vec![0xff, 0x59, 0x02, 0xf9, 0x00]));
+        assert_eq!(key_event("A# minor"), Ok(vec![0xff, 0x59, 0x02, 0x07, 0x01]));
+        assert_eq!(key_event("D dorian"), Ok(vec![0xff, 0x59, 0x02, 0x00, 0x00]));
+        assert_eq!(key_event("e harmonic minor"), Ok(vec![0xff, 0x59, 0x02, 0x01, 0x01]));
+        assert_eq!(key_event("G# major"), Err(HarmonyError::OutOfRange("Key signature has more than 7 accidentals")));
+    }
+
+    #[test]
+    fn tempo() {
+        let mut file = MidiFile::new();
+        assert_eq!(file.bpm(), 120.0);
+        file.set_bpm(90.0).unwrap();
+        assert_eq!(file.tempo, 666_667);
+        assert_eq!(&file.to_bytes(MidiFormat::SingleTrack).unwrap()[22..29], &[0x00, 0xff, 0x51, 0x03, 0x0a, 0x2c, 0x2b]);
+        assert!(file.set_bpm(0.0).is_err());
+        assert!(file.set_bpm(-60.0).is_err());
+        assert!(file.set_bpm(1.0).is_err());
+    }
+
+    #[test]
+    fn errors() {
+        let mut file = MidiFile::new();
+        file.tracks.push(MidiTrack::from_sequence("", 16, &NoteSequence::from_str("C4").unwrap(), 96, 100));
+        assert_eq!(file.to_bytes(MidiFormat::MultiTrack), Err(HarmonyError::OutOfRange("MIDI channel must be between 0 and 15")));
+        file.tracks = vec![MidiTrack::from_sequence("", 0, &NoteSequence::from_str("C4").unwrap(), 96, 0)];
+        assert_eq!(file.to_bytes(MidiFormat::MultiTrack), Err(HarmonyError::OutOfRange("Velocity must be between 1 and 127")));
+        file.tracks = vec![MidiTrack::from_sequence("", 0, &NoteSequence::from_str("C10").unwrap(), 96, 100)];
+        assert_eq!(file.to_bytes(MidiFormat::MultiTrack), Err(HarmonyError::OutOfRange("Note is outside of the MIDI range")));
+        file.tracks.clear();
+        file.time_signature = (3, 3);
+        assert_eq!(file.to_bytes(MidiFormat::MultiTrack), Err(HarmonyError::InvalidArgument("Invalid time signature")));
+        file.time_signature = (3, 8);
+        file.ticks_per_quarter = 0;
+        assert!(file.to_bytes(MidiFormat::MultiTrack).is_err());
+    }
+
+    #[test]
+    fn save() {
+        let mut file = MidiFile::new();
+        file.tracks.push(MidiTrack::from_sequence("Melody", 0, &NoteSequence::from_str("C4 D4 E4").unwrap(), 480, 100));
+        let path = std::env::temp_dir().join("harmony_midi_files_test.mid");
+        file.save(&path, MidiFormat::MultiTrack).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), file.to_bytes(MidiFormat::MultiTrack).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        file.time_signature = (0, 4);
+        assert_eq!(file.save(&path, MidiFormat::MultiTrack).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    fn keys(notes: &[u8]) -> Vec<(u32, u8)> {
+        notes.iter().enumerate().map(|(i, x)| (i as u32 * 480, *x)).collect()
+    }
+
+    fn spelled(file: &MidiFile, notes: &[(u32, u8)]) -> String {
+        format!("{}", NoteSequence {notes: file.spell(notes).unwrap()})
+    }
+
+    #[test]
+    fn round_trip() {
+        let chords = [NoteSequence::from_str("F3 A3 C4 F4").unwrap(), NoteSequence::from_str("E3 Bb3 C4 G4").unwrap(), NoteSequence::from_str("F3 A3 C4 F4").unwrap(), NoteSequence::from_str("D3 F#3 A3 D4").unwrap()];
+        let mut file = MidiFile::new();
+        file.time_signature = (3, 4);
+        file.key = Some(Key::from_str("F major").unwrap());
+        file.key_changes = vec![(1440, Key::from_str("G major").unwrap())];
+        file.tempo_changes = vec![(960, 600_000)];
+        file.tracks = MidiTrack::voices(&chords, 480, 90);
+        file.tracks[1].name = String::from("Alto");
+        let bytes = file.to_bytes(MidiFormat::MultiTrack).unwrap();
+        assert_eq!(MidiFile::from_bytes(&bytes), Ok(file.clone()));
+
+        // a single track is split into its channels, names are not written
+        let single = MidiFile::from_bytes(&file.to_bytes(MidiFormat::SingleTrack).unwrap()).unwrap();
+        assert_eq!(single.tracks.len(), 4);
+        for (track, original) in single.tracks.iter().zip(&file.tracks) {
+            assert_eq!(track.name, "");
+            assert_eq!((track.channel, &track.notes), (original.channel, &original.notes));
+        }
+        assert_eq!(single.key_changes, file.key_changes);
+
+        let path = std::env::temp_dir().join("harmony_midi_files_load.mid");
+        file.save(&path, MidiFormat::MultiTrack).unwrap();
+        assert_eq!(MidiFile::load(&path).unwrap(), file);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn running_status() {
+        let bytes = [
+            b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96,
+            // an unknown chunk is skipped
+            b'X', b'y', b'z', b'w', 0, 0, 0, 2, 1, 2,
+            b'M', b'T', b'r', b'k', 0, 0, 0, 35,
+            0x00, 0xf0, 0x03, 0x7e, 0x09, 0xf7,
+            0x00, 0x91, 60, 100,
+            0x00, 64, 90,
+            0x60, 60, 0,
+            0x00, 0xc1, 5,
+            0x30, 0x91, 64, 0,
+            0x00, 0xb1, 7, 100,
+            0x00, 0x81, 67, 64,
+            0x00, 0xff, 0x2f, 0x00,
+        ];
+        let file = MidiFile::from_bytes(&bytes).unwrap();
+        assert_eq!(file.ticks_per_quarter, 96);
+        assert_eq!(file.tempo, 500_000);
+        assert_eq!(file.tracks.len(), 1);
+        assert_eq!(file.tracks[0].channel, 1);
+        assert_eq!(file.tracks[0].notes, vec![
+            MidiNote {note: Note::from_str("C4").unwrap(), start: 0, duration: 96, velocity: 100},
+            MidiNote {note: Note::from_str("E4").unwrap(), start: 0, duration: 144, velocity: 90},
+        ]);
+    }
+
+    #[test]
+    fn tempo_map() {
+        let mut file = MidiFile::new();
+        file.tempo_changes = vec![(480, 250_000), (1440, 1_000_000)];
+        assert_eq!(file.seconds(0), 0.0);
+        assert_eq!(file.seconds(240), 0.25);
+        assert_eq!(file.seconds(480), 0.5);
+        assert_eq!(file.seconds(960), 0.75);
+        assert_eq!(file.seconds(1440), 1.0);
+        assert_eq!(file.seconds(1920), 2.0);
+        let mut unsorted = file.clone();
+        unsorted.tempo_changes.reverse();
+        for tick in [0, 240, 960, 1440, 1920] {
+            assert_eq!(unsorted.seconds(tick), file.seconds(tick));
+        }
+        let bytes = file.to_bytes(MidiFormat::SingleTrack).unwrap();
+        assert_eq!(MidiFile::from_bytes(&bytes).unwrap().tempo_changes, file.tempo_changes);
+    }
+
+    #[test]
+    fn key_spelling() {
+        let mut file = MidiFile::new();
+        file.key = Some(Key::from_str("Db major").unwrap());
+        file.key_changes = vec![(960, Key::from_str("e minor").unwrap())];
+        assert_eq!(file.key_at(0), Some(Key::from_str("Db major").unwrap()));
+        assert_eq!(file.key_at(2000), Some(Key::from_str("e minor").unwrap()));
+        assert_eq!(spelled(&file, &keys(&[61, 66, 66, 63])), "Db4 Gb4 F#4 D#4");
+    }
+
+    #[test]
+    fn inferred_spelling() {
+        let file = MidiFile::new();
+        assert_eq!(spelled(&file, &keys(&[69, 71, 73, 74, 76, 78, 80, 81])), "A4 B4 C#5 D5 E5 F#5 G#5 A5");
+        assert_eq!(spelled(&file, &keys(&[63, 65, 67, 68, 70, 72, 74, 75])), "Eb4 F4 G4 Ab4 Bb4 C5 D5 Eb5");
+        assert_eq!(spelled(&file, &keys(&[66, 68, 70, 71, 73, 75, 77, 78])), "F#4 G#4 A#4 B4 C#5 D#5 E#5 F#5");
+        assert_eq!(spelled(&file, &keys(&[65, 67, 68, 70, 72, 73, 76, 77])), "F4 G4 Ab4 Bb4 C5 Db5 E5 F5");
+        // the E major chord in C major brings a G#, the F minor chord an Ab
+        let notes = [(0, 60), (0, 64), (0, 67), (480, 64), (480, 68), (480, 71), (960, 57), (960, 60), (960, 64)];
+        assert_eq!(spelled(&file, &notes), "C4 E4 G4 E4 G#4 B4 A3 C4 E4");
+        let notes = [(0, 60), (0, 64), (0, 67), (480, 65), (480, 68), (480, 72), (960, 67), (960, 71), (960, 74)];
+        assert_eq!(spelled(&file, &notes), "C4 E4 G4 F4 Ab4 C5 G4 B4 D5");
+    }
+
+    #[test]
+    fn analysis() {
+        // an unsigned file with a melody in E major and a chorale in A minor
+        let mut file = MidiFile::new();
+        let melody = [64, 66, 68, 69, 71, 73, 75].iter().map(|x| Note::from_midi(*x, SpellingPolicy::Flats).unwrap()).collect();
+        file.tracks.push(MidiTrack::from_sequence("Melody", 0, &NoteSequence {notes: melody}, 480, 100));
+        let bytes = file.to_bytes(MidiFormat::MultiTrack).unwrap();
+        let melody = MidiFile::from_bytes(&bytes).unwrap().tracks[0].sequence();
+        assert_eq!(format!("{}", melody), "E4 F#4 G#4 A4 B4 C#5 D#5");
+        assert_eq!(Mode::identify(&melody), Ok(Mode::Ionian));
+
+        let chords = [[57, 60, 64, 69], [52, 59, 64, 68], [57, 60, 64, 69]];
+        let mut file = MidiFile::new();
+        file.tracks = MidiTrack::voices(&chords.iter().map(|x| NoteSequence {
+            notes: x.iter().map(|y| Note::from_midi(*y, SpellingPolicy::Flats).unwrap()).collect(),
+        }).collect::<Vec<_>>(), 480, 100);
+        let file = MidiFile::from_bytes(&file.to_bytes(MidiFormat::MultiTrack).unwrap()).unwrap();
+        let chords = file.chords();
+        assert_eq!(chords.len(), 3);
+        assert_eq!(format!("{}", chords[1]), "E3 B3 E4 G#4");
+        let symbols: Vec<String> = chords.iter().map(|x| format!("{}", Chord::identify(x).unwrap())).collect();
+        assert_eq!(symbols, ["Am", "E", "Am"]);
+    }
+
+    #[test]
+    fn import_errors() {
+        let header = |format: u8, division: [u8; 2]| {
+            let mut bytes = vec![b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, format, 0, 1, division[0], division[1]];
+            bytes.extend([b'M', b'T', b'r', b'k', 0, 0, 0, 4, 0x00, 0xff, 0x2f, 0x00]);
+            bytes
+        };
+        assert!(MidiFile::from_bytes(&header(1, [1, 0xe0])).is_ok());
+        assert_eq!(MidiFile::from_bytes(b"RIFF"), Err(HarmonyError::InvalidArgument("Missing MThd header")));
+        assert_eq!(MidiFile::from_bytes(&header(2, [1, 0xe0])), Err(HarmonyError::InvalidArgument("Only SMF formats 0 and 1 are supported")));
+        assert_eq!(MidiFile::from_bytes(&header(0, [0xe7, 0x28])), Err(HarmonyError::InvalidArgument("SMPTE time division is not supported")));
+        assert_eq!(MidiFile::from_bytes(&header(0, [1, 0xe0])[..20]), Err(HarmonyError::InvalidArgument("Unexpected end of file")));
+
+        let track = |events: &[u8]| {
+            let mut bytes = vec![b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 0, 0, 1, 0, 96, b'M', b'T', b'r', b'k', 0, 0, 0, events.len() as u8];
+            bytes.extend(events);
+            MidiFile::from_bytes(&bytes)
+        };
+        assert_eq!(track(&[0x00, 60, 100]), Err(HarmonyError::InvalidArgument("Running status without a previous status byte")));
+        assert_eq!(track(&[0x00, 0x90, 60]), Err(HarmonyError::InvalidArgument("Unexpected end of file")));
+        assert_eq!(track(&[0x00, 0x90, 0x90, 100]), Err(HarmonyError::InvalidArgument("Expected a data byte")));
+        assert_eq!(track(&[0x00, 0xf4]), Err(HarmonyError::InvalidArgument("Unexpected status byte")));
+        assert_eq!(track(&[0xff, 0xff, 0xff, 0xff, 0x00]), Err(HarmonyError::InvalidArgument("Variable length quantity is longer than four bytes")));
+        // notes without a note off end with the track
+        assert_eq!(track(&[0x00, 0x90, 60, 100, 0x60, 0xff, 0x2f, 0x00]).unwrap().tracks[0].notes[0].duration, 96);
+    }
+}
@@ -0,0 +1,876 @@
+use std::str::FromStr;
+
+use roxmltree::{Document, Node, ParsingOptions};
+
+use crate::errors::HarmonyError;
+use crate::notenames::NoteName;
+use crate::accidentals::Accidental;
+use crate::notes::Note;
+use crate::notesequences::NoteSequence;
+use crate::intervals::Interval;
+use crate::chords::{Chord, ChordQuality};
+use crate::keys::{Key, KeySignature};
+use crate::diatonic_scales::Mode;
+
+// (kind, note value in quarters as a fraction) from the longest to the shortest
+const NOTE_TYPES: [(&str, u32, u32); 8] = [
+    ("breve", 8, 1), ("whole", 4, 1), ("half", 2, 1), ("quarter", 1, 1),
+    ("eighth", 1, 2), ("16th", 1, 4), ("32nd", 1, 8), ("64th", 1, 16),
+];
+
+// semitones above the root of every chord degree in a dominant chord
+const DEGREE_HSTEPS: [(u32, i32); 10] = [(1, 0), (2, 2), (3, 4), (4, 5), (5, 7), (6, 9), (7, 10), (9, 14), (11, 17), (13, 21)];
+
+type Degrees = &'static [(u32, i32, &'static str)];
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScoreNote {
+    // None is a rest
+    pub pitch: Option<Note>,
+    // start and duration in divisions, the start counts from the barline and
+    // a note tied over the barline lasts into the following measures
+    pub start: u32,
+    pub duration: u32,
+    pub voice: u8,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Measure {
+    // set where the key or time signature changes
+    pub key: Option<Key>,
+    pub time: Option<(u8, u8)>,
+    pub notes: Vec<ScoreNote>,
+    // chord symbols with their start in divisions
+    pub harmonies: Vec<(u32, Chord)>,
+}
+
+impl Measure {
+    pub fn new() -> Self {
+        Self {key: None, time: None, notes: Vec::new(), harmonies: Vec::new()}
+    }
+}
+
+impl Default for Measure {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Part {
+    pub name: String,
+    // divisions per quarter note
+    pub divisions: u32,
+    pub measures: Vec<Measure>,
+}
+
+impl Part {
+    pub fn new(name: &str, divisions: u32) -> Self {
+        Self {name: String::from(name), divisions, measures: Vec::new()}
+    }
+
+    // The notes one after the other, each lasting duration divisions, filling
+    // measures of the given time signature.
+    pub fn from_sequence(name: &str, sequence: &NoteSequence, divisions: u32, duration: u32, time: (u8, u8))
+        -> Result<Self, HarmonyError> {
+        let chords: Vec<NoteSequence> = sequence.notes.iter().map(|x| NoteSequence {notes: vec![*x]}).collect();
+        Self::from_progression(name, &chords, divisions, duration, time)
+    }
+
+    // each chord as a block in a single voice
+    pub fn from_progression(name: &str, chords: &[NoteSequence], divisions: u32, duration: u32, time: (u8, u8))
+        -> Result<Self, HarmonyError> {
+        let length = measure_length(time, divisions)?;
+        if duration == 0 || !length.is_multiple_of(duration) {
+            return Err(HarmonyError::InvalidArgument("Notes must fill measures evenly"));
+        }
+        let mut part = Self::new(name, divisions);
+        for (i, chord) in chords.iter().enumerate() {
+            let start = i as u32 * duration;
+            if start.is_multiple_of(length) {
+                part.measures.push(Measure::new());
+            }
+            let measure = part.measures.last_mut().unwrap();
+            for note in &chord.notes {
+                measure.notes.push(ScoreNote {pitch: Some(*note), start: start % length, duration, voice: 1});
+            }
+        }
+        if let Some(measure) = part.measures.first_mut() {
+            measure.time = Some(time);
+        }
+        Ok(part)
+    }
+
+    // the pitches of a voice in order, chords are listed lowest first
+    pub fn sequence(&self, voice: u8) -> NoteSequence {
+        let mut notes = Vec::new();
+        for measure in &self.measures {
+            let mut pitches: Vec<(u32, Note)> = measure.notes.iter()
+                .filter(|x| x.voice == voice)
+                .filter_map(|x| x.pitch.map(|y| (x.start, y)))
+                .collect();
+            pitches.sort_by_key(|(start, note)| (*start, Note::default().dist_hsteps(note)));
+            notes.extend(pitches.iter().map(|(_, note)| *note));
+        }
+        NoteSequence {notes}
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Score {
+    pub title: String,
+    pub parts: Vec<Part>,
+}
+
+impl Score {
+    pub fn new(title: &str) -> Self {
+        Self {title: String::from(title), parts: Vec::new()}
+    }
+
+    // tied notes are merged into the first one
+    pub fn from_musicxml(s: &str) -> Result<Self, HarmonyError> {
+        let options = ParsingOptions {allow_dtd: true, ..ParsingOptions::default()};
+        let document = Document::parse_with_options(s, options)
+            .map_err(|x| HarmonyError::InvalidArgument("Malformed XML").at_line(x.pos().row as usize))?;
+        let root = document.root_element();
+        if !root.has_tag_name("score-partwise") {
+            return Err(HarmonyError::InvalidArgument("Only partwise MusicXML is supported").at_line(line(root)));
+        }
+        let title = child(root, "work").and_then(|x| child(x, "work-title"))
+            .or(child(root, "movement-title"))
+            .map(text)
+            .unwrap_or("");
+        let mut score = Self::new(title);
+        let names: Vec<(&str, &str)> = child(root, "part-list").iter()
+            .flat_map(|x| x.children().filter(|y| y.has_tag_name("score-part")))
+            .map(|x| (x.attribute("id").unwrap_or(""), child(x, "part-name").map(text).unwrap_or("")))
+            .collect();
+        for node in root.children().filter(|x| x.has_tag_name("part")) {
+            let id = node.attribute("id").unwrap_or("");
+            let name = names.iter().find(|(x, _)| *x == id).map(|(_, x)| *x).unwrap_or("");
+            score.parts.push(read_part(node, name)?);
+        }
+        Ok(score)
+    }
+
+    pub fn to_musicxml(&self) -> String {
+        let mut s = String::new();
+        s += "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n";
+        s += "<!DOCTYPE score-partwise PUBLIC \"-//Recordare//DTD MusicXML 4.0 Partwise//EN\" \"http://www.musicxml.org/dtds/partwise.dtd\">\n";
+        s += "<score-partwise version=\"4.0\">\n";
+        s += &format!("  <work>\n    <work-title>{}</work-title>\n  </work>\n", escape(&self.title));
+        s += "  <part-list>\n";
+        for (i, part) in self.parts.iter().enumerate() {
+            s += &format!("    <score-part id=\"P{}\">\n      <part-name>{}</part-name>\n    </score-part>\n", i + 1, escape(&part.name));
+        }
+        s += "  </part-list>\n";
+        for (i, part) in self.parts.iter().enumerate() {
+            s += &format!("  <part id=\"P{}\">\n", i + 1);
+            write_part(&mut s, part);
+            s += "  </part>\n";
+        }
+        s += "</score-partwise>\n";
+        s
+    }
+}
+
+fn measure_length(time: (u8, u8), divisions: u32) -> Result<u32, HarmonyError> {
+    let (beats, beat_type) = time;
+    if beats == 0 || !beat_type.is_power_of_two() {
+        return Err(HarmonyError::InvalidArgument("Invalid time signature"));
+    }
+    let quarters = divisions.checked_mul(4 * beats as u32).ok_or(HarmonyError::OutOfRange("Measure is too long"))?;
+    if !quarters.is_multiple_of(beat_type as u32) {
+        return Err(HarmonyError::InvalidArgument("Invalid time signature"));
+    }
+    Ok(quarters / beat_type as u32)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn line(node: Node) -> usize {
+    node.document().text_pos_at(node.range().start).row as usize
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|x| x.has_tag_name(name))
+}
+
+fn text<'a>(node: Node<'a, '_>) -> &'a str {
+    node.text().unwrap_or("").trim()
+}
+
+fn required<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Result<Node<'a, 'input>, HarmonyError> {
+    child(node, name).ok_or_else(|| HarmonyError::parse(node.tag_name().name(), 0, "", &[name]).at_line(line(node)))
+}
+
+fn number<T: FromStr>(node: Node) -> Result<T, HarmonyError> {
+    let s = text(node);
+    s.parse().map_err(|_| HarmonyError::parse(s, 0, s, &[node.tag_name().name()]).at_line(line(node)))
+}
+
+fn read_part(node: Node, name: &str) -> Result<Part, HarmonyError> {
+    let mut divisions = None;
+    let mut measures: Vec<Measure> = Vec::new();
+    // measure and note index of the notes waiting for their tie to end
+    let mut tied: Vec<(usize, usize)> = Vec::new();
+    for measure_node in node.children().filter(|x| x.has_tag_name("measure")) {
+        let mut measure = Measure::new();
+        let mut position: u32 = 0;
+        let mut last_start = 0;
+        for element in measure_node.children().filter(|x| x.is_element()) {
+            match element.tag_name().name() {
+                "attributes" => {
+                    if let Some(value) = child(element, "divisions") {
+                        let value: u32 = number(value)?;
+                        if divisions.is_some_and(|x| x != value) {
+                            return Err(HarmonyError::InvalidArgument("Changing divisions are not supported").at_line(line(element)));
+                        }
+                        divisions = Some(value);
+                    }
+                    if let Some(key) = child(element, "key") {
+                        measure.key = Some(read_key(key)?);
+                    }
+                    if let Some(time) = child(element, "time") {
+                        measure.time = Some(read_time(time)?);
+                    }
+                },
+                "note" => {
+                    // grace notes take no time and are left out
+                    if child(element, "grace").is_some() {
+                        continue;
+                    }
+                    let duration: u32 = number(required(element, "duration")?)?;
+                    let start = if child(element, "chord").is_some() {last_start} else {position};
+                    let pitch = match child(element, "pitch") {
+                        Some(pitch) => Some(read_pitch(pitch)?),
+                        None => {
+                            required(element, "rest")?;
+                            None
+                        },
+                    };
+                    let voice = child(element, "voice").map(number).transpose()?.unwrap_or(1);
+                    let ties: Vec<&str> = element.children().filter(|x| x.has_tag_name("tie")).filter_map(|x| x.attribute("type")).collect();
+                    let note = ScoreNote {pitch, start, duration, voice};
+                    let found = tied.iter().position(|(imeasure, inote)| {
+                        let first = if *imeasure == measures.len() {&measure.notes[*inote]} else {&measures[*imeasure].notes[*inote]};
+                        first.pitch.is_some() && first.pitch == note.pitch && first.voice == note.voice
+                    });
+                    match found.filter(|_| ties.contains(&"stop")) {
+                        Some(itied) => {
+                            let (imeasure, inote) = tied[itied];
+                            let first = if imeasure == measures.len() {&mut measure.notes[inote]} else {&mut measures[imeasure].notes[inote]};
+                            first.duration = first.duration.checked_add(duration)
+                                .ok_or(HarmonyError::OutOfRange("Tied note is too long").at_line(line(element)))?;
+                            if !ties.contains(&"start") {
+                                tied.remove(itied);
+                            }
+                        },
+                        None => {
+                            if ties.contains(&"start") && note.pitch.is_some() {
+                                tied.push((measures.len(), measure.notes.len()));
+                            }
+                            measure.notes.push(note);
+                        },
+                    }
+                    last_start = start;
+                    position = start.checked_add(duration)
+                        .ok_or(HarmonyError::OutOfRange("Measure is too long").at_line(line(element)))?;
+                },
+                "backup" => {
+                    let duration: u32 = number(required(element, "duration")?)?;
+                    position = position.checked_sub(duration)
+                        .ok_or(HarmonyError::OutOfRange("Backup before the start of the measure").at_line(line(element)))?;
+                },
+                "forward" => {
+                    let duration: u32 = number(required(element, "duration")?)?;
+                    position = position.checked_add(duration)
+                        .ok_or(HarmonyError::OutOfRange("Measure is too long").at_line(line(element)))?;
+                },
+                "harmony" => {
+                    let offset: i64 = child(element, "offset").map(number).transpose()?.unwrap_or(0);
+                    let start = u32::try_from(position as i64 + offset)
+                        .map_err(|_| HarmonyError::OutOfRange("Harmony before the start of the measure").at_line(line(element)))?;
+                    measure.harmonies.push((start, read_harmony(element)?));
+                },
+                _ => (),
+            }
+        }
+        measures.push(measure);
+    }
+    Ok(Part {name: String::from(name), divisions: divisions.unwrap_or(1), measures})
+}
+
+fn read_step(node: Node, alter: Option<Node>) -> Result<(NoteName, Accidental), HarmonyError> {
+    let name = NoteName::from_str(text(node)).map_err(|x| x.at_line(line(node)))?;
+    let accidental = match alter {
+        Some(alter) => {
+            let offset: i32 = number(alter)?;
+            Accidental::from_offset(offset).map_err(|x| x.at_line(line(alter)))?
+        },
+        None => Accidental::Natural,
+    };
+    Ok((name, accidental))
+}
+
+fn read_pitch(node: Node) -> Result<Note, HarmonyError> {
+    let (name, accidental) = read_step(required(node, "step")?, child(node, "alter"))?;
+    let octave: i8 = number(required(node, "octave")?)?;
+    Ok(Note::new(name, accidental, octave))
+}
+
+fn read_key(node: Node) -> Result<Key, HarmonyError> {
+    let fifths: i32 = number(required(node, "fifths")?)?;
+    let signature = KeySignature::from_fifths(fifths).map_err(|x| x.at_line(line(node)))?;
+    let mode = match child(node, "mode").map(text).unwrap_or("major") {
+        "minor" => Mode::Aeolian,
+        mode => Mode::from_str(mode).unwrap_or(Mode::Ionian),
+    };
+    Key::from_signature(signature, mode).map_err(|x| x.at_line(line(node)))
+}
+
+fn read_time(node: Node) -> Result<(u8, u8), HarmonyError> {
+    let beats_node = required(node, "beats")?;
+    // additive meters like 2+2+3 count all beats
+    let beats: Option<u32> = text(beats_node).split('+').map(|x| x.trim().parse::<u32>().ok()).sum();
+    let beats = beats.and_then(|x| u8::try_from(x).ok())
+        .ok_or_else(|| HarmonyError::parse(text(beats_node), 0, text(beats_node), &["beats"]).at_line(line(beats_node)))?;
+    Ok((beats, number(required(node, "beat-type")?)?))
+}
+
+fn degree_interval(number: u32, alter: i32) -> Option<Interval> {
+    let (_, hsteps) = DEGREE_HSTEPS.iter().find(|(x, _)| *x == number)?;
+    Interval::from_steps(number as i32 - 1, hsteps + alter).ok()
+}
+
+fn read_harmony(node: Node) -> Result<Chord, HarmonyError> {
+    let root = required(node, "root")?;
+    let (name, accidental) = read_step(required(root, "root-step")?, child(root, "root-alter"))?;
+    let root = Note::new(name, accidental, Note::default().octave());
+    let kind_node = required(node, "kind")?;
+    let base = kind_quality(text(kind_node)).ok_or_else(|| {
+        let expected: Vec<&str> = ChordQuality::ALL.iter().map(|x| quality_kind(*x)).filter(|(_, x)| x.is_empty()).map(|(x, _)| x).collect();
+        HarmonyError::parse(text(kind_node), 0, text(kind_node), &expected).at_line(line(kind_node))
+    })?;
+
+    // apply the degrees to the intervals of the kind and look for a quality
+    // with the same intervals
+    let mut intervals = base.intervals();
+    let mut alterations = Vec::new();
+    for degree in node.children().filter(|x| x.has_tag_name("degree")) {
+        let value: u32 = number(required(degree, "degree-value")?)?;
+        let alter: i32 = child(degree, "degree-alter").map(number).transpose()?.unwrap_or(0);
+        let interval = degree_interval(value, alter)
+            .ok_or(HarmonyError::InvalidArgument("Unsupported chord degree").at_line(line(degree)))?;
+        match child(degree, "degree-type").map(text).unwrap_or("add") {
+            "subtract" => intervals.retain(|x| x.steps() % 7 != interval.steps() % 7),
+            kind => {
+                if kind == "alter" {
+                    intervals.retain(|x| x.steps() % 7 != interval.steps() % 7);
+                }
+                intervals.push(interval);
+                alterations.push(interval);
+            },
+        }
+    }
+    intervals.sort_by_key(|x| (x.steps(), x.hsteps()));
+    let mut chord = match ChordQuality::ALL.iter().find(|x| x.intervals() == intervals) {
+        Some(quality) => Chord::from_root(root, *quality)?,
+        None => {
+            let mut chord = Chord::from_root(root, base)?;
+            for alteration in alterations {
+                chord = chord.alter(alteration)?;
+            }
+            chord
+        },
+    };
+    if let Some(bass) = child(node, "bass") {
+        let (name, accidental) = read_step(required(bass, "bass-step")?, child(bass, "bass-alter"))?;
+        chord = chord.with_bass_spelling(Note::new(name, accidental, Note::default().octave()))?;
+    }
+    Ok(chord)
+}
+
+// the MusicXML kind of a quality and the degrees (number, alter, type) added to it
+fn quality_kind(quality: ChordQuality) -> (&'static str, Degrees) {
+    match quality {
+        ChordQuality::Major => ("major", &[] as Degrees),
+        ChordQuality::Minor => ("minor", &[]),
+        ChordQuality::Diminished => ("diminished", &[]),
+        ChordQuality::Augmented => ("augmented", &[]),
+        ChordQuality::Sus2 => ("suspended-second", &[]),
+        ChordQuality::Sus4 => ("suspended-fourth", &[]),
+        ChordQuality::Major6 => ("major-sixth", &[]),
+        ChordQuality::Minor6 => ("minor-sixth", &[]),
+        ChordQuality::Dominant7 => ("dominant", &[]),
+        ChordQuality::Major7 => ("major-seventh", &[]),
+        ChordQuality::Minor7 => ("minor-seventh", &[]),
+        ChordQuality::MinorMajor7 => ("major-minor", &[]),
+        ChordQuality::HalfDiminished7 => ("half-diminished", &[]),
+        ChordQuality::Diminished7 => ("diminished-seventh", &[]),
+        ChordQuality::Augmented7 => ("augmented-seventh", &[]),
+        ChordQuality::Dominant9 => ("dominant-ninth", &[]),
+        ChordQuality::Major9 => ("major-ninth", &[]),
+        ChordQuality::Minor9 => ("minor-ninth", &[]),
+        ChordQuality::Dominant11 => ("dominant-11th", &[]),
+        ChordQuality::Minor11 => ("minor-11th", &[]),
+        ChordQuality::Dominant13 => ("dominant-13th", &[]),
+        ChordQuality::Major13 => ("major-13th", &[]),
+        ChordQuality::Minor13 => ("minor-13th", &[]),
+        ChordQuality::AugmentedMajor7 => ("major-seventh", &[(5, 1, "alter")]),
+        ChordQuality::Dominant7Sus4 => ("suspended-fourth", &[(7, 0, "add")]),
+        ChordQuality::Add9 => ("major", &[(9, 0, "add")]),
+        ChordQuality::MinorAdd9 => ("minor", &[(9, 0, "add")]),
+        ChordQuality::Dominant7Flat5 => ("dominant", &[(5, -1, "alter")]),
+        ChordQuality::Dominant7Flat9 => ("dominant", &[(9, -1, "add")]),
+        ChordQuality::Dominant7Sharp9 => ("dominant", &[(9, 1, "add")]),
+        ChordQuality::Dominant7Sharp11 => ("dominant", &[(11, 1, "add")]),
+        ChordQuality::Dominant7Flat13 => ("dominant", &[(13, -1, "add")]),
+        ChordQuality::Altered => ("dominant", &[(5, 0, "subtract"), (9, -1, "add"), (9, 1, "add"), (11, 1, "add"), (13, -1, "add")]),
+    }
+}
+
+fn kind_quality(kind: &str) -> Option<ChordQuality> {
+    ChordQuality::ALL.iter().copied()
+        .find(|x| quality_kind(*x) == (kind, &[]))
+}
+
+fn write_step(s: &mut String, prefix: &str, name: NoteName, accidental: Accidental, indent: &str) {
+    *s += &format!("{}<{}step>{}</{}step>\n", indent, prefix, name.to_str(), prefix);
+    if accidental != Accidental::Natural {
+        *s += &format!("{}<{}alter>{}</{}alter>\n", indent, prefix, accidental.offset(), prefix);
+    }
+}
+
+fn write_harmony(s: &mut String, chord: &Chord, offset: i64) {
+    let root = chord.root();
+    *s += "      <harmony>\n        <root>\n";
+    write_step(s, "root-", root.name(), root.accidental(), "          ");
+    *s += "        </root>\n";
+    let (kind, degrees) = quality_kind(chord.quality());
+    *s += &format!("        <kind>{}</kind>\n", kind);
+    if !chord.bass().same_spelling(&root) {
+        *s += "        <bass>\n";
+        write_step(s, "bass-", chord.bass().name(), chord.bass().accidental(), "          ");
+        *s += "        </bass>\n";
+    }
+    if offset != 0 {
+        *s += &format!("        <offset>{}</offset>\n", offset);
+    }
+    let mut degrees: Vec<(u32, i32, &str)> = degrees.to_vec();
+    let tones = chord.quality().intervals();
+    for alteration in chord.alterations() {
+        let number = alteration.steps() as u32 + 1;
+        let hsteps = DEGREE_HSTEPS.iter().find(|(x, _)| *x == number).map(|(_, x)| *x).unwrap_or(alteration.hsteps());
+        let kind = if tones.iter().any(|x| x.steps() % 7 == alteration.steps() % 7) {"alter"} else {"add"};
+        degrees.push((number, alteration.hsteps() - hsteps, kind));
+    }
+    for (number, alter, kind) in degrees {
+        *s += &format!(
+            "        <degree>\n          <degree-value>{}</degree-value>\n          <degree-alter>{}</degree-alter>\n          <degree-type>{}</degree-type>\n        </degree>\n",
+            number, alter, kind,
+        );
+    }
+    *s += "      </harmony>\n";
+}
+
+// Notes lasting past the end of their measure are split at the barline and
+// tied over, as far as the time signature is known.
+fn write_part(s: &mut String, part: &Part) {
+    let mut time = None;
+    let mut carried: Vec<ScoreNote> = Vec::new();
+    let mut imeasure = 0;
+    while imeasure < part.measures.len() || !carried.is_empty() {
+        let mut measure = part.measures.get(imeasure).cloned().unwrap_or_default();
+        time = measure.time.or(time);
+        let length = time.and_then(|x| measure_length(x, part.divisions).ok());
+        // (start, stop) of the tie on each note
+        let mut ties = vec![(false, true); carried.len()];
+        ties.resize(carried.len() + measure.notes.len(), (false, false));
+        let mut notes = std::mem::take(&mut carried);
+        notes.append(&mut measure.notes);
+        for (note, tie) in notes.iter_mut().zip(ties.iter_mut()) {
+            if let Some(length) = length.filter(|x| note.start < *x && note.duration > *x - note.start) {
+                carried.push(ScoreNote {start: 0, duration: note.duration - (length - note.start), ..note.clone()});
+                note.duration = length - note.start;
+                tie.0 = true;
+            }
+            if note.pitch.is_none() {
+                *tie = (false, false);
+            }
+        }
+        measure.notes = notes;
+        *s += &format!("    <measure number=\"{}\">\n", imeasure + 1);
+        write_measure(s, &measure, &ties, part.divisions, imeasure == 0);
+        *s += "    </measure>\n";
+        imeasure += 1;
+    }
+}
+
+fn write_measure(s: &mut String, measure: &Measure, ties: &[(bool, bool)], divisions: u32, first: bool) {
+    if first || measure.key.is_some() || measure.time.is_some() {
+        *s += "      <attributes>\n";
+        if first {
+            *s += &format!("        <divisions>{}</divisions>\n", divisions);
+        }
+        if let Some(key) = measure.key {
+            // other modes keep their signature but lose the mode
+            let mode = match key.mode() {
+                Mode::Ionian => "major",
+                Mode::Aeolian | Mode::HarmonicMinor | Mode::MelodicMinor => "minor",
+                Mode::Dorian => "dorian",
+                Mode::Phrygian => "phrygian",
+                Mode::Lydian => "lydian",
+                Mode::Mixolydian => "mixolydian",
+                Mode::Locrian => "locrian",
+                _ => "none",
+            };
+            *s += &format!("        <key>\n          <fifths>{}</fifths>\n          <mode>{}</mode>\n        </key>\n", key.signature().fifths(), mode);
+        }
+        if let Some((beats, beat_type)) = measure.time {
+            *s += &format!("        <time>\n          <beats>{}</beats>\n          <beat-type>{}</beat-type>\n        </time>\n", beats, beat_type);
+        }
+        *s += "      </attributes>\n";
+    }
+
+    let mut harmonies: Vec<&(u32, Chord)> = measure.harmonies.iter().collect();
+    harmonies.sort_by_key(|(start, _)| *start);
+    let mut harmonies = harmonies.into_iter().peekable();
+    let mut voices: Vec<u8> = measure.notes.iter().map(|x| x.voice).collect();
+    voices.sort();
+    voices.dedup();
+    let mut position = 0;
+    for (i, voice) in voices.iter().enumerate() {
+        if position > 0 {
+            *s += &format!("      <backup>\n        <duration>{}</duration>\n      </backup>\n", position);
+            position = 0;
+        }
+        let mut notes: Vec<(&ScoreNote, (bool, bool))> = measure.notes.iter().zip(ties.iter().copied())
+            .filter(|(x, _)| x.voice == *voice)
+            .collect();
+        notes.sort_by_key(|(x, _)| x.start);
+        let mut last_start = None;
+        for (note, tie) in notes {
+            let chord = last_start == Some(note.start);
+            if !chord {
+                // chord symbols go with the first voice
+                while i == 0 && harmonies.peek().is_some_and(|(start, _)| *start <= note.start) {
+                    let (start, harmony) = harmonies.next().unwrap();
+                    write_harmony(s, harmony, *start as i64 - position as i64);
+                }
+                if note.start > position {
+                    *s += &format!("      <forward>\n        <duration>{}</duration>\n      </forward>\n", note.start - position);
+                }
+                position = note.start + note.duration;
+                last_start = Some(note.start);
+            }
+            write_note(s, note, chord, tie, divisions);
+        }
+        if i == 0 {
+            for (start, harmony) in harmonies.by_ref() {
+                write_harmony(s, harmony, *start as i64 - position as i64);
+            }
+        }
+    }
+    // a measure without notes
+    for (start, harmony) in harmonies {
+        write_harmony(s, harmony, *start as i64 - position as i64);
+    }
+}
+
+fn write_note(s: &mut String, note: &ScoreNote, chord: bool, tie: (bool, bool), divisions: u32) {
+    *s += "      <note>\n";
+    if chord {
+        *s += "        <chord/>\n";
+    }
+    match note.pitch {
+        Some(pitch) => {
+            *s += "        <pitch>\n";
+            write_step(s, "", pitch.name(), pitch.accidental(), "          ");
+            *s += &format!("          <octave>{}</octave>\n        </pitch>\n", pitch.octave());
+        },
+        None => *s += "        <rest/>\n",
+    }
+    *s += &format!("        <duration>{}</duration>\n", note.duration);
+    let types: Vec<&str> = [(tie.1, "stop"), (tie.0, "start")].iter().filter(|(x, _)| *x).map(|(_, x)| *x).collect();
+    for kind in &types {
+        *s += &format!("        <tie type=\"{}\"/>\n", kind);
+    }
+    *s += &format!("        <voice>{}</voice>\n", note.voice);
+    // plain and dotted values get a note type for display
+    for (kind, numerator, denominator) in NOTE_TYPES {
+        if note.duration * denominator == divisions * numerator {
+            *s += &format!("        <type>{}</type>\n", kind);
+        } else if 2 * note.duration * denominator == 3 * divisions * numerator {
+            *s += &format!("        <type>{}</type>\n        <dot/>\n", kind);
+        } else {
+            continue;
+        }
+        break;
+    }
+    if !types.is_empty() {
+        *s += "        <notations>\n";
+        for kind in &types {
+            *s += &format!("          <tied type=\"{}\"/>\n", kind);
+        }
+        *s += "        </notations>\n";
+    }
+    *s += "      </note>\n";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pitched(pitch: &str, start: u32, duration: u32, voice: u8) -> ScoreNote {
+        ScoreNote {pitch: Some(Note::from_str(pitch).unwrap()), start, duration, voice}
+    }
+
+    fn sample() -> Score {
+        let mut score = Score::new("Chorale & Melody");
+        let mut soprano = Part::from_sequence("Soprano", &NoteSequence::from_str("E5 D5 C5 D5 E5 E5 E5").unwrap(), 2, 2, (4, 4)).unwrap();
+        soprano.measures[0].key = Some(Key::from_str("C major").unwrap());
+        soprano.measures[1].notes.push(ScoreNote {pitch: None, start: 6, duration: 2, voice: 1});
+        soprano.measures[0].harmonies = vec![(0, Chord::from_symbol("C").unwrap()), (4, Chord::from_symbol("G7/B").unwrap())];
+        soprano.measures[1].harmonies = vec![(3, Chord::from_symbol("Am7b5").unwrap())];
+
+        // two voices with a chord and a gap
+        let mut piano = Part::new("Piano", 4);
+        piano.measures.push(Measure {
+            key: Some(Key::from_str("Bb dorian").unwrap()),
+            time: Some((3, 4)),
+            notes: vec![
+                pitched("Bb4", 0, 4, 1), pitched("Db5", 0, 4, 1), pitched("F5", 0, 4, 1),
+                pitched("C5", 4, 6, 1), pitched("Eb5", 10, 2, 1),
+                pitched("Bb2", 0, 12, 2),
+            ],
+            harmonies: Vec::new(),
+        });
+        piano.measures.push(Measure {
+            key: None,
+            time: Some((7, 8)),
+            notes: vec![pitched("F##4", 0, 14, 1), pitched("Gbb2", 4, 2, 2)],
+            harmonies: vec![(2, Chord::from_symbol("Ebm").unwrap())],
+        });
+        score.parts = vec![soprano, piano];
+        score
+    }
+
+    #[test]
+    fn write() {
+        let xml = sample().to_musicxml();
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n<!DOCTYPE score-partwise"));
+        assert!(xml.contains("<work-title>Chorale &amp; Melody</work-title>"));
+        assert!(xml.contains("<score-part id=\"P2\">\n      <part-name>Piano</part-name>"));
+        assert!(xml.contains(concat!(
+            "    <measure number=\"1\">\n",
+            "      <attributes>\n",
+            "        <divisions>2</divisions>\n",
+            "        <key>\n          <fifths>0</fifths>\n          <mode>major</mode>\n        </key>\n",
+            "        <time>\n          <beats>4</beats>\n          <beat-type>4</beat-type>\n        </time>\n",
+            "      </attributes>\n",
+            "      <harmony>\n        <root>\n          <root-step>C</root-step>\n        </root>\n        <kind>major</kind>\n      </harmony>\n",
+            "      <note>\n",
+            "        <pitch>\n          <step>E</step>\n          <octave>5</octave>\n        </pitch>\n",
+            "        <duration>2</duration>\n        <voice>1</voice>\n        <type>quarter</type>\n",
+            "      </note>\n",
+        )));
+        assert!(xml.contains(concat!(
+            "      <harmony>\n        <root>\n          <root-step>G</root-step>\n        </root>\n        <kind>dominant</kind>\n",
+            "        <bass>\n          <bass-step>B</bass-step>\n        </bass>\n      </harmony>\n",
+        )));
+        assert!(xml.contains("<key>\n          <fifths>-4</fifths>\n          <mode>dorian</mode>\n        </key>"));
+        assert!(xml.contains("<rest/>\n        <duration>2</duration>"));
+        assert!(xml.contains("<chord/>\n        <pitch>\n          <step>D</step>\n          <alter>-1</alter>\n          <octave>5</octave>"));
+        assert!(xml.contains("<duration>6</duration>\n        <voice>1</voice>\n        <type>quarter</type>\n        <dot/>"));
+        assert!(xml.contains("<backup>\n        <duration>12</duration>\n      </backup>"));
+        assert!(xml.contains("<forward>\n        <duration>4</duration>\n      </forward>"));
+        assert!(xml.contains("<step>F</step>\n          <alter>2</alter>"));
+        // the chord symbol falls inside the first note
+        assert!(xml.contains("<kind>minor</kind>\n        <offset>-12</offset>"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let score = sample();
+        let read = Score::from_musicxml(&score.to_musicxml()).unwrap();
+        assert_eq!(read.title, score.title);
+        assert_eq!(read.parts.len(), 2);
+        for (read, part) in read.parts.iter().zip(&score.parts) {
+            assert_eq!((&read.name, read.divisions), (&part.name, part.divisions));
+            for (read, measure) in read.measures.iter().zip(&part.measures) {
+                let mut notes = measure.notes.clone();
+                notes.sort_by_key(|x| (x.voice, x.start));
+                assert_eq!(read.notes, notes);
+                assert_eq!(read.time, measure.time);
+                assert_eq!(read.harmonies, measure.harmonies);
+            }
+        }
+        assert_eq!(read.parts[1].measures[0].key, Some(Key::from_str("Bb dorian").unwrap()));
+        assert_eq!(Score::from_musicxml(&read.to_musicxml()), Ok(read));
+    }
+
+    #[test]
+    fn chord_kinds() {
+        let mut part = Part::new("Chords", 1);
+        let mut measure = Measure::new();
+        for (i, quality) in ChordQuality::ALL.iter().enumerate() {
+            measure.harmonies.push((i as u32, Chord::from_root(Note::from_str("Eb4").unwrap(), *quality).unwrap()));
+        }
+        measure.harmonies.push((40, Chord::from_symbol("C7(b9,#11)").unwrap()));
+        measure.harmonies.push((41, Chord::from_symbol("Cmaj7#5/G#").unwrap()));
+        part.measures.push(measure);
+        let mut score = Score::new("");
+        score.parts.push(part.clone());
+        let read = Score::from_musicxml(&score.to_musicxml()).unwrap();
+        let harmonies = &read.parts[0].measures[0].harmonies;
+        assert_eq!(harmonies.len(), ChordQuality::ALL.len() + 2);
+        for ((start, read), (_, chord)) in harmonies.iter().zip(&part.measures[0].harmonies) {
+            assert_eq!(format!("{} {}", start, read), format!("{} {}", start, chord));
+            let pitch_classes = |x: &Chord| x.tones().iter().map(|y| y.pitch_class()).collect::<Vec<_>>();
+            assert_eq!(pitch_classes(read), pitch_classes(chord));
+        }
+    }
+
+    #[test]
+    fn read() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE score-partwise PUBLIC "-//Recordare//DTD MusicXML 3.1 Partwise//EN" "http://www.musicxml.org/dtds/partwise.dtd">
+<score-partwise version="3.1">
+  <movement-title>Minuet</movement-title>
+  <part-list>
+    <score-part id="P1"><part-name>Violin</part-name><score-instrument id="P1-I1"/></score-part>
+  </part-list>
+  <part id="P1">
+    <measure number="1">
+      <print new-page="yes"/>
+      <attributes>
+        <divisions>24</divisions>
+        <key><fifths>1</fifths><mode>minor</mode></key>
+        <time symbol="common"><beats>2+2+3</beats><beat-type>8</beat-type></time>
+        <clef><sign>G</sign><line>2</line></clef>
+      </attributes>
+      <direction><direction-type><words>dolce</words></direction-type></direction>
+      <harmony><root><root-step>E</root-step></root><kind text="m">minor</kind></harmony>
+      <note><grace/><pitch><step>A</step><octave>4</octave></pitch><voice>1</voice><type>eighth</type></note>
+      <note><pitch><step>B</step><octave>4</octave></pitch><duration>24</duration><voice>1</voice><type>quarter</type></note>
+      <note><chord/><pitch><step>G</step><octave>4</octave></pitch><duration>24</duration><voice>1</voice></note>
+      <note><rest/><duration>12</duration><voice>1</voice></note>
+      <harmony><root><root-step>D</root-step></root><kind>dominant</kind><degree><degree-value>9</degree-value><degree-alter>-1</degree-alter><degree-type>add</degree-type></degree></harmony>
+      <note><pitch><step>D</step><alter>1</alter><octave>5</octave></pitch><duration>48</duration><tie type="start"/><voice>1</voice></note>
+      <backup><duration>84</duration></backup>
+      <forward><duration>12</duration></forward>
+      <note><pitch><step>E</step><octave>3</octave></pitch><duration>72</duration><voice>2</voice></note>
+    </measure>
+  </part>
+</score-partwise>"#;
+        let score = Score::from_musicxml(xml).unwrap();
+        assert_eq!(score.title, "Minuet");
+        let part = &score.parts[0];
+        assert_eq!((part.name.as_str(), part.divisions), ("Violin", 24));
+        let measure = &part.measures[0];
+        assert_eq!(measure.key, Some(Key::from_str("e minor").unwrap()));
+        assert_eq!(measure.time, Some((7, 8)));
+        assert_eq!(measure.notes, vec![
+            pitched("B4", 0, 24, 1), pitched("G4", 0, 24, 1),
+            ScoreNote {pitch: None, start: 24, duration: 12, voice: 1},
+            pitched("D#5", 36, 48, 1), pitched("E3", 12, 72, 2),
+        ]);
+        assert_eq!(measure.harmonies, vec![(0, Chord::from_symbol("Em").unwrap()), (36, Chord::from_symbol("D7b9").unwrap())]);
+        assert_eq!(format!("{}", part.sequence(1)), "G4 B4 D#5");
+        assert_eq!(format!("{}", part.sequence(2)), "E3");
+    }
+
+    #[test]
+    fn ties() {
+        let xml = r#"<score-partwise>
+  <part-list><score-part id="P1"><part-name>Piano</part-name></score-part></part-list>
+  <part id="P1">
+    <measure number="1">
+      <attributes><divisions>2</divisions><time><beats>2</beats><beat-type>4</beat-type></time></attributes>
+      <note><pitch><step>C</step><octave>4</octave></pitch><duration>2</duration><voice>1</voice></note>
+      <note><pitch><step>E</step><octave>4</octave></pitch><duration>2</duration><tie type="start"/><voice>1</voice></note>
+      <note><chord/><pitch><step>G</step><octave>4</octave></pitch><duration>2</duration><tie type="start"/><voice>1</voice></note>
+      <backup><duration>4</duration></backup>
+      <note><pitch><step>A</step><octave>3</octave></pitch><duration>2</duration><tie type="start"/><voice>2</voice></note>
+      <note><pitch><step>A</step><octave>3</octave></pitch><duration>2</duration><tie type="stop"/><voice>2</voice></note>
+    </measure>
+    <measure number="2">
+      <note><pitch><step>E</step><octave>4</octave></pitch><duration>4</duration><tie type="stop"/><tie type="start"/><voice>1</voice></note>
+      <note><chord/><pitch><step>G</step><octave>4</octave></pitch><duration>4</duration><tie type="stop"/><voice>1</voice></note>
+    </measure>
+    <measure number="3">
+      <note><pitch><step>E</step><octave>4</octave></pitch><duration>2</duration><tie type="stop"/><voice>1</voice></note>
+      <note><pitch><step>D</step><octave>4</octave></pitch><duration>2</duration><tie type="stop"/><voice>1</voice></note>
+    </measure>
+  </part>
+</score-partwise>"#;
+        let score = Score::from_musicxml(xml).unwrap();
+        let part = &score.parts[0];
+        assert_eq!(part.measures[0].notes, vec![pitched("C4", 0, 2, 1), pitched("E4", 2, 8, 1), pitched("G4", 2, 6, 1), pitched("A3", 0, 4, 2)]);
+        assert_eq!(part.measures[1].notes, vec![]);
+        // a tie without a start is read as a plain note
+        assert_eq!(part.measures[2].notes, vec![pitched("D4", 2, 2, 1)]);
+        assert_eq!(format!("{}", part.sequence(1)), "C4 E4 G4 D4");
+
+        // written back the notes are tied over the barlines again
+        let written = score.to_musicxml();
+        assert_eq!(written.matches("<tie type=\"start\"/>").count(), 3);
+        assert_eq!(written.matches("<tie type=\"stop\"/>").count(), 3);
+        assert!(written.contains("<tie type=\"stop\"/>\n        <tie type=\"start\"/>\n        <voice>1</voice>"));
+        assert!(written.contains("<notations>\n          <tied type=\"start\"/>\n        </notations>"));
+        assert_eq!(Score::from_musicxml(&written), Ok(score));
+
+        // measures are added for notes lasting past the last one
+        let mut part = Part::new("", 1);
+        part.measures.push(Measure {time: Some((2, 4)), notes: vec![pitched("C4", 0, 5, 1)], ..Measure::new()});
+        let mut score = Score::new("");
+        score.parts.push(part);
+        let written = score.to_musicxml();
+        assert!(written.contains("<measure number=\"3\">"));
+        let read = Score::from_musicxml(&written).unwrap();
+        assert_eq!(read.parts[0].measures.len(), 3);
+        assert_eq!(read.parts[0].measures[0].notes, vec![pitched("C4", 0, 5, 1)]);
+    }
+
+    #[test]
+    fn read_errors() {
+        let wrap = |measure: &str| format!(
+            "<score-partwise>\n<part-list/>\n<part id=\"P1\">\n<measure number=\"1\">\n{}\n</measure>\n</part>\n</score-partwise>", measure);
+        assert_eq!(Score::from_musicxml("<score-partwise>\n<part>\n</score-partwise>"),
+            Err(HarmonyError::InvalidArgument("Malformed XML").at_line(3)));
+        assert_eq!(Score::from_musicxml("<score-timewise/>"),
+            Err(HarmonyError::InvalidArgument("Only partwise MusicXML is supported").at_line(1)));
+        assert_eq!(Score::from_musicxml(&wrap("<note><pitch><step>H</step><octave>4</octave></pitch><duration>1</duration></note>")),
+            Err(HarmonyError::parse("H", 0, "H", &["C", "D", "E", "F", "G", "A", "B"]).at_line(5)));
+        assert_eq!(Score::from_musicxml(&wrap("<note>\n<pitch><step>C</step><octave>4</octave></pitch></note>")),
+            Err(HarmonyError::parse("note", 0, "", &["duration"]).at_line(5)));
+        assert_eq!(Score::from_musicxml(&wrap("<note><pitch><step>C</step><alter>0.5</alter><octave>4</octave></pitch><duration>1</duration></note>")),
+            Err(HarmonyError::parse("0.5", 0, "0.5", &["alter"]).at_line(5)));
+        assert_eq!(Score::from_musicxml(&wrap("<note><pitch><step>C</step><alter>3</alter><octave>4</octave></pitch><duration>1</duration></note>")),
+            Err(HarmonyError::OutOfRange("Offset can not be expressed by a single accidental").at_line(5)));
+        assert_eq!(Score::from_musicxml(&wrap("\n<backup><duration>2</duration></backup>")),
+            Err(HarmonyError::OutOfRange("Backup before the start of the measure").at_line(6)));
+        assert_eq!(Score::from_musicxml(&wrap("<forward><duration>4294967295</duration></forward>\n<forward><duration>1</duration></forward>")),
+            Err(HarmonyError::OutOfRange("Measure is too long").at_line(6)));
+        assert_eq!(Score::from_musicxml(&wrap("<forward><duration>4294967295</duration></forward>\n<note><rest/><duration>1</duration></note>")),
+            Err(HarmonyError::OutOfRange("Measure is too long").at_line(6)));
+        assert_eq!(Score::from_musicxml(&wrap("<attributes><divisions>2</divisions></attributes>\n<attributes><divisions>4</divisions></attributes>")),
+            Err(HarmonyError::InvalidArgument("Changing divisions are not supported").at_line(6)));
+        assert!(Score::from_musicxml(&wrap("<harmony><root><root-step>C</root-step></root><kind>pedal</kind></harmony>")).is_err());
+    }
+
+    #[test]
+    fn builders() {
+        let part = Part::from_progression("Piano", &[NoteSequence::from_str("C4 E4 G4").unwrap(), NoteSequence::from_str("B3 D4 G4").unwrap(), NoteSequence::from_str("C4 E4 G4").unwrap()], 1, 2, (2, 2)).unwrap();
+        assert_eq!(part.measures.len(), 2);
+        assert_eq!(part.measures[0].notes.len(), 6);
+        assert_eq!(part.measures[1].notes[0], pitched("C4", 0, 2, 1));
+        assert_eq!(part.measures[0].time, Some((2, 2)));
+        assert_eq!(Part::from_sequence("", &NoteSequence::from_str("C4").unwrap(), 2, 3, (4, 4)), Err(HarmonyError::InvalidArgument("Notes must fill measures evenly")));
+        assert_eq!(Part::from_sequence("", &NoteSequence::from_str("C4").unwrap(), 1, 1, (3, 5)), Err(HarmonyError::InvalidArgument("Invalid time signature")));
+        assert_eq!(Part::from_sequence("", &NoteSequence::from_str("C4").unwrap(), u32::MAX, 1, (4, 4)), Err(HarmonyError::OutOfRange("Measure is too long")));
+    }
+}
@@ -0,0 +1,364 @@
+use crate::notenames::NoteName;
+use crate::accidentals::Accidental;
+use crate::notes::Note;
+use crate::errors::HarmonyError;
+
+const ACCIDENTALS: [Accidental; 5] = [
+    Accidental::Doubleflat, Accidental::Flat, Accidental::Natural, Accidental::Sharp, Accidental::Doublesharp,
+];
+
+// A notation locale decides how note names and accidentals are spelled in text.
+// Only names and accidentals have to be provided; locales that merge both into
+// one word (like German "Es" or "B") override the pitch class methods.
+pub trait Notation {
+    fn name_to_str(&self, name: NoteName) -> String;
+
+    fn name_from_str(&self, s: &str) -> Result<NoteName, HarmonyError>;
+
+    fn accidental_to_str(&self, accidental: Accidental) -> String;
+
+    fn accidental_from_str(&self, s: &str) -> Result<Accidental, HarmonyError>;
+
+    fn class_to_str(&self, name: NoteName, accidental: Accidental) -> String {
+        format!("{}{}", self.name_to_str(name), self.accidental_to_str(accidental))
+    }
+
+    fn class_from_str(&self, s: &str) -> Result<(NoteName, Accidental), HarmonyError> {
+        // the longest prefix that is a note name wins, the rest is the accidental
+        let nchars = s.chars().count();
+        let mut accidental_error = None;
+        for name_len in (1..=nchars).rev() {
+            let name_str: String = s.chars().take(name_len).collect();
+            if let Ok(name) = self.name_from_str(&name_str) {
+                let accidental_str: String = s.chars().skip(name_len).collect();
+                match self.accidental_from_str(&accidental_str) {
+                    Ok(accidental) => return Ok((name, accidental)),
+                    Err(error) => {
+                        accidental_error.get_or_insert(error.within(s, name_len));
+                    },
+                }
+            }
+        }
+        match accidental_error {
+            Some(error) => Err(error),
+            None => Err(self.name_from_str(s).err().unwrap_or(HarmonyError::parse(s, 0, s, &[])).within(s, 0)),
+        }
+    }
+
+    fn note_to_str(&self, note: &Note) -> String {
+        format!("{}{}", self.class_to_str(note.name(), note.accidental()), note.octave())
+    }
+
+    fn note_from_str(&self, s: &str) -> Result<Note, HarmonyError> {
+        let chars: Vec<char> = s.chars().collect();
+        let octave_start_idx = chars.iter()
+            .position(|c| c.is_ascii_digit() || *c == '-')
+            .unwrap_or(chars.len());
+        let class_str: String = chars[..octave_start_idx].iter().collect();
+        let (name, accidental) = self.class_from_str(&class_str).map_err(|x| x.within(s, 0))?;
+        let octave_str: String = chars[octave_start_idx..].iter().collect();
+        let octave = octave_str.parse::<i8>()
+            .map_err(|_| HarmonyError::parse(s, octave_start_idx, &octave_str, &["octave number"]))?;
+        Ok(Note::new(name, accidental, octave))
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct English;
+
+impl Notation for English {
+    fn name_to_str(&self, name: NoteName) -> String {
+        String::from(name.to_str())
+    }
+
+    fn name_from_str(&self, s: &str) -> Result<NoteName, HarmonyError> {
+        NoteName::from_str(s)
+    }
+
+    fn accidental_to_str(&self, accidental: Accidental) -> String {
+        String::from(accidental.to_str())
+    }
+
+    fn accidental_from_str(&self, s: &str) -> Result<Accidental, HarmonyError> {
+        Accidental::from_str(s)
+    }
+}
+
+// Printed music symbols. Naturals are only written if explicitly requested,
+// ASCII accidentals are accepted as well when parsing.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Unicode {
+    pub explicit_naturals: bool,
+}
+
+impl Notation for Unicode {
+    fn name_to_str(&self, name: NoteName) -> String {
+        String::from(name.to_str())
+    }
+
+    fn name_from_str(&self, s: &str) -> Result<NoteName, HarmonyError> {
+        NoteName::from_str(s)
+    }
+
+    fn accidental_to_str(&self, accidental: Accidental) -> String {
+        let symbol = match accidental {
+            Accidental::Doubleflat => "𝄫",
+            Accidental::Flat => "♭",
+            Accidental::Natural if self.explicit_naturals => "♮",
+            Accidental::Natural => "",
+            Accidental::Sharp => "♯",
+            Accidental::Doublesharp => "𝄪",
+        };
+        String::from(symbol)
+    }
+
+    fn accidental_from_str(&self, s: &str) -> Result<Accidental, HarmonyError> {
+        match s {
+            "𝄫" | "♭♭" => Ok(Accidental::Doubleflat),
+            "♭" => Ok(Accidental::Flat),
+            "♮" => Ok(Accidental::Natural),
+            "♯" => Ok(Accidental::Sharp),
+            "𝄪" | "♯♯" => Ok(Accidental::Doublesharp),
+            _ => Accidental::from_str(s).map_err(|_| HarmonyError::parse(s, 0, s, &["𝄫", "♭", "", "♮", "♯", "𝄪"])),
+        }
+    }
+}
+
+// Fixed-do solfège as used in Romance languages, "Do" is always C.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Solfege;
+
+impl Notation for Solfege {
+    fn name_to_str(&self, name: NoteName) -> String {
+        let syllable = match name {
+            NoteName::C => "Do",
+            NoteName::D => "Re",
+            NoteName::E => "Mi",
+            NoteName::F => "Fa",
+            NoteName::G => "Sol",
+            NoteName::A => "La",
+            NoteName::B => "Si",
+        };
+        String::from(syllable)
+    }
+
+    fn name_from_str(&self, s: &str) -> Result<NoteName, HarmonyError> {
+        let lower = s.to_lowercase();
+        match lower.as_str() {
+            "do" | "ut" => Ok(NoteName::C),
+            "re" | "ré" => Ok(NoteName::D),
+            "mi" => Ok(NoteName::E),
+            "fa" => Ok(NoteName::F),
+            "sol" | "so" => Ok(NoteName::G),
+            "la" => Ok(NoteName::A),
+            "si" | "ti" => Ok(NoteName::B),
+            _ => Err(HarmonyError::parse(s, 0, s, &["Do", "Re", "Mi", "Fa", "Sol", "La", "Si"])),
+        }
+    }
+
+    fn accidental_to_str(&self, accidental: Accidental) -> String {
+        English.accidental_to_str(accidental)
+    }
+
+    fn accidental_from_str(&self, s: &str) -> Result<Accidental, HarmonyError> {
+        Unicode {explicit_naturals: false}.accidental_from_str(s)
+            .map_err(|_| HarmonyError::parse(s, 0, s, &ACCIDENTALS.map(|x| x.to_str())))
+    }
+}
+
+// German names: H is the natural B, B the flat B, and accidentals are the
+// suffixes -is and -es, contracted to "Es" and "As" after vowels.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct German;
+
+impl Notation for German {
+    fn name_to_str(&self, name: NoteName) -> String {
+        match name {
+            NoteName::B => String::from("H"),
+            name => String::from(name.to_str()),
+        }
+    }
+
+    fn name_from_str(&self, s: &str) -> Result<NoteName, HarmonyError> {
+        match s.to_uppercase().as_str() {
+            "H" => Ok(NoteName::B),
+            "B" => Err(HarmonyError::parse(s, 0, s, &["C", "D", "E", "F", "G", "A", "H"])),
+            _ => NoteName::from_str(s).map_err(|_| HarmonyError::parse(s, 0, s, &["C", "D", "E", "F", "G", "A", "H"])),
+        }
+    }
+
+    fn accidental_to_str(&self, accidental: Accidental) -> String {
+        let suffix = match accidental {
+            Accidental::Doubleflat => "eses",
+            Accidental::Flat => "es",
+            Accidental::Natural => "",
+            Accidental::Sharp => "is",
+            Accidental::Doublesharp => "isis",
+        };
+        String::from(suffix)
+    }
+
+    fn accidental_from_str(&self, s: &str) -> Result<Accidental, HarmonyError> {
+        match s.to_lowercase().as_str() {
+            "eses" => Ok(Accidental::Doubleflat),
+            "es" => Ok(Accidental::Flat),
+            "" => Ok(Accidental::Natural),
+            "is" => Ok(Accidental::Sharp),
+            "isis" => Ok(Accidental::Doublesharp),
+            _ => Err(HarmonyError::parse(s, 0, s, &["eses", "es", "", "is", "isis"])),
+        }
+    }
+
+    fn class_to_str(&self, name: NoteName, accidental: Accidental) -> String {
+        match (name, accidental) {
+            (NoteName::B, Accidental::Flat) => String::from("B"),
+            (NoteName::E | NoteName::A, Accidental::Flat | Accidental::Doubleflat) => {
+                format!("{}{}", name.to_str(), &self.accidental_to_str(accidental)[1..])
+            },
+            (name, accidental) => format!("{}{}", self.name_to_str(name), self.accidental_to_str(accidental)),
+        }
+    }
+
+    fn class_from_str(&self, s: &str) -> Result<(NoteName, Accidental), HarmonyError> {
+        let lower = s.to_lowercase();
+        match lower.as_str() {
+            "b" => return Ok((NoteName::B, Accidental::Flat)),
+            "bes" => return Ok((NoteName::B, Accidental::Doubleflat)),
+            _ => (),
+        }
+        let mut chars = lower.chars();
+        let name_str: String = chars.next().into_iter().collect();
+        let name = self.name_from_str(&name_str).map_err(|x| x.within(s, 0))?;
+        let suffix = chars.as_str();
+        // "As", "Ases", "Es" and "Eses" drop the e of the suffix
+        let suffix = match (name, suffix) {
+            (NoteName::E | NoteName::A, "s" | "ses") => format!("e{}", suffix),
+            _ => String::from(suffix),
+        };
+        let accidental = self.accidental_from_str(&suffix).map_err(|x| x.within(s, 1))?;
+        Ok((name, accidental))
+    }
+}
+
+impl NoteName {
+    pub fn from_str_with(s: &str, notation: &dyn Notation) -> Result<Self, HarmonyError> {
+        notation.name_from_str(s)
+    }
+
+    pub fn to_str_with(self, notation: &dyn Notation) -> String {
+        notation.name_to_str(self)
+    }
+}
+
+impl Accidental {
+    pub fn from_str_with(s: &str, notation: &dyn Notation) -> Result<Self, HarmonyError> {
+        notation.accidental_from_str(s)
+    }
+
+    pub fn to_str_with(self, notation: &dyn Notation) -> String {
+        notation.accidental_to_str(self)
+    }
+}
+
+impl Note {
+    pub fn from_str_with(s: &str, notation: &dyn Notation) -> Result<Self, HarmonyError> {
+        notation.note_from_str(s)
+    }
+
+    pub fn to_str_with(self, notation: &dyn Notation) -> String {
+        notation.note_to_str(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAMES: [NoteName; 7] = [NoteName::C, NoteName::D, NoteName::E, NoteName::F, NoteName::G, NoteName::A, NoteName::B];
+
+    #[test]
+    fn english() {
+        assert_eq!(Note::from_str_with("F#4", &English), Ok(Note::from_str("F#4").unwrap()));
+        assert_eq!(Note::from_str_with("bb-1", &English), Ok(Note::from_str("Bb-1").unwrap()));
+        assert_eq!(Note::from_str("Ebb2").unwrap().to_str_with(&English), "Ebb2");
+        assert_eq!(Note::from_str_with("H3", &English).unwrap_err().position(), Some(0));
+        assert_eq!(Note::from_str_with("C!3", &English).unwrap_err().position(), Some(1));
+    }
+
+    #[test]
+    fn unicode() {
+        let unicode = Unicode {explicit_naturals: false};
+        assert_eq!(Note::from_str_with("F♯4", &unicode), Ok(Note::from_str("F#4").unwrap()));
+        assert_eq!(Note::from_str_with("B𝄫3", &unicode), Ok(Note::from_str("Bbb3").unwrap()));
+        assert_eq!(Note::from_str_with("G𝄪5", &unicode), Ok(Note::from_str("G##5").unwrap()));
+        assert_eq!(Note::from_str_with("A♮3", &unicode), Ok(Note::from_str("A3").unwrap()));
+        assert_eq!(Note::from_str_with("Db3", &unicode), Ok(Note::from_str("Db3").unwrap()));
+        assert_eq!(Note::from_str("Eb4").unwrap().to_str_with(&unicode), "E♭4");
+        assert_eq!(Note::from_str("C##4").unwrap().to_str_with(&unicode), "C𝄪4");
+        assert_eq!(Note::from_str("A3").unwrap().to_str_with(&unicode), "A3");
+        assert_eq!(Note::from_str("A3").unwrap().to_str_with(&Unicode {explicit_naturals: true}), "A♮3");
+        assert_eq!(Accidental::Doubleflat.to_str_with(&unicode), "𝄫");
+        assert_eq!(Note::from_str_with("C♪4", &unicode),
+            Err(HarmonyError::parse("C♪4", 1, "♪", &["𝄫", "♭", "", "♮", "♯", "𝄪"])));
+    }
+
+    #[test]
+    fn solfege() {
+        assert_eq!(Note::from_str_with("Do4", &Solfege), Ok(Note::from_str("C4").unwrap()));
+        assert_eq!(Note::from_str_with("Sol#3", &Solfege), Ok(Note::from_str("G#3").unwrap()));
+        assert_eq!(Note::from_str_with("sib2", &Solfege), Ok(Note::from_str("Bb2").unwrap()));
+        assert_eq!(Note::from_str_with("Ré♭5", &Solfege), Ok(Note::from_str("Db5").unwrap()));
+        assert_eq!(Note::from_str_with("Ti4", &Solfege), Ok(Note::from_str("B4").unwrap()));
+        assert_eq!(Note::from_str("F#4").unwrap().to_str_with(&Solfege), "Fa#4");
+        assert_eq!(Note::from_str("Gbb3").unwrap().to_str_with(&Solfege), "Solbb3");
+        assert_eq!(NoteName::A.to_str_with(&Solfege), "La");
+        assert_eq!(NoteName::from_str_with("Fa", &Solfege), Ok(NoteName::F));
+        assert_eq!(Note::from_str_with("Xo4", &Solfege).unwrap_err().position(), Some(0));
+        assert_eq!(Note::from_str_with("Fax4", &Solfege).unwrap_err().position(), Some(2));
+    }
+
+    #[test]
+    fn german() {
+        assert_eq!(Note::from_str_with("H3", &German), Ok(Note::from_str("B3").unwrap()));
+        assert_eq!(Note::from_str_with("B3", &German), Ok(Note::from_str("Bb3").unwrap()));
+        assert_eq!(Note::from_str_with("Fis4", &German), Ok(Note::from_str("F#4").unwrap()));
+        assert_eq!(Note::from_str_with("Es4", &German), Ok(Note::from_str("Eb4").unwrap()));
+        assert_eq!(Note::from_str_with("As2", &German), Ok(Note::from_str("Ab2").unwrap()));
+        assert_eq!(Note::from_str_with("Ases2", &German), Ok(Note::from_str("Abb2").unwrap()));
+        assert_eq!(Note::from_str_with("Eses2", &German), Ok(Note::from_str("Ebb2").unwrap()));
+        assert_eq!(Note::from_str_with("Heses2", &German), Ok(Note::from_str("Bbb2").unwrap()));
+        assert_eq!(Note::from_str_with("Cisis5", &German), Ok(Note::from_str("C##5").unwrap()));
+        assert_eq!(Note::from_str_with("des3", &German), Ok(Note::from_str("Db3").unwrap()));
+        assert_eq!(Note::from_str_with("His3", &German), Ok(Note::from_str("B#3").unwrap()));
+
+        assert_eq!(Note::from_str("B3").unwrap().to_str_with(&German), "H3");
+        assert_eq!(Note::from_str("Bb3").unwrap().to_str_with(&German), "B3");
+        assert_eq!(Note::from_str("Bbb3").unwrap().to_str_with(&German), "Heses3");
+        assert_eq!(Note::from_str("Eb4").unwrap().to_str_with(&German), "Es4");
+        assert_eq!(Note::from_str("Abb4").unwrap().to_str_with(&German), "Ases4");
+        assert_eq!(Note::from_str("Gb4").unwrap().to_str_with(&German), "Ges4");
+        assert_eq!(Note::from_str("D#4").unwrap().to_str_with(&German), "Dis4");
+        assert_eq!(Note::from_str("A#4").unwrap().to_str_with(&German), "Ais4");
+        assert_eq!(NoteName::B.to_str_with(&German), "H");
+        assert_eq!(Accidental::Sharp.to_str_with(&German), "is");
+
+        assert_eq!(Note::from_str_with("Fus4", &German),
+            Err(HarmonyError::parse("Fus4", 1, "us", &["eses", "es", "", "is", "isis"])));
+        assert_eq!(NoteName::from_str_with("B", &German).unwrap_err().position(), Some(0));
+    }
+
+    #[test]
+    fn round_trip() {
+        let notations: [&dyn Notation; 5] = [
+            &English, &Unicode {explicit_naturals: false}, &Unicode {explicit_naturals: true}, &Solfege, &German,
+        ];
+        for notation in notations {
+            for name in NAMES {
+                for accidental in ACCIDENTALS {
+                    let note = Note::new(name, accidental, 4);
+                    assert_eq!(Note::from_str_with(&note.to_str_with(notation), notation), Ok(note));
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,14 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::errors::HarmonyError;
+
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum NoteName {
     C, D, E, F, G, A, B
 }
 
 impl NoteName {
-    pub fn from_str(s:&str) -> Result<Self, &'static str> {
+    pub fn from_str(s:&str) -> Result<Self, HarmonyError> {
         match s.to_uppercase().as_str() {
             "C" => Ok(NoteName::C),
             "D" => Ok(NoteName::D),
@@ -13,7 +17,7 @@ impl NoteName {
             "G" => Ok(NoteName::G),
             "A" => Ok(NoteName::A),
             "B" => Ok(NoteName::B),
-            _ => Err("Invalid Note Name")
+            _ => Err(HarmonyError::parse(s, 0, s, &["C", "D", "E", "F", "G", "A", "B"]))
         }
     }
 
@@ -115,6 +119,20 @@ impl NoteName {
     }
 }
 
+impl FromStr for NoteName {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str(s)
+    }
+}
+
+impl fmt::Display for NoteName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -259,4 +277,11 @@ mod tests {
         assert_eq!(NoteName::A.dist_hsteps(&NoteName::C), -9);
         assert_eq!(NoteName::B.dist_hsteps(&NoteName::C), -11);
     }
+
+    #[test]
+    fn from_str_errors() {
+        assert_eq!(NoteName::from_str("H"), Err(HarmonyError::parse("H", 0, "H", &["C", "D", "E", "F", "G", "A", "B"])));
+        assert_eq!("g".parse::<NoteName>(), Ok(NoteName::G));
+        assert_eq!(format!("{}", NoteName::F), "F");
+    }
 }
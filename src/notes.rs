@@ -1,5 +1,18 @@
+use std::fmt;
+use std::str::FromStr;
 use crate::notenames::NoteName;
 use crate::accidentals::Accidental;
+use crate::intervals::Interval;
+use crate::keys::Key;
+use crate::errors::HarmonyError;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum SpellingPolicy {
+    Sharps,
+    Flats,
+    Key(Key),
+    Minimal,
+}
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Note {
@@ -17,42 +30,33 @@ impl Note {
         }
     }
 
-    pub fn from_str(s: &str) -> Result<Self, &'static str> {
-        let mut note = Self::default();
+    pub fn new(name: NoteName, accidental: Accidental, octave: i8) -> Self {
+        Self {
+            name, accidental, octave,
+        }
+    }
+
+    pub fn from_str(s: &str) -> Result<Self, HarmonyError> {
+        let chars: Vec<char> = s.chars().collect();
 
         // notename
-        let name_start_idx = 0;
-        let name_end_idx = name_start_idx+1;
-        if s.len() >= name_end_idx {
-            note.name = NoteName::from_str(&s[name_start_idx..name_end_idx])?;
-        } else {
-            return Err("Unable to parse note");
-        }
-        
+        let name_str: String = chars.iter().take(1).collect();
+        let name = NoteName::from_str(&name_str).map_err(|x| x.within(s, 0))?;
+
         // accidental
-        let accicental_start_idx = name_end_idx;
-        let mut accicental_end_idx = accicental_start_idx;
-        for c in s[accicental_end_idx..].chars() {
-            if c.is_numeric() || c == '-' {
-                break;
-            } else {
-                accicental_end_idx += 1;
-            }
-        }
-        if s.len() >= accicental_end_idx {
-            note.accidental = Accidental::from_str(&s[accicental_start_idx..accicental_end_idx])?
-        } else {
-            return Err("Unable to parse accidental");
-        }
+        let accidental_start_idx = 1;
+        let accidental_end_idx = chars.iter()
+            .position(|c| c.is_numeric() || *c == '-')
+            .unwrap_or(chars.len())
+            .max(accidental_start_idx);
+        let accidental_str: String = chars[accidental_start_idx..accidental_end_idx].iter().collect();
+        let accidental = Accidental::from_str(&accidental_str).map_err(|x| x.within(s, accidental_start_idx))?;
 
         // octave
-        let octave_start_idx = accicental_end_idx;
-        let octave_end_idx = s.len();
-        match s[octave_start_idx..octave_end_idx].parse::<i8>() {
-            Ok(oct) => note.octave = oct,
-            Err(_) => return Err("Invalid Octave")
-        }
-        Ok(note)
+        let octave_str: String = chars[accidental_end_idx..].iter().collect();
+        let octave = octave_str.parse::<i8>()
+            .map_err(|_| HarmonyError::parse(s, accidental_end_idx, &octave_str, &["octave number"]))?;
+        Ok(Self::new(name, accidental, octave))
     }
 
     pub fn to_str(self) -> String {
@@ -67,12 +71,120 @@ impl Note {
         note_str
     }
 
+    pub fn name(&self) -> NoteName {
+        self.name
+    }
+
+    pub fn accidental(&self) -> Accidental {
+        self.accidental
+    }
+
+    pub fn octave(&self) -> i8 {
+        self.octave
+    }
+
+    pub fn pitch_class(&self) -> i32 {
+        (NoteName::C.dist_hsteps(&self.name) + self.accidental.offset()).rem_euclid(12)
+    }
+
+    // position on the line of fifths, C is 0, G is 1 and F is -1
+    pub fn line_of_fifths(&self) -> i32 {
+        let natural = match self.name {
+            NoteName::F => -1,
+            NoteName::C => 0,
+            NoteName::G => 1,
+            NoteName::D => 2,
+            NoteName::A => 3,
+            NoteName::E => 4,
+            NoteName::B => 5,
+        };
+        natural + 7 * self.accidental.offset()
+    }
+
+    pub fn same_spelling(&self, other: &Note) -> bool {
+        self.name == other.name && self.accidental == other.accidental
+    }
+
+    // MIDI note 60 is C4, the octave number belongs to the letter name,
+    // so B#3 sounds like C4 and Cb4 like B3
+    pub fn to_midi(&self) -> Result<u8, HarmonyError> {
+        u8::try_from(self.midi_pitch()).ok()
+            .filter(|x| *x <= 127)
+            .ok_or(HarmonyError::OutOfRange("Note is outside of the MIDI range"))
+    }
+
+    pub fn from_midi(n: u8, policy: SpellingPolicy) -> Result<Self, HarmonyError> {
+        if n > 127 {
+            return Err(HarmonyError::OutOfRange("MIDI note numbers range from 0 to 127"));
+        }
+        let spellings = Self::spellings(n as i32);
+        let find = |accidental: Accidental| spellings.iter().find(|x| x.accidental == accidental).copied();
+        let note = match policy {
+            SpellingPolicy::Sharps => find(Accidental::Natural).or(find(Accidental::Sharp)),
+            SpellingPolicy::Flats => find(Accidental::Natural).or(find(Accidental::Flat)),
+            // black keys are spelled the way they are most commonly written
+            SpellingPolicy::Minimal => find(Accidental::Natural).or(spellings.iter().find(|x| matches!(
+                (x.name, x.accidental),
+                (NoteName::C, Accidental::Sharp) | (NoteName::E, Accidental::Flat) | (NoteName::F, Accidental::Sharp)
+                | (NoteName::A, Accidental::Flat) | (NoteName::B, Accidental::Flat))).copied()),
+            SpellingPolicy::Key(key) => match spellings.iter().find(|x| key.is_diatonic(x)) {
+                Some(note) => Some(*note),
+                None if key.signature().fifths() < 0 => Some(Self::from_midi(n, SpellingPolicy::Flats)?),
+                None => Some(Self::from_midi(n, SpellingPolicy::Sharps)?),
+            },
+        };
+        note.ok_or(HarmonyError::OutOfRange("Unable to spell MIDI note"))
+    }
+
+    // all spellings of the same pitch with at most double accidentals, including this one
+    pub fn enharmonics(&self) -> Vec<Self> {
+        Self::spellings(self.midi_pitch())
+    }
+
+    fn midi_pitch(&self) -> i32 {
+        12*(self.octave as i32 + 1) + NoteName::C.dist_hsteps(&self.name) + self.accidental.offset()
+    }
+
+    fn spellings(pitch: i32) -> Vec<Self> {
+        let mut spellings = Vec::new();
+        for name in [NoteName::C, NoteName::D, NoteName::E, NoteName::F, NoteName::G, NoteName::A, NoteName::B] {
+            for offset in -2..=2 {
+                let natural_pitch = pitch - NoteName::C.dist_hsteps(&name) - offset;
+                if natural_pitch.rem_euclid(12) != 0 {
+                    continue;
+                }
+                if let Ok(octave) = i8::try_from(natural_pitch / 12 - 1) {
+                    spellings.push(Self::new(name, Accidental::from_offset(offset).unwrap(), octave));
+                }
+            }
+        }
+        spellings.sort_by_key(|x| Self::default().dist_steps(x));
+        spellings
+    }
+
     pub fn dist_hsteps(&self, other: &Note) -> i32 {
         12*(other.octave as i32 - self.octave as i32)
             + self.name.dist_hsteps(&other.name)
             + other.accidental.offset() - self.accidental.offset()
     }
 
+    pub fn dist_steps(&self, other: &Note) -> i32 {
+        7*(other.octave as i32 - self.octave as i32)
+            + self.name.dist(&other.name)
+    }
+
+    pub fn transpose(&self, interval: Interval) -> Result<Self, HarmonyError> {
+        let target = self.shift_natural(interval.steps());
+        let offset = interval.hsteps() - self.dist_hsteps(&target);
+        Ok(target.set_accidental(Accidental::from_offset(offset)?))
+    }
+
+    pub fn transpose_down(&self, interval: Interval) -> Result<Self, HarmonyError> {
+        let target = self.shift_natural(-interval.steps());
+        let offset = -interval.hsteps() - self.dist_hsteps(&target);
+        Ok(target.set_accidental(Accidental::from_offset(offset)?))
+    }
+
     pub fn set_accidental(&self, accidental: Accidental) -> Self {
         let mut accidented_note = *self;
         accidented_note.accidental = accidental;
@@ -130,6 +242,20 @@ impl Note {
     }
 }
 
+impl FromStr for Note {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str(s)
+    }
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -259,6 +385,95 @@ mod test {
         assert_eq!(note1.dist_hsteps(&note2), 10);
     }
 
+    #[test]
+    fn pitch_class() {
+        assert_eq!(Note::from_str("C3").unwrap().pitch_class(), 0);
+        assert_eq!(Note::from_str("C#3").unwrap().pitch_class(), 1);
+        assert_eq!(Note::from_str("Db3").unwrap().pitch_class(), 1);
+        assert_eq!(Note::from_str("E4").unwrap().pitch_class(), 4);
+        assert_eq!(Note::from_str("B-1").unwrap().pitch_class(), 11);
+        assert_eq!(Note::from_str("Cb3").unwrap().pitch_class(), 11);
+        assert_eq!(Note::from_str("B#3").unwrap().pitch_class(), 0);
+        assert_eq!(Note::from_str("Cbb3").unwrap().pitch_class(), 10);
+    }
+
+    #[test]
+    fn line_of_fifths() {
+        assert_eq!(Note::from_str("C4").unwrap().line_of_fifths(), 0);
+        assert_eq!(Note::from_str("F2").unwrap().line_of_fifths(), -1);
+        assert_eq!(Note::from_str("B4").unwrap().line_of_fifths(), 5);
+        assert_eq!(Note::from_str("F#4").unwrap().line_of_fifths(), 6);
+        assert_eq!(Note::from_str("Bb4").unwrap().line_of_fifths(), -2);
+        assert_eq!(Note::from_str("G#4").unwrap().line_of_fifths(), 8);
+        assert_eq!(Note::from_str("Ab4").unwrap().line_of_fifths(), -4);
+        assert_eq!(Note::from_str("Ebb4").unwrap().line_of_fifths(), -10);
+    }
+
+    #[test]
+    fn same_spelling() {
+        assert!(Note::from_str("C3").unwrap().same_spelling(&Note::from_str("C5").unwrap()));
+        assert!(Note::from_str("F#3").unwrap().same_spelling(&Note::from_str("F#-1").unwrap()));
+        assert!(!Note::from_str("F#3").unwrap().same_spelling(&Note::from_str("Gb3").unwrap()));
+        assert!(!Note::from_str("F#3").unwrap().same_spelling(&Note::from_str("F3").unwrap()));
+    }
+
+    #[test]
+    fn dist_steps() {
+        let note1 = Note::from_str("C3").unwrap();
+        let note2 = Note::from_str("C4").unwrap();
+        assert_eq!(note1.dist_steps(&note2), 7);
+        assert_eq!(note2.dist_steps(&note1), -7);
+
+        let note1 = Note::from_str("B#3").unwrap();
+        let note2 = Note::from_str("C4").unwrap();
+        assert_eq!(note1.dist_steps(&note2), 1);
+        let note1 = Note::from_str("Cb4").unwrap();
+        let note2 = Note::from_str("B3").unwrap();
+        assert_eq!(note1.dist_steps(&note2), -1);
+        let note1 = Note::from_str("F#3").unwrap();
+        let note2 = Note::from_str("Gb3").unwrap();
+        assert_eq!(note1.dist_steps(&note2), 1);
+        let note1 = Note::from_str("E3").unwrap();
+        let note2 = Note::from_str("D5").unwrap();
+        assert_eq!(note1.dist_steps(&note2), 13);
+    }
+
+    #[test]
+    fn transpose() {
+        let transpose = |note: &str, interval: &str| {
+            Note::from_str(note).unwrap().transpose(Interval::from_str(interval).unwrap())
+        };
+        assert_eq!(transpose("C4", "P1"), Ok(Note::from_str("C4").unwrap()));
+        assert_eq!(transpose("C4", "M3"), Ok(Note::from_str("E4").unwrap()));
+        assert_eq!(transpose("C4", "A4"), Ok(Note::from_str("F#4").unwrap()));
+        assert_eq!(transpose("C4", "d5"), Ok(Note::from_str("Gb4").unwrap()));
+        assert_eq!(transpose("E4", "m3"), Ok(Note::from_str("G4").unwrap()));
+        assert_eq!(transpose("B3", "m2"), Ok(Note::from_str("C4").unwrap()));
+        assert_eq!(transpose("B3", "A1"), Ok(Note::from_str("B#3").unwrap()));
+        assert_eq!(transpose("Eb3", "d7"), Ok(Note::from_str("Dbb4").unwrap()));
+        assert_eq!(transpose("G#3", "M7"), Ok(Note::from_str("F##4").unwrap()));
+        assert_eq!(transpose("D3", "P12"), Ok(Note::from_str("A4").unwrap()));
+        assert_eq!(transpose("F3", "M10"), Ok(Note::from_str("A4").unwrap()));
+
+        assert!(transpose("B##3", "A4").is_err());
+        assert!(transpose("Fbb3", "d5").is_err());
+    }
+
+    #[test]
+    fn transpose_down() {
+        let transpose_down = |note: &str, interval: &str| {
+            Note::from_str(note).unwrap().transpose_down(Interval::from_str(interval).unwrap())
+        };
+        assert_eq!(transpose_down("C4", "P1"), Ok(Note::from_str("C4").unwrap()));
+        assert_eq!(transpose_down("C4", "M3"), Ok(Note::from_str("Ab3").unwrap()));
+        assert_eq!(transpose_down("C4", "A4"), Ok(Note::from_str("Gb3").unwrap()));
+        assert_eq!(transpose_down("C4", "d5"), Ok(Note::from_str("F#3").unwrap()));
+        assert_eq!(transpose_down("C4", "m2"), Ok(Note::from_str("B3").unwrap()));
+        assert_eq!(transpose_down("F#4", "M9"), Ok(Note::from_str("E3").unwrap()));
+
+        assert!(transpose_down("Fbb3", "A4").is_err());
+    }
+
     #[test]
     fn rm_accidental() {
         let doubleflat_note = Note::from_str("Cbb3").unwrap();
@@ -312,4 +527,87 @@ mod test {
         assert_eq!(Note::from_str("B3").unwrap().prev_natural(),   Note::from_str("A3").unwrap());
         assert_eq!(Note::from_str("Cb4").unwrap().prev_natural(),  Note::from_str("B3").unwrap());
     }
+
+    #[test]
+    fn from_str_errors() {
+        let names = ["C", "D", "E", "F", "G", "A", "B"];
+        assert_eq!(Note::from_str(""), Err(HarmonyError::parse("", 0, "", &names)));
+        assert_eq!(Note::from_str("H3"), Err(HarmonyError::parse("H3", 0, "H", &names)));
+        assert_eq!(Note::from_str("C!3"), Err(HarmonyError::parse("C!3", 1, "!", &["bb", "b", "", "#", "##"])));
+        assert_eq!(Note::from_str("Cx3").unwrap_err().position(), Some(1));
+        assert_eq!(Note::from_str("C#"), Err(HarmonyError::parse("C#", 2, "", &["octave number"])));
+        assert_eq!(Note::from_str("C#3x"), Err(HarmonyError::parse("C#3x", 2, "3x", &["octave number"])));
+        assert_eq!(Note::from_str("Ä3").unwrap_err().position(), Some(0));
+        assert_eq!(Note::from_str("C♯3").unwrap_err().position(), Some(1));
+    }
+
+    #[test]
+    fn traits() {
+        assert_eq!("Eb4".parse::<Note>(), Ok(Note::new(NoteName::E, Accidental::Flat, 4)));
+        assert_eq!(format!("{}", Note::new(NoteName::F, Accidental::Doublesharp, -1)), "F##-1");
+    }
+
+    #[test]
+    fn to_midi() {
+        assert_eq!(Note::from_str("C4").unwrap().to_midi(), Ok(60));
+        assert_eq!(Note::from_str("A4").unwrap().to_midi(), Ok(69));
+        assert_eq!(Note::from_str("C-1").unwrap().to_midi(), Ok(0));
+        assert_eq!(Note::from_str("G9").unwrap().to_midi(), Ok(127));
+        assert_eq!(Note::from_str("B#3").unwrap().to_midi(), Ok(60));
+        assert_eq!(Note::from_str("Cb4").unwrap().to_midi(), Ok(59));
+        assert_eq!(Note::from_str("B##3").unwrap().to_midi(), Ok(61));
+        assert_eq!(Note::from_str("Cbb4").unwrap().to_midi(), Ok(58));
+        assert!(Note::from_str("Cb-1").unwrap().to_midi().is_err());
+        assert!(Note::from_str("G#9").unwrap().to_midi().is_err());
+    }
+
+    #[test]
+    fn from_midi() {
+        let from_midi = |n, policy| Note::from_midi(n, policy).unwrap().to_str();
+        assert_eq!(from_midi(60, SpellingPolicy::Sharps), "C4");
+        assert_eq!(from_midi(61, SpellingPolicy::Sharps), "C#4");
+        assert_eq!(from_midi(61, SpellingPolicy::Flats), "Db4");
+        assert_eq!(from_midi(70, SpellingPolicy::Sharps), "A#4");
+        assert_eq!(from_midi(70, SpellingPolicy::Minimal), "Bb4");
+        assert_eq!(from_midi(63, SpellingPolicy::Minimal), "Eb4");
+        assert_eq!(from_midi(66, SpellingPolicy::Minimal), "F#4");
+        assert_eq!(from_midi(64, SpellingPolicy::Minimal), "E4");
+        assert_eq!(from_midi(0, SpellingPolicy::Flats), "C-1");
+
+        let key = |s| SpellingPolicy::Key(Key::from_str(s).unwrap());
+        assert_eq!(from_midi(60, key("C#")), "B#3");
+        assert_eq!(from_midi(59, key("Gb")), "Cb4");
+        assert_eq!(from_midi(65, key("C#")), "E#4");
+        assert_eq!(from_midi(68, key("E")), "G#4");
+        assert_eq!(from_midi(68, key("c")), "Ab4");
+        assert_eq!(from_midi(62, key("Db")), "D4");
+        assert_eq!(from_midi(61, key("F")), "Db4");
+        assert_eq!(from_midi(63, key("G")), "D#4");
+        assert_eq!(from_midi(69, key("A# harmonic minor")), "G##4");
+
+        assert!(Note::from_midi(128, SpellingPolicy::Sharps).is_err());
+        for n in 0..=127 {
+            for policy in [SpellingPolicy::Sharps, SpellingPolicy::Flats, SpellingPolicy::Minimal, key("Cb"), key("C#")] {
+                assert_eq!(Note::from_midi(n, policy).unwrap().to_midi(), Ok(n));
+            }
+        }
+    }
+
+    #[test]
+    fn enharmonics() {
+        let enharmonics = |s| -> Vec<String> {
+            Note::from_str(s).unwrap().enharmonics().iter().map(|x| x.to_str()).collect()
+        };
+        assert_eq!(enharmonics("C4"), vec!["B#3", "C4", "Dbb4"]);
+        assert_eq!(enharmonics("B3"), vec!["A##3", "B3", "Cb4"]);
+        assert_eq!(enharmonics("Dbb4"), vec!["B#3", "C4", "Dbb4"]);
+        assert_eq!(enharmonics("G#4"), vec!["G#4", "Ab4"]);
+        assert_eq!(enharmonics("E4"), vec!["D##4", "E4", "Fb4"]);
+        assert_eq!(enharmonics("B#3"), vec!["B#3", "C4", "Dbb4"]);
+        assert_eq!(enharmonics("Cbb0"), vec!["A#-1", "Bb-1", "Cbb0"]);
+        for note in Note::from_str("F##2").unwrap().enharmonics() {
+            assert_eq!(note.to_midi(), Ok(43));
+            assert_eq!(Note::from_str("F##2").unwrap().dist_hsteps(&note), 0);
+        }
+    }
 }
@@ -1,3 +1,6 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::errors::HarmonyError;
 use crate::notes::Note;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -12,7 +15,7 @@ impl NoteSequence {
         }
     }
 
-    pub fn from_strs(svec: Vec<&str>) -> Result<Self, &'static str> {
+    pub fn from_strs(svec: Vec<&str>) -> Result<Self, HarmonyError> {
         let mut sequence: Self = NoteSequence::empty();
         for note in svec.iter().map(|x| Note::from_str(x)) {
             match note {
@@ -24,6 +27,35 @@ impl NoteSequence {
     }
 }
 
+// notes are separated by whitespace, e.g. "C4 E4 G4"
+impl FromStr for NoteSequence {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut sequence = NoteSequence::empty();
+        let mut token_start_idx = None;
+        for (idx, c) in s.chars().chain(std::iter::once(' ')).enumerate() {
+            match (c.is_whitespace(), token_start_idx) {
+                (false, None) => token_start_idx = Some(idx),
+                (true, Some(start_idx)) => {
+                    let token: String = s.chars().skip(start_idx).take(idx - start_idx).collect();
+                    sequence.notes.push(Note::from_str(&token).map_err(|x| x.within(s, start_idx))?);
+                    token_start_idx = None;
+                },
+                _ => (),
+            }
+        }
+        Ok(sequence)
+    }
+}
+
+impl fmt::Display for NoteSequence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let strs: Vec<String> = self.notes.iter().map(|x| x.to_str()).collect();
+        write!(f, "{}", strs.join(" "))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -44,4 +76,14 @@ mod test {
         let strs = ["C3","D3","E3","F3","G#","A3","B3"];
         assert!(NoteSequence::from_strs(strs.to_vec()).is_err());
     }
+
+    #[test]
+    fn traits() {
+        let sequence: NoteSequence = " C4  E4\tG4 ".parse().unwrap();
+        assert_eq!(sequence, NoteSequence::from_strs(["C4", "E4", "G4"].to_vec()).unwrap());
+        assert_eq!(format!("{}", sequence), "C4 E4 G4");
+        assert_eq!("".parse::<NoteSequence>(), Ok(NoteSequence::empty()));
+        assert_eq!("C4 E!4 G4".parse::<NoteSequence>(), Err(HarmonyError::parse("C4 E!4 G4", 4, "!", &["bb", "b", "", "#", "##"])));
+        assert_eq!("C4 E4 G".parse::<NoteSequence>().unwrap_err().position(), Some(7));
+    }
 }
@@ -0,0 +1,869 @@
+use std::fmt;
+use std::cmp::Ordering;
+use std::ops::{Add, Sub, Mul, Div};
+use std::str::FromStr;
+
+use crate::errors::HarmonyError;
+use crate::notes::Note;
+use crate::notesequences::NoteSequence;
+
+pub fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {a} else {gcd(b, a % b)}
+}
+
+// An exact fraction, used for lengths in whole notes.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub struct Rational {
+    numerator: i64,
+    denominator: i64,
+}
+
+impl Rational {
+    pub const ZERO: Rational = Rational {numerator: 0, denominator: 1};
+    pub const ONE: Rational = Rational {numerator: 1, denominator: 1};
+
+    pub fn new(numerator: i64, denominator: i64) -> Result<Self, HarmonyError> {
+        if denominator == 0 {
+            return Err(HarmonyError::InvalidArgument("Denominator must not be zero"));
+        }
+        Self::reduced_wide(numerator as i128, denominator as i128).ok_or(HarmonyError::OutOfRange("Fraction is out of range"))
+    }
+
+    pub fn integer(n: i64) -> Self {
+        Self {numerator: n, denominator: 1}
+    }
+
+    fn reduced(numerator: i64, denominator: i64) -> Self {
+        Self::reduced_wide(numerator as i128, denominator as i128).expect("attempt to negate with overflow")
+    }
+
+    // products of two fractions fit into i128, None if the reduced result does not fit into i64
+    fn reduced_wide(numerator: i128, denominator: i128) -> Option<Self> {
+        let divisor = wide_gcd(numerator.unsigned_abs(), denominator.unsigned_abs()).max(1) as i128 * denominator.signum();
+        Some(Self {
+            numerator: i64::try_from(numerator / divisor).ok()?,
+            denominator: i64::try_from(denominator / divisor).ok()?,
+        })
+    }
+
+    pub fn from_str(s: &str) -> Result<Self, HarmonyError> {
+        let (numerator_str, denominator_str) = s.split_once('/').unwrap_or((s, "1"));
+        let numerator = numerator_str.parse::<i64>()
+            .map_err(|_| HarmonyError::parse(s, 0, numerator_str, &["integer"]))?;
+        let position = numerator_str.chars().count() + 1;
+        let denominator = denominator_str.parse::<i64>().ok().filter(|x| *x != 0)
+            .ok_or(HarmonyError::parse(s, position, denominator_str, &["nonzero integer"]))?;
+        Self::reduced_wide(numerator as i128, denominator as i128).ok_or(HarmonyError::OutOfRange("Fraction is out of range"))
+    }
+
+    pub fn to_str(self) -> String {
+        if self.denominator == 1 {
+            self.numerator.to_string()
+        } else {
+            format!("{}/{}", self.numerator, self.denominator)
+        }
+    }
+
+    pub fn numerator(&self) -> i64 {
+        self.numerator
+    }
+
+    pub fn denominator(&self) -> i64 {
+        self.denominator
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.numerator == 0
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    pub fn checked_add(self, other: Rational) -> Option<Rational> {
+        let (a, b, c, d) = self.wide(other);
+        Rational::reduced_wide(a * d + c * b, b * d)
+    }
+
+    pub fn checked_sub(self, other: Rational) -> Option<Rational> {
+        let (a, b, c, d) = self.wide(other);
+        Rational::reduced_wide(a * d - c * b, b * d)
+    }
+
+    pub fn checked_mul(self, other: Rational) -> Option<Rational> {
+        let (a, b, c, d) = self.wide(other);
+        Rational::reduced_wide(a * c, b * d)
+    }
+
+    pub fn checked_div(self, other: Rational) -> Option<Rational> {
+        let (a, b, c, d) = self.wide(other);
+        if c == 0 {
+            return None;
+        }
+        Rational::reduced_wide(a * d, b * c)
+    }
+
+    fn wide(self, other: Rational) -> (i128, i128, i128, i128) {
+        (self.numerator as i128, self.denominator as i128, other.numerator as i128, other.denominator as i128)
+    }
+}
+
+fn wide_gcd(a: u128, b: u128) -> u128 {
+    if b == 0 {a} else {wide_gcd(b, a % b)}
+}
+
+impl Add for Rational {
+    type Output = Rational;
+
+    fn add(self, other: Rational) -> Rational {
+        self.checked_add(other).expect("attempt to add with overflow")
+    }
+}
+
+impl Sub for Rational {
+    type Output = Rational;
+
+    fn sub(self, other: Rational) -> Rational {
+        self.checked_sub(other).expect("attempt to subtract with overflow")
+    }
+}
+
+impl Mul for Rational {
+    type Output = Rational;
+
+    fn mul(self, other: Rational) -> Rational {
+        self.checked_mul(other).expect("attempt to multiply with overflow")
+    }
+}
+
+// the operators panic on overflow and division by zero like the integer types
+impl Div for Rational {
+    type Output = Rational;
+
+    fn div(self, other: Rational) -> Rational {
+        assert!(other.numerator != 0, "attempt to divide by zero");
+        self.checked_div(other).expect("attempt to divide with overflow")
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.numerator as i128 * other.denominator as i128).cmp(&(other.numerator as i128 * self.denominator as i128))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for Rational {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str(s)
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum NoteValue {
+    Whole,
+    Half,
+    Quarter,
+    Eighth,
+    Sixteenth,
+    ThirtySecond,
+    SixtyFourth,
+    HundredTwentyEighth,
+}
+
+impl NoteValue {
+    pub const ALL: [NoteValue; 8] = [
+        NoteValue::Whole, NoteValue::Half, NoteValue::Quarter, NoteValue::Eighth,
+        NoteValue::Sixteenth, NoteValue::ThirtySecond, NoteValue::SixtyFourth, NoteValue::HundredTwentyEighth,
+    ];
+
+    // the denominator of the value, 4 for a quarter
+    pub fn from_number(number: u8) -> Result<Self, HarmonyError> {
+        Self::ALL.iter().copied().find(|x| x.number() == number)
+            .ok_or(HarmonyError::OutOfRange("Note values go from 1 to 128"))
+    }
+
+    pub fn number(self) -> u8 {
+        1 << Self::ALL.iter().position(|x| *x == self).unwrap()
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            NoteValue::Whole => "whole",
+            NoteValue::Half => "half",
+            NoteValue::Quarter => "quarter",
+            NoteValue::Eighth => "eighth",
+            NoteValue::Sixteenth => "16th",
+            NoteValue::ThirtySecond => "32nd",
+            NoteValue::SixtyFourth => "64th",
+            NoteValue::HundredTwentyEighth => "128th",
+        }
+    }
+
+    pub fn length(self) -> Rational {
+        Rational::reduced(1, self.number() as i64)
+    }
+
+    // eighths and shorter have flags and can be beamed
+    pub fn has_flag(self) -> bool {
+        self.number() >= 8
+    }
+}
+
+impl fmt::Display for NoteValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+// A note value with dots, optionally played as a tuplet where actual notes take
+// the time of normal ones (3 in the time of 2 for triplets).
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Duration {
+    value: NoteValue,
+    dots: u8,
+    tuplet: Option<(u8, u8)>,
+}
+
+impl Duration {
+    pub fn new(value: NoteValue, dots: u8) -> Result<Self, HarmonyError> {
+        if dots > 4 {
+            return Err(HarmonyError::OutOfRange("At most four dots are supported"));
+        }
+        Ok(Self {value, dots, tuplet: None})
+    }
+
+    pub fn tuplet(self, actual: u8, normal: u8) -> Result<Self, HarmonyError> {
+        if actual == 0 || normal == 0 {
+            return Err(HarmonyError::OutOfRange("Tuplets need a positive number of notes"));
+        }
+        Ok(Self {tuplet: Some((actual, normal)), ..self})
+    }
+
+    // A length without tuplet as a single value with up to two dots.
+    pub fn from_length(length: Rational) -> Option<Self> {
+        NoteValue::ALL.iter()
+            .flat_map(|value| (0..=2).map(|dots| Self {value: *value, dots, tuplet: None}))
+            .find(|x| x.length() == length)
+    }
+
+    // Tied values that add up to a length, longest first. Lengths that can not
+    // be written without tuplets are an error.
+    pub fn decompose(length: Rational) -> Result<Vec<Self>, HarmonyError> {
+        if let Some(duration) = Self::from_length(length) {
+            return Ok(vec![duration]);
+        }
+        let candidates: Vec<Self> = NoteValue::ALL.iter()
+            .flat_map(|value| [1, 0].map(|dots| Self {value: *value, dots, tuplet: None}))
+            .collect();
+        let mut durations = Vec::new();
+        let mut remaining = length;
+        while remaining > Rational::ZERO {
+            let duration = candidates.iter().find(|x| x.length() <= remaining)
+                .ok_or(HarmonyError::InvalidArgument("Length can not be written with plain or dotted values"))?;
+            durations.push(*duration);
+            remaining = remaining - duration.length();
+        }
+        Ok(durations)
+    }
+
+    pub fn value(&self) -> NoteValue {
+        self.value
+    }
+
+    pub fn dots(&self) -> u8 {
+        self.dots
+    }
+
+    pub fn tuplet_ratio(&self) -> Option<(u8, u8)> {
+        self.tuplet
+    }
+
+    // the length in whole notes
+    pub fn length(&self) -> Rational {
+        let base = self.value.length();
+        // every dot adds half of the previous addition
+        let dotted = base * Rational::reduced((1 << (self.dots + 1)) - 1, 1 << self.dots);
+        match self.tuplet {
+            Some((actual, normal)) => dotted * Rational::reduced(normal as i64, actual as i64),
+            None => dotted,
+        }
+    }
+
+    // like 4 for a quarter, 8.. for a double dotted eighth and 8:3/2 for a
+    // triplet eighth
+    pub fn from_str(s: &str) -> Result<Self, HarmonyError> {
+        let (value_str, tuplet_str) = s.split_once(':').unwrap_or((s, ""));
+        let number_str = value_str.trim_end_matches('.');
+        let dots = (value_str.len() - number_str.len()) as u8;
+        let value = number_str.parse::<u8>().ok().and_then(|x| NoteValue::from_number(x).ok())
+            .ok_or(HarmonyError::parse(s, 0, number_str, &["1", "2", "4", "8", "16", "32", "64", "128"]))?;
+        let duration = Self::new(value, dots).map_err(|_| HarmonyError::parse(s, number_str.len() + 4, ".", &[":"]))?;
+        if tuplet_str.is_empty() && !s.contains(':') {
+            return Ok(duration);
+        }
+        let position = value_str.len() + 1;
+        let ratio = tuplet_str.split_once('/')
+            .and_then(|(x, y)| Some((x.parse::<u8>().ok()?, y.parse::<u8>().ok()?)))
+            .filter(|(x, y)| *x > 0 && *y > 0)
+            .ok_or(HarmonyError::parse(s, position, tuplet_str, &["tuplet ratio"]))?;
+        duration.tuplet(ratio.0, ratio.1)
+    }
+
+    pub fn to_str(self) -> String {
+        let mut s = format!("{}{}", self.value.number(), ".".repeat(self.dots as usize));
+        if let Some((actual, normal)) = self.tuplet {
+            s += &format!(":{}/{}", actual, normal);
+        }
+        s
+    }
+}
+
+impl FromStr for Duration {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str(s)
+    }
+}
+
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+// Beats grouped by plus signs like 2+2+3/8 are additive. Other meters with a
+// multiple of three beats above three are compound.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TimeSignature {
+    groups: Vec<u8>,
+    beat_type: u8,
+}
+
+impl TimeSignature {
+    pub fn new(beats: u8, beat_type: u8) -> Result<Self, HarmonyError> {
+        Self::additive(&[beats], beat_type)
+    }
+
+    pub fn additive(groups: &[u8], beat_type: u8) -> Result<Self, HarmonyError> {
+        if groups.is_empty() || groups.contains(&0) || groups.iter().map(|x| *x as u32).sum::<u32>() > 255 {
+            return Err(HarmonyError::OutOfRange("Beats must be between 1 and 255"));
+        }
+        NoteValue::from_number(beat_type)?;
+        Ok(Self {groups: groups.to_vec(), beat_type})
+    }
+
+    pub fn from_str(s: &str) -> Result<Self, HarmonyError> {
+        let (beats_str, beat_type_str) = s.split_once('/')
+            .ok_or(HarmonyError::parse(s, s.chars().count(), "", &["/"]))?;
+        let mut groups = Vec::new();
+        let mut position = 0;
+        for group_str in beats_str.split('+') {
+            let group = group_str.trim().parse::<u8>().ok().filter(|x| *x > 0)
+                .ok_or(HarmonyError::parse(s, position, group_str, &["beats"]))?;
+            groups.push(group);
+            position += group_str.chars().count() + 1;
+        }
+        let beat_type = beat_type_str.trim().parse::<u8>().ok().filter(|x| NoteValue::from_number(*x).is_ok())
+            .ok_or(HarmonyError::parse(s, beats_str.chars().count() + 1, beat_type_str, &["1", "2", "4", "8", "16", "32", "64", "128"]))?;
+        Self::additive(&groups, beat_type)
+    }
+
+    pub fn to_str(&self) -> String {
+        let groups: Vec<String> = self.groups.iter().map(|x| x.to_string()).collect();
+        format!("{}/{}", groups.join("+"), self.beat_type)
+    }
+
+    pub fn beats(&self) -> u8 {
+        self.groups.iter().sum()
+    }
+
+    pub fn beat_type(&self) -> u8 {
+        self.beat_type
+    }
+
+    pub fn is_additive(&self) -> bool {
+        self.groups.len() > 1
+    }
+
+    pub fn is_compound(&self) -> bool {
+        !self.is_additive() && self.beats() > 3 && self.beats().is_multiple_of(3)
+    }
+
+    pub fn is_simple(&self) -> bool {
+        !self.is_additive() && !self.is_compound()
+    }
+
+    pub fn measure_length(&self) -> Rational {
+        Rational::reduced(self.beats() as i64, self.beat_type as i64)
+    }
+
+    // The lengths of the beats that notes are beamed within: the groups of an
+    // additive meter, dotted beats in compound meters and single beats otherwise.
+    pub fn beat_groups(&self) -> Vec<Rational> {
+        let beat = Rational::reduced(1, self.beat_type as i64);
+        if self.is_additive() {
+            self.groups.iter().map(|x| beat * Rational::integer(*x as i64)).collect()
+        } else if self.is_compound() {
+            vec![beat * Rational::integer(3); self.beats() as usize / 3]
+        } else {
+            vec![beat; self.beats() as usize]
+        }
+    }
+}
+
+impl FromStr for TimeSignature {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str(s)
+    }
+}
+
+impl fmt::Display for TimeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+// A note, chord or rest (no notes). A tie joins it to the next event.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Event {
+    pub notes: Vec<Note>,
+    pub duration: Duration,
+    pub tie: bool,
+}
+
+impl Event {
+    pub fn note(note: Note, duration: Duration) -> Self {
+        Self {notes: vec![note], duration, tie: false}
+    }
+
+    pub fn chord(chord: &NoteSequence, duration: Duration) -> Self {
+        Self {notes: chord.notes.clone(), duration, tie: false}
+    }
+
+    pub fn rest(duration: Duration) -> Self {
+        Self {notes: Vec::new(), duration, tie: false}
+    }
+
+    pub fn is_rest(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    pub fn length(&self) -> Rational {
+        self.duration.length()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Measure {
+    pub time: TimeSignature,
+    pub events: Vec<Event>,
+}
+
+impl Measure {
+    pub fn length(&self) -> Rational {
+        self.events.iter().fold(Rational::ZERO, |sum, x| sum + x.length())
+    }
+
+    // Less or greater when the events do not fill the measure.
+    pub fn fill(&self) -> Ordering {
+        self.length().cmp(&self.time.measure_length())
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.fill() == Ordering::Equal
+    }
+
+    // Indices of the notes beamed together. Notes with flags are beamed when
+    // they are in the same beat group, rests break the beams.
+    pub fn beams(&self) -> Vec<Vec<usize>> {
+        let mut ends = Vec::new();
+        let mut end = Rational::ZERO;
+        for group in self.time.beat_groups() {
+            end = end + group;
+            ends.push(end);
+        }
+        let group_of = |start: Rational, length: Rational| {
+            let first = ends.iter().position(|x| start < *x);
+            let last = ends.iter().position(|x| start + length <= *x);
+            first.filter(|x| Some(*x) == last)
+        };
+
+        let mut beams: Vec<Vec<usize>> = Vec::new();
+        let mut current: Vec<usize> = Vec::new();
+        let mut current_group = None;
+        let mut start = Rational::ZERO;
+        for (i, event) in self.events.iter().enumerate() {
+            let group = group_of(start, event.length());
+            let beamable = !event.is_rest() && event.duration.value().has_flag() && group.is_some();
+            if !beamable || group != current_group {
+                if current.len() > 1 {
+                    beams.push(current.clone());
+                }
+                current.clear();
+            }
+            if beamable {
+                current.push(i);
+                current_group = group;
+            } else {
+                current_group = None;
+            }
+            start = start + event.length();
+        }
+        if current.len() > 1 {
+            beams.push(current);
+        }
+        beams
+    }
+}
+
+// Timed events in a meter. A pickup shortens the first measure.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TimedSequence {
+    pub time: TimeSignature,
+    pub pickup: Rational,
+    pub events: Vec<Event>,
+}
+
+impl TimedSequence {
+    pub fn new(time: TimeSignature) -> Self {
+        Self {time, pickup: Rational::ZERO, events: Vec::new()}
+    }
+
+    pub fn from_sequence(sequence: &NoteSequence, duration: Duration, time: TimeSignature) -> Self {
+        Self {
+            events: sequence.notes.iter().map(|x| Event::note(*x, duration)).collect(),
+            ..Self::new(time)
+        }
+    }
+
+    pub fn from_progression(chords: &[NoteSequence], duration: Duration, time: TimeSignature) -> Self {
+        Self {
+            events: chords.iter().map(|x| Event::chord(x, duration)).collect(),
+            ..Self::new(time)
+        }
+    }
+
+    pub fn length(&self) -> Rational {
+        self.events.iter().fold(Rational::ZERO, |sum, x| sum + x.length())
+    }
+
+    pub fn onsets(&self) -> Vec<Rational> {
+        let mut onsets = Vec::new();
+        let mut start = Rational::ZERO;
+        for event in &self.events {
+            onsets.push(start);
+            start = start + event.length();
+        }
+        onsets
+    }
+
+    // the sounding notes in order, continuations of ties are left out
+    pub fn notesequence(&self) -> NoteSequence {
+        let mut notes = Vec::new();
+        for (i, event) in self.events.iter().enumerate() {
+            let continued = i > 0 && self.events[i - 1].tie && self.events[i - 1].notes == event.notes;
+            if !continued {
+                notes.extend(event.notes.iter().copied());
+            }
+        }
+        NoteSequence {notes}
+    }
+
+    fn barlines(&self, end: Rational) -> Vec<Rational> {
+        let mut barlines = Vec::new();
+        let mut barline = if self.pickup.is_zero() {self.time.measure_length()} else {self.pickup};
+        while barline < end {
+            barlines.push(barline);
+            barline = barline + self.time.measure_length();
+        }
+        barlines
+    }
+
+    // The events split into measures. Events crossing a bar line are an error,
+    // tie_across_barlines splits them first.
+    pub fn measures(&self) -> Result<Vec<Measure>, HarmonyError> {
+        let barlines = self.barlines(self.length());
+        let mut measures = vec![Measure {time: self.time.clone(), events: Vec::new()}];
+        let mut start = Rational::ZERO;
+        let mut next = barlines.iter().peekable();
+        for event in &self.events {
+            if next.next_if(|x| **x <= start).is_some() {
+                measures.push(Measure {time: self.time.clone(), events: Vec::new()});
+            }
+            let end = start + event.length();
+            if next.peek().is_some_and(|x| **x < end) {
+                return Err(HarmonyError::InvalidArgument("Event crosses the barline"));
+            }
+            measures.last_mut().unwrap().events.push(event.clone());
+            start = end;
+        }
+        Ok(measures)
+    }
+
+    // Checks that every measure is full. Only the pickup and the last measure
+    // after a pickup may be shorter.
+    pub fn validate(&self) -> Result<(), HarmonyError> {
+        if self.pickup < Rational::ZERO || self.pickup > self.time.measure_length() {
+            return Err(HarmonyError::OutOfRange("Pickup must be at most one measure long"));
+        }
+        let measures = self.measures()?;
+        let last = measures.len() - 1;
+        for (i, measure) in measures.iter().enumerate() {
+            let valid = match (i, measure.fill()) {
+                (_, Ordering::Equal) => true,
+                (0, _) if !self.pickup.is_zero() => measure.length() == self.pickup,
+                (i, Ordering::Less) if i == last => !self.pickup.is_zero(),
+                _ => false,
+            };
+            if !valid {
+                return Err(HarmonyError::InvalidArgument("Measure is not filled"));
+            }
+        }
+        Ok(())
+    }
+
+    // splits the events at the bar lines into tied parts
+    pub fn tie_across_barlines(&self) -> Result<Self, HarmonyError> {
+        let barlines = self.barlines(self.length());
+        let mut events = Vec::new();
+        let mut start = Rational::ZERO;
+        for event in &self.events {
+            let end = start + event.length();
+            let crossed: Vec<Rational> = barlines.iter().copied().filter(|x| start < *x && *x < end).collect();
+            if crossed.is_empty() {
+                events.push(event.clone());
+                start = end;
+                continue;
+            }
+            if event.duration.tuplet_ratio().is_some() {
+                return Err(HarmonyError::InvalidArgument("Tuplets can not be tied across a barline"));
+            }
+            let mut bounds = vec![start];
+            bounds.extend(crossed);
+            bounds.push(end);
+            for (i, part) in bounds.windows(2).enumerate() {
+                let durations = Duration::decompose(part[1] - part[0])?;
+                let last_part = i == bounds.len() - 2;
+                for (j, duration) in durations.iter().enumerate() {
+                    let last = last_part && j == durations.len() - 1;
+                    // rests are not tied
+                    let tie = if last {event.tie} else {!event.is_rest()};
+                    events.push(Event {notes: event.notes.clone(), duration: *duration, tie});
+                }
+            }
+            start = end;
+        }
+        Ok(Self {events, ..self.clone()})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ratio(s: &str) -> Rational {
+        Rational::from_str(s).unwrap()
+    }
+
+    fn duration(s: &str) -> Duration {
+        Duration::from_str(s).unwrap()
+    }
+
+    fn time(s: &str) -> TimeSignature {
+        TimeSignature::from_str(s).unwrap()
+    }
+
+    fn events(s: &str) -> Vec<Event> {
+        // durations with an r for rests, like 8 8r 4.
+        s.split_whitespace()
+            .map(|x| match x.strip_suffix('r') {
+                Some(rest) => Event::rest(duration(rest)),
+                None => Event::note(Note::from_str("C4").unwrap(), duration(x)),
+            })
+            .collect()
+    }
+
+    fn passage(time_str: &str, s: &str) -> TimedSequence {
+        TimedSequence {events: events(s), ..TimedSequence::new(time(time_str))}
+    }
+
+    #[test]
+    fn rationals() {
+        assert_eq!(ratio("2/4"), ratio("1/2"));
+        assert_eq!(ratio("3/-6").to_string(), "-1/2");
+        assert_eq!(ratio("4/2").to_string(), "2");
+        assert_eq!(ratio("1/3") + ratio("1/6"), ratio("1/2"));
+        assert_eq!(ratio("1/4") - ratio("3/8"), ratio("-1/8"));
+        assert_eq!(ratio("2/3") * ratio("3/4"), ratio("1/2"));
+        assert_eq!(ratio("1/2") / ratio("1/8"), Rational::integer(4));
+        assert!(ratio("1/3") < ratio("3/8"));
+        assert!(ratio("-1/2") < Rational::ZERO);
+        assert_eq!(Rational::new(1, 0), Err(HarmonyError::InvalidArgument("Denominator must not be zero")));
+        assert_eq!(Rational::from_str("1/0"), Err(HarmonyError::parse("1/0", 2, "0", &["nonzero integer"])));
+        assert_eq!(Rational::from_str("x/2"), Err(HarmonyError::parse("x/2", 0, "x", &["integer"])));
+        assert_eq!(ratio("3/4").to_f64(), 0.75);
+
+        // exact as long as the reduced result fits
+        let big = Rational::new(i64::MAX, 3).unwrap();
+        assert_eq!(big * ratio("3/7"), Rational::new(i64::MAX, 7).unwrap());
+        assert_eq!(big - big, Rational::ZERO);
+        assert_eq!(Rational::integer(i64::MAX).checked_add(Rational::ONE), None);
+        assert_eq!(Rational::integer(i64::MIN).checked_sub(Rational::ONE), None);
+        assert_eq!(Rational::new(1, i64::MAX).unwrap().checked_mul(Rational::new(1, 2).unwrap()), None);
+        assert_eq!(Rational::ONE.checked_div(Rational::ZERO), None);
+        assert_eq!(Rational::new(i64::MIN, -1), Err(HarmonyError::OutOfRange("Fraction is out of range")));
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(duration("1").length(), Rational::ONE);
+        assert_eq!(duration("4").length(), ratio("1/4"));
+        assert_eq!(duration("4.").length(), ratio("3/8"));
+        assert_eq!(duration("8..").length(), ratio("7/32"));
+        assert_eq!(duration("128").length(), ratio("1/128"));
+        assert_eq!(duration("8:3/2").length(), ratio("1/12"));
+        assert_eq!(duration("16:5/4").length(), ratio("1/20"));
+        assert_eq!(duration("4.:3/2").length(), ratio("1/4"));
+        assert_eq!(duration("8..").to_string(), "8..");
+        assert_eq!(duration("8:3/2").to_string(), "8:3/2");
+        assert_eq!(duration("2").value(), NoteValue::Half);
+        assert_eq!(duration("2").value().to_string(), "half");
+
+        assert_eq!(Duration::from_str("3"), Err(HarmonyError::parse("3", 0, "3", &["1", "2", "4", "8", "16", "32", "64", "128"])));
+        assert_eq!(Duration::from_str("8:3"), Err(HarmonyError::parse("8:3", 2, "3", &["tuplet ratio"])));
+        assert_eq!(Duration::from_str("8:0/2"), Err(HarmonyError::parse("8:0/2", 2, "0/2", &["tuplet ratio"])));
+        assert!(Duration::from_str("4.....").is_err());
+        assert_eq!(NoteValue::from_number(3), Err(HarmonyError::OutOfRange("Note values go from 1 to 128")));
+
+        assert_eq!(Duration::from_length(ratio("3/16")), Some(duration("8.")));
+        assert_eq!(Duration::from_length(ratio("1/3")), None);
+        let decomposed = |s: &str| Duration::decompose(ratio(s)).map(|x| x.iter().map(|y| y.to_string()).collect::<Vec<_>>().join(" "));
+        assert_eq!(decomposed("5/8"), Ok(String::from("2 8")));
+        assert_eq!(decomposed("7/8"), Ok(String::from("2..")));
+        assert_eq!(decomposed("15/16"), Ok(String::from("2. 8.")));
+        assert_eq!(decomposed("5/4"), Ok(String::from("1 4")));
+        assert_eq!(decomposed("1/3"), Err(HarmonyError::InvalidArgument("Length can not be written with plain or dotted values")));
+    }
+
+    #[test]
+    fn time_signatures() {
+        let simple = time("3/4");
+        assert!(simple.is_simple());
+        assert_eq!(simple.measure_length(), ratio("3/4"));
+        assert_eq!(simple.beat_groups(), vec![ratio("1/4"); 3]);
+
+        let compound = time("6/8");
+        assert!(compound.is_compound());
+        assert_eq!(compound.measure_length(), ratio("3/4"));
+        assert_eq!(compound.beat_groups(), vec![ratio("3/8"); 2]);
+        assert!(time("12/16").is_compound());
+        assert!(time("3/8").is_simple());
+
+        let additive = time("2+2+3/8");
+        assert!(additive.is_additive());
+        assert_eq!((additive.beats(), additive.beat_type()), (7, 8));
+        assert_eq!(additive.beat_groups(), vec![ratio("1/4"), ratio("1/4"), ratio("3/8")]);
+        assert_eq!(additive.to_string(), "2+2+3/8");
+        assert_eq!(TimeSignature::additive(&[3, 2, 2], 8).unwrap().to_string(), "3+2+2/8");
+        assert_eq!(time("7/8").beat_groups().len(), 7);
+
+        assert_eq!(TimeSignature::from_str("4"), Err(HarmonyError::parse("4", 1, "", &["/"])));
+        assert_eq!(TimeSignature::from_str("2+x/8"), Err(HarmonyError::parse("2+x/8", 2, "x", &["beats"])));
+        assert_eq!(TimeSignature::from_str("3/5"), Err(HarmonyError::parse("3/5", 2, "5", &["1", "2", "4", "8", "16", "32", "64", "128"])));
+        assert_eq!(TimeSignature::new(0, 4), Err(HarmonyError::OutOfRange("Beats must be between 1 and 255")));
+    }
+
+    #[test]
+    fn measures() {
+        let waltz = passage("3/4", "2 4 4 4 4 2.");
+        let measures = waltz.measures().unwrap();
+        assert_eq!(measures.len(), 3);
+        assert_eq!(measures.iter().map(|x| x.events.len()).collect::<Vec<_>>(), vec![2, 3, 1]);
+        assert!(measures.iter().all(|x| x.is_full()));
+        assert_eq!(waltz.validate(), Ok(()));
+        assert_eq!(waltz.onsets()[3], ratio("1"));
+
+        assert_eq!(passage("3/4", "2 4 4 4").validate(), Err(HarmonyError::InvalidArgument("Measure is not filled")));
+        assert_eq!(passage("3/4", "2 2 2").validate(), Err(HarmonyError::InvalidArgument("Event crosses the barline")));
+        assert_eq!(passage("3/4", "2 2 2").measures(), Err(HarmonyError::InvalidArgument("Event crosses the barline")));
+        assert_eq!(passage("4/4", "4:3/2 4:3/2 4:3/2 2").validate(), Ok(()));
+
+        // a pickup of one beat
+        let mut pickup = passage("3/4", "4 2 4 4 2");
+        pickup.pickup = ratio("1/4");
+        assert_eq!(pickup.validate(), Ok(()));
+        assert_eq!(pickup.measures().unwrap().len(), 3);
+        pickup.pickup = ratio("1/2");
+        assert_eq!(pickup.validate(), Err(HarmonyError::InvalidArgument("Event crosses the barline")));
+        let mut full = passage("3/4", "2 4 2 4");
+        full.pickup = ratio("3/4");
+        assert_eq!(full.validate(), Ok(()));
+        pickup.pickup = ratio("1");
+        assert_eq!(pickup.validate(), Err(HarmonyError::OutOfRange("Pickup must be at most one measure long")));
+        pickup.pickup = ratio("-1/4");
+        assert_eq!(pickup.validate(), Err(HarmonyError::OutOfRange("Pickup must be at most one measure long")));
+    }
+
+    #[test]
+    fn ties() {
+        let tied = passage("3/4", "2 2 2").tie_across_barlines().unwrap();
+        assert_eq!(tied.events.iter().map(|x| x.duration.to_string()).collect::<Vec<_>>(), vec!["2", "4", "4", "2"]);
+        assert_eq!(tied.events.iter().map(|x| x.tie).collect::<Vec<_>>(), vec![false, true, false, false]);
+        assert_eq!(tied.validate(), Ok(()));
+        assert_eq!(tied.notesequence().notes.len(), 3);
+
+        let tied = passage("4/4", "8 1 2. 2r 8.").tie_across_barlines().unwrap();
+        assert_eq!(tied.events.iter().map(|x| x.duration.to_string()).collect::<Vec<_>>(),
+            vec!["8", "2..", "8", "2.", "8", "4.", "8."]);
+        // rests are split without ties
+        assert_eq!(tied.events.iter().map(|x| x.tie).collect::<Vec<_>>(),
+            vec![false, true, false, false, false, false, false]);
+        assert_eq!(tied.measures().unwrap().len(), 3);
+        assert!(passage("2/4", "4 4:3/2 4:3/2 4:3/2").tie_across_barlines().is_err());
+    }
+
+    #[test]
+    fn beams() {
+        let beams = |time_str: &str, s: &str| passage(time_str, s).measures().unwrap()[0].beams();
+        assert_eq!(beams("4/4", "8 8 8 8 4 16 16 16 16"), vec![vec![0, 1], vec![2, 3], vec![5, 6, 7, 8]]);
+        assert_eq!(beams("6/8", "8 8 8 8 8 8"), vec![vec![0, 1, 2], vec![3, 4, 5]]);
+        assert_eq!(beams("6/8", "4 8 8. 16 8"), vec![vec![2, 3, 4]]);
+        assert_eq!(beams("2+2+3/8", "8 8 8 8 8 8 8"), vec![vec![0, 1], vec![2, 3], vec![4, 5, 6]]);
+        assert_eq!(beams("3+2+2/8", "8 8 8 8 8 8 8"), vec![vec![0, 1, 2], vec![3, 4], vec![5, 6]]);
+        // rests and notes across beats break the beams
+        assert_eq!(beams("2/4", "8 8r 8 8"), vec![vec![2, 3]]);
+        assert_eq!(beams("2/4", "16 8 8 16"), vec![vec![0, 1]]);
+        assert_eq!(beams("2/4", "8:3/2 8:3/2 8:3/2 4"), vec![vec![0, 1, 2]]);
+    }
+
+    #[test]
+    fn progressions() {
+        let chords: Vec<NoteSequence> = ["C4 E4 G4", "F4 A4 C5", "G4 B4 D5", "C4 E4 G4"].iter().map(|x| x.parse().unwrap()).collect();
+        let progression = TimedSequence::from_progression(&chords, duration("1"), time("2/2"));
+        assert_eq!(progression.validate(), Ok(()));
+        assert_eq!(progression.length(), Rational::integer(4));
+        assert_eq!(progression.measures().unwrap().len(), 4);
+        assert_eq!(progression.notesequence().notes.len(), 12);
+
+        let melody = TimedSequence::from_sequence(&"C4 D4 E4".parse().unwrap(), duration("4"), time("3/4"));
+        assert_eq!(melody.validate(), Ok(()));
+        assert_eq!(melody.events[1], Event::note(Note::from_str("D4").unwrap(), duration("4")));
+    }
+}
@@ -0,0 +1,636 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::accidentals::Accidental;
+use crate::notes::Note;
+use crate::intervals::Interval;
+use crate::notesequences::NoteSequence;
+use crate::chords::{Chord, ChordQuality};
+use crate::diatonic_scales::Mode;
+use crate::keys::Key;
+use crate::errors::HarmonyError;
+
+const NUMERALS: [&str; 7] = ["I", "II", "III", "IV", "V", "VI", "VII"];
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum AugmentedSixth {
+    Italian,
+    French,
+    German,
+}
+
+impl AugmentedSixth {
+    fn interval_strs(self) -> &'static [&'static str] {
+        match self {
+            AugmentedSixth::Italian => &["m6", "P8", "A11"],
+            AugmentedSixth::French => &["m6", "P8", "M9", "A11"],
+            AugmentedSixth::German => &["m6", "P8", "m10", "A11"],
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            AugmentedSixth::Italian => "It+6",
+            AugmentedSixth::French => "Fr+6",
+            AugmentedSixth::German => "Ger+6",
+        }
+    }
+}
+
+impl fmt::Display for AugmentedSixth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum RomanNumeral {
+    Degree {
+        accidental: Accidental,
+        degree: u8,
+        quality: ChordQuality,
+        inversion: usize,
+        target: Option<(u8, bool)>,
+    },
+    Neapolitan {
+        inversion: usize,
+    },
+    AugmentedSixth(AugmentedSixth),
+    CadentialSixFour,
+}
+
+impl RomanNumeral {
+    pub fn analyze(noteseq: &NoteSequence, key: &Key) -> Result<Self, HarmonyError> {
+        let tonic = key.tonic();
+        for sixth in [AugmentedSixth::Italian, AugmentedSixth::French, AugmentedSixth::German] {
+            let tones: Vec<Note> = sixth.interval_strs().iter()
+                .map(|x| tonic.transpose(Interval::from_str(x).unwrap()))
+                .collect::<Result<Vec<Note>, HarmonyError>>()?;
+            if same_spelled_classes(&noteseq.notes, &tones) {
+                return Ok(RomanNumeral::AugmentedSixth(sixth));
+            }
+        }
+
+        let mut chord = Chord::identify(noteseq)?;
+        // sixth chords like F6 are also seventh chords in inversion like Dm7/F
+        if !is_numeral_quality(chord.quality()) {
+            if let Some(seventh) = Chord::identify_seventh(noteseq)? {
+                chord = seventh;
+            }
+        }
+        let inversion = chord.inversion().ok_or(HarmonyError::Unidentified("Slash chords have no Roman numeral"))?;
+        let quality = chord.quality();
+        if !is_numeral_quality(quality) {
+            return Err(HarmonyError::Unidentified("Chord quality has no Roman numeral"));
+        }
+
+        let root = chord.root();
+        let degree = tonic.dist_steps(&root).rem_euclid(7) as u8 + 1;
+        let neapolitan = tonic.transpose(Interval::from_str("m2").unwrap())?;
+        if root.same_spelling(&neapolitan) && quality == ChordQuality::Major {
+            return Ok(RomanNumeral::Neapolitan {inversion});
+        }
+
+        let diatonic = chord.tones().iter().all(|x| is_diatonic(key, x));
+        if !diatonic {
+            if let Some(target) = secondary_target(key, &root, quality)? {
+                return Ok(RomanNumeral::Degree {
+                    accidental: Accidental::Natural,
+                    degree: if is_dominant(quality) {5} else {7},
+                    quality,
+                    inversion,
+                    target: Some(target),
+                });
+            }
+        }
+
+        let scale_note = key.scale().notesequence().notes[degree as usize - 1];
+        let offset = (root.pitch_class() - scale_note.pitch_class() + 6).rem_euclid(12) - 6;
+        let mut accidental = Accidental::from_offset(offset)?;
+        if raises_leading_tone(key, degree, quality) && accidental == Accidental::Sharp {
+            accidental = Accidental::Natural;
+        }
+        Ok(RomanNumeral::Degree {
+            accidental, degree, quality, inversion, target: None,
+        })
+    }
+
+    pub fn analyze_progression(progression: &[NoteSequence], key: &Key) -> Vec<Result<Self, HarmonyError>> {
+        let mut numerals: Vec<Result<Self, HarmonyError>> = progression.iter()
+            .map(|x| Self::analyze(x, key))
+            .collect();
+        for inumeral in 0..numerals.len().saturating_sub(1) {
+            let tonic_six_four = matches!(numerals[inumeral],
+                Ok(RomanNumeral::Degree {accidental: Accidental::Natural, degree: 1, quality: ChordQuality::Major | ChordQuality::Minor, inversion: 2, target: None}));
+            let dominant = matches!(numerals[inumeral+1],
+                Ok(RomanNumeral::Degree {accidental: Accidental::Natural, degree: 5, quality: ChordQuality::Major | ChordQuality::Dominant7, target: None, ..}));
+            if tonic_six_four && dominant {
+                numerals[inumeral] = Ok(RomanNumeral::CadentialSixFour);
+            }
+        }
+        numerals
+    }
+
+    pub fn from_str(s: &str) -> Result<Self, HarmonyError> {
+        match s {
+            "It+6" | "It6" => return Ok(RomanNumeral::AugmentedSixth(AugmentedSixth::Italian)),
+            "Fr+6" | "Fr6" => return Ok(RomanNumeral::AugmentedSixth(AugmentedSixth::French)),
+            "Ger+6" | "Ger6" => return Ok(RomanNumeral::AugmentedSixth(AugmentedSixth::German)),
+            "Cad64" => return Ok(RomanNumeral::CadentialSixFour),
+            _ => (),
+        }
+        if let Some(figure) = s.strip_prefix('N') {
+            return match figure {
+                "" => Ok(RomanNumeral::Neapolitan {inversion: 0}),
+                "6" => Ok(RomanNumeral::Neapolitan {inversion: 1}),
+                "64" => Ok(RomanNumeral::Neapolitan {inversion: 2}),
+                _ => Err(HarmonyError::parse(s, 1, figure, &["", "6", "64"])),
+            };
+        }
+
+        let (numeral_str, target_str) = match s.split_once('/') {
+            Some((numeral_str, target_str)) => (numeral_str, Some(target_str)),
+            None => (s, None),
+        };
+        let (accidental, rest) = match numeral_str.chars().next() {
+            Some('b') => (Accidental::Flat, &numeral_str[1..]),
+            Some('#') => (Accidental::Sharp, &numeral_str[1..]),
+            _ => (Accidental::Natural, numeral_str),
+        };
+        // position of a remainder of numeral_str within s
+        let position = |rest: &str| numeral_str.chars().count() - rest.chars().count();
+        let (degree, minor, rest) = parse_numeral(rest).map_err(|x| x.within(s, position(rest)))?;
+        let mark_start_idx = position(rest);
+
+        let mut chars = rest.chars().peekable();
+        let mark = match chars.peek() {
+            Some(c @ ('o' | '°' | 'ø' | '%' | '+')) => {
+                let c = *c;
+                chars.next();
+                Some(c)
+            },
+            _ => None,
+        };
+        let major_seventh = chars.next_if_eq(&'M').is_some();
+        let figure: String = chars.collect();
+        let figure_start_idx = numeral_str.chars().count() - figure.chars().count();
+        let (seventh, inversion) = match figure.as_str() {
+            "" => (false, 0),
+            "6" => (false, 1),
+            "64" => (false, 2),
+            "7" => (true, 0),
+            "65" => (true, 1),
+            "43" => (true, 2),
+            "42" | "2" => (true, 3),
+            _ => return Err(HarmonyError::parse(s, figure_start_idx, &figure, &["", "6", "64", "7", "65", "43", "42"])),
+        };
+        let quality = match (minor, mark, major_seventh, seventh) {
+            (false, None, false, false) => ChordQuality::Major,
+            (true, None, false, false) => ChordQuality::Minor,
+            (true, Some('o' | '°'), false, false) => ChordQuality::Diminished,
+            (false, Some('+'), false, false) => ChordQuality::Augmented,
+            (false, None, false, true) => ChordQuality::Dominant7,
+            (false, None, true, true) => ChordQuality::Major7,
+            (true, None, false, true) => ChordQuality::Minor7,
+            (true, None, true, true) => ChordQuality::MinorMajor7,
+            (true, Some('ø' | '%'), false, true) => ChordQuality::HalfDiminished7,
+            (true, Some('o' | '°'), false, true) => ChordQuality::Diminished7,
+            (false, Some('+'), false, true) => ChordQuality::Augmented7,
+            (false, Some('+'), true, true) => ChordQuality::AugmentedMajor7,
+            _ => {
+                let found: String = rest.chars().take(figure_start_idx - mark_start_idx).collect();
+                let expected: &[&str] = if minor {&["", "o", "ø", "M"]} else {&["", "+", "M", "+M"]};
+                return Err(HarmonyError::parse(s, mark_start_idx, &found, expected));
+            },
+        };
+
+        let target = match target_str {
+            Some(target_str) => {
+                let target_start_idx = numeral_str.chars().count() + 1;
+                let (target_degree, target_minor, rest) = parse_numeral(target_str)
+                    .map_err(|x| x.within(s, target_start_idx))?;
+                if !rest.is_empty() {
+                    return Err(HarmonyError::parse(s, s.chars().count() - rest.chars().count(), rest, &[]));
+                }
+                Some((target_degree, target_minor))
+            },
+            None => None,
+        };
+        Ok(RomanNumeral::Degree {accidental, degree, quality, inversion, target})
+    }
+
+    pub fn to_str(&self) -> Result<String, HarmonyError> {
+        let (accidental, degree, quality, inversion, target) = match self {
+            RomanNumeral::Degree {accidental, degree, quality, inversion, target} => (accidental, degree, quality, inversion, target),
+            RomanNumeral::Neapolitan {inversion} => return Ok(format!("N{}", triad_figure(*inversion)?)),
+            RomanNumeral::AugmentedSixth(sixth) => return Ok(String::from(sixth.to_str())),
+            RomanNumeral::CadentialSixFour => return Ok(String::from("Cad64")),
+        };
+        if !(1..=7).contains(degree) || target.is_some_and(|(x, _)| !(1..=7).contains(&x)) {
+            return Err(HarmonyError::OutOfRange("Scale degrees range from 1 to 7"));
+        }
+        let mut numeral = String::from(accidental.to_str());
+        numeral.push_str(&numeral_str(*degree, is_minor(*quality)));
+        numeral.push_str(match quality {
+            ChordQuality::Diminished | ChordQuality::Diminished7 => "o",
+            ChordQuality::HalfDiminished7 => "ø",
+            ChordQuality::Augmented | ChordQuality::Augmented7 | ChordQuality::AugmentedMajor7 => "+",
+            _ => "",
+        });
+        if matches!(quality, ChordQuality::Major7 | ChordQuality::MinorMajor7 | ChordQuality::AugmentedMajor7) {
+            numeral.push('M');
+        }
+        if is_seventh(*quality) {
+            numeral.push_str(seventh_figure(*inversion)?);
+        } else {
+            numeral.push_str(triad_figure(*inversion)?);
+        }
+        if let Some((target_degree, target_minor)) = target {
+            numeral.push('/');
+            numeral.push_str(&numeral_str(*target_degree, *target_minor));
+        }
+        Ok(numeral)
+    }
+
+    pub fn to_notes(&self, key: &Key) -> Result<NoteSequence, HarmonyError> {
+        let tonic = key.tonic();
+        let chord = match self {
+            RomanNumeral::AugmentedSixth(sixth) => {
+                let notes = sixth.interval_strs().iter()
+                    .map(|x| tonic.transpose(Interval::from_str(x).unwrap()))
+                    .collect::<Result<Vec<Note>, HarmonyError>>()?;
+                return Ok(NoteSequence {notes});
+            },
+            RomanNumeral::Neapolitan {inversion} => {
+                let root = tonic.transpose(Interval::from_str("m2").unwrap())?;
+                Chord::from_root(root, ChordQuality::Major)?.invert(*inversion)?
+            },
+            RomanNumeral::CadentialSixFour => {
+                let quality = if is_minor_key(key) {ChordQuality::Minor} else {ChordQuality::Major};
+                Chord::from_root(tonic, quality)?.invert(2)?
+            },
+            RomanNumeral::Degree {accidental, degree, quality, inversion, target} => {
+                if !(1..=7).contains(degree) || target.is_some_and(|(x, _)| !(1..=7).contains(&x)) {
+                    return Err(HarmonyError::OutOfRange("Scale degrees range from 1 to 7"));
+                }
+                let root = match target {
+                    Some((target_degree, target_minor)) => {
+                        let target_root = key.scale().notesequence().notes[*target_degree as usize - 1];
+                        let target_key = Key::new(target_root, if *target_minor {Mode::Aeolian} else {Mode::Ionian})?;
+                        let root = target_key.scale().notesequence().notes[*degree as usize - 1];
+                        if raises_leading_tone(&target_key, *degree, *quality) {
+                            root.transpose(Interval::from_str("A1").unwrap())?
+                        } else {
+                            root
+                        }
+                    },
+                    None => key.scale().notesequence().notes[*degree as usize - 1],
+                };
+                let mut offset = accidental.offset();
+                if target.is_none() && raises_leading_tone(key, *degree, *quality) {
+                    offset += 1;
+                }
+                let root = root.set_accidental(Accidental::from_offset(root.accidental().offset() + offset)?);
+                // a plain seventh like IV7 in major is the diatonic major seventh chord
+                let quality = if *quality == ChordQuality::Dominant7 && target.is_none() && *accidental == Accidental::Natural
+                    && diatonic_seventh(key, *degree)? == ChordQuality::Major7 {
+                    ChordQuality::Major7
+                } else {
+                    *quality
+                };
+                Chord::from_root(root, quality)?.invert(*inversion)?
+            },
+        };
+        Ok(chord.notesequence().clone())
+    }
+
+    pub fn realize(s: &str) -> Result<NoteSequence, HarmonyError> {
+        let (numeral_str, key_str) = s.split_once(" in ")
+            .ok_or(HarmonyError::parse(s, s.chars().count(), "", &[" in "]))?;
+        let numeral = Self::from_str(numeral_str.trim_end()).map_err(|x| x.within(s, 0))?;
+        let key = Key::from_str(key_str).map_err(|x| x.within(s, numeral_str.chars().count() + 4))?;
+        numeral.to_notes(&key)
+    }
+}
+
+impl FromStr for RomanNumeral {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str(s)
+    }
+}
+
+impl fmt::Display for RomanNumeral {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.to_str() {
+            Ok(s) => write!(f, "{}", s),
+            // numerals built by hand may be invalid
+            Err(_) => write!(f, "{:?}", self),
+        }
+    }
+}
+
+fn parse_numeral(s: &str) -> Result<(u8, bool, &str), HarmonyError> {
+    for (idegree, numeral) in NUMERALS.iter().enumerate().rev() {
+        for minor in [false, true] {
+            let numeral = if minor {numeral.to_lowercase()} else {numeral.to_string()};
+            if let Some(rest) = s.strip_prefix(numeral.as_str()) {
+                return Ok((idegree as u8 + 1, minor, rest));
+            }
+        }
+    }
+    let found: String = s.chars().take(1).collect();
+    Err(HarmonyError::parse(s, 0, &found, &NUMERALS))
+}
+
+fn triad_figure(inversion: usize) -> Result<&'static str, HarmonyError> {
+    ["", "6", "64"].get(inversion).copied().ok_or(HarmonyError::OutOfRange("Triads have no more than two inversions"))
+}
+
+fn seventh_figure(inversion: usize) -> Result<&'static str, HarmonyError> {
+    ["7", "65", "43", "42"].get(inversion).copied().ok_or(HarmonyError::OutOfRange("Seventh chords have no more than three inversions"))
+}
+
+fn numeral_str(degree: u8, minor: bool) -> String {
+    let numeral = NUMERALS[degree as usize - 1];
+    if minor {numeral.to_lowercase()} else {numeral.to_string()}
+}
+
+fn is_numeral_quality(quality: ChordQuality) -> bool {
+    matches!(quality,
+        ChordQuality::Major | ChordQuality::Minor | ChordQuality::Diminished | ChordQuality::Augmented
+        | ChordQuality::Dominant7 | ChordQuality::Major7 | ChordQuality::Minor7 | ChordQuality::MinorMajor7
+        | ChordQuality::HalfDiminished7 | ChordQuality::Diminished7 | ChordQuality::Augmented7
+        | ChordQuality::AugmentedMajor7)
+}
+
+fn is_minor(quality: ChordQuality) -> bool {
+    matches!(quality,
+        ChordQuality::Minor | ChordQuality::Diminished | ChordQuality::Minor7 | ChordQuality::MinorMajor7
+        | ChordQuality::HalfDiminished7 | ChordQuality::Diminished7)
+}
+
+fn is_seventh(quality: ChordQuality) -> bool {
+    quality.intervals().len() == 4
+}
+
+fn is_dominant(quality: ChordQuality) -> bool {
+    matches!(quality, ChordQuality::Major | ChordQuality::Dominant7)
+}
+
+fn is_minor_key(key: &Key) -> bool {
+    matches!(key.mode(), Mode::Aeolian | Mode::HarmonicMinor | Mode::MelodicMinor)
+}
+
+// In minor keys the diminished chords on the seventh degree are built on the
+// raised leading tone without writing an accidental in front of the numeral.
+fn raises_leading_tone(key: &Key, degree: u8, quality: ChordQuality) -> bool {
+    key.mode() == Mode::Aeolian && degree == 7
+        && matches!(quality, ChordQuality::Diminished | ChordQuality::Diminished7 | ChordQuality::HalfDiminished7)
+}
+
+fn is_diatonic(key: &Key, note: &Note) -> bool {
+    if key.is_diatonic(note) {
+        return true;
+    }
+    // raised sixth and seventh degree of minor keys
+    is_minor_key(key)
+        && [Mode::HarmonicMinor, Mode::MelodicMinor].iter().any(|x| key.parallel(*x).is_ok_and(|y| y.is_diatonic(note)))
+}
+
+fn secondary_target(key: &Key, root: &Note, quality: ChordQuality) -> Result<Option<(u8, bool)>, HarmonyError> {
+    let leading = matches!(quality, ChordQuality::Diminished | ChordQuality::Diminished7 | ChordQuality::HalfDiminished7);
+    if !is_dominant(quality) && !leading {
+        return Ok(None);
+    }
+    for degree in 2..=7u8 {
+        let target_root = key.scale().notesequence().notes[degree as usize - 1];
+        let target_minor = match diatonic_triad(key, degree)? {
+            ChordQuality::Major => false,
+            ChordQuality::Minor => true,
+            _ => continue,
+        };
+        let candidate = if leading {
+            target_root.transpose_down(Interval::from_str("m2").unwrap())?
+        } else {
+            target_root.transpose(Interval::from_str("P5").unwrap())?
+        };
+        if candidate.same_spelling(root) {
+            return Ok(Some((degree, target_minor)));
+        }
+    }
+    Ok(None)
+}
+
+fn diatonic_triad(key: &Key, degree: u8) -> Result<ChordQuality, HarmonyError> {
+    if is_minor_key(key) && degree == 5 {
+        return Ok(ChordQuality::Major);
+    }
+    diatonic_chord(key, degree, &[0, 2, 4])
+}
+
+fn diatonic_seventh(key: &Key, degree: u8) -> Result<ChordQuality, HarmonyError> {
+    diatonic_chord(key, degree, &[0, 2, 4, 6])
+}
+
+fn diatonic_chord(key: &Key, degree: u8, steps: &[usize]) -> Result<ChordQuality, HarmonyError> {
+    let scale = key.scale();
+    let notes = &scale.notesequence().notes;
+    let chord = NoteSequence {
+        notes: steps.iter().map(|x| notes[(degree as usize - 1 + x) % 7]).collect(),
+    };
+    Ok(Chord::identify(&chord)?.quality())
+}
+
+fn same_spelled_classes(notes: &[Note], tones: &[Note]) -> bool {
+    notes.iter().all(|x| tones.iter().any(|y| y.same_spelling(x)))
+        && tones.iter().all(|x| notes.iter().any(|y| y.same_spelling(x)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyze(strs: &[&str], key: &str) -> String {
+        RomanNumeral::analyze(&NoteSequence::from_strs(strs.to_vec()).unwrap(), &Key::from_str(key).unwrap())
+            .unwrap()
+            .to_str()
+            .unwrap()
+    }
+
+    fn realize(s: &str) -> NoteSequence {
+        RomanNumeral::realize(s).unwrap()
+    }
+
+    #[test]
+    fn analyze_diatonic() {
+        assert_eq!(analyze(&["C3", "E3", "G3"], "C"), "I");
+        assert_eq!(analyze(&["F3", "D4", "A4"], "C"), "ii6");
+        assert_eq!(analyze(&["D3", "G3", "B3"], "C"), "V64");
+        assert_eq!(analyze(&["G2", "B3", "D4", "F4"], "C"), "V7");
+        assert_eq!(analyze(&["B2", "D3", "F3", "G3"], "C"), "V65");
+        assert_eq!(analyze(&["D3", "F3", "G3", "B3"], "C"), "V43");
+        assert_eq!(analyze(&["F3", "G3", "B3", "D4"], "C"), "V42");
+        assert_eq!(analyze(&["B2", "D3", "F3", "A3"], "C"), "viiø7");
+        assert_eq!(analyze(&["C3", "E3", "G3", "B3"], "C"), "IM7");
+        assert_eq!(analyze(&["D3", "F3", "A3", "C4"], "C"), "ii7");
+        // not the sixth chords on the bass
+        assert_eq!(analyze(&["F3", "A3", "C4", "D4"], "C"), "ii65");
+        assert_eq!(analyze(&["F3", "Ab3", "C4", "D4"], "c"), "iiø65");
+        assert_eq!(analyze(&["C3", "E3", "G3", "A3"], "C"), "vi65");
+        assert_eq!(analyze(&["Eb3", "G3", "Bb3"], "Bb"), "IV");
+
+        assert_eq!(analyze(&["A2", "C3", "E3"], "a"), "i");
+        assert_eq!(analyze(&["E2", "G#3", "B3"], "a"), "V");
+        assert_eq!(analyze(&["G#2", "B2", "D3", "F3"], "a"), "viio7");
+        assert_eq!(analyze(&["B2", "D3", "F3"], "a"), "iio");
+        assert_eq!(analyze(&["C3", "E3", "G3"], "a"), "III");
+        assert_eq!(analyze(&["G2", "B2", "D3"], "a"), "VII");
+    }
+
+    #[test]
+    fn analyze_chromatic() {
+        assert_eq!(analyze(&["D3", "F#3", "A3", "C4"], "C"), "V7/V");
+        assert_eq!(analyze(&["F#3", "A3", "C4", "D4"], "C"), "V65/V");
+        assert_eq!(analyze(&["E3", "G#3", "B3"], "C"), "V/vi");
+        assert_eq!(analyze(&["A2", "C#3", "E3", "G3"], "C"), "V7/ii");
+        assert_eq!(analyze(&["F#3", "A3", "C4", "Eb4"], "C"), "viio7/V");
+        assert_eq!(analyze(&["C3", "E3", "G3", "Bb3"], "C"), "V7/IV");
+        assert_eq!(analyze(&["Ab2", "C3", "Eb3"], "C"), "bVI");
+        assert_eq!(analyze(&["Bb2", "D3", "F3"], "C"), "bVII");
+        assert_eq!(analyze(&["Eb3", "G3", "Bb3"], "C"), "bIII");
+        assert_eq!(analyze(&["F3", "Ab3", "C4"], "C"), "iv");
+        assert_eq!(analyze(&["F3", "Ab3", "Db4"], "C"), "N6");
+        assert_eq!(analyze(&["D3", "F3", "Bb3"], "a"), "N6");
+        assert_eq!(analyze(&["B2", "D#3", "F#3", "A3"], "a"), "V7/V");
+    }
+
+    #[test]
+    fn analyze_augmented_sixths() {
+        assert_eq!(analyze(&["Ab2", "C4", "F#4"], "C"), "It+6");
+        assert_eq!(analyze(&["Ab2", "C4", "D4", "F#4"], "C"), "Fr+6");
+        assert_eq!(analyze(&["Ab2", "C4", "Eb4", "F#4"], "C"), "Ger+6");
+        assert_eq!(analyze(&["F2", "A3", "C4", "D#4"], "a"), "Ger+6");
+        // a spelled dominant seventh is not a German sixth
+        assert_eq!(analyze(&["Ab2", "C4", "Eb4", "Gb4"], "Db"), "V7");
+    }
+
+    #[test]
+    fn analyze_errors() {
+        let key = Key::from_str("C").unwrap();
+        assert!(RomanNumeral::analyze(&NoteSequence::from_strs(["C3", "E3", "G3", "Bb3", "D4"].to_vec()).unwrap(), &key).is_err());
+        assert!(RomanNumeral::analyze(&NoteSequence::from_strs(["F#2", "C3", "E3", "G3"].to_vec()).unwrap(), &key).is_err());
+        assert!(RomanNumeral::analyze(&NoteSequence::from_strs(["C3", "C#3", "D3"].to_vec()).unwrap(), &key).is_err());
+    }
+
+    #[test]
+    fn analyze_progression() {
+        let key = Key::from_str("C").unwrap();
+        let progression = [
+            NoteSequence::from_strs(["C3", "E3", "G3"].to_vec()).unwrap(),
+            NoteSequence::from_strs(["F3", "A3", "D4"].to_vec()).unwrap(),
+            NoteSequence::from_strs(["G2", "C4", "E4"].to_vec()).unwrap(),
+            NoteSequence::from_strs(["G2", "B3", "D4", "F4"].to_vec()).unwrap(),
+            NoteSequence::from_strs(["C3", "E3", "G3"].to_vec()).unwrap(),
+        ];
+        let numerals: Vec<String> = RomanNumeral::analyze_progression(&progression, &key).iter()
+            .map(|x| x.as_ref().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(numerals, vec!["I", "ii6", "Cad64", "V7", "I"]);
+
+        let progression = [NoteSequence::from_strs(["G2", "C4", "E4"].to_vec()).unwrap(), NoteSequence::from_strs(["F2", "A3", "C4"].to_vec()).unwrap()];
+        let numerals: Vec<String> = RomanNumeral::analyze_progression(&progression, &key).iter()
+            .map(|x| x.as_ref().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(numerals, vec!["I64", "IV"]);
+    }
+
+    #[test]
+    fn from_str() {
+        for s in ["I", "ii6", "V65", "V43", "V42", "viio7", "viiø7", "IM7", "iiø65", "III+", "V7/V",
+                  "V65/ii", "viio7/V", "bVI", "bVII", "#ivo", "N6", "It+6", "Fr+6", "Ger+6", "Cad64"] {
+            assert_eq!(RomanNumeral::from_str(s).unwrap().to_str().unwrap(), s);
+        }
+        assert_eq!(RomanNumeral::from_str("V2").unwrap().to_str().unwrap(), "V42");
+        assert_eq!(RomanNumeral::from_str("vii°7").unwrap().to_str().unwrap(), "viio7");
+
+        assert!(RomanNumeral::from_str("").is_err());
+        assert!(RomanNumeral::from_str("X").is_err());
+        assert!(RomanNumeral::from_str("V5").is_err());
+        assert!(RomanNumeral::from_str("Io").is_err());
+        assert!(RomanNumeral::from_str("ii+").is_err());
+        assert!(RomanNumeral::from_str("V7/x").is_err());
+        assert!(RomanNumeral::from_str("N7").is_err());
+    }
+
+    #[test]
+    fn realize_numerals() {
+        assert_eq!(realize("I in C"), NoteSequence::from_strs(["C3", "E3", "G3"].to_vec()).unwrap());
+        assert_eq!(realize("ii6 in C"), NoteSequence::from_strs(["F3", "A3", "D4"].to_vec()).unwrap());
+        assert_eq!(realize("V7 in Eb"), NoteSequence::from_strs(["Bb3", "D4", "F4", "Ab4"].to_vec()).unwrap());
+        assert_eq!(realize("V65/ii in Bb"), NoteSequence::from_strs(["B3", "D4", "F4", "G4"].to_vec()).unwrap());
+        assert_eq!(realize("viio7 in a"), NoteSequence::from_strs(["G#4", "B4", "D5", "F5"].to_vec()).unwrap());
+        assert_eq!(realize("viio7/V in C"), NoteSequence::from_strs(["F#4", "A4", "C5", "Eb5"].to_vec()).unwrap());
+        assert_eq!(realize("V/V in a"), NoteSequence::from_strs(["B3", "D#4", "F#4"].to_vec()).unwrap());
+        assert_eq!(realize("bVI in C"), NoteSequence::from_strs(["Ab3", "C4", "Eb4"].to_vec()).unwrap());
+        assert_eq!(realize("N6 in C"), NoteSequence::from_strs(["F3", "Ab3", "Db4"].to_vec()).unwrap());
+        assert_eq!(realize("It+6 in C"), NoteSequence::from_strs(["Ab3", "C4", "F#4"].to_vec()).unwrap());
+        assert_eq!(realize("Ger+6 in c"), NoteSequence::from_strs(["Ab3", "C4", "Eb4", "F#4"].to_vec()).unwrap());
+        assert_eq!(realize("Cad64 in G"), NoteSequence::from_strs(["D4", "G4", "B4"].to_vec()).unwrap());
+        // plain sevenths on major seventh chords of the key
+        assert_eq!(realize("IV65 in C"), NoteSequence::from_strs(["A3", "C4", "E4", "F4"].to_vec()).unwrap());
+        assert_eq!(realize("VI7 in a"), NoteSequence::from_strs(["F4", "A4", "C5", "E5"].to_vec()).unwrap());
+        assert_eq!(realize("V7/IV in C"), NoteSequence::from_strs(["C4", "E4", "G4", "Bb4"].to_vec()).unwrap());
+        assert_eq!(realize("bVII7 in C"), NoteSequence::from_strs(["Bb3", "D4", "F4", "Ab4"].to_vec()).unwrap());
+
+        assert!(RomanNumeral::realize("V7 Eb").is_err());
+        assert!(RomanNumeral::realize("V7 in H").is_err());
+        let key = Key::from_str("C").unwrap();
+        for (degree, target) in [(0, None), (8, None), (5, Some((0, false))), (5, Some((8, true)))] {
+            let numeral = RomanNumeral::Degree {accidental: Accidental::Natural, degree, quality: ChordQuality::Major, inversion: 0, target};
+            assert_eq!(numeral.to_notes(&key), Err(HarmonyError::OutOfRange("Scale degrees range from 1 to 7")));
+        }
+    }
+
+    #[test]
+    fn analyze_realize_round_trip() {
+        let major = ["I", "ii6", "IV", "V7", "ii65", "vi65", "IVM65", "V65/V", "viio7/V", "V7/IV", "bVI", "N6", "Ger+6"];
+        let minor = ["i", "iio6", "iv", "V7", "iiø65", "iv65", "V65", "V65/V", "viio7", "VI", "N6", "It+6", "Fr+6"];
+        for (key_str, numerals) in [("C", major), ("Eb", major), ("f#", minor), ("bb", minor)] {
+            let key = Key::from_str(key_str).unwrap();
+            for numeral in numerals {
+                let numeral = RomanNumeral::from_str(numeral).unwrap();
+                let notes = numeral.to_notes(&key).unwrap();
+                assert_eq!(RomanNumeral::analyze(&notes, &key), Ok(numeral.clone()), "{} in {}", numeral, key_str);
+            }
+        }
+    }
+
+    #[test]
+    fn from_str_errors() {
+        assert_eq!(RomanNumeral::from_str("X"), Err(HarmonyError::parse("X", 0, "X", &NUMERALS)));
+        assert_eq!(RomanNumeral::from_str("bX").unwrap_err().position(), Some(1));
+        assert_eq!(RomanNumeral::from_str("V5"), Err(HarmonyError::parse("V5", 1, "5", &["", "6", "64", "7", "65", "43", "42"])));
+        assert_eq!(RomanNumeral::from_str("Io"), Err(HarmonyError::parse("Io", 1, "o", &["", "+", "M", "+M"])));
+        assert_eq!(RomanNumeral::from_str("V7/x").unwrap_err().position(), Some(3));
+        assert_eq!(RomanNumeral::from_str("V7/ii7").unwrap_err().position(), Some(5));
+        assert_eq!(RomanNumeral::realize("V7"), Err(HarmonyError::parse("V7", 2, "", &[" in "])));
+        assert_eq!(RomanNumeral::realize("V7 in H").unwrap_err().position(), Some(6));
+        assert_eq!("V65/ii".parse::<RomanNumeral>().unwrap().to_str().unwrap(), "V65/ii");
+        assert_eq!(format!("{}", RomanNumeral::AugmentedSixth(AugmentedSixth::French)), "Fr+6");
+    }
+
+    #[test]
+    fn to_str_errors() {
+        let degree = |degree, quality, inversion, target| RomanNumeral::Degree {accidental: Accidental::Natural, degree, quality, inversion, target};
+        assert_eq!(degree(0, ChordQuality::Major, 0, None).to_str(), Err(HarmonyError::OutOfRange("Scale degrees range from 1 to 7")));
+        assert_eq!(degree(8, ChordQuality::Major, 0, None).to_str(), Err(HarmonyError::OutOfRange("Scale degrees range from 1 to 7")));
+        assert_eq!(degree(5, ChordQuality::Major, 0, Some((9, false))).to_str(), Err(HarmonyError::OutOfRange("Scale degrees range from 1 to 7")));
+        assert_eq!(degree(5, ChordQuality::Major, 3, None).to_str(), Err(HarmonyError::OutOfRange("Triads have no more than two inversions")));
+        assert_eq!(degree(5, ChordQuality::Dominant7, 4, None).to_str(), Err(HarmonyError::OutOfRange("Seventh chords have no more than three inversions")));
+        assert_eq!(degree(5, ChordQuality::Dominant7, 3, Some((2, true))).to_str(), Ok(String::from("V42/ii")));
+        assert_eq!(RomanNumeral::Neapolitan {inversion: 3}.to_str(), Err(HarmonyError::OutOfRange("Triads have no more than two inversions")));
+        assert_eq!(RomanNumeral::Neapolitan {inversion: 1}.to_str(), Ok(String::from("N6")));
+        assert_eq!(format!("{}", RomanNumeral::Neapolitan {inversion: 3}), "Neapolitan { inversion: 3 }");
+    }
+}
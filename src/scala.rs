@@ -0,0 +1,512 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::errors::HarmonyError;
+use crate::notes::Note;
+use crate::tunings::NoteTuning;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ScalaPitch {
+    Cents(f64),
+    Ratio(u64, u64),
+}
+
+impl ScalaPitch {
+    // a pitch containing a period is in cents, anything else is a ratio
+    pub fn from_str(s: &str) -> Result<Self, HarmonyError> {
+        let start = s.chars().take_while(|x| x.is_whitespace()).count();
+        let token = s.split_whitespace().next().unwrap_or("");
+        let error = |expected: &[&str]| Err(HarmonyError::parse(s, start, token, expected));
+        if token.contains('.') {
+            return match token.parse::<f64>() {
+                Ok(cents) if cents.is_finite() => Ok(ScalaPitch::Cents(cents)),
+                _ => error(&["cents"]),
+            };
+        }
+        let (numerator, denominator) = token.split_once('/').unwrap_or((token, "1"));
+        match (numerator.parse::<u64>(), denominator.parse::<u64>()) {
+            (Ok(0), Ok(_)) | (Ok(_), Ok(0)) => error(&["positive ratio"]),
+            (Ok(numerator), Ok(denominator)) => Ok(ScalaPitch::Ratio(numerator, denominator)),
+            _ => error(&["ratio", "cents"]),
+        }
+    }
+
+    pub fn to_str(self) -> String {
+        match self {
+            // the debug format always keeps the period
+            ScalaPitch::Cents(cents) => format!("{:?}", cents),
+            ScalaPitch::Ratio(numerator, denominator) => format!("{}/{}", numerator, denominator),
+        }
+    }
+
+    pub fn cents(self) -> f64 {
+        match self {
+            ScalaPitch::Cents(cents) => cents,
+            ScalaPitch::Ratio(numerator, denominator) => 1200.0 * (numerator as f64 / denominator as f64).log2(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScalaScale {
+    description: String,
+    // the unison is implicit, the last pitch is the period, an empty scale only
+    // has the unison
+    pitches: Vec<ScalaPitch>,
+}
+
+impl ScalaScale {
+    pub fn new(description: &str, pitches: Vec<ScalaPitch>) -> Result<Self, HarmonyError> {
+        if description.contains('\n') {
+            return Err(HarmonyError::InvalidArgument("Description must be a single line"));
+        }
+        Ok(Self {description: String::from(description), pitches})
+    }
+
+    pub fn from_scl(s: &str) -> Result<Self, HarmonyError> {
+        let mut lines = s.lines().enumerate()
+            .map(|(i, line)| (i + 1, line.trim_end_matches('\r')))
+            .filter(|(_, line)| !line.starts_with('!'));
+
+        let description = match lines.next() {
+            Some((_, line)) => line.trim(),
+            None => return Err(HarmonyError::InvalidArgument("Missing description line")),
+        };
+        let count = match lines.next() {
+            Some((number, line)) => count_from_str(line).map_err(|x| x.at_line(number))?,
+            None => return Err(HarmonyError::InvalidArgument("Missing number of notes")),
+        };
+        let mut pitches = Vec::new();
+        let mut last = 0;
+        for (number, line) in lines {
+            last = number;
+            if pitches.len() == count {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                return Err(HarmonyError::parse(line, 0, line.trim(), &[]).at_line(number));
+            }
+            pitches.push(ScalaPitch::from_str(line).map_err(|x| x.at_line(number))?);
+        }
+        if pitches.len() < count {
+            return Err(HarmonyError::InvalidArgument("Fewer pitches than the number of notes").at_line(last));
+        }
+        Self::new(description, pitches)
+    }
+
+    pub fn to_scl(&self) -> String {
+        let mut s = format!("!\n{}\n {}\n!\n", self.description, self.pitches.len());
+        for pitch in &self.pitches {
+            s += &format!(" {}\n", pitch.to_str());
+        }
+        s
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn pitches(&self) -> &[ScalaPitch] {
+        &self.pitches
+    }
+
+    pub fn len(&self) -> usize {
+        self.pitches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pitches.is_empty()
+    }
+
+    // cents above the unison, degrees beyond the period repeat the scale
+    pub fn cents(&self, degree: i32) -> f64 {
+        if self.pitches.is_empty() {
+            return 0.0;
+        }
+        let len = self.pitches.len() as i32;
+        let period = self.pitches[self.pitches.len() - 1].cents();
+        let step = match degree.rem_euclid(len) {
+            0 => 0.0,
+            step => self.pitches[step as usize - 1].cents(),
+        };
+        degree.div_euclid(len) as f64 * period + step
+    }
+}
+
+// An empty mapping maps the keys linearly onto the scale degrees. Otherwise
+// the mapping repeats every mapping.len() keys, moving up by octave_degree
+// scale degrees, and None marks an unmapped key.
+#[derive(Debug, PartialEq, Clone)]
+pub struct KeyboardMapping {
+    pub first_key: u8,
+    pub last_key: u8,
+    pub middle_key: u8,
+    pub reference_key: u8,
+    pub reference_frequency: f64,
+    pub octave_degree: usize,
+    pub mapping: Vec<Option<usize>>,
+}
+
+impl Default for KeyboardMapping {
+    fn default() -> Self {
+        Self {
+            first_key: 0,
+            last_key: 127,
+            middle_key: 60,
+            reference_key: 69,
+            reference_frequency: 440.0,
+            octave_degree: 0,
+            mapping: Vec::new(),
+        }
+    }
+}
+
+impl KeyboardMapping {
+    pub fn from_kbm(s: &str) -> Result<Self, HarmonyError> {
+        let mut lines = s.lines().enumerate()
+            .map(|(i, line)| (i + 1, line.trim_end_matches('\r')))
+            .filter(|(_, line)| !line.starts_with('!') && !line.trim().is_empty());
+        let mut header = |what: &'static str| lines.next().ok_or(HarmonyError::InvalidArgument(what));
+
+        let (number, line) = header("Missing size of map")?;
+        let size = count_from_str(line).map_err(|x| x.at_line(number))?;
+        let mut keys = [0; 4];
+        for key in keys.iter_mut() {
+            let (number, line) = header("Missing MIDI key")?;
+            *key = key_from_str(line).map_err(|x| x.at_line(number))?;
+        }
+        let (number, line) = header("Missing reference frequency")?;
+        let reference_frequency = frequency_from_str(line).map_err(|x| x.at_line(number))?;
+        let (number, line) = header("Missing formal octave degree")?;
+        let octave_degree = count_from_str(line).map_err(|x| x.at_line(number))?;
+
+        let mut mapping = Vec::new();
+        for (number, line) in lines {
+            if mapping.len() == size {
+                return Err(HarmonyError::parse(line, 0, line.trim(), &[]).at_line(number));
+            }
+            mapping.push(entry_from_str(line).map_err(|x| x.at_line(number))?);
+        }
+        // missing entries at the end are unmapped
+        mapping.resize(size, None);
+
+        let [first_key, last_key, middle_key, reference_key] = keys;
+        let result = Self {first_key, last_key, middle_key, reference_key, reference_frequency, octave_degree, mapping};
+        result.validate()?;
+        Ok(result)
+    }
+
+    pub fn to_kbm(&self) -> String {
+        let mut s = String::new();
+        s += &format!("! Size of map:\n{}\n", self.mapping.len());
+        s += &format!("! First MIDI note number to retune:\n{}\n", self.first_key);
+        s += &format!("! Last MIDI note number to retune:\n{}\n", self.last_key);
+        s += &format!("! Middle note where the first entry of the mapping is mapped to:\n{}\n", self.middle_key);
+        s += &format!("! Reference note for which frequency is given:\n{}\n", self.reference_key);
+        s += &format!("! Frequency to tune the above note to\n{:?}\n", self.reference_frequency);
+        s += &format!("! Scale degree to consider as formal octave:\n{}\n", self.octave_degree);
+        s += "! Mapping.\n";
+        for entry in &self.mapping {
+            match entry {
+                Some(degree) => s += &format!("{}\n", degree),
+                None => s += "x\n",
+            }
+        }
+        s
+    }
+
+    pub fn validate(&self) -> Result<(), HarmonyError> {
+        let keys = [self.first_key, self.last_key, self.middle_key, self.reference_key];
+        if keys.iter().any(|x| *x > 127) {
+            return Err(HarmonyError::OutOfRange("MIDI key must be between 0 and 127"));
+        }
+        if self.first_key > self.last_key {
+            return Err(HarmonyError::InvalidArgument("First key is above the last key"));
+        }
+        if !self.reference_frequency.is_finite() || self.reference_frequency <= 0.0 {
+            return Err(HarmonyError::InvalidArgument("Reference frequency must be positive"));
+        }
+        Ok(())
+    }
+
+    // the scale degree a key plays, None for unmapped keys
+    pub fn degree(&self, key: u8, scale_len: usize) -> Option<i32> {
+        let offset = key as i32 - self.middle_key as i32;
+        if self.mapping.is_empty() {
+            return Some(offset);
+        }
+        let len = self.mapping.len() as i32;
+        let octave_degree = match self.octave_degree {
+            0 => scale_len,
+            degree => degree,
+        };
+        self.mapping[offset.rem_euclid(len) as usize]
+            .map(|x| x as i32 + offset.div_euclid(len) * octave_degree as i32)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScalaTuning {
+    scale: ScalaScale,
+    mapping: KeyboardMapping,
+}
+
+impl ScalaTuning {
+    pub fn new(scale: ScalaScale, mapping: KeyboardMapping) -> Result<Self, HarmonyError> {
+        mapping.validate()?;
+        if mapping.degree(mapping.reference_key, scale.len()).is_none() {
+            return Err(HarmonyError::InvalidArgument("Reference key is unmapped"));
+        }
+        Ok(Self {scale, mapping})
+    }
+
+    pub fn from_files(scl: &str, kbm: &str) -> Result<Self, HarmonyError> {
+        Self::new(ScalaScale::from_scl(scl)?, KeyboardMapping::from_kbm(kbm)?)
+    }
+
+    pub fn scale(&self) -> &ScalaScale {
+        &self.scale
+    }
+
+    pub fn mapping(&self) -> &KeyboardMapping {
+        &self.mapping
+    }
+
+    pub fn key_frequency(&self, key: u8) -> Result<f64, HarmonyError> {
+        if key < self.mapping.first_key || key > self.mapping.last_key {
+            return Err(HarmonyError::OutOfRange("Key is outside of the retuned range"));
+        }
+        let degree = self.mapping.degree(key, self.scale.len())
+            .ok_or(HarmonyError::OutOfRange("Key is unmapped"))?;
+        // checked in new
+        let reference = self.mapping.degree(self.mapping.reference_key, self.scale.len()).unwrap();
+        let cents = self.scale.cents(degree) - self.scale.cents(reference);
+        Ok(self.mapping.reference_frequency * 2.0_f64.powf(cents / 1200.0))
+    }
+
+    pub fn frequency(&self, note: &Note) -> Result<f64, HarmonyError> {
+        self.key_frequency(note.to_midi()?)
+    }
+}
+
+impl NoteTuning for ScalaTuning {
+    fn note_frequency(&self, note: &Note) -> Result<f64, HarmonyError> {
+        self.frequency(note)
+    }
+}
+
+fn count_from_str(s: &str) -> Result<usize, HarmonyError> {
+    let start = s.chars().take_while(|x| x.is_whitespace()).count();
+    let token = s.split_whitespace().next().unwrap_or("");
+    token.parse::<usize>().map_err(|_| HarmonyError::parse(s, start, token, &["count"]))
+}
+
+fn key_from_str(s: &str) -> Result<u8, HarmonyError> {
+    let start = s.chars().take_while(|x| x.is_whitespace()).count();
+    let token = s.split_whitespace().next().unwrap_or("");
+    token.parse::<u8>().ok()
+        .filter(|x| *x <= 127)
+        .ok_or(HarmonyError::parse(s, start, token, &["MIDI key"]))
+}
+
+fn frequency_from_str(s: &str) -> Result<f64, HarmonyError> {
+    let start = s.chars().take_while(|x| x.is_whitespace()).count();
+    let token = s.split_whitespace().next().unwrap_or("");
+    token.parse::<f64>().ok()
+        .filter(|x| x.is_finite() && *x > 0.0)
+        .ok_or(HarmonyError::parse(s, start, token, &["frequency"]))
+}
+
+fn entry_from_str(s: &str) -> Result<Option<usize>, HarmonyError> {
+    let start = s.chars().take_while(|x| x.is_whitespace()).count();
+    let token = s.split_whitespace().next().unwrap_or("");
+    match token {
+        "x" | "X" => Ok(None),
+        _ => token.parse::<usize>().map(Some).map_err(|_| HarmonyError::parse(s, start, token, &["scale degree", "x"])),
+    }
+}
+
+impl FromStr for ScalaPitch {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str(s)
+    }
+}
+
+impl fmt::Display for ScalaPitch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tunings::{Tuning, Temperament};
+
+    const PYTHAGOREAN: &str = include_str!("../scala/pythagorean.scl");
+    const MEANTONE: &str = include_str!("../scala/meantone.scl");
+    const BOHLEN_PIERCE: &str = include_str!("../scala/bohlen_pierce.scl");
+    const A440: &str = include_str!("../scala/a440.kbm");
+    const WHITE_KEYS: &str = include_str!("../scala/white_keys.kbm");
+
+    fn assert_close(left: f64, right: f64) {
+        assert!((left - right).abs() < 1e-4, "{} != {}", left, right);
+    }
+
+    #[test]
+    fn pitches() {
+        assert_eq!(ScalaPitch::from_str(" 3/2"), Ok(ScalaPitch::Ratio(3, 2)));
+        assert_eq!(ScalaPitch::from_str("2"), Ok(ScalaPitch::Ratio(2, 1)));
+        assert_eq!(ScalaPitch::from_str("701.955 fifth"), Ok(ScalaPitch::Cents(701.955)));
+        assert_eq!(ScalaPitch::from_str("-5.0"), Ok(ScalaPitch::Cents(-5.0)));
+        assert_eq!(ScalaPitch::from_str("100."), Ok(ScalaPitch::Cents(100.0)));
+        assert_eq!(ScalaPitch::from_str(" 3/x"), Err(HarmonyError::parse(" 3/x", 1, "3/x", &["ratio", "cents"])));
+        assert_eq!(ScalaPitch::from_str("1.2.3"), Err(HarmonyError::parse("1.2.3", 0, "1.2.3", &["cents"])));
+        assert_eq!(ScalaPitch::from_str("3/0"), Err(HarmonyError::parse("3/0", 0, "3/0", &["positive ratio"])));
+        assert_eq!(ScalaPitch::from_str("-3/2"), Err(HarmonyError::parse("-3/2", 0, "-3/2", &["ratio", "cents"])));
+        assert_eq!(ScalaPitch::from_str(""), Err(HarmonyError::parse("", 0, "", &["ratio", "cents"])));
+
+        assert_eq!(ScalaPitch::Cents(100.0).to_str(), "100.0");
+        assert_eq!(ScalaPitch::Ratio(3, 2).to_str(), "3/2");
+        assert_close(ScalaPitch::Ratio(2, 1).cents(), 1200.0);
+        assert_close(ScalaPitch::Ratio(3, 2).cents(), 701.955001);
+    }
+
+    #[test]
+    fn read_scl() {
+        let scale = ScalaScale::from_scl(PYTHAGOREAN).unwrap();
+        assert_eq!(scale.description(), "12-tone Pythagorean scale");
+        assert_eq!(scale.len(), 12);
+        assert_eq!(scale.pitches()[6], ScalaPitch::Ratio(3, 2));
+        assert_close(scale.cents(12), 1200.0);
+        assert_close(scale.cents(-12), -1200.0);
+        assert_close(scale.cents(19), 1901.955001);
+
+        let scale = ScalaScale::from_scl(MEANTONE).unwrap();
+        assert_eq!(scale.pitches()[0], ScalaPitch::Cents(76.049));
+        assert_eq!(scale.pitches()[11], ScalaPitch::Ratio(2, 1));
+
+        let scale = ScalaScale::from_scl(BOHLEN_PIERCE).unwrap();
+        assert_eq!(scale.len(), 13);
+        assert_eq!(scale.pitches()[12], ScalaPitch::Ratio(3, 1));
+
+        let scale = ScalaScale::from_scl("unison\n 0\n").unwrap();
+        assert!(scale.is_empty());
+        assert_eq!(scale.to_scl(), "!\nunison\n 0\n!\n");
+        assert_close(scale.cents(5), 0.0);
+        assert_close(scale.cents(-3), 0.0);
+    }
+
+    #[test]
+    fn scl_errors() {
+        assert_eq!(ScalaScale::from_scl("! only comments\n"), Err(HarmonyError::InvalidArgument("Missing description line")));
+        assert_eq!(ScalaScale::from_scl("desc\n"), Err(HarmonyError::InvalidArgument("Missing number of notes")));
+        assert_eq!(ScalaScale::from_scl("desc\n twelve\n"),
+            Err(HarmonyError::parse(" twelve", 1, "twelve", &["count"]).at_line(2)));
+        assert_eq!(ScalaScale::from_scl("!\ndesc\n 2\n!\n 3/2\n 2/x\n"),
+            Err(HarmonyError::parse(" 2/x", 1, "2/x", &["ratio", "cents"]).at_line(6)));
+        assert_eq!(ScalaScale::from_scl("desc\n 2\n 1.2.3\n 2/1\n"),
+            Err(HarmonyError::parse(" 1.2.3", 1, "1.2.3", &["cents"]).at_line(3)));
+        assert_eq!(ScalaScale::from_scl("desc\n 3\n 3/2\n 2/1\n"),
+            Err(HarmonyError::InvalidArgument("Fewer pitches than the number of notes").at_line(4)));
+        assert_eq!(ScalaScale::from_scl("desc\n 1\n 2/1\n 3/1\n"),
+            Err(HarmonyError::parse(" 3/1", 0, "3/1", &[]).at_line(4)));
+        assert!(ScalaScale::from_scl("desc\n 1\n 2/1\n\n").is_ok());
+    }
+
+    #[test]
+    fn read_kbm() {
+        let mapping = KeyboardMapping::from_kbm(A440).unwrap();
+        assert_eq!(mapping.mapping.len(), 12);
+        assert_eq!(mapping.reference_key, 69);
+        assert_eq!(mapping.degree(60, 12), Some(0));
+        assert_eq!(mapping.degree(73, 12), Some(13));
+        assert_eq!(mapping.degree(47, 12), Some(-13));
+
+        let mapping = KeyboardMapping::from_kbm(WHITE_KEYS).unwrap();
+        assert_eq!((mapping.first_key, mapping.last_key), (21, 108));
+        assert_eq!(mapping.octave_degree, 7);
+        assert_eq!(mapping.degree(61, 7), None);
+        assert_eq!(mapping.degree(71, 7), Some(6));
+        assert_eq!(mapping.degree(72, 7), Some(7));
+        assert_eq!(mapping.degree(57, 7), Some(-2));
+
+        // missing entries are unmapped
+        let mapping = KeyboardMapping::from_kbm("3\n0\n127\n60\n60\n261.6\n3\n0\n1\n").unwrap();
+        assert_eq!(mapping.mapping, [Some(0), Some(1), None].to_vec());
+    }
+
+    #[test]
+    fn kbm_errors() {
+        assert_eq!(KeyboardMapping::from_kbm("12\n0\n127\n60\n"), Err(HarmonyError::InvalidArgument("Missing MIDI key")));
+        assert_eq!(KeyboardMapping::from_kbm("12\n0\n128\n60\n69\n440\n12\n"),
+            Err(HarmonyError::parse("128", 0, "128", &["MIDI key"]).at_line(3)));
+        assert_eq!(KeyboardMapping::from_kbm("! size\n0\n0\n127\n60\n69\n-440\n12\n"),
+            Err(HarmonyError::parse("-440", 0, "-440", &["frequency"]).at_line(7)));
+        assert_eq!(KeyboardMapping::from_kbm("2\n0\n127\n60\n69\n440\n12\n0\ny\n"),
+            Err(HarmonyError::parse("y", 0, "y", &["scale degree", "x"]).at_line(9)));
+        assert_eq!(KeyboardMapping::from_kbm("1\n0\n127\n60\n69\n440\n12\n0\n1\n"),
+            Err(HarmonyError::parse("1", 0, "1", &[]).at_line(9)));
+        assert_eq!(KeyboardMapping::from_kbm("0\n100\n20\n60\n69\n440\n12\n"),
+            Err(HarmonyError::InvalidArgument("First key is above the last key")));
+    }
+
+    #[test]
+    fn round_trip() {
+        for scl in [PYTHAGOREAN, MEANTONE, BOHLEN_PIERCE] {
+            let scale = ScalaScale::from_scl(scl).unwrap();
+            assert_eq!(ScalaScale::from_scl(&scale.to_scl()), Ok(scale.clone()));
+            assert_eq!(ScalaScale::from_scl(&scale.to_scl()).unwrap().to_scl(), scale.to_scl());
+        }
+        for kbm in [A440, WHITE_KEYS] {
+            let mapping = KeyboardMapping::from_kbm(kbm).unwrap();
+            assert_eq!(KeyboardMapping::from_kbm(&mapping.to_kbm()), Ok(mapping.clone()));
+        }
+        let mapping = KeyboardMapping::default();
+        assert_eq!(KeyboardMapping::from_kbm(&mapping.to_kbm()), Ok(mapping));
+
+        let scale = ScalaScale::new("cents", [ScalaPitch::Cents(350.123456789), ScalaPitch::Ratio(2, 1)].to_vec()).unwrap();
+        assert_eq!(scale.to_scl(), "!\ncents\n 2\n!\n 350.123456789\n 2/1\n");
+        assert_eq!(ScalaScale::from_scl(&scale.to_scl()), Ok(scale));
+    }
+
+    #[test]
+    fn frequencies() {
+        let pythagorean = ScalaTuning::from_files(PYTHAGOREAN, A440).unwrap();
+        let tuning = Tuning::new(Temperament::Pythagorean, 440.0, Note::from_str("C4").unwrap()).unwrap();
+        for s in ["C4", "D4", "E4", "F4", "G4", "Ab4", "A4", "Bb4", "C5", "A2", "Eb6"] {
+            assert_close(pythagorean.frequency(&Note::from_str(s).unwrap()).unwrap(), tuning.frequency(&Note::from_str(s).unwrap()));
+        }
+
+        let meantone = ScalaTuning::from_files(MEANTONE, A440).unwrap();
+        let tuning = Tuning::new(Temperament::QuarterCommaMeantone, 440.0, Note::from_str("C4").unwrap()).unwrap();
+        for s in ["C4", "C#4", "D4", "Eb4", "E4", "F4", "F#4", "G4", "G#4", "A4", "Bb4", "B4"] {
+            assert_close(meantone.frequency(&Note::from_str(s).unwrap()).unwrap(), tuning.frequency(&Note::from_str(s).unwrap()));
+        }
+
+        let bohlen_pierce = ScalaTuning::new(ScalaScale::from_scl(BOHLEN_PIERCE).unwrap(), KeyboardMapping::default()).unwrap();
+        assert_close(bohlen_pierce.key_frequency(69).unwrap(), 440.0);
+        assert_close(bohlen_pierce.key_frequency(73).unwrap() / bohlen_pierce.key_frequency(60).unwrap(), 3.0);
+        assert_close(bohlen_pierce.key_frequency(62).unwrap() / bohlen_pierce.key_frequency(60).unwrap(), 25.0/21.0);
+
+        let scl = "just major\n 7\n 9/8\n 5/4\n 4/3\n 3/2\n 5/3\n 15/8\n 2/1\n";
+        let white_keys = ScalaTuning::from_files(scl, WHITE_KEYS).unwrap();
+        assert_close(white_keys.frequency(&Note::from_str("C4").unwrap()).unwrap(), 261.625565);
+        assert_close(white_keys.frequency(&Note::from_str("E4").unwrap()).unwrap(), 261.625565 * 5.0/4.0);
+        assert_close(white_keys.frequency(&Note::from_str("A3").unwrap()).unwrap(), 261.625565 * 5.0/6.0);
+        assert_close(white_keys.frequency(&Note::from_str("B#4").unwrap()).unwrap(), 523.25113);
+        assert_eq!(white_keys.frequency(&Note::from_str("C#4").unwrap()), Err(HarmonyError::OutOfRange("Key is unmapped")));
+        assert_eq!(white_keys.frequency(&Note::from_str("C0").unwrap()), Err(HarmonyError::OutOfRange("Key is outside of the retuned range")));
+        assert!(white_keys.frequency(&Note::from_str("C10").unwrap()).is_err());
+
+        let unison = ScalaTuning::new(ScalaScale::new("unison", Vec::new()).unwrap(), KeyboardMapping::default()).unwrap();
+        assert_close(unison.key_frequency(40).unwrap(), 440.0);
+        assert_close(unison.key_frequency(100).unwrap(), 440.0);
+
+        let mapping = KeyboardMapping {reference_key: 61, ..KeyboardMapping::from_kbm(WHITE_KEYS).unwrap()};
+        assert_eq!(ScalaTuning::new(ScalaScale::from_scl(scl).unwrap(), mapping),
+            Err(HarmonyError::InvalidArgument("Reference key is unmapped")));
+    }
+}
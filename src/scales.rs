@@ -0,0 +1,296 @@
+use std::fmt;
+use crate::notes::Note;
+use crate::accidentals::Accidental;
+use crate::intervals::{Interval, Quality};
+use crate::notesequences::NoteSequence;
+use crate::diatonic_scales::Mode;
+use crate::errors::HarmonyError;
+
+const CATALOG: [(&str, &[&str]); 12] = [
+    ("major pentatonic",      &["P1", "M2", "M3", "P5", "M6"]),
+    ("minor pentatonic",      &["P1", "m3", "P4", "P5", "m7"]),
+    ("blues",                 &["P1", "m3", "P4", "d5", "P5", "m7"]),
+    ("major blues",           &["P1", "M2", "m3", "M3", "P5", "M6"]),
+    ("whole tone",            &["P1", "M2", "M3", "A4", "A5", "A6"]),
+    ("half-whole diminished", &["P1", "m2", "m3", "M3", "A4", "P5", "M6", "m7"]),
+    ("whole-half diminished", &["P1", "M2", "m3", "P4", "d5", "m6", "M6", "M7"]),
+    ("bebop dominant",        &["P1", "M2", "M3", "P4", "P5", "M6", "m7", "M7"]),
+    ("bebop major",           &["P1", "M2", "M3", "P4", "P5", "A5", "M6", "M7"]),
+    ("bebop dorian",          &["P1", "M2", "m3", "M3", "P4", "P5", "M6", "m7"]),
+    ("chromatic",             &["P1", "A1", "M2", "A2", "M3", "P4", "A4", "P5", "A5", "M6", "A6", "M7"]),
+    ("octatonic",             &["P1", "m2", "m3", "M3", "A4", "P5", "M6", "m7"]),
+];
+
+const ALIASES: [(&str, &str); 4] = [
+    ("major", "Ionian"),
+    ("natural minor", "Aeolian"),
+    ("minor", "Aeolian"),
+    ("super locrian", "Altered"),
+];
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Scale {
+    tonic: Note,
+    name: String,
+    notesequence: NoteSequence,
+}
+
+impl Scale {
+    pub fn from_intervals(tonic: Note, name: &str, intervals: &[Interval]) -> Result<Self, HarmonyError> {
+        if intervals.first().map(|x| x.hsteps()) != Some(0) {
+            return Err(HarmonyError::InvalidArgument("Scale pattern must start on the tonic"));
+        }
+        if intervals.windows(2).any(|x| x[0].hsteps() >= x[1].hsteps()) {
+            return Err(HarmonyError::InvalidArgument("Scale pattern must be strictly ascending"));
+        }
+        let spelled: Result<Vec<Note>, HarmonyError> = intervals.iter().map(|x| tonic.transpose(*x)).collect();
+        let notes = match spelled {
+            Ok(notes) => notes,
+            // fall back to a generic spelling if the pattern is unspellable from this tonic
+            Err(_) => spell(tonic, &intervals.iter().map(|x| x.hsteps()).collect::<Vec<i32>>())?,
+        };
+        Ok(Self {
+            tonic, name: String::from(name), notesequence: NoteSequence {notes},
+        })
+    }
+
+    pub fn from_hsteps(tonic: Note, name: &str, dists: &[i32]) -> Result<Self, HarmonyError> {
+        if dists.iter().any(|x| *x <= 0) {
+            return Err(HarmonyError::InvalidArgument("Scale pattern must be strictly ascending"));
+        }
+        let mut hsteps = vec![0];
+        for dist in dists {
+            hsteps.push(hsteps.last().unwrap() + dist);
+        }
+        Ok(Self {
+            tonic, name: String::from(name), notesequence: NoteSequence {notes: spell(tonic, &hsteps)?},
+        })
+    }
+
+    pub fn from_mode(tonic: Note, mode: Mode) -> Result<Self, HarmonyError> {
+        let mut intervals = vec![Interval::from_steps(0, 0)?];
+        let mut hsteps = 0;
+        for (steps, dist) in mode.get_dists().iter().enumerate() {
+            hsteps += dist;
+            intervals.push(Interval::from_steps(steps as i32 + 1, hsteps)?);
+        }
+        Self::from_intervals(tonic, mode.to_str(), &intervals)
+    }
+
+    pub fn from_name(tonic: Note, input: &str) -> Result<Self, HarmonyError> {
+        let name = input.trim().to_lowercase();
+        let name = ALIASES.iter()
+            .find(|(alias, _)| *alias == name)
+            .map_or(name.clone(), |(_, target)| target.to_lowercase());
+        if let Some(mode) = Mode::ALL.iter().find(|x| x.to_str().to_lowercase() == name) {
+            return Self::from_mode(tonic, *mode);
+        }
+        match CATALOG.iter().find(|(x, _)| *x == name) {
+            Some((name, interval_strs)) => {
+                let intervals: Vec<Interval> = interval_strs.iter().map(|x| Interval::from_str(x).unwrap()).collect();
+                Self::from_intervals(tonic, name, &intervals)
+            },
+            None => Err(HarmonyError::parse(input, 0, input, &Self::catalog_names())),
+        }
+    }
+
+    pub fn catalog_names() -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = Mode::ALL.iter().map(|x| x.to_str()).collect();
+        names.extend(CATALOG.iter().map(|(x, _)| *x));
+        names
+    }
+
+    pub fn tonic(&self) -> Note {
+        self.tonic
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn notesequence(&self) -> &NoteSequence {
+        &self.notesequence
+    }
+
+    pub fn contains(&self, note: &Note) -> bool {
+        self.notesequence.notes.iter().any(|x| x.pitch_class() == note.pitch_class())
+    }
+}
+
+// Chooses a spelling for every pitch (given in half steps above the tonic)
+// by minimizing accidentals and augmented or diminished steps between
+// neighbouring notes, including the step back to the octave.
+fn spell(tonic: Note, hsteps: &[i32]) -> Result<Vec<Note>, HarmonyError> {
+    if hsteps.first() != Some(&0) {
+        return Err(HarmonyError::InvalidArgument("Scale pattern must start on the tonic"));
+    }
+    let octave = Interval::from_str("P8").unwrap();
+    let candidates = |hstep: i32| -> Vec<Note> {
+        let mut notes = Vec::new();
+        for steps in 0..=(hstep/12 + 1)*7 {
+            let natural = tonic.shift_natural(steps);
+            if let Ok(accidental) = Accidental::from_offset(hstep - tonic.dist_hsteps(&natural)) {
+                notes.push(natural.set_accidental(accidental));
+            }
+        }
+        notes
+    };
+
+    // every layer holds (note, accumulated cost, index of predecessor)
+    let mut layers: Vec<Vec<(Note, i32, usize)>> = vec![vec![(tonic, 0, 0)]];
+    for hstep in &hsteps[1..] {
+        let mut layer = Vec::new();
+        for note in candidates(*hstep) {
+            let best = layers.last().unwrap().iter().enumerate()
+                .filter_map(|(iprev, (prev, cost, _))| step_cost(prev, &note).map(|x| (cost + x, iprev)))
+                .min_by_key(|(cost, _)| *cost);
+            if let Some((cost, iprev)) = best {
+                layer.push((note, cost + note.accidental().offset().abs(), iprev));
+            }
+        }
+        if layer.is_empty() {
+            return Err(HarmonyError::OutOfRange("Unable to spell scale"));
+        }
+        layers.push(layer);
+    }
+
+    let closing = tonic.transpose(octave)?;
+    let (mut inote, _) = layers.last().unwrap().iter().enumerate()
+        .map(|(inote, (note, cost, _))| (inote, cost + step_cost(note, &closing).unwrap_or(0)))
+        .min_by_key(|(_, cost)| *cost)
+        .unwrap();
+    let mut notes = Vec::new();
+    for layer in layers.iter().rev() {
+        notes.push(layer[inote].0);
+        inote = layer[inote].2;
+    }
+    notes.reverse();
+    Ok(notes)
+}
+
+fn step_cost(prev: &Note, note: &Note) -> Option<i32> {
+    let steps = prev.dist_steps(note);
+    if steps < 0 || prev.dist_hsteps(note) <= 0 {
+        return None;
+    }
+    let mut cost = match Interval::between(prev, note).ok()?.quality() {
+        Quality::Augmented(n) | Quality::Diminished(n) => 2*n as i32,
+        _ => 0,
+    };
+    if steps == 0 {
+        cost += 1;
+    }
+    if prev.accidental().offset()*note.accidental().offset() < 0 {
+        cost += 1;
+    }
+    Some(cost)
+}
+
+impl fmt::Display for Scale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.notesequence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scale_notes(tonic: &str, name: &str) -> NoteSequence {
+        Scale::from_name(Note::from_str(tonic).unwrap(), name).unwrap().notesequence
+    }
+
+    #[test]
+    fn from_name() {
+        assert_eq!(scale_notes("C4", "major pentatonic"), NoteSequence::from_strs(["C4", "D4", "E4", "G4", "A4"].to_vec()).unwrap());
+        assert_eq!(scale_notes("A3", "minor pentatonic"), NoteSequence::from_strs(["A3", "C4", "D4", "E4", "G4"].to_vec()).unwrap());
+        assert_eq!(scale_notes("C4", "blues"), NoteSequence::from_strs(["C4", "Eb4", "F4", "Gb4", "G4", "Bb4"].to_vec()).unwrap());
+        assert_eq!(scale_notes("C4", "whole tone"), NoteSequence::from_strs(["C4", "D4", "E4", "F#4", "G#4", "A#4"].to_vec()).unwrap());
+        assert_eq!(scale_notes("C4", "half-whole diminished"), NoteSequence::from_strs(["C4", "Db4", "Eb4", "E4", "F#4", "G4", "A4", "Bb4"].to_vec()).unwrap());
+        assert_eq!(scale_notes("C4", "whole-half diminished"), NoteSequence::from_strs(["C4", "D4", "Eb4", "F4", "Gb4", "Ab4", "A4", "B4"].to_vec()).unwrap());
+        assert_eq!(scale_notes("G3", "bebop dominant"), NoteSequence::from_strs(["G3", "A3", "B3", "C4", "D4", "E4", "F4", "F#4"].to_vec()).unwrap());
+        assert_eq!(scale_notes("C4", "chromatic"), NoteSequence::from_strs(["C4", "C#4", "D4", "D#4", "E4", "F4", "F#4", "G4", "G#4", "A4", "A#4", "B4"].to_vec()).unwrap());
+        assert_eq!(scale_notes("Eb4", "Major"), NoteSequence::from_strs(["Eb4", "F4", "G4", "Ab4", "Bb4", "C5", "D5"].to_vec()).unwrap());
+        assert_eq!(scale_notes("E4", "Phrygian dominant"), NoteSequence::from_strs(["E4", "F4", "G#4", "A4", "B4", "C5", "D5"].to_vec()).unwrap());
+        assert_eq!(scale_notes("D4", " natural minor "), NoteSequence::from_strs(["D4", "E4", "F4", "G4", "A4", "Bb4", "C5"].to_vec()).unwrap());
+
+        assert!(Scale::from_name(Note::from_str("C4").unwrap(), "hungarian gypsy").is_err());
+        assert_eq!(Scale::from_name(Note::from_str("C4").unwrap(), "BLUES").unwrap().name(), "blues");
+    }
+
+    #[test]
+    fn unspellable_patterns_fall_back() {
+        // G## whole tone would need triple sharps
+        let scale = Scale::from_name(Note::from_str("G##3").unwrap(), "whole tone").unwrap();
+        assert_eq!(scale.notesequence.notes.len(), 6);
+        let tonic = scale.tonic();
+        let hsteps: Vec<i32> = scale.notesequence.notes.iter().map(|x| tonic.dist_hsteps(x)).collect();
+        assert_eq!(hsteps, vec![0, 2, 4, 6, 8, 10]);
+
+        // modes that DiatonicScale::from_tonic can not spell
+        let scale = Scale::from_mode(Note::from_str("Cb3").unwrap(), Mode::Ultralocrian).unwrap();
+        let tonic = scale.tonic();
+        let hsteps: Vec<i32> = scale.notesequence.notes.iter().map(|x| tonic.dist_hsteps(x)).collect();
+        assert_eq!(hsteps, vec![0, 1, 3, 4, 6, 8, 9]);
+    }
+
+    #[test]
+    fn from_hsteps() {
+        let scale = |tonic: &str, dists: &[i32]| Scale::from_hsteps(Note::from_str(tonic).unwrap(), "custom", dists).unwrap().notesequence;
+        assert_eq!(scale("C4", &[2, 2, 1, 2, 2, 2]), NoteSequence::from_strs(["C4", "D4", "E4", "F4", "G4", "A4", "B4"].to_vec()).unwrap());
+        assert_eq!(scale("F4", &[2, 2, 1, 2, 2, 2]), NoteSequence::from_strs(["F4", "G4", "A4", "Bb4", "C5", "D5", "E5"].to_vec()).unwrap());
+        assert_eq!(scale("C4", &[2, 2, 3, 2]), NoteSequence::from_strs(["C4", "D4", "E4", "G4", "A4"].to_vec()).unwrap());
+        assert_eq!(scale("Eb4", &[2, 2, 3, 2]), NoteSequence::from_strs(["Eb4", "F4", "G4", "Bb4", "C5"].to_vec()).unwrap());
+        assert_eq!(scale("C4", &[2, 2, 2, 2, 2]), NoteSequence::from_strs(["C4", "D4", "E4", "F#4", "G#4", "A#4"].to_vec()).unwrap());
+        assert_eq!(scale("C4", &[1, 3, 1, 2, 1, 3]), NoteSequence::from_strs(["C4", "Db4", "E4", "F4", "G4", "Ab4", "B4"].to_vec()).unwrap());
+        assert_eq!(scale("C4", &[14]), NoteSequence::from_strs(["C4", "D5"].to_vec()).unwrap());
+
+        assert!(Scale::from_hsteps(Note::from_str("C4").unwrap(), "custom", &[2, 0, 3]).is_err());
+        assert!(Scale::from_hsteps(Note::from_str("C4").unwrap(), "custom", &[2, -1]).is_err());
+    }
+
+    #[test]
+    fn from_intervals() {
+        let intervals: Vec<Interval> = ["P1", "m3", "P4", "A4", "P5", "m7"].iter().map(|x| Interval::from_str(x).unwrap()).collect();
+        let scale = Scale::from_intervals(Note::from_str("A3").unwrap(), "my blues", &intervals).unwrap();
+        assert_eq!(scale.notesequence, NoteSequence::from_strs(["A3", "C4", "D4", "D#4", "E4", "G4"].to_vec()).unwrap());
+        assert_eq!(scale.name(), "my blues");
+
+        let intervals: Vec<Interval> = ["M2", "M3"].iter().map(|x| Interval::from_str(x).unwrap()).collect();
+        assert!(Scale::from_intervals(Note::from_str("A3").unwrap(), "no tonic", &intervals).is_err());
+        let intervals: Vec<Interval> = ["P1", "M3", "M2"].iter().map(|x| Interval::from_str(x).unwrap()).collect();
+        assert!(Scale::from_intervals(Note::from_str("A3").unwrap(), "descending", &intervals).is_err());
+    }
+
+    #[test]
+    fn contains() {
+        let scale = Scale::from_name(Note::from_str("C4").unwrap(), "blues").unwrap();
+        assert!(scale.contains(&Note::from_str("Eb2").unwrap()));
+        assert!(scale.contains(&Note::from_str("F#5").unwrap()));
+        assert!(!scale.contains(&Note::from_str("E4").unwrap()));
+    }
+
+    #[test]
+    fn catalog_names() {
+        let names = Scale::catalog_names();
+        assert!(names.contains(&"Ionian"));
+        assert!(names.contains(&"Altered"));
+        assert!(names.contains(&"whole tone"));
+        for name in names {
+            assert!(Scale::from_name(Note::from_str("C4").unwrap(), name).is_ok());
+        }
+    }
+
+    #[test]
+    fn from_name_errors() {
+        let tonic = Note::from_str("C4").unwrap();
+        match Scale::from_name(tonic, "Hungarian gypsy punk") {
+            Err(HarmonyError::Parse {found, expected, ..}) => {
+                assert_eq!(found, "Hungarian gypsy punk");
+                assert_eq!(expected.len(), Scale::catalog_names().len());
+            },
+            _ => panic!("expected a parse error"),
+        }
+        assert_eq!(format!("{}", Scale::from_name(tonic, "major pentatonic").unwrap()), "C4 D4 E4 G4 A4");
+    }
+}
@@ -0,0 +1,541 @@
+use std::fmt;
+use crate::notenames::NoteName;
+use crate::accidentals::Accidental;
+use crate::notes::Note;
+use crate::notesequences::NoteSequence;
+use crate::keys::KeySignature;
+use crate::clefs::Clef;
+
+// Layout is measured in staff spaces, the distance between two staff lines,
+// with y growing downwards like on screen.
+pub const NOTEHEAD_WIDTH: f32 = 1.3;
+pub const ACCIDENTAL_WIDTH: f32 = 1.0;
+pub const STEM_LENGTH: f32 = 3.5;
+const CLEF_WIDTH: f32 = 3.0;
+const LEDGER_OVERHANG: f32 = 0.35;
+const COLUMN_GAP: f32 = 1.5;
+// between the bottom line of the treble and the top line of the bass staff
+const GRAND_STAFF_GAP: f32 = 6.0;
+const MARGIN: f32 = 1.0;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum StaffKind {
+    Treble,
+    Bass,
+    Alto,
+    Tenor,
+    Grand,
+}
+
+impl StaffKind {
+    pub const ALL: [StaffKind; 5] = [StaffKind::Treble, StaffKind::Bass, StaffKind::Alto, StaffKind::Tenor, StaffKind::Grand];
+
+    // from top to bottom
+    pub fn clefs(self) -> Vec<Clef> {
+        match self {
+            StaffKind::Treble => vec![Clef::Treble],
+            StaffKind::Bass => vec![Clef::Bass],
+            StaffKind::Alto => vec![Clef::Alto],
+            StaffKind::Tenor => vec![Clef::Tenor],
+            StaffKind::Grand => vec![Clef::Treble, Clef::Bass],
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            StaffKind::Treble => "treble",
+            StaffKind::Bass => "bass",
+            StaffKind::Alto => "alto",
+            StaffKind::Tenor => "tenor",
+            StaffKind::Grand => "grand",
+        }
+    }
+}
+
+impl fmt::Display for StaffKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum StemDirection {
+    Up,
+    Down,
+}
+
+impl StemDirection {
+    // away from the note farthest from the middle line, down when balanced
+    pub fn from_positions(positions: &[i32]) -> Self {
+        let highest = positions.iter().max().copied().unwrap_or(4);
+        let lowest = positions.iter().min().copied().unwrap_or(4);
+        if highest - 4 >= 4 - lowest {
+            StemDirection::Down
+        } else {
+            StemDirection::Up
+        }
+    }
+}
+
+// Symbols are placed by their center, the clef by the line it names.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Glyph {
+    StaffLine {x0: f32, x1: f32, y: f32},
+    LedgerLine {x0: f32, x1: f32, y: f32},
+    // joins the staves of a grand staff
+    SystemLine {x: f32, y0: f32, y1: f32},
+    Clef {clef: Clef, x: f32, y: f32},
+    Accidental {accidental: Accidental, x: f32, y: f32},
+    Notehead {x: f32, y: f32},
+    Stem {x: f32, y0: f32, y1: f32},
+}
+
+impl Glyph {
+    fn shift(self, dy: f32) -> Self {
+        match self {
+            Glyph::StaffLine {x0, x1, y} => Glyph::StaffLine {x0, x1, y: y + dy},
+            Glyph::LedgerLine {x0, x1, y} => Glyph::LedgerLine {x0, x1, y: y + dy},
+            Glyph::SystemLine {x, y0, y1} => Glyph::SystemLine {x, y0: y0 + dy, y1: y1 + dy},
+            Glyph::Clef {clef, x, y} => Glyph::Clef {clef, x, y: y + dy},
+            Glyph::Accidental {accidental, x, y} => Glyph::Accidental {accidental, x, y: y + dy},
+            Glyph::Notehead {x, y} => Glyph::Notehead {x, y: y + dy},
+            Glyph::Stem {x, y0, y1} => Glyph::Stem {x, y0: y0 + dy, y1: y1 + dy},
+        }
+    }
+
+    fn vertical_extent(&self) -> (f32, f32) {
+        match *self {
+            Glyph::StaffLine {y, ..} | Glyph::LedgerLine {y, ..} => (y, y),
+            Glyph::SystemLine {y0, y1, ..} | Glyph::Stem {y0, y1, ..} => (y0.min(y1), y0.max(y1)),
+            Glyph::Notehead {y, ..} => (y - 0.5, y + 0.5),
+            // flats reach above their line or space
+            Glyph::Accidental {y, ..} => (y - 1.5, y + 1.0),
+            Glyph::Clef {clef, y, ..} => {
+                let position = Staff::clef_position(clef) as f32;
+                (y - (8.0 - position) / 2.0 - 1.5, y + position / 2.0 + 1.5)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StaffLayout {
+    pub glyphs: Vec<Glyph>,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Staff {
+    pub kind: StaffKind,
+    pub signature: KeySignature,
+}
+
+impl Staff {
+    pub fn new(kind: StaffKind) -> Self {
+        Self {kind, signature: KeySignature::from_fifths(0).unwrap()}
+    }
+
+    // diatonic steps above the bottom line, lines are even
+    pub fn position(clef: Clef, note: &Note) -> i32 {
+        let bottom = match clef {
+            Clef::Treble => Note::new(NoteName::E, Accidental::Natural, 4),
+            Clef::Bass => Note::new(NoteName::G, Accidental::Natural, 2),
+            Clef::Alto => Note::new(NoteName::F, Accidental::Natural, 3),
+            Clef::Tenor => Note::new(NoteName::D, Accidental::Natural, 3),
+        };
+        bottom.dist_steps(note)
+    }
+
+    // the line the clef names: G for treble, F for bass and middle C for the C clefs
+    pub fn clef_position(clef: Clef) -> i32 {
+        match clef {
+            Clef::Treble => 2,
+            Clef::Bass => 6,
+            Clef::Alto => 4,
+            Clef::Tenor => 6,
+        }
+    }
+
+    // Positions of the key signature accidentals in the order they are written,
+    // the tenor clef keeps its sharps within the staff.
+    pub fn signature_positions(clef: Clef, signature: KeySignature) -> Vec<(Accidental, i32)> {
+        let sharps = signature.fifths() >= 0;
+        let positions = match (clef, sharps) {
+            (Clef::Tenor, true) => [2, 6, 3, 7, 4, 8, 5],
+            (_, true) => [8, 5, 9, 6, 3, 7, 4],
+            (_, false) => [4, 7, 3, 6, 2, 5, 1],
+        };
+        // relative to the treble clef
+        let shift = match clef {
+            Clef::Treble => 0,
+            Clef::Bass => -2,
+            Clef::Alto => -1,
+            Clef::Tenor if sharps => 0,
+            Clef::Tenor => 1,
+        };
+        signature.accidentals().iter().enumerate()
+            .map(|(i, (_, accidental))| (*accidental, positions[i % 7] + shift))
+            .collect()
+    }
+
+    // one note after the other
+    pub fn layout_melody(&self, notes: &NoteSequence) -> StaffLayout {
+        let columns: Vec<Vec<Note>> = notes.notes.iter().map(|x| vec![*x]).collect();
+        self.layout(&columns)
+    }
+
+    // all notes at once
+    pub fn layout_chord(&self, notes: &NoteSequence) -> StaffLayout {
+        self.layout(std::slice::from_ref(&notes.notes))
+    }
+
+    // Each column sounds at once. Accidentals that the key signature or an earlier
+    // note of the same letter and octave already implies are left out.
+    pub fn layout(&self, columns: &[Vec<Note>]) -> StaffLayout {
+        let clefs = self.kind.clefs();
+        let tops: Vec<f32> = (0..clefs.len()).map(|i| i as f32 * (4.0 + GRAND_STAFF_GAP)).collect();
+        let y = |staff: usize, position: i32| tops[staff] + (8 - position) as f32 / 2.0;
+        let mut glyphs = Vec::new();
+        let mut current: Vec<Vec<(NoteName, i8, Accidental)>> = vec![Vec::new(); clefs.len()];
+
+        // clefs and key signatures
+        for (staff, clef) in clefs.iter().enumerate() {
+            glyphs.push(Glyph::Clef {clef: *clef, x: CLEF_WIDTH / 2.0, y: y(staff, Self::clef_position(*clef))});
+            for (i, (accidental, position)) in Self::signature_positions(*clef, self.signature).iter().enumerate() {
+                glyphs.push(Glyph::Accidental {
+                    accidental: *accidental,
+                    x: CLEF_WIDTH + (i as f32 + 0.5) * ACCIDENTAL_WIDTH,
+                    y: y(staff, *position),
+                });
+            }
+        }
+        let mut x = CLEF_WIDTH + self.signature.accidentals().len() as f32 * ACCIDENTAL_WIDTH + COLUMN_GAP;
+
+        for column in columns {
+            // notes per staff as (note, position, accidental to show), top down
+            let mut parts: Vec<Vec<(Note, i32, Option<Accidental>)>> = vec![Vec::new(); clefs.len()];
+            for note in column {
+                let staff = match self.kind {
+                    StaffKind::Grand if Note::new(NoteName::C, Accidental::Natural, 4).dist_steps(note) < 0 => 1,
+                    _ => 0,
+                };
+                let implied = current[staff].iter().rev()
+                    .find(|(name, octave, _)| *name == note.name() && *octave == note.octave())
+                    .map_or(self.signature.accidental(note.name()), |(_, _, accidental)| *accidental);
+                let shown = (implied != note.accidental()).then_some(note.accidental());
+                parts[staff].push((*note, Self::position(clefs[staff], note), shown));
+            }
+            for (staff, part) in parts.iter_mut().enumerate() {
+                part.sort_by_key(|(_, position, _)| -position);
+                for (note, _, _) in part.iter() {
+                    current[staff].push((note.name(), note.octave(), note.accidental()));
+                }
+            }
+
+            let stems: Vec<StemDirection> = parts.iter()
+                .map(|x| StemDirection::from_positions(&x.iter().map(|(_, position, _)| *position).collect::<Vec<_>>()))
+                .collect();
+            let seconds: Vec<Vec<bool>> = parts.iter().zip(&stems).map(|(part, stem)| seconds(part, *stem)).collect();
+            let left_shift = parts.iter().zip(&stems).zip(&seconds)
+                .any(|((_, stem), seconds)| *stem == StemDirection::Down && seconds.contains(&true));
+            let accidental_columns: Vec<Vec<Option<usize>>> = parts.iter().map(|x| accidental_columns(x)).collect();
+            let accidentals_width = accidental_columns.iter().flatten().flatten().map(|x| x + 1).max().unwrap_or(0) as f32
+                * ACCIDENTAL_WIDTH;
+            let head_x = x + accidentals_width + if left_shift { NOTEHEAD_WIDTH } else { 0.0 } + NOTEHEAD_WIDTH / 2.0;
+            let mut right = head_x + NOTEHEAD_WIDTH / 2.0;
+
+            for (staff, part) in parts.iter().enumerate() {
+                if part.is_empty() {
+                    continue;
+                }
+                let stem = stems[staff];
+                let mut ledgers: Vec<(i32, f32, f32)> = Vec::new();
+                let mut heads_left = head_x - NOTEHEAD_WIDTH / 2.0;
+                for (i, (_, position, _)) in part.iter().enumerate() {
+                    let center = match (seconds[staff][i], stem) {
+                        (false, _) => head_x,
+                        (true, StemDirection::Up) => head_x + NOTEHEAD_WIDTH,
+                        (true, StemDirection::Down) => head_x - NOTEHEAD_WIDTH,
+                    };
+                    heads_left = heads_left.min(center - NOTEHEAD_WIDTH / 2.0);
+                    right = right.max(center + NOTEHEAD_WIDTH / 2.0);
+                    glyphs.push(Glyph::Notehead {x: center, y: y(staff, *position)});
+                    let lines = (*position..=-2).chain(10..=*position).filter(|x| x % 2 == 0);
+                    for line in lines {
+                        let (x0, x1) = (center - NOTEHEAD_WIDTH / 2.0 - LEDGER_OVERHANG, center + NOTEHEAD_WIDTH / 2.0 + LEDGER_OVERHANG);
+                        match ledgers.iter_mut().find(|(x, _, _)| *x == line) {
+                            Some(ledger) => *ledger = (line, ledger.1.min(x0), ledger.2.max(x1)),
+                            None => ledgers.push((line, x0, x1)),
+                        }
+                    }
+                }
+                for (line, x0, x1) in ledgers {
+                    glyphs.push(Glyph::LedgerLine {x0, x1, y: y(staff, line)});
+                }
+                for ((_, position, accidental), column) in part.iter().zip(&accidental_columns[staff]) {
+                    if let (Some(accidental), Some(column)) = (accidental, column) {
+                        let x = heads_left - (*column as f32 + 0.5) * ACCIDENTAL_WIDTH;
+                        glyphs.push(Glyph::Accidental {accidental: *accidental, x, y: y(staff, *position)});
+                    }
+                }
+                // stems reach at least the middle line
+                let (top, bottom) = (part[0].1, part[part.len() - 1].1);
+                glyphs.push(match stem {
+                    StemDirection::Up => Glyph::Stem {
+                        x: head_x + NOTEHEAD_WIDTH / 2.0,
+                        y0: y(staff, bottom),
+                        y1: (y(staff, top) - STEM_LENGTH).min(y(staff, 4)),
+                    },
+                    StemDirection::Down => Glyph::Stem {
+                        x: head_x - NOTEHEAD_WIDTH / 2.0,
+                        y0: y(staff, top),
+                        y1: (y(staff, bottom) + STEM_LENGTH).max(y(staff, 4)),
+                    },
+                });
+            }
+            x = right + COLUMN_GAP;
+        }
+
+        let width = x;
+        for (staff, _) in clefs.iter().enumerate() {
+            for line in 0..5 {
+                glyphs.push(Glyph::StaffLine {x0: 0.0, x1: width, y: tops[staff] + line as f32});
+            }
+        }
+        if clefs.len() > 1 {
+            glyphs.push(Glyph::SystemLine {x: 0.0, y0: tops[0], y1: tops[clefs.len() - 1] + 4.0});
+        }
+
+        // move everything below the top margin
+        let (top, bottom) = glyphs.iter().map(|x| x.vertical_extent())
+            .fold((f32::MAX, f32::MIN), |(top, bottom), (y0, y1)| (top.min(y0), bottom.max(y1)));
+        let glyphs = glyphs.iter().map(|x| x.shift(MARGIN - top)).collect();
+        StaffLayout {glyphs, width, height: bottom - top + 2.0 * MARGIN}
+    }
+}
+
+// Noteheads a step apart can't share a side of the stem, so the lower one of
+// a second goes left and every other note of a cluster moves across.
+fn seconds(part: &[(Note, i32, Option<Accidental>)], stem: StemDirection) -> Vec<bool> {
+    let mut shifted = vec![false; part.len()];
+    let order: Vec<usize> = match stem {
+        StemDirection::Up => (0..part.len()).rev().collect(),
+        StemDirection::Down => (0..part.len()).collect(),
+    };
+    for pair in order.windows(2) {
+        let (previous, i) = (pair[0], pair[1]);
+        shifted[i] = (part[i].1 - part[previous].1).abs() == 1 && !shifted[previous];
+    }
+    shifted
+}
+
+// Accidentals are stacked from the top, each going into the column nearest to
+// the notes where it keeps a sixth away from the others.
+fn accidental_columns(part: &[(Note, i32, Option<Accidental>)]) -> Vec<Option<usize>> {
+    let mut columns: Vec<Vec<i32>> = Vec::new();
+    part.iter().map(|(_, position, accidental)| {
+        accidental.as_ref()?;
+        let column = columns.iter().position(|x| x.iter().all(|other| (other - position).abs() >= 6))
+            .unwrap_or(columns.len());
+        if column == columns.len() {
+            columns.push(Vec::new());
+        }
+        columns[column].push(*position);
+        Some(column)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn signature(s: &str) -> KeySignature {
+        KeySignature::from_str(s).unwrap()
+    }
+
+    fn noteheads(layout: &StaffLayout) -> Vec<(f32, f32)> {
+        layout.glyphs.iter().filter_map(|x| match x {
+            Glyph::Notehead {x, y} => Some((*x, *y)),
+            _ => None,
+        }).collect()
+    }
+
+    fn accidentals(layout: &StaffLayout) -> Vec<(Accidental, f32, f32)> {
+        layout.glyphs.iter().filter_map(|x| match x {
+            Glyph::Accidental {accidental, x, y} => Some((*accidental, *x, *y)),
+            _ => None,
+        }).collect()
+    }
+
+    fn ledgers(layout: &StaffLayout) -> Vec<f32> {
+        layout.glyphs.iter().filter_map(|x| match x {
+            Glyph::LedgerLine {y, ..} => Some(*y),
+            _ => None,
+        }).collect()
+    }
+
+    fn stems(layout: &StaffLayout) -> Vec<(f32, f32, f32)> {
+        layout.glyphs.iter().filter_map(|x| match x {
+            Glyph::Stem {x, y0, y1} => Some((*x, *y0, *y1)),
+            _ => None,
+        }).collect()
+    }
+
+    fn staff_lines(layout: &StaffLayout) -> Vec<f32> {
+        layout.glyphs.iter().filter_map(|x| match x {
+            Glyph::StaffLine {y, ..} => Some(*y),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn position() {
+        assert_eq!(Staff::position(Clef::Treble, &Note::from_str("E4").unwrap()), 0);
+        assert_eq!(Staff::position(Clef::Treble, &Note::from_str("F5").unwrap()), 8);
+        assert_eq!(Staff::position(Clef::Treble, &Note::from_str("C4").unwrap()), -2);
+        assert_eq!(Staff::position(Clef::Treble, &Note::from_str("Cb4").unwrap()), -2);
+        assert_eq!(Staff::position(Clef::Bass, &Note::from_str("G2").unwrap()), 0);
+        assert_eq!(Staff::position(Clef::Bass, &Note::from_str("C#4").unwrap()), 10);
+        assert_eq!(Staff::position(Clef::Alto, &Note::from_str("C4").unwrap()), 4);
+        assert_eq!(Staff::position(Clef::Tenor, &Note::from_str("C4").unwrap()), 6);
+        assert_eq!(Staff::position(Clef::Tenor, &Note::from_str("B2").unwrap()), -2);
+    }
+
+    #[test]
+    fn signature_positions() {
+        let positions = |clef, s| Staff::signature_positions(clef, signature(s)).iter().map(|(_, x)| *x).collect::<Vec<_>>();
+        assert_eq!(positions(Clef::Treble, "4#"), [8, 5, 9, 6]);
+        assert_eq!(positions(Clef::Treble, "3b"), [4, 7, 3]);
+        assert_eq!(positions(Clef::Bass, "2#"), [6, 3]);
+        assert_eq!(positions(Clef::Bass, "7b"), [2, 5, 1, 4, 0, 3, -1]);
+        assert_eq!(positions(Clef::Alto, "1#"), [7]);
+        assert_eq!(positions(Clef::Alto, "2b"), [3, 6]);
+        assert_eq!(positions(Clef::Tenor, "3#"), [2, 6, 3]);
+        assert_eq!(positions(Clef::Tenor, "1b"), [5]);
+        assert_eq!(positions(Clef::Treble, "0"), []);
+        assert_eq!(Staff::signature_positions(Clef::Treble, signature("8#"))[7], (Accidental::Doublesharp, 8));
+    }
+
+    #[test]
+    fn stem_direction() {
+        assert_eq!(StemDirection::from_positions(&[4]), StemDirection::Down);
+        assert_eq!(StemDirection::from_positions(&[3]), StemDirection::Up);
+        assert_eq!(StemDirection::from_positions(&[-2, 2, 9]), StemDirection::Up);
+        assert_eq!(StemDirection::from_positions(&[0, 10]), StemDirection::Down);
+    }
+
+    #[test]
+    fn melody() {
+        let layout = Staff::new(StaffKind::Treble).layout_melody(&NoteSequence::from_str("C4 B4 A5").unwrap());
+        let lines = staff_lines(&layout);
+        let top = lines[0];
+        assert_eq!(lines, [top, top + 1.0, top + 2.0, top + 3.0, top + 4.0]);
+        let heads = noteheads(&layout);
+        assert_eq!(heads.iter().map(|(_, y)| *y).collect::<Vec<_>>(), [top + 5.0, top + 2.0, top - 1.0]);
+        assert!(heads.windows(2).all(|x| x[0].0 < x[1].0));
+        assert_eq!(ledgers(&layout), [top + 5.0, top - 1.0]);
+        // C4 up, B4 on the middle line down, A5 down to the middle line
+        let stems = stems(&layout);
+        assert_eq!(stems[0], (heads[0].0 + 0.65, top + 5.0, top + 1.5));
+        assert_eq!(stems[1], (heads[1].0 - 0.65, top + 2.0, top + 5.5));
+        assert_eq!(stems[2], (heads[2].0 - 0.65, top - 1.0, top + 2.5));
+        // A5 and the treble clef reach 1.5 above the staff, C4 and the clef 1.5 below
+        assert_eq!(top, MARGIN + 1.5);
+        assert_eq!(layout.height, 1.5 + 4.0 + 1.5 + 2.0 * MARGIN);
+    }
+
+    #[test]
+    fn melody_accidentals() {
+        let mut staff = Staff::new(StaffKind::Treble);
+        staff.signature = signature("2#");
+        let layout = staff.layout_melody(&NoteSequence::from_str("F#4 F4 F4 F#4 C##5 Bbb4 C5 C#4").unwrap());
+        let shown: Vec<Accidental> = accidentals(&layout).iter().skip(2).map(|x| x.0).collect();
+        assert_eq!(shown, [Accidental::Natural, Accidental::Sharp, Accidental::Doublesharp, Accidental::Doubleflat,
+            Accidental::Natural]);
+        // the key signature comes after the clef
+        let top = staff_lines(&layout)[0];
+        assert_eq!(accidentals(&layout)[..2], [(Accidental::Sharp, 3.5, top), (Accidental::Sharp, 4.5, top + 1.5)]);
+        // accidentals sit left of their notehead
+        let heads = noteheads(&layout);
+        let natural = accidentals(&layout)[2];
+        assert_eq!((natural.1, natural.2), (heads[1].0 - 0.65 - 0.5, heads[1].1));
+        // only notes showing an accidental make room for it
+        let heads = noteheads(&Staff::new(StaffKind::Treble).layout_melody(&NoteSequence::from_str("C4 D4 D#4").unwrap()));
+        assert!((heads[1].0 - heads[0].0 - (NOTEHEAD_WIDTH + COLUMN_GAP)).abs() < 1e-5);
+        assert!((heads[2].0 - heads[1].0 - (NOTEHEAD_WIDTH + COLUMN_GAP + ACCIDENTAL_WIDTH)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn chord_seconds() {
+        // stem up, D4 moves right of the stem
+        let layout = Staff::new(StaffKind::Treble).layout_chord(&NoteSequence::from_str("C4 D4 G4").unwrap());
+        let heads = noteheads(&layout);
+        assert_eq!(stems(&layout)[0].0, heads[0].0 + 0.65);
+        assert_eq!(heads[1].0, heads[0].0 + NOTEHEAD_WIDTH);
+        assert_eq!(heads[2].0, heads[0].0);
+        // stem down, the lower note of the second moves left
+        let layout = Staff::new(StaffKind::Treble).layout_chord(&NoteSequence::from_str("F5 G5").unwrap());
+        let heads = noteheads(&layout);
+        assert_eq!(heads[1].0, heads[0].0 - NOTEHEAD_WIDTH);
+        assert_eq!(stems(&layout)[0].0, heads[0].0 - 0.65);
+        // a cluster alternates
+        let layout = Staff::new(StaffKind::Treble).layout_chord(&NoteSequence::from_str("E4 F4 G4").unwrap());
+        let xs: Vec<f32> = noteheads(&layout).iter().map(|x| x.0).collect();
+        assert_eq!(xs, [xs[0], xs[0] + NOTEHEAD_WIDTH, xs[0]]);
+    }
+
+    #[test]
+    fn chord_accidentals() {
+        // Db5 and Eb4 are a seventh apart and share the first column
+        let layout = Staff::new(StaffKind::Treble).layout_chord(&NoteSequence::from_str("C#4 Eb4 G#4 Db5").unwrap());
+        let columns: Vec<f32> = accidentals(&layout).iter().map(|x| x.1).collect();
+        assert_eq!(columns[2], columns[0]);
+        assert_eq!(columns[1], columns[0] - ACCIDENTAL_WIDTH);
+        assert_eq!(columns[3], columns[0] - 2.0 * ACCIDENTAL_WIDTH);
+        assert_eq!(columns[0], noteheads(&layout)[0].0 - 0.65 - 0.5);
+    }
+
+    #[test]
+    fn chord_ledger_lines() {
+        assert_eq!(ledgers(&Staff::new(StaffKind::Treble).layout_chord(&NoteSequence::from_str("B3 C4 E4").unwrap())).len(), 1);
+        assert_eq!(ledgers(&Staff::new(StaffKind::Treble).layout_chord(&NoteSequence::from_str("A3 E4").unwrap())).len(), 2);
+        assert_eq!(ledgers(&Staff::new(StaffKind::Bass).layout_chord(&NoteSequence::from_str("E4 G4").unwrap())).len(), 3);
+        // the ledger line under a second spans both noteheads
+        let layout = Staff::new(StaffKind::Treble).layout_chord(&NoteSequence::from_str("B3 C4").unwrap());
+        let heads = noteheads(&layout);
+        assert!(layout.glyphs.contains(&Glyph::LedgerLine {
+            x0: heads[1].0 - 0.65 - LEDGER_OVERHANG,
+            x1: heads[0].0 + 0.65 + LEDGER_OVERHANG,
+            y: heads[0].1,
+        }));
+    }
+
+    #[test]
+    fn grand_staff() {
+        let layout = Staff::new(StaffKind::Grand).layout_chord(&NoteSequence::from_str("C3 G3 E4 C5").unwrap());
+        let lines = staff_lines(&layout);
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[5] - lines[4], GRAND_STAFF_GAP);
+        assert!(layout.glyphs.contains(&Glyph::SystemLine {x: 0.0, y0: lines[0], y1: lines[9]}));
+        assert_eq!(stems(&layout).len(), 2);
+        let clefs: Vec<Clef> = layout.glyphs.iter().filter_map(|x| match x {
+            Glyph::Clef {clef, ..} => Some(*clef),
+            _ => None,
+        }).collect();
+        assert_eq!(clefs, [Clef::Treble, Clef::Bass]);
+        // E4 and C5 on the treble staff, C3 and G3 on the bass staff, both at the same x
+        let heads = noteheads(&layout);
+        assert!(heads[..2].iter().all(|(_, y)| *y <= lines[4]));
+        assert!(heads[2..].iter().all(|(_, y)| *y >= lines[5]));
+        assert!(heads.iter().all(|(x, _)| *x == heads[0].0));
+    }
+
+    #[test]
+    fn clef_glyph() {
+        let layout = Staff::new(StaffKind::Alto).layout_melody(&NoteSequence::from_str("C4").unwrap());
+        let lines = staff_lines(&layout);
+        assert!(layout.glyphs.contains(&Glyph::Clef {clef: Clef::Alto, x: 1.5, y: lines[2]}));
+    }
+}
@@ -0,0 +1,443 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::errors::HarmonyError;
+use crate::notes::Note;
+use crate::notesequences::NoteSequence;
+use crate::diatonic_scales::DiatonicScale;
+use crate::tunings::{Tuning, NoteTuning};
+
+// peak level of a rendered note, leaves some headroom
+const AMPLITUDE: f64 = 0.8;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Timbre {
+    Sine,
+    Organ,
+    PluckedString,
+    Piano,
+}
+
+impl Timbre {
+    pub const ALL: [Timbre; 4] = [Timbre::Sine, Timbre::Organ, Timbre::PluckedString, Timbre::Piano];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Timbre::Sine => "sine",
+            Timbre::Organ => "organ",
+            Timbre::PluckedString => "plucked string",
+            Timbre::Piano => "piano",
+        }
+    }
+
+    fn envelope(self) -> Envelope {
+        match self {
+            Timbre::Sine | Timbre::Organ => Envelope {attack: 0.01, decay: 0.0, sustain: 1.0, release: 0.01},
+            Timbre::PluckedString => Envelope {attack: 0.002, decay: 0.0, sustain: 1.0, release: 0.01},
+            Timbre::Piano => Envelope {attack: 0.005, decay: 0.3, sustain: 0.4, release: 0.15},
+        }
+    }
+
+    // relative amplitudes of the harmonics and their extra decay per second
+    fn partials(self) -> &'static [(f64, f64)] {
+        match self {
+            Timbre::Sine | Timbre::PluckedString => &[(1.0, 0.0)],
+            Timbre::Organ => &[(1.0, 0.0), (0.5, 0.0), (0.33, 0.0), (0.25, 0.0), (0.0, 0.0), (0.15, 0.0), (0.0, 0.0), (0.1, 0.0)],
+            Timbre::Piano => &[(1.0, 0.5), (0.5, 1.0), (0.33, 1.5), (0.25, 2.0), (0.2, 2.5), (0.16, 3.0)],
+        }
+    }
+}
+
+impl fmt::Display for Timbre {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+// times in seconds, the release is taken from the end of the note
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Envelope {
+    pub attack: f64,
+    pub decay: f64,
+    pub sustain: f64,
+    pub release: f64,
+}
+
+impl Envelope {
+    pub fn level(&self, time: f64, duration: f64) -> f64 {
+        let level = if time < self.attack {
+            time / self.attack
+        } else if time < self.attack + self.decay {
+            1.0 - (1.0 - self.sustain) * (time - self.attack) / self.decay
+        } else {
+            self.sustain
+        };
+        let remaining = duration - time;
+        if remaining < self.release {
+            level * (remaining / self.release).max(0.0)
+        } else {
+            level
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum BitDepth {
+    Sixteen,
+    TwentyFour,
+}
+
+impl BitDepth {
+    pub fn bits(self) -> u16 {
+        match self {
+            BitDepth::Sixteen => 16,
+            BitDepth::TwentyFour => 24,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Synth<T = Tuning> {
+    timbre: Timbre,
+    sample_rate: u32,
+    tuning: T,
+}
+
+impl<T: NoteTuning> Synth<T> {
+    pub fn new(timbre: Timbre, sample_rate: u32, tuning: T) -> Result<Self, HarmonyError> {
+        if sample_rate == 0 {
+            return Err(HarmonyError::InvalidArgument("Sample rate must be positive"));
+        }
+        Ok(Self {timbre, sample_rate, tuning})
+    }
+
+    pub fn timbre(&self) -> Timbre {
+        self.timbre
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn tuning(&self) -> &T {
+        &self.tuning
+    }
+
+    pub fn render_frequency(&self, frequency: f64, duration: f64) -> Result<Vec<f32>, HarmonyError> {
+        if !duration.is_finite() || duration < 0.0 {
+            return Err(HarmonyError::InvalidArgument("Duration must not be negative"));
+        }
+        if !frequency.is_finite() || frequency <= 0.0 {
+            return Err(HarmonyError::InvalidArgument("Frequency must be positive"));
+        }
+        if frequency >= self.sample_rate as f64 / 2.0 {
+            return Err(HarmonyError::OutOfRange("Frequency is above the Nyquist frequency"));
+        }
+        let length = (duration * self.sample_rate as f64).round() as usize;
+        let samples = match self.timbre {
+            Timbre::PluckedString => self.karplus_strong(frequency, length),
+            _ => self.additive(frequency, length),
+        };
+        let envelope = self.timbre.envelope();
+        Ok(samples.iter().enumerate()
+            .map(|(i, x)| (x * AMPLITUDE * envelope.level(i as f64 / self.sample_rate as f64, duration)) as f32)
+            .collect())
+    }
+
+    pub fn render_note(&self, note: &Note, duration: f64) -> Result<Vec<f32>, HarmonyError> {
+        self.render_frequency(self.tuning.note_frequency(note)?, duration)
+    }
+
+    // all notes sound at once
+    pub fn render_chord(&self, notes: &NoteSequence, duration: f64) -> Result<Vec<f32>, HarmonyError> {
+        let mut samples = vec![0.0; (duration.max(0.0) * self.sample_rate as f64).round() as usize];
+        for note in &notes.notes {
+            for (sample, x) in samples.iter_mut().zip(self.render_note(note, duration)?) {
+                *sample += x / notes.notes.len() as f32;
+            }
+        }
+        Ok(samples)
+    }
+
+    // one note after the other, each lasting note_duration
+    pub fn render_melody(&self, notes: &NoteSequence, note_duration: f64) -> Result<Vec<f32>, HarmonyError> {
+        let mut samples = Vec::new();
+        for note in &notes.notes {
+            samples.extend(self.render_note(note, note_duration)?);
+        }
+        Ok(samples)
+    }
+
+    // ascending, ending on the tonic an octave up
+    pub fn render_scale(&self, scale: &DiatonicScale, note_duration: f64) -> Result<Vec<f32>, HarmonyError> {
+        let mut notes = scale.notesequence().clone();
+        let tonic = scale.tonic();
+        notes.notes.push(Note::new(tonic.name(), tonic.accidental(), tonic.octave() + 1));
+        self.render_melody(&notes, note_duration)
+    }
+
+    fn additive(&self, frequency: f64, length: usize) -> Vec<f64> {
+        let nyquist = self.sample_rate as f64 / 2.0;
+        let partials: Vec<(f64, f64, f64)> = self.timbre.partials().iter().enumerate()
+            .map(|(i, (amplitude, decay))| (frequency * (i + 1) as f64, *amplitude, *decay))
+            .filter(|(frequency, _, _)| *frequency < nyquist)
+            .collect();
+        let total: f64 = partials.iter().map(|(_, amplitude, _)| amplitude).sum();
+        (0..length).map(|i| {
+            let time = i as f64 / self.sample_rate as f64;
+            partials.iter()
+                .map(|(frequency, amplitude, decay)| amplitude * (-decay * time).exp() * (2.0 * PI * frequency * time).sin())
+                .sum::<f64>() / total
+        }).collect()
+    }
+
+    // A delay line filled with noise and fed back through an averaging filter,
+    // which delays by another half sample.
+    fn karplus_strong(&self, frequency: f64, length: usize) -> Vec<f64> {
+        let period = ((self.sample_rate as f64 / frequency - 0.5).round() as usize).max(2);
+        // deterministic noise so renders can be compared
+        let mut seed: u32 = 0x2545_f491;
+        let mut line: Vec<f64> = (0..period).map(|_| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 8) as f64 / (1 << 23) as f64 - 1.0
+        }).collect();
+        let mean = line.iter().sum::<f64>() / period as f64;
+        let peak = line.iter().map(|x| (x - mean).abs()).fold(0.0, f64::max);
+        line.iter_mut().for_each(|x| *x = (*x - mean) / peak);
+
+        let mut samples = Vec::with_capacity(length);
+        let mut position = 0;
+        for _ in 0..length {
+            let next = (position + 1) % period;
+            samples.push(line[position]);
+            line[position] = 0.996 * 0.5 * (line[position] + line[next]);
+            position = next;
+        }
+        samples
+    }
+}
+
+// mono PCM, samples are clipped to [-1, 1]
+pub fn write_wav<W: Write>(mut writer: W, samples: &[f32], sample_rate: u32, depth: BitDepth) -> io::Result<()> {
+    let bytes = depth.bits() as u32 / 8;
+    let data_size = samples.len() as u32 * bytes;
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_size).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16_u32.to_le_bytes())?;
+    writer.write_all(&1_u16.to_le_bytes())?;
+    writer.write_all(&1_u16.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * bytes).to_le_bytes())?;
+    writer.write_all(&(bytes as u16).to_le_bytes())?;
+    writer.write_all(&depth.bits().to_le_bytes())?;
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())?;
+
+    let scale = ((1_i32 << (depth.bits() - 1)) - 1) as f32;
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * scale).round() as i32;
+        writer.write_all(&value.to_le_bytes()[..bytes as usize])?;
+    }
+    writer.flush()
+}
+
+pub fn save_wav<P: AsRef<Path>>(path: P, samples: &[f32], sample_rate: u32, depth: BitDepth) -> io::Result<()> {
+    write_wav(BufWriter::new(File::create(path)?), samples, sample_rate, depth)
+}
+
+// Plays through the platform's command line player from a temporary WAV file,
+// returns once the player is started. Every call gets its own file so that
+// overlapping playbacks don't clash.
+pub fn play(samples: &[f32], sample_rate: u32) -> io::Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!("harmony-{}-{}.wav", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+    save_wav(&path, samples, sample_rate, BitDepth::Sixteen)?;
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("afplay")
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-Command", "(New-Object Media.SoundPlayer $args[0]).PlaySync()"]);
+        command
+    } else {
+        let mut command = Command::new("aplay");
+        command.arg("-q");
+        command
+    };
+    let mut child = command.arg(&path).stdout(Stdio::null()).stderr(Stdio::null()).spawn()?;
+    std::thread::spawn(move || {
+        let _ = child.wait();
+        let _ = std::fs::remove_file(path);
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diatonic_scales::Mode;
+    use crate::scala::{ScalaScale, ScalaPitch, ScalaTuning, KeyboardMapping};
+
+    const RATE: u32 = 44100;
+
+    fn synth(timbre: Timbre) -> Synth {
+        Synth::new(timbre, RATE, Tuning::equal(440.0).unwrap()).unwrap()
+    }
+
+    // magnitude of a single frequency, normalized to the amplitude of a sine
+    fn magnitude(samples: &[f32], frequency: f64) -> f64 {
+        let coefficient = 2.0 * (2.0 * PI * frequency / RATE as f64).cos();
+        let (mut previous, mut before) = (0.0, 0.0);
+        for sample in samples {
+            let current = *sample as f64 + coefficient * previous - before;
+            before = previous;
+            previous = current;
+        }
+        let power = previous * previous + before * before - coefficient * previous * before;
+        2.0 * power.sqrt() / samples.len() as f64
+    }
+
+    fn rms(samples: &[f32]) -> f64 {
+        (samples.iter().map(|x| (*x as f64).powi(2)).sum::<f64>() / samples.len() as f64).sqrt()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0, |x, y| x.max(y.abs()))
+    }
+
+    #[test]
+    fn envelope() {
+        let envelope = Envelope {attack: 0.1, decay: 0.2, sustain: 0.5, release: 0.1};
+        assert_eq!(envelope.level(0.0, 1.0), 0.0);
+        assert_eq!(envelope.level(0.05, 1.0), 0.5);
+        assert_eq!(envelope.level(0.1, 1.0), 1.0);
+        assert!((envelope.level(0.2, 1.0) - 0.75).abs() < 1e-9);
+        assert_eq!(envelope.level(0.5, 1.0), 0.5);
+        assert!((envelope.level(0.95, 1.0) - 0.25).abs() < 1e-9);
+        assert_eq!(envelope.level(1.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn durations() {
+        for timbre in Timbre::ALL {
+            let synth = synth(timbre);
+            assert_eq!(synth.render_note(&Note::from_str("A4").unwrap(), 0.5).unwrap().len(), 22050);
+            assert_eq!(synth.render_note(&Note::from_str("A4").unwrap(), 0.0).unwrap().len(), 0);
+            let chord = NoteSequence::from_strs(["C4", "E4", "G4"].to_vec()).unwrap();
+            assert_eq!(synth.render_chord(&chord, 1.0).unwrap().len(), 44100);
+            assert_eq!(synth.render_melody(&chord, 0.25).unwrap().len(), 33075);
+            let scale = DiatonicScale::from_tonic(Note::from_str("D4").unwrap(), Mode::Dorian).unwrap();
+            assert_eq!(synth.render_scale(&scale, 0.1).unwrap().len(), 8 * 4410);
+        }
+    }
+
+    #[test]
+    fn levels() {
+        for timbre in Timbre::ALL {
+            let samples = synth(timbre).render_note(&Note::from_str("C3").unwrap(), 1.0).unwrap();
+            assert!(peak(&samples) <= AMPLITUDE as f32 + 1e-6);
+            assert!(peak(&samples) > 0.2);
+            // starts and ends silent
+            assert!(samples[0].abs() < 1e-3);
+            assert!(samples[samples.len() - 1].abs() < 1e-2);
+            let chord = NoteSequence::from_strs(["C4", "E4", "G4", "C5"].to_vec()).unwrap();
+            assert!(peak(&synth(timbre).render_chord(&chord, 0.5).unwrap()) <= AMPLITUDE as f32 + 1e-6);
+        }
+    }
+
+    #[test]
+    fn spectra() {
+        let sine = synth(Timbre::Sine).render_note(&Note::from_str("A4").unwrap(), 1.0).unwrap();
+        assert!(magnitude(&sine, 440.0) > 0.75);
+        assert!(magnitude(&sine, 880.0) < 0.01);
+        assert!(magnitude(&sine, 466.16) < 0.01);
+
+        let organ = synth(Timbre::Organ).render_note(&Note::from_str("A3").unwrap(), 1.0).unwrap();
+        assert!(magnitude(&organ, 220.0) > 0.3);
+        assert!(magnitude(&organ, 440.0) > 0.15);
+        assert!(magnitude(&organ, 660.0) > 0.1);
+        assert!(magnitude(&organ, 1100.0) < 0.01);
+        assert!(magnitude(&organ, 330.0) < 0.01);
+
+        let piano = synth(Timbre::Piano).render_note(&Note::from_str("A3").unwrap(), 1.0).unwrap();
+        assert!(magnitude(&piano, 220.0) > magnitude(&piano, 440.0));
+        assert!(magnitude(&piano, 440.0) > 0.02);
+        assert!(magnitude(&piano, 233.08) < 0.01);
+        assert!(rms(&piano[..4410]) > 1.5 * rms(&piano[22050..26460]));
+
+        let string = synth(Timbre::PluckedString).render_note(&Note::from_str("A3").unwrap(), 1.0).unwrap();
+        assert!(magnitude(&string, 220.0) > 3.0 * magnitude(&string, 207.65));
+        assert!(magnitude(&string, 220.0) > 3.0 * magnitude(&string, 233.08));
+        assert!(rms(&string[..4410]) > 2.0 * rms(&string[39690..]));
+
+        let chord = NoteSequence::from_strs(["C4", "E4", "G4"].to_vec()).unwrap();
+        let chord = synth(Timbre::Sine).render_chord(&chord, 1.0).unwrap();
+        for frequency in [261.63, 329.63, 392.0] {
+            assert!(magnitude(&chord, frequency) > 0.2);
+        }
+        assert!(magnitude(&chord, 293.66) < 0.01);
+    }
+
+    #[test]
+    fn tunings() {
+        let synth = Synth::new(Timbre::Sine, RATE, Tuning::equal(415.0).unwrap()).unwrap();
+        let samples = synth.render_note(&Note::from_str("A4").unwrap(), 1.0).unwrap();
+        assert!(magnitude(&samples, 415.0) > 0.75);
+        assert!(magnitude(&samples, 440.0) < 0.01);
+        let samples = synth.render_frequency(1000.0, 1.0).unwrap();
+        assert!(magnitude(&samples, 1000.0) > 0.75);
+
+        // five equal steps to the octave
+        let scale = ScalaScale::new("5-EDO", [240.0, 480.0, 720.0, 960.0, 1200.0].map(ScalaPitch::Cents).to_vec()).unwrap();
+        let scala = Synth::new(Timbre::Sine, RATE, ScalaTuning::new(scale, KeyboardMapping::default()).unwrap()).unwrap();
+        let samples = scala.render_note(&Note::from_str("A#4").unwrap(), 1.0).unwrap();
+        assert!(magnitude(&samples, 440.0 * 2.0_f64.powf(0.2)) > 0.75);
+        assert!(magnitude(&samples, 466.16) < 0.01);
+        assert!(scala.render_note(&Note::from_str("C10").unwrap(), 1.0).is_err());
+    }
+
+    #[test]
+    fn errors() {
+        let sine = synth(Timbre::Sine);
+        assert_eq!(Synth::new(Timbre::Sine, 0, Tuning::equal(440.0).unwrap()), Err(HarmonyError::InvalidArgument("Sample rate must be positive")));
+        assert_eq!(sine.render_note(&Note::from_str("A4").unwrap(), -1.0), Err(HarmonyError::InvalidArgument("Duration must not be negative")));
+        assert_eq!(sine.render_frequency(0.0, 1.0), Err(HarmonyError::InvalidArgument("Frequency must be positive")));
+        assert_eq!(sine.render_note(&Note::from_str("A10").unwrap(), 1.0), Err(HarmonyError::OutOfRange("Frequency is above the Nyquist frequency")));
+    }
+
+    #[test]
+    fn wav() {
+        let samples = [0.0, 1.0, -1.0, 0.5, 2.0];
+        let mut bytes = Vec::new();
+        write_wav(&mut bytes, &samples, 8000, BitDepth::Sixteen).unwrap();
+        assert_eq!(bytes.len(), 44 + 10);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(&bytes[4..8], &46_u32.to_le_bytes());
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(&bytes[20..24], &[1, 0, 1, 0]);
+        assert_eq!(&bytes[24..28], &8000_u32.to_le_bytes());
+        assert_eq!(&bytes[28..32], &16000_u32.to_le_bytes());
+        assert_eq!(&bytes[32..36], &[2, 0, 16, 0]);
+        assert_eq!(&bytes[36..44], b"data\x0a\x00\x00\x00");
+        assert_eq!(&bytes[44..], &[0, 0, 0xff, 0x7f, 0x01, 0x80, 0x00, 0x40, 0xff, 0x7f]);
+
+        let mut bytes = Vec::new();
+        write_wav(&mut bytes, &samples, 48000, BitDepth::TwentyFour).unwrap();
+        assert_eq!(bytes.len(), 44 + 15);
+        assert_eq!(&bytes[28..32], &144000_u32.to_le_bytes());
+        assert_eq!(&bytes[32..36], &[3, 0, 24, 0]);
+        assert_eq!(&bytes[44..], &[0, 0, 0, 0xff, 0xff, 0x7f, 0x01, 0x00, 0x80, 0x00, 0x00, 0x40, 0xff, 0xff, 0x7f]);
+
+        let path = std::env::temp_dir().join("harmony_synth_test.wav");
+        let samples = synth(Timbre::Piano).render_note(&Note::from_str("C4").unwrap(), 0.1).unwrap();
+        save_wav(&path, &samples, RATE, BitDepth::TwentyFour).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 44 + 3 * 4410);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,239 @@
+use std::fmt;
+
+use crate::notenames::NoteName;
+use crate::accidentals::Accidental;
+use crate::notes::Note;
+use crate::errors::HarmonyError;
+
+// cents of the twelve pitch classes above the tonic
+const WERCKMEISTER: [f64; 12] = [
+    0.0, 90.225, 192.180, 294.135, 390.225, 498.045, 588.270, 696.090, 792.180, 888.270, 996.090, 1092.180,
+];
+const VALLOTTI: [f64; 12] = [
+    0.0, 94.135, 196.090, 298.045, 392.180, 501.955, 592.180, 698.045, 796.090, 894.135, 1000.000, 1090.225,
+];
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Temperament {
+    Equal,
+    Pythagorean,
+    QuarterCommaMeantone,
+    JustIntonation,
+    Werckmeister,
+    Vallotti,
+}
+
+impl Temperament {
+    pub const ALL: [Temperament; 6] = [
+        Temperament::Equal, Temperament::Pythagorean, Temperament::QuarterCommaMeantone,
+        Temperament::JustIntonation, Temperament::Werckmeister, Temperament::Vallotti,
+    ];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Temperament::Equal => "12-tone equal temperament",
+            Temperament::Pythagorean => "Pythagorean",
+            Temperament::QuarterCommaMeantone => "quarter-comma meantone",
+            Temperament::JustIntonation => "5-limit just intonation",
+            Temperament::Werckmeister => "Werckmeister III",
+            Temperament::Vallotti => "Vallotti",
+        }
+    }
+}
+
+impl fmt::Display for Temperament {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+// Gives notes their frequency, implemented by temperaments and by Scala tunings.
+pub trait NoteTuning {
+    fn note_frequency(&self, note: &Note) -> Result<f64, HarmonyError>;
+}
+
+// A4 sounds at the reference frequency in every temperament. The tonic is the
+// center of the chain of fifths for spelled temperaments and takes the place of
+// C in the well-temperament tables.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Tuning {
+    temperament: Temperament,
+    reference: f64,
+    tonic: Note,
+}
+
+impl Tuning {
+    pub fn new(temperament: Temperament, reference: f64, tonic: Note) -> Result<Self, HarmonyError> {
+        if !reference.is_finite() || reference <= 0.0 {
+            return Err(HarmonyError::InvalidArgument("Reference frequency must be positive"));
+        }
+        Ok(Self {temperament, reference, tonic})
+    }
+
+    pub fn equal(reference: f64) -> Result<Self, HarmonyError> {
+        Self::new(Temperament::Equal, reference, Note::new(NoteName::C, Accidental::Natural, 4))
+    }
+
+    pub fn temperament(&self) -> Temperament {
+        self.temperament
+    }
+
+    pub fn reference(&self) -> f64 {
+        self.reference
+    }
+
+    pub fn tonic(&self) -> Note {
+        self.tonic
+    }
+
+    // deviation of a note from its equal tempered pitch
+    pub fn deviation(&self, note: &Note) -> f64 {
+        match self.temperament {
+            Temperament::Equal => 0.0,
+            Temperament::Pythagorean => fifths_deviation(self.fifths(note), ratio_cents(3.0/2.0), 0.0),
+            Temperament::QuarterCommaMeantone => fifths_deviation(self.fifths(note), ratio_cents(5.0_f64.powf(0.25)), 0.0),
+            Temperament::JustIntonation => fifths_deviation(self.fifths(note), ratio_cents(3.0/2.0), ratio_cents(81.0/80.0)),
+            Temperament::Werckmeister => self.table_deviation(note, &WERCKMEISTER),
+            Temperament::Vallotti => self.table_deviation(note, &VALLOTTI),
+        }
+    }
+
+    pub fn frequency(&self, note: &Note) -> f64 {
+        let a4 = Note::new(NoteName::A, Accidental::Natural, 4);
+        let cents = 100.0 * a4.dist_hsteps(note) as f64 + self.deviation(note) - self.deviation(&a4);
+        self.reference * 2.0_f64.powf(cents / 1200.0)
+    }
+
+    fn fifths(&self, note: &Note) -> i32 {
+        note.line_of_fifths() - self.tonic.line_of_fifths()
+    }
+
+    fn table_deviation(&self, note: &Note, table: &[f64; 12]) -> f64 {
+        let pitch_class = (note.pitch_class() - self.tonic.pitch_class()).rem_euclid(12);
+        table[pitch_class as usize] - 100.0 * pitch_class as f64
+    }
+}
+
+impl NoteTuning for Tuning {
+    fn note_frequency(&self, note: &Note) -> Result<f64, HarmonyError> {
+        Ok(self.frequency(note))
+    }
+}
+
+fn ratio_cents(ratio: f64) -> f64 {
+    1200.0 * ratio.log2()
+}
+
+// Deviation of a note reached by a chain of fifths from the tonic. Just
+// intonation lowers every fourth fifth by a syntonic comma, which turns the
+// Pythagorean thirds and sixths into pure ones.
+fn fifths_deviation(fifths: i32, fifth_cents: f64, comma_cents: f64) -> f64 {
+    let commas = (fifths.abs() + 1) / 4 * fifths.signum();
+    fifths as f64 * (fifth_cents - 700.0) - commas as f64 * comma_cents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tuning(temperament: Temperament, tonic: &str) -> Tuning {
+        Tuning::new(temperament, 440.0, Note::from_str(tonic).unwrap()).unwrap()
+    }
+
+    fn assert_close(left: f64, right: f64) {
+        assert!((left - right).abs() < 1e-6, "{} != {}", left, right);
+    }
+
+    fn ratio(tuning: &Tuning, low: &str, high: &str) -> f64 {
+        tuning.frequency(&Note::from_str(high).unwrap()) / tuning.frequency(&Note::from_str(low).unwrap())
+    }
+
+    #[test]
+    fn equal_temperament() {
+        let equal = Tuning::equal(440.0).unwrap();
+        assert_close(equal.frequency(&Note::from_str("A4").unwrap()), 440.0);
+        assert_close(equal.frequency(&Note::from_str("A5").unwrap()), 880.0);
+        assert_close(equal.frequency(&Note::from_str("A3").unwrap()), 220.0);
+        assert_close(equal.frequency(&Note::from_str("C4").unwrap()), 261.625565);
+        assert_close(equal.frequency(&Note::from_str("B#3").unwrap()), equal.frequency(&Note::from_str("C4").unwrap()));
+        assert_close(equal.frequency(&Note::from_str("G#4").unwrap()), equal.frequency(&Note::from_str("Ab4").unwrap()));
+        assert_close(Tuning::equal(442.0).unwrap().frequency(&Note::from_str("A4").unwrap()), 442.0);
+        assert_close(Tuning::equal(415.0).unwrap().frequency(&Note::from_str("A3").unwrap()), 207.5);
+
+        assert!(Tuning::equal(0.0).is_err());
+        assert!(Tuning::equal(-440.0).is_err());
+        assert!(Tuning::equal(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn reference_pitch() {
+        for temperament in Temperament::ALL {
+            for tonic in ["C4", "Eb2", "F#3", "A4"] {
+                assert_close(tuning(temperament, tonic).frequency(&Note::from_str("A4").unwrap()), 440.0);
+            }
+        }
+    }
+
+    #[test]
+    fn pythagorean() {
+        let pythagorean = tuning(Temperament::Pythagorean, "C4");
+        assert_close(ratio(&pythagorean, "C4", "G4"), 3.0/2.0);
+        assert_close(ratio(&pythagorean, "C4", "D4"), 9.0/8.0);
+        assert_close(ratio(&pythagorean, "C4", "E4"), 81.0/64.0);
+        assert_close(ratio(&pythagorean, "C4", "F4"), 4.0/3.0);
+        assert_close(ratio(&pythagorean, "C4", "C5"), 2.0);
+        // sharps are higher than their enharmonic flats by a Pythagorean comma
+        assert_close(ratio(&pythagorean, "Ab4", "G#4"), 531441.0/524288.0);
+    }
+
+    #[test]
+    fn meantone() {
+        let meantone = tuning(Temperament::QuarterCommaMeantone, "C4");
+        assert_close(ratio(&meantone, "C4", "E4"), 5.0/4.0);
+        assert_close(ratio(&meantone, "C4", "G4"), 5.0_f64.powf(0.25));
+        assert_close(ratio(&meantone, "C4", "C5"), 2.0);
+        // sharps are lower than their enharmonic flats
+        assert!(meantone.frequency(&Note::from_str("G#4").unwrap()) < meantone.frequency(&Note::from_str("Ab4").unwrap()));
+        assert_close(ratio(&meantone, "G#4", "Ab4"), 128.0/125.0);
+    }
+
+    #[test]
+    fn just_intonation() {
+        let just = tuning(Temperament::JustIntonation, "C4");
+        let ratios = [
+            ("C4", 1.0), ("Db4", 16.0/15.0), ("D4", 9.0/8.0), ("Eb4", 6.0/5.0), ("E4", 5.0/4.0),
+            ("F4", 4.0/3.0), ("F#4", 45.0/32.0), ("Gb4", 64.0/45.0), ("G4", 3.0/2.0), ("Ab4", 8.0/5.0),
+            ("A4", 5.0/3.0), ("Bb4", 16.0/9.0), ("B4", 15.0/8.0), ("C5", 2.0), ("C#4", 25.0/24.0),
+        ];
+        for (high, expected) in ratios {
+            assert_close(ratio(&just, "C4", high), expected);
+        }
+
+        // relative to the tonic
+        let just = tuning(Temperament::JustIntonation, "Eb3");
+        assert_close(ratio(&just, "Eb4", "G4"), 5.0/4.0);
+        assert_close(ratio(&just, "Eb4", "Bb4"), 3.0/2.0);
+        assert_close(ratio(&just, "Eb4", "C5"), 5.0/3.0);
+        assert_close(ratio(&just, "Eb4", "F4"), 9.0/8.0);
+        assert_close(ratio(&tuning(Temperament::JustIntonation, "C3"), "Eb4", "F4"), 10.0/9.0);
+    }
+
+    #[test]
+    fn well_temperaments() {
+        let werckmeister = tuning(Temperament::Werckmeister, "C4");
+        assert_close(werckmeister.frequency(&Note::from_str("G#4").unwrap()), werckmeister.frequency(&Note::from_str("Ab4").unwrap()));
+        assert_close(werckmeister.deviation(&Note::from_str("C4").unwrap()), 0.0);
+        assert_close(werckmeister.deviation(&Note::from_str("G4").unwrap()), -3.91);
+        assert_close(ratio(&werckmeister, "C4", "C5"), 2.0);
+        assert_close(ratio(&werckmeister, "C4", "E4"), 2.0_f64.powf(390.225 / 1200.0));
+
+        let vallotti = tuning(Temperament::Vallotti, "C4");
+        assert_close(vallotti.deviation(&Note::from_str("Bb2").unwrap()), 0.0);
+        assert_close(ratio(&vallotti, "C4", "G4"), 2.0_f64.powf(698.045 / 1200.0));
+        assert_close(vallotti.frequency(&Note::from_str("D#4").unwrap()), vallotti.frequency(&Note::from_str("Eb4").unwrap()));
+
+        // the table is transposed to the tonic
+        let transposed = tuning(Temperament::Vallotti, "D4");
+        assert_close(ratio(&transposed, "D4", "A4"), 2.0_f64.powf(698.045 / 1200.0));
+    }
+}
@@ -0,0 +1,527 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::notes::Note;
+use crate::intervals::Interval;
+use crate::notesequences::NoteSequence;
+use crate::chords::Chord;
+use crate::keys::Key;
+use crate::errors::HarmonyError;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Voice {
+    Soprano,
+    Alto,
+    Tenor,
+    Bass,
+}
+
+impl Voice {
+    pub const ALL: [Voice; 4] = [Voice::Soprano, Voice::Alto, Voice::Tenor, Voice::Bass];
+
+    pub fn from_str(s: &str) -> Result<Self, HarmonyError> {
+        Voice::ALL.iter()
+            .find(|x| x.to_str() == s.trim().to_lowercase())
+            .copied()
+            .ok_or_else(|| HarmonyError::parse(s, 0, s, &Voice::ALL.map(|x| x.to_str())))
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Voice::Soprano => "soprano",
+            Voice::Alto => "alto",
+            Voice::Tenor => "tenor",
+            Voice::Bass => "bass",
+        }
+    }
+
+    pub fn range(self) -> (Note, Note) {
+        let (low, high) = match self {
+            Voice::Soprano => ("C4", "G5"),
+            Voice::Alto => ("G3", "D5"),
+            Voice::Tenor => ("C3", "G4"),
+            Voice::Bass => ("E2", "C4"),
+        };
+        (Note::from_str(low).unwrap(), Note::from_str(high).unwrap())
+    }
+
+    fn is_inner(self) -> bool {
+        matches!(self, Voice::Alto | Voice::Tenor)
+    }
+}
+
+impl FromStr for Voice {
+    type Err = HarmonyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str(s)
+    }
+}
+
+impl fmt::Display for Voice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ViolationKind {
+    ParallelFifths,
+    ParallelOctaves,
+    HiddenFifths,
+    HiddenOctaves,
+    VoiceCrossing,
+    VoiceOverlap,
+    Spacing,
+    Range,
+    UnresolvedLeadingTone,
+    UnresolvedSeventh,
+    DoubledLeadingTone,
+}
+
+impl ViolationKind {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            ViolationKind::ParallelFifths => "parallel fifths",
+            ViolationKind::ParallelOctaves => "parallel octaves",
+            ViolationKind::HiddenFifths => "hidden fifths",
+            ViolationKind::HiddenOctaves => "hidden octaves",
+            ViolationKind::VoiceCrossing => "voice crossing",
+            ViolationKind::VoiceOverlap => "voice overlap",
+            ViolationKind::Spacing => "spacing",
+            ViolationKind::Range => "range",
+            ViolationKind::UnresolvedLeadingTone => "unresolved leading tone",
+            ViolationKind::UnresolvedSeventh => "unresolved seventh",
+            ViolationKind::DoubledLeadingTone => "doubled leading tone",
+        }
+    }
+}
+
+impl fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
+
+// Measure and beat are counted from one. Violations between two chords are
+// reported at the chord they lead into.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    pub measure: usize,
+    pub beat: usize,
+    pub voices: Vec<Voice>,
+    pub notes: Vec<Note>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let voices: Vec<&str> = self.voices.iter().map(|x| x.to_str()).collect();
+        let notes: Vec<String> = self.notes.iter().map(|x| x.to_str()).collect();
+        write!(f, "measure {}, beat {}: {} in {} ({})",
+            self.measure, self.beat, self.kind, voices.join(" and "), notes.join(" "))
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct VoiceLeadingChecker {
+    key: Key,
+    beats_per_measure: usize,
+}
+
+impl VoiceLeadingChecker {
+    pub fn new(key: Key, beats_per_measure: usize) -> Result<Self, HarmonyError> {
+        if beats_per_measure == 0 {
+            return Err(HarmonyError::InvalidArgument("A measure needs at least one beat"));
+        }
+        Ok(Self {key, beats_per_measure})
+    }
+
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    pub fn beats_per_measure(&self) -> usize {
+        self.beats_per_measure
+    }
+
+    pub fn check(&self, soprano: &NoteSequence, alto: &NoteSequence, tenor: &NoteSequence, bass: &NoteSequence)
+        -> Result<Vec<Violation>, HarmonyError> {
+        let voices = [soprano, alto, tenor, bass];
+        let nchords = soprano.notes.len();
+        if voices.iter().any(|x| x.notes.len() != nchords) {
+            return Err(HarmonyError::InvalidArgument("All voices need the same number of notes"));
+        }
+        let chords: Vec<[Note; 4]> = (0..nchords)
+            .map(|ichord| [soprano.notes[ichord], alto.notes[ichord], tenor.notes[ichord], bass.notes[ichord]])
+            .collect();
+
+        let mut violations = Vec::new();
+        for (ichord, chord) in chords.iter().enumerate() {
+            self.check_chord(ichord, chord, &mut violations)?;
+            if ichord > 0 {
+                self.check_motion(ichord, &chords[ichord-1], chord, &mut violations);
+                self.check_resolutions(ichord, &chords[ichord-1], chord, &mut violations)?;
+            }
+        }
+        Ok(violations)
+    }
+
+    fn violation(&self, kind: ViolationKind, ichord: usize, voices: Vec<Voice>, notes: Vec<Note>) -> Violation {
+        Violation {
+            kind,
+            measure: ichord / self.beats_per_measure + 1,
+            beat: ichord % self.beats_per_measure + 1,
+            voices,
+            notes,
+        }
+    }
+
+    fn check_chord(&self, ichord: usize, chord: &[Note; 4], violations: &mut Vec<Violation>) -> Result<(), HarmonyError> {
+        for (voice, note) in Voice::ALL.iter().zip(chord) {
+            let (low, high) = voice.range();
+            if pitch(note) < pitch(&low) || pitch(note) > pitch(&high) {
+                violations.push(self.violation(ViolationKind::Range, ichord, vec![*voice], vec![*note]));
+            }
+        }
+        for iupper in 0..3 {
+            let (upper, lower) = (chord[iupper], chord[iupper+1]);
+            let voices = vec![Voice::ALL[iupper], Voice::ALL[iupper+1]];
+            if pitch(&upper) < pitch(&lower) {
+                violations.push(self.violation(ViolationKind::VoiceCrossing, ichord, voices.clone(), vec![upper, lower]));
+            }
+            // tenor and bass may be further apart than an octave
+            if iupper < 2 && pitch(&upper) - pitch(&lower) > 12 {
+                violations.push(self.violation(ViolationKind::Spacing, ichord, voices, vec![upper, lower]));
+            }
+        }
+        let leading_tone = self.leading_tone()?;
+        let ivoices: Vec<usize> = (0..4).filter(|x| chord[*x].same_spelling(&leading_tone)).collect();
+        if ivoices.len() > 1 {
+            violations.push(self.violation(
+                ViolationKind::DoubledLeadingTone, ichord,
+                ivoices.iter().map(|x| Voice::ALL[*x]).collect(),
+                ivoices.iter().map(|x| chord[*x]).collect()));
+        }
+        Ok(())
+    }
+
+    fn check_motion(&self, ichord: usize, prev: &[Note; 4], next: &[Note; 4], violations: &mut Vec<Violation>) {
+        for iupper in 0..4 {
+            for ilower in iupper+1..4 {
+                let voices = vec![Voice::ALL[iupper], Voice::ALL[ilower]];
+                let notes = vec![prev[iupper], prev[ilower], next[iupper], next[ilower]];
+                let upper_motion = pitch(&next[iupper]) - pitch(&prev[iupper]);
+                let lower_motion = pitch(&next[ilower]) - pitch(&prev[ilower]);
+                let prev_class = (pitch(&prev[iupper]) - pitch(&prev[ilower])).rem_euclid(12);
+                let next_class = (pitch(&next[iupper]) - pitch(&next[ilower])).rem_euclid(12);
+                let (parallel, hidden) = match next_class {
+                    7 => (ViolationKind::ParallelFifths, ViolationKind::HiddenFifths),
+                    0 => (ViolationKind::ParallelOctaves, ViolationKind::HiddenOctaves),
+                    _ => continue,
+                };
+                if upper_motion != 0 && lower_motion != 0 && prev_class == next_class {
+                    violations.push(self.violation(parallel, ichord, voices, notes));
+                } else if iupper == 0 && ilower == 3
+                    && upper_motion.signum() == lower_motion.signum() && upper_motion.abs() > 2 {
+                    violations.push(self.violation(hidden, ichord, voices, notes));
+                }
+            }
+        }
+        for iupper in 0..3 {
+            let ilower = iupper + 1;
+            if pitch(&next[ilower]) > pitch(&prev[iupper]) {
+                violations.push(self.violation(
+                    ViolationKind::VoiceOverlap, ichord,
+                    vec![Voice::ALL[iupper], Voice::ALL[ilower]],
+                    vec![prev[iupper], next[ilower]]));
+            } else if pitch(&next[iupper]) < pitch(&prev[ilower]) {
+                violations.push(self.violation(
+                    ViolationKind::VoiceOverlap, ichord,
+                    vec![Voice::ALL[iupper], Voice::ALL[ilower]],
+                    vec![prev[ilower], next[iupper]]));
+            }
+        }
+    }
+
+    fn check_resolutions(&self, ichord: usize, prev: &[Note; 4], next: &[Note; 4], violations: &mut Vec<Violation>)
+        -> Result<(), HarmonyError> {
+        let (prev_chord, next_chord) = match (identify(prev), identify(next)) {
+            (Some(prev_chord), next_chord) => (prev_chord, next_chord),
+            (None, _) => return Ok(()),
+        };
+        // a repeated or revoiced chord postpones the resolution
+        if next_chord.as_ref().is_some_and(|x| x.root().same_spelling(&prev_chord.root()) && x.quality() == prev_chord.quality()) {
+            return Ok(());
+        }
+
+        let tonic = self.key.tonic();
+        let dominant = tonic.transpose(Interval::from_str("P5").unwrap())?;
+        let leading_tone = self.leading_tone()?;
+        let is_dominant = |chord: &Chord| chord.root().same_spelling(&dominant) || chord.root().same_spelling(&leading_tone);
+        if is_dominant(&prev_chord) && !next_chord.as_ref().is_some_and(is_dominant) {
+            for (ivoice, voice) in Voice::ALL.iter().enumerate() {
+                if !prev[ivoice].same_spelling(&leading_tone) {
+                    continue;
+                }
+                let motion = pitch(&next[ivoice]) - pitch(&prev[ivoice]);
+                let resolved = motion == 1 && next[ivoice].same_spelling(&tonic);
+                // inner voices may leap down to the fifth of the tonic
+                let frustrated = voice.is_inner() && motion == -4 && next[ivoice].same_spelling(&dominant);
+                if !resolved && !frustrated {
+                    violations.push(self.violation(
+                        ViolationKind::UnresolvedLeadingTone, ichord, vec![*voice], vec![prev[ivoice], next[ivoice]]));
+                }
+            }
+        }
+
+        if let Some(seventh) = seventh(&prev_chord) {
+            for (ivoice, voice) in Voice::ALL.iter().enumerate() {
+                if !prev[ivoice].same_spelling(&seventh) {
+                    continue;
+                }
+                let motion = pitch(&next[ivoice]) - pitch(&prev[ivoice]);
+                let step_down = prev[ivoice].dist_steps(&next[ivoice]) == -1 && (motion == -1 || motion == -2);
+                if !step_down {
+                    violations.push(self.violation(
+                        ViolationKind::UnresolvedSeventh, ichord, vec![*voice], vec![prev[ivoice], next[ivoice]]));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn leading_tone(&self) -> Result<Note, HarmonyError> {
+        self.key.tonic().transpose_down(Interval::from_str("m2").unwrap())
+    }
+}
+
+fn pitch(note: &Note) -> i32 {
+    Note::default().dist_hsteps(note)
+}
+
+fn identify(chord: &[Note; 4]) -> Option<Chord> {
+    let mut notes = chord.to_vec();
+    notes.reverse();
+    let noteseq = NoteSequence {notes};
+    let chord = Chord::identify(&noteseq).ok().filter(|x| !x.is_slash())?;
+    // a sixth chord like F6 is read as the seventh chord Dm7/F
+    if seventh(&chord).is_none() {
+        if let Ok(Some(chord)) = Chord::identify_seventh(&noteseq) {
+            return Some(chord);
+        }
+    }
+    Some(chord)
+}
+
+// the tone a seventh above the root of seventh chords, but not of sixth or added ninth chords
+fn seventh(chord: &Chord) -> Option<Note> {
+    if chord.quality().intervals().len() != 4 {
+        return None;
+    }
+    chord.intervals().iter().zip(chord.tones())
+        .find(|(x, _)| x.number() == 7)
+        .map(|(_, x)| x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(key: &str, voices: [&[&str]; 4]) -> Vec<Violation> {
+        let [soprano, alto, tenor, bass] = voices.map(|x| NoteSequence::from_strs(x.to_vec()).unwrap());
+        VoiceLeadingChecker::new(Key::from_str(key).unwrap(), 4).unwrap()
+            .check(&soprano, &alto, &tenor, &bass)
+            .unwrap()
+    }
+
+    fn kinds(violations: &[Violation]) -> Vec<ViolationKind> {
+        violations.iter().map(|x| x.kind).collect()
+    }
+
+    fn notes(strs: &[&str]) -> Vec<Note> {
+        strs.iter().map(|x| Note::from_str(x).unwrap()).collect()
+    }
+
+    #[test]
+    fn correct_progression() {
+        // I V7 I in C
+        let violations = check("C", [
+            &["E4", "F4", "E4"],
+            &["C4", "D4", "C4"],
+            &["G3", "B3", "C4"],
+            &["C3", "G2", "C3"],
+        ]);
+        assert_eq!(violations, vec![]);
+    }
+
+    #[test]
+    fn parallels() {
+        let violations = check("C", [
+            &["G4", "A4"],
+            &["E4", "F4"],
+            &["C4", "C4"],
+            &["C3", "D3"],
+        ]);
+        assert_eq!(kinds(&violations), vec![ViolationKind::ParallelFifths]);
+        assert_eq!(violations[0].voices, vec![Voice::Soprano, Voice::Bass]);
+        assert_eq!(violations[0].notes, notes(&["G4", "C3", "A4", "D3"]));
+        assert_eq!((violations[0].measure, violations[0].beat), (1, 2));
+
+        let violations = check("C", [
+            &["E4", "E4", "E4", "E4", "E4", "F4"],
+            &["C4", "C4", "C4", "C4", "C4", "D4"],
+            &["G3", "G3", "G3", "G3", "G3", "F3"],
+            &["C3", "C3", "C3", "C3", "C3", "D3"],
+        ]);
+        assert_eq!(kinds(&violations), vec![ViolationKind::ParallelOctaves]);
+        assert_eq!(violations[0].voices, vec![Voice::Alto, Voice::Bass]);
+        assert_eq!((violations[0].measure, violations[0].beat), (2, 2));
+    }
+
+    #[test]
+    fn hidden() {
+        let violations = check("C", [
+            &["E4", "G4"],
+            &["C4", "D4"],
+            &["G3", "B3"],
+            &["C3", "G2"],
+        ]);
+        assert_eq!(kinds(&violations), vec![]);
+
+        let violations = check("C", [
+            &["E4", "C5"],
+            &["C4", "E4"],
+            &["A3", "G3"],
+            &["A2", "C3"],
+        ]);
+        assert_eq!(kinds(&violations), vec![ViolationKind::HiddenOctaves]);
+        assert_eq!(violations[0].notes, notes(&["E4", "A2", "C5", "C3"]));
+    }
+
+    #[test]
+    fn crossing_overlap_spacing() {
+        let violations = check("C", [
+            &["E4"],
+            &["G4"],
+            &["C4"],
+            &["C3"],
+        ]);
+        assert_eq!(kinds(&violations), vec![ViolationKind::VoiceCrossing]);
+
+        let violations = check("C", [
+            &["E4", "F4"],
+            &["C4", "C4"],
+            &["G3", "C4"],
+            &["C3", "A3"],
+        ]);
+        assert_eq!(kinds(&violations), vec![ViolationKind::VoiceOverlap]);
+        assert_eq!(violations[0].voices, vec![Voice::Tenor, Voice::Bass]);
+
+        let violations = check("C", [
+            &["G5"],
+            &["C4"],
+            &["E3"],
+            &["C3"],
+        ]);
+        assert_eq!(kinds(&violations), vec![ViolationKind::Spacing]);
+        assert_eq!(violations[0].voices, vec![Voice::Soprano, Voice::Alto]);
+    }
+
+    #[test]
+    fn ranges() {
+        let violations = check("C", [
+            &["C6"],
+            &["E5"],
+            &["G4"],
+            &["C2"],
+        ]);
+        assert_eq!(kinds(&violations), vec![ViolationKind::Range, ViolationKind::Range, ViolationKind::Range]);
+        assert_eq!(violations.iter().map(|x| x.voices[0]).collect::<Vec<Voice>>(), vec![Voice::Soprano, Voice::Alto, Voice::Bass]);
+        assert!(violations.iter().any(|x| x.kind == ViolationKind::Range && x.notes == notes(&["C2"])));
+    }
+
+    #[test]
+    fn resolutions() {
+        // leading tone in the soprano moves down, seventh in the alto moves up
+        let violations = check("C", [
+            &["B4", "G4"],
+            &["F4", "G4"],
+            &["D4", "E4"],
+            &["G3", "C4"],
+        ]);
+        assert_eq!(kinds(&violations), vec![ViolationKind::UnresolvedLeadingTone, ViolationKind::UnresolvedSeventh]);
+        assert_eq!(violations[0].notes, notes(&["B4", "G4"]));
+        assert_eq!(violations[1].voices, vec![Voice::Alto]);
+        assert_eq!((violations[0].measure, violations[0].beat), (1, 2));
+        assert_eq!((violations[1].measure, violations[1].beat), (1, 2));
+
+        // frustrated leading tone in an inner voice
+        let violations = check("C", [
+            &["D5", "C5"],
+            &["B4", "G4"],
+            &["G4", "E4"],
+            &["G3", "C4"],
+        ]);
+        assert_eq!(kinds(&violations), vec![]);
+
+        // the seventh may wait through a revoiced chord
+        let violations = check("a", [
+            &["B4", "D5", "C5"],
+            &["G#4", "G#4", "A4"],
+            &["D4", "B3", "C4"],
+            &["E3", "E3", "A2"],
+        ]);
+        assert_eq!(kinds(&violations), vec![]);
+
+        // the seventh of ii65 is in the tenor, not the added sixth in the soprano
+        let violations = check("C", [
+            &["D5", "D5"],
+            &["A4", "B4"],
+            &["C4", "B3"],
+            &["F3", "G3"],
+        ]);
+        assert_eq!(kinds(&violations), vec![ViolationKind::DoubledLeadingTone]);
+        let violations = check("C", [
+            &["D5", "D5"],
+            &["A4", "G4"],
+            &["C4", "D4"],
+            &["F3", "B2"],
+        ]);
+        assert_eq!(kinds(&violations), vec![ViolationKind::UnresolvedSeventh]);
+        assert_eq!((violations[0].voices.clone(), violations[0].notes.clone()), (vec![Voice::Tenor], notes(&["C4", "D4"])));
+    }
+
+    #[test]
+    fn doubled_leading_tone() {
+        let violations = check("G", [
+            &["F#4"],
+            &["D4"],
+            &["F#3"],
+            &["D3"],
+        ]);
+        assert_eq!(kinds(&violations), vec![ViolationKind::DoubledLeadingTone]);
+        assert_eq!(violations[0].voices, vec![Voice::Soprano, Voice::Tenor]);
+    }
+
+    #[test]
+    fn errors() {
+        let checker = VoiceLeadingChecker::new(Key::from_str("C").unwrap(), 3).unwrap();
+        let two = NoteSequence::from_strs(["C4", "D4"].to_vec()).unwrap();
+        let one = NoteSequence::from_strs(["C4"].to_vec()).unwrap();
+        assert!(checker.check(&two, &two, &two, &one).is_err());
+        assert!(VoiceLeadingChecker::new(Key::from_str("C").unwrap(), 0).is_err());
+    }
+
+    #[test]
+    fn traits() {
+        assert_eq!("Tenor".parse::<Voice>(), Ok(Voice::Tenor));
+        assert!(Voice::from_str("baritone").is_err());
+        let violations = check("C", [
+            &["G4", "A4"],
+            &["E4", "F4"],
+            &["C4", "C4"],
+            &["C3", "D3"],
+        ]);
+        assert_eq!(format!("{}", violations[0]), "measure 1, beat 2: parallel fifths in soprano and bass (G4 C3 A4 D3)");
+    }
+}